@@ -0,0 +1,287 @@
+//! Document chunking for passage-level retrieval.
+//!
+//! Context documents are split into passages before they are embedded, so
+//! a query about one section of a long research note matches that section
+//! instead of a single vector averaged over the whole file.
+//!
+//! Splitting is markdown-aware: every heading starts a new section (code
+//! fences are skipped, so `# comment` lines inside them don't count), and
+//! each chunk remembers the heading path it sits under ("Core Modes >
+//! Fix Mode"). Sections longer than the token window are cut on word
+//! boundaries into overlapping windows, so a sentence that straddles a cut
+//! is whole in at least one chunk. Every chunk keeps its byte span in the
+//! source document, so results can point back at the original text.
+//!
+//! Token counts use the same 4-characters-per-token estimate as
+//! [`ContextUsageTracker::estimate_tokens`].
+
+use crate::context_token_manager::ContextUsageTracker;
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+/// How documents are cut into chunks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkerConfig {
+    /// Longest chunk, in estimated tokens.
+    pub max_tokens: usize,
+    /// How many tokens consecutive windows of one section share.
+    pub overlap_tokens: usize,
+}
+
+impl Default for ChunkerConfig {
+    fn default() -> Self {
+        Self {
+            max_tokens: 256,
+            overlap_tokens: 32,
+        }
+    }
+}
+
+impl ChunkerConfig {
+    pub fn with_max_tokens(mut self, max_tokens: usize) -> Self {
+        self.max_tokens = max_tokens.max(1);
+        self
+    }
+
+    pub fn with_overlap_tokens(mut self, overlap_tokens: usize) -> Self {
+        self.overlap_tokens = overlap_tokens;
+        self
+    }
+}
+
+/// A passage of a document.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Chunk {
+    /// Position among the document's chunks
+    pub index: usize,
+    /// Headings the chunk sits under, outermost first ("A > B")
+    pub heading: Option<String>,
+    /// Byte offsets of the chunk in the source document
+    pub start: usize,
+    pub end: usize,
+    /// The chunk's text, exactly `content[start..end]`
+    pub text: String,
+}
+
+impl Chunk {
+    pub fn span(&self) -> Range<usize> {
+        self.start..self.end
+    }
+
+    /// What gets embedded: the heading path gives a short chunk the
+    /// context its body alone would lack.
+    pub fn embedding_text(&self) -> String {
+        match &self.heading {
+            Some(heading) => format!("{}\n{}", heading, self.text),
+            None => self.text.clone(),
+        }
+    }
+}
+
+/// Split `content` into chunks.
+pub fn chunk_document(content: &str, config: &ChunkerConfig) -> Vec<Chunk> {
+    let mut chunks = Vec::new();
+    for section in sections(content) {
+        for span in windows(content, section.span.clone(), config) {
+            chunks.push(Chunk {
+                index: chunks.len(),
+                heading: section.heading.clone(),
+                start: span.start,
+                end: span.end,
+                text: content[span].to_string(),
+            });
+        }
+    }
+    chunks
+}
+
+/// A heading and the text up to the next one.
+struct Section {
+    heading: Option<String>,
+    span: Range<usize>,
+}
+
+/// Cut `content` at markdown headings. A heading with no body of its own
+/// is folded into the section after it, so "## Goals" followed directly
+/// by "### Short term" yields one section, not an empty one.
+fn sections(content: &str) -> Vec<Section> {
+    let mut sections = Vec::new();
+    // (level, title) of the headings enclosing the current line.
+    let mut path: Vec<(usize, String)> = Vec::new();
+    let mut current = Section {
+        heading: None,
+        span: 0..0,
+    };
+    let mut has_body = false;
+    let mut in_fence = false;
+    let mut offset = 0;
+
+    for line in content.split_inclusive('\n') {
+        let trimmed = line.trim();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+        }
+        let heading = if in_fence {
+            None
+        } else {
+            heading_level(trimmed)
+        };
+
+        if let Some((level, title)) = heading {
+            if has_body {
+                current.span.end = offset;
+                sections.push(current);
+                current = Section {
+                    heading: None,
+                    span: offset..offset,
+                };
+                has_body = false;
+            }
+            path.retain(|(l, _)| *l < level);
+            path.push((level, title.to_string()));
+            current.heading = Some(
+                path.iter()
+                    .map(|(_, t)| t.as_str())
+                    .collect::<Vec<_>>()
+                    .join(" > "),
+            );
+        } else if !trimmed.is_empty() {
+            has_body = true;
+        }
+        offset += line.len();
+    }
+
+    if has_body || current.heading.is_some() {
+        current.span.end = offset;
+        sections.push(current);
+    }
+    sections
+}
+
+/// `# Title` -> `(1, "Title")`.
+fn heading_level(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|&c| c == '#').count();
+    if level == 0 || level > 6 {
+        return None;
+    }
+    let rest = &line[level..];
+    if !rest.starts_with(' ') {
+        return None;
+    }
+    let title = rest.trim().trim_end_matches('#').trim();
+    (!title.is_empty()).then_some((level, title))
+}
+
+/// Cut `span` of `content` into windows of at most `max_tokens`, on word
+/// boundaries, each sharing about `overlap_tokens` with the one before.
+fn windows(content: &str, span: Range<usize>, config: &ChunkerConfig) -> Vec<Range<usize>> {
+    let text = &content[span.clone()];
+    let tokens = |r: &Range<usize>| ContextUsageTracker::estimate_tokens(&content[r.clone()]);
+
+    let words: Vec<Range<usize>> = word_spans(text)
+        .into_iter()
+        .map(|w| span.start + w.start..span.start + w.end)
+        .collect();
+    let Some(last) = words.last() else {
+        return Vec::new();
+    };
+    let trimmed = words[0].start..last.end;
+    if tokens(&trimmed) <= config.max_tokens {
+        return vec![trimmed];
+    }
+
+    let mut windows = Vec::new();
+    let mut first = 0;
+    loop {
+        // Grow the window word by word; a single over-long word still
+        // makes a window of its own.
+        let mut last = first;
+        while last + 1 < words.len()
+            && tokens(&(words[first].start..words[last + 1].end)) <= config.max_tokens
+        {
+            last += 1;
+        }
+        windows.push(words[first].start..words[last].end);
+        if last + 1 == words.len() {
+            break;
+        }
+        // Step back from the end for the overlap, but always move forward.
+        let mut next = last + 1;
+        while next > first + 1
+            && tokens(&(words[next - 1].start..words[last].end)) <= config.overlap_tokens
+        {
+            next -= 1;
+        }
+        first = next;
+    }
+    windows
+}
+
+/// Byte ranges of the whitespace-separated words in `text`.
+fn word_spans(text: &str) -> Vec<Range<usize>> {
+    let mut spans = Vec::new();
+    let mut start = None;
+    for (i, c) in text.char_indices() {
+        match (c.is_whitespace(), start) {
+            (true, Some(s)) => {
+                spans.push(s..i);
+                start = None;
+            }
+            (false, None) => start = Some(i),
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        spans.push(s..text.len());
+    }
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunks_follow_headings() {
+        let doc = "# Guide\nIntro text.\n\n## Setup\n### Linux\nRun the installer.\n\n```sh\n# not a heading\nmake\n```\n## Usage\nOpen the app.\n";
+        let chunks = chunk_document(doc, &ChunkerConfig::default());
+
+        let headings: Vec<_> = chunks.iter().map(|c| c.heading.as_deref()).collect();
+        assert_eq!(
+            headings,
+            vec![
+                Some("Guide"),
+                Some("Guide > Setup > Linux"),
+                Some("Guide > Usage")
+            ]
+        );
+        for chunk in &chunks {
+            assert_eq!(&doc[chunk.span()], chunk.text);
+        }
+        // The empty "## Setup" heading is folded into "### Linux", and the
+        // fenced comment stays in its section.
+        assert!(chunks[1].text.starts_with("## Setup"));
+        assert!(chunks[1].text.contains("# not a heading"));
+    }
+
+    #[test]
+    fn test_long_sections_are_windowed_with_overlap() {
+        let body: Vec<String> = (0..200).map(|i| format!("word{:03}", i)).collect();
+        let doc = format!("# Notes\n{}\n", body.join(" "));
+        let config = ChunkerConfig::default()
+            .with_max_tokens(50)
+            .with_overlap_tokens(10);
+        let chunks = chunk_document(&doc, &config);
+
+        assert!(chunks.len() > 1);
+        for pair in chunks.windows(2) {
+            assert!(pair[1].start < pair[0].end, "windows should overlap");
+            assert!(pair[1].start > pair[0].start, "windows should advance");
+        }
+        for chunk in &chunks {
+            assert!(ContextUsageTracker::estimate_tokens(&chunk.text) <= 50);
+            assert_eq!(chunk.heading.as_deref(), Some("Notes"));
+        }
+        assert!(chunks.last().unwrap().text.ends_with("word199"));
+    }
+}
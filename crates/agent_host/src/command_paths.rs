@@ -0,0 +1,917 @@
+//! Find the filesystem paths a shell command would touch.
+//!
+//! `PathSandbox::validate_command` checks every path this module returns.
+//! The command line is parsed with `shell_syntax`, so quoting, chains and
+//! redirections are handled the way the shell would handle them, and each
+//! simple command's arguments are read according to what the program does
+//! with them:
+//!
+//! - Known commands (`cat`, `cp`, `grep`, `tar`, `curl`, ...) have a table
+//!   entry saying which operands are paths and which options take a path
+//!   value (`-o FILE`, `--file=FILE`). Their operands count as paths even
+//!   without a separator, so `cd .. && cat passwd` is caught.
+//! - Other commands get a guess: operands and `--opt=value` values that look
+//!   like paths (`/`, `\`, `~`, a leading `.`) or name an existing file.
+//! - Redirection targets are always paths.
+//! - Code run inline is read too: `sh -c`/`eval` code is parsed as a
+//!   command line of its own, and the argv of `find -exec ... ;` as a
+//!   command. Interpreter code (`python -c`, `node -e`) can't be parsed,
+//!   so anything in it that looks like a path counts.
+//!
+//! `cd` and `pushd` move the virtual working directory for the commands
+//! after them, and globs are expanded against that directory so each match
+//! is checked.
+
+use crate::shell_syntax::{self, program_name, unwrap_command, RedirectKind, SimpleCommand};
+use std::path::{Component, Path, PathBuf};
+
+/// A path found in a command: the text as written and where it resolves.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandPath {
+    pub raw: String,
+    pub path: PathBuf,
+}
+
+/// How a command's operands (its non-option arguments) are used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operands {
+    /// Every operand is a path (`cat`, `ls`, `cp`).
+    Paths,
+    /// The first operand is a pattern, script, mode or owner; the rest are
+    /// paths. If a `pattern_options` option supplied it, all are paths.
+    AfterFirst,
+    /// Operands up to the first option are paths (`find DIR... -name x`).
+    Leading,
+    /// Operands aren't paths (`echo` text, `curl` URLs).
+    NotPaths,
+    /// Unknown: operands that look like paths.
+    Guess,
+}
+
+struct CommandSpec {
+    names: &'static [&'static str],
+    operands: Operands,
+    /// Options whose value is a path.
+    path_options: &'static [&'static str],
+    /// Options whose value is something else, so it isn't an operand.
+    value_options: &'static [&'static str],
+    /// Options that supply the first operand of an `AfterFirst` command.
+    pattern_options: &'static [&'static str],
+}
+
+const fn spec(names: &'static [&'static str], operands: Operands) -> CommandSpec {
+    CommandSpec {
+        names,
+        operands,
+        path_options: &[],
+        value_options: &[],
+        pattern_options: &[],
+    }
+}
+
+/// What known commands do with their arguments. Commands not listed here
+/// fall back to `Operands::Guess`.
+const COMMAND_SPECS: &[CommandSpec] = &[
+    spec(
+        &[
+            "cat",
+            "less",
+            "more",
+            "ls",
+            "dir",
+            "stat",
+            "file",
+            "touch",
+            "rmdir",
+            "rm",
+            "shred",
+            "diff",
+            "cmp",
+            "comm",
+            "uniq",
+            "wc",
+            "nano",
+            "vim",
+            "nvim",
+            "vi",
+            "code",
+            "source",
+            ".",
+            "cd",
+            "pushd",
+            "type",
+            "md5sum",
+            "sha1sum",
+            "sha256sum",
+            "realpath",
+            "readlink",
+            "zip",
+            "zipinfo",
+            "open",
+            "xdg-open",
+            "notepad",
+            "del",
+            "erase",
+            "rd",
+            "md",
+        ],
+        Operands::Paths,
+    ),
+    CommandSpec {
+        value_options: &["-n", "-c", "--lines", "--bytes"],
+        ..spec(&["head", "tail"], Operands::Paths)
+    },
+    CommandSpec {
+        value_options: &["-d", "--max-depth", "-L", "-P", "-I", "-m", "--mode"],
+        ..spec(&["du", "tree", "mkdir"], Operands::Paths)
+    },
+    CommandSpec {
+        path_options: &["-t", "--target-directory"],
+        value_options: &["-S", "--suffix", "-m", "--mode"],
+        ..spec(
+            &[
+                "cp", "mv", "ln", "install", "copy", "move", "xcopy", "robocopy",
+            ],
+            Operands::Paths,
+        )
+    },
+    CommandSpec {
+        path_options: &["-o", "--output", "-T", "--temporary-directory"],
+        value_options: &["-k", "--key", "-t", "--field-separator"],
+        ..spec(&["sort"], Operands::Paths)
+    },
+    CommandSpec {
+        path_options: &["-f", "--file", "-C", "--directory"],
+        ..spec(&["tar"], Operands::Paths)
+    },
+    CommandSpec {
+        path_options: &["-d"],
+        ..spec(&["unzip"], Operands::Paths)
+    },
+    CommandSpec {
+        path_options: &["--reference"],
+        ..spec(&["chmod", "chown", "chgrp"], Operands::AfterFirst)
+    },
+    CommandSpec {
+        path_options: &["-f", "--file", "--ignore-file"],
+        value_options: &[
+            "-m",
+            "--max-count",
+            "-A",
+            "-B",
+            "-C",
+            "--context",
+            "-g",
+            "--glob",
+            "-t",
+            "--type",
+            "--include",
+            "--exclude",
+            "-d",
+        ],
+        pattern_options: &["-e", "--regexp", "-f", "--file"],
+        ..spec(
+            &["grep", "egrep", "fgrep", "rg", "ag"],
+            Operands::AfterFirst,
+        )
+    },
+    CommandSpec {
+        path_options: &["-f", "--file"],
+        pattern_options: &["-e", "--expression", "-f", "--file"],
+        ..spec(&["sed"], Operands::AfterFirst)
+    },
+    CommandSpec {
+        path_options: &["-f"],
+        value_options: &["-v", "-F"],
+        pattern_options: &["-f"],
+        ..spec(&["awk", "gawk"], Operands::AfterFirst)
+    },
+    CommandSpec {
+        path_options: &["-newer", "-fprint", "-fls"],
+        ..spec(&["find"], Operands::Leading)
+    },
+    CommandSpec {
+        path_options: &[
+            "-o",
+            "--output",
+            "-T",
+            "--upload-file",
+            "-K",
+            "--config",
+            "-c",
+            "--cookie-jar",
+            "-D",
+            "--dump-header",
+            "--cacert",
+            "--cert",
+            "--key",
+        ],
+        value_options: &[
+            "-X",
+            "--request",
+            "-H",
+            "--header",
+            "-d",
+            "--data",
+            "--data-binary",
+            "-F",
+            "--form",
+            "-u",
+            "--user",
+            "-A",
+            "--user-agent",
+            "-e",
+            "--referer",
+            "-w",
+            "--write-out",
+        ],
+        ..spec(&["curl"], Operands::NotPaths)
+    },
+    CommandSpec {
+        path_options: &[
+            "-O",
+            "--output-document",
+            "-P",
+            "--directory-prefix",
+            "-i",
+            "--input-file",
+            "-o",
+            "--output-file",
+            "-a",
+            "--append-output",
+        ],
+        value_options: &["-U", "--user-agent", "--header"],
+        ..spec(&["wget"], Operands::NotPaths)
+    },
+    CommandSpec {
+        path_options: &["-C", "--git-dir", "--work-tree"],
+        value_options: &["-c", "-m", "--message"],
+        ..spec(&["git"], Operands::Guess)
+    },
+    CommandSpec {
+        value_options: &["-c", "-m", "-e", "--eval"],
+        ..spec(
+            &[
+                "python", "python3", "node", "ruby", "perl", "sh", "bash", "zsh", "dash", "ksh",
+            ],
+            Operands::Guess,
+        )
+    },
+    spec(
+        &[
+            "echo",
+            "printf",
+            "ping",
+            "kill",
+            "killall",
+            "pkill",
+            "ps",
+            "which",
+            "whereis",
+            "hostname",
+            "date",
+            "uname",
+            "whoami",
+            "id",
+            "nslookup",
+            "dig",
+            "host",
+            "traceroute",
+            "man",
+            "tldr",
+        ],
+        Operands::NotPaths,
+    ),
+];
+
+fn spec_for(name: &str) -> Option<&'static CommandSpec> {
+    COMMAND_SPECS.iter().find(|s| s.names.contains(&name))
+}
+
+/// Most glob matches checked for one pattern.
+const MAX_GLOB_MATCHES: usize = 1000;
+
+/// Every path `cmd` would touch when run from `cwd`.
+pub fn command_paths(cmd: &str, cwd: &Path) -> Vec<CommandPath> {
+    // Windows commands use `\` as a separator, not an escape.
+    let input = if cfg!(windows) {
+        cmd.replace('\\', "\\\\")
+    } else {
+        cmd.to_string()
+    };
+    let commands = match shell_syntax::parse(&input) {
+        Ok(commands) => commands,
+        Err(_) => return guess_tokens(cmd, cwd),
+    };
+
+    let mut paths = Vec::new();
+    let mut cwd = cwd.to_path_buf();
+    for command in &commands {
+        let mut found = Vec::new();
+        for redirect in &command.redirects {
+            match redirect.kind {
+                RedirectKind::Duplicate | RedirectKind::HereDoc => {}
+                // A here-string fed to a shell is code it runs.
+                RedirectKind::HereString => {
+                    let shell = unwrapped(command)
+                        .first()
+                        .is_some_and(|name| SHELLS.contains(&program_name(name).as_str()));
+                    if shell {
+                        paths.extend(command_paths(&redirect.target, &cwd));
+                    }
+                }
+                _ => found.push(redirect.target.clone()),
+            }
+        }
+        let mut next_cwd = None;
+        if let Some((name, args)) = unwrapped(command).split_first() {
+            let name = program_name(name);
+            if matches!(name.as_str(), "cd" | "pushd") {
+                next_cwd = Some(match args.iter().find(|a| !a.starts_with('-')) {
+                    Some(dir) => resolve(dir, &cwd),
+                    None => dirs::home_dir().unwrap_or_else(|| cwd.clone()),
+                });
+            }
+        }
+        add_expanded(&found, &cwd, &mut paths);
+        word_paths(&command.words, &cwd, &mut paths);
+        if let Some(next) = next_cwd {
+            cwd = next;
+        }
+    }
+    paths
+}
+
+/// Shells that take code with `-c`.
+pub const SHELLS: &[&str] = &["sh", "bash", "zsh", "dash", "ksh"];
+
+/// Code a command runs inline, as `sh -c CODE`, `eval CODE...` or
+/// `python -c CODE` do, or sets up to run later, as `trap CODE SIGNAL`
+/// and `alias name=CODE` do.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InlineCode {
+    /// A shell command line
+    Shell(String),
+    /// Code for another interpreter
+    Script(String),
+}
+
+/// The code `name` runs inline when given `args`, if any.
+pub fn inline_code(name: &str, args: &[String]) -> Option<InlineCode> {
+    match name {
+        "eval" => Some(InlineCode::Shell(args.join(" "))),
+        "trap" => {
+            // `trap -p`, `trap - SIGNAL` and `trap '' SIGNAL` run nothing.
+            let code = args
+                .iter()
+                .find(|a| !a.starts_with('-') || a.as_str() == "-")?;
+            (code != "-" && !code.trim().is_empty()).then(|| InlineCode::Shell(code.clone()))
+        }
+        "alias" => {
+            let code: Vec<&str> = args
+                .iter()
+                .filter_map(|a| a.split_once('=').map(|(_, value)| value))
+                .filter(|value| !value.trim().is_empty())
+                .collect();
+            (!code.is_empty()).then(|| InlineCode::Shell(code.join("\n")))
+        }
+        name if SHELLS.contains(&name) => {
+            // `-c`, or a cluster ending in it (`-lc`, `-ec`)
+            let i = args.iter().position(|a| {
+                a.strip_prefix('-').is_some_and(|flags| {
+                    flags.ends_with('c') && flags.chars().all(|c| c.is_ascii_alphabetic())
+                })
+            })?;
+            args.get(i + 1).cloned().map(InlineCode::Shell)
+        }
+        "python" | "python3" | "node" | "ruby" | "perl" => {
+            let mut args = args.iter();
+            while let Some(arg) = args.next() {
+                if matches!(arg.as_str(), "-c" | "-e" | "-E" | "--eval") {
+                    return args.next().cloned().map(InlineCode::Script);
+                }
+                if let Some(code) = arg.strip_prefix("--eval=") {
+                    return Some(InlineCode::Script(code.to_string()));
+                }
+                if !arg.starts_with('-') {
+                    // A script file: what follows are its arguments.
+                    break;
+                }
+            }
+            None
+        }
+        _ => None,
+    }
+}
+
+/// Whether any simple command in `cmd` (or in code it runs inline) runs
+/// code passed as a string, whose effects can only be guessed at.
+pub fn runs_inline_code(cmd: &str) -> bool {
+    fn runs(words: &[String]) -> bool {
+        let Some((name, args)) = unwrap_words(words).split_first() else {
+            return false;
+        };
+        let name = program_name(name);
+        inline_code(&name, args).is_some() || (name == "find" && exec_commands(args).any(runs))
+    }
+    shell_syntax::parse(cmd).is_ok_and(|commands| commands.iter().any(|c| runs(&c.words)))
+}
+
+/// Resolve and expand the raw paths in `found`, skipping streams.
+fn add_expanded(found: &[String], cwd: &Path, paths: &mut Vec<CommandPath>) {
+    for raw in found {
+        if is_stream(raw) {
+            continue;
+        }
+        paths.extend(expand(raw, cwd));
+    }
+}
+
+/// The paths a simple command's words (wrappers and all) name: its path
+/// arguments, the paths in code it runs inline, and those of the commands
+/// `find -exec` runs.
+fn word_paths(words: &[String], cwd: &Path, paths: &mut Vec<CommandPath>) {
+    let Some((name, args)) = unwrap_words(words).split_first() else {
+        return;
+    };
+    let name = program_name(name);
+    match inline_code(&name, args) {
+        Some(InlineCode::Shell(code)) => paths.extend(command_paths(&code, cwd)),
+        Some(InlineCode::Script(code)) => paths.extend(script_paths(&code, cwd)),
+        None => {}
+    }
+    if matches!(name.as_str(), "eval" | "trap" | "alias") {
+        // Their arguments are all code.
+        return;
+    }
+    if name == "find" {
+        for exec in exec_commands(args) {
+            let exec: Vec<String> = exec.iter().filter(|w| *w != "{}").cloned().collect();
+            word_paths(&exec, cwd, paths);
+        }
+    }
+    let mut found = Vec::new();
+    path_arguments(&name, args, cwd, &mut found);
+    add_expanded(&found, cwd, paths);
+}
+
+/// The commands `find` runs for its matches: the words between each
+/// `-exec`/`-execdir`/`-ok`/`-okdir` and the `;` or `+` that ends it.
+fn exec_commands(args: &[String]) -> impl Iterator<Item = &[String]> {
+    let mut rest = args;
+    std::iter::from_fn(move || {
+        let start = rest
+            .iter()
+            .position(|a| matches!(a.as_str(), "-exec" | "-execdir" | "-ok" | "-okdir"))?;
+        let after = &rest[start + 1..];
+        let end = after
+            .iter()
+            .position(|a| a == ";" || a == "+")
+            .unwrap_or(after.len());
+        rest = after.get(end + 1..).unwrap_or_default();
+        Some(&after[..end])
+    })
+}
+
+/// Anything in interpreter code that looks like a path: the words between
+/// quotes, brackets, commas and the like.
+fn script_paths(code: &str, cwd: &Path) -> Vec<CommandPath> {
+    code.split(|c: char| c.is_whitespace() || "\"'`()[]{},;=+<>".contains(c))
+        .filter(|t| !t.is_empty() && !t.starts_with('-') && !t.contains("://"))
+        // `.` only as a directory, not a method call (`open(f).read()`)
+        .filter(|t| {
+            t.starts_with(['/', '~'])
+                || t.starts_with("$HOME")
+                || t.contains('/')
+                || matches!(*t, "." | "..")
+        })
+        .filter(|t| !is_stream(t))
+        .flat_map(|t| expand(t, cwd))
+        .collect()
+}
+
+/// The words of the command proper, past `sudo`, `env` and other wrappers.
+pub(crate) fn unwrapped(command: &SimpleCommand) -> &[String] {
+    unwrap_words(&command.words)
+}
+
+fn unwrap_words(mut words: &[String]) -> &[String] {
+    while let Some((_, inner)) = unwrap_command(words) {
+        words = inner;
+    }
+    words
+}
+
+/// Collect the arguments of `name` that are paths into `found`. Inline
+/// code and `find -exec` commands are left to `word_paths`.
+fn path_arguments(name: &str, args: &[String], cwd: &Path, found: &mut Vec<String>) {
+    let spec = spec_for(name);
+    let operands = spec.map_or(Operands::Guess, |s| s.operands);
+    let path_options = spec.map_or(&[][..], |s| s.path_options);
+    let value_options = spec.map_or(&[][..], |s| s.value_options);
+    let pattern_options = spec.map_or(&[][..], |s| s.pattern_options);
+
+    let mut pattern_given = false;
+    let mut options_done = false;
+    let mut seen_option = false;
+    let mut operand_index = 0;
+    let mut i = 0;
+    while let Some(arg) = args.get(i) {
+        i += 1;
+        if !options_done && arg == "--" {
+            options_done = true;
+            continue;
+        }
+        if !options_done && arg.starts_with('-') && arg.len() > 1 {
+            seen_option = true;
+            let (option, attached) = split_option(arg, path_options, value_options);
+            let takes_value = path_options.contains(&option)
+                || value_options.contains(&option)
+                || pattern_options.contains(&option);
+            let value = match attached {
+                Some(value) => Some(value.to_string()),
+                None if takes_value => {
+                    i += 1;
+                    args.get(i - 1).cloned()
+                }
+                None => None,
+            };
+            pattern_given |= pattern_options.contains(&option);
+            let Some(value) = value else { continue };
+            if path_options.contains(&option) {
+                found.push(value);
+            } else if name == "curl" && (value.starts_with('@') || value.contains("=@")) {
+                // `-d @file` and `-F name=@file` upload a file's contents.
+                let file = value.rsplit_once('@').map_or("", |(_, f)| f);
+                if !file.is_empty() {
+                    found.push(file.to_string());
+                }
+            } else if !value_options.contains(&option)
+                && !pattern_options.contains(&option)
+                && operands != Operands::NotPaths
+                && looks_like_path(&value, cwd)
+            {
+                // `--config=/etc/x` on a command we don't know.
+                found.push(value);
+            }
+            continue;
+        }
+
+        if name == "dd" {
+            if let Some(file) = arg.strip_prefix("if=").or_else(|| arg.strip_prefix("of=")) {
+                found.push(file.to_string());
+            }
+            continue;
+        }
+        let is_path = match operands {
+            Operands::Paths => true,
+            Operands::AfterFirst => pattern_given || operand_index > 0,
+            Operands::Leading => !seen_option && !matches!(arg.as_str(), "(" | ")" | "!"),
+            Operands::NotPaths => false,
+            Operands::Guess => looks_like_path(arg, cwd),
+        };
+        operand_index += 1;
+        if is_path {
+            found.push(arg.clone());
+        }
+    }
+}
+
+/// Split an option into its name and attached value: `--file=x` gives
+/// (`--file`, `x`), `-o/tmp/x` gives (`-o`, `/tmp/x`) when `-o` takes a
+/// value, and a cluster like `-czf` gives (`-f`, none) so its value is the
+/// next word.
+fn split_option<'a>(
+    arg: &'a str,
+    path_options: &[&'a str],
+    value_options: &[&'a str],
+) -> (&'a str, Option<&'a str>) {
+    if arg.starts_with("--") {
+        return match arg.split_once('=') {
+            Some((option, value)) => (option, Some(value)),
+            None => (arg, None),
+        };
+    }
+    let takes_value = |o: &str| path_options.contains(&o) || value_options.contains(&o);
+    if takes_value(arg) {
+        return (arg, None);
+    }
+    if arg.len() > 2 && arg.is_char_boundary(2) {
+        // `-o/tmp/x`
+        let (option, value) = arg.split_at(2);
+        if takes_value(option) {
+            return (option, Some(value));
+        }
+        // `-czf`: the last flag in a cluster may take the next word.
+        if let Some(last) = arg.chars().last() {
+            let (start, _) = arg.split_at(arg.len() - last.len_utf8());
+            if start.chars().skip(1).all(|c| c.is_ascii_alphabetic()) {
+                let flag = format!("-{}", last);
+                if let Some(option) = path_options
+                    .iter()
+                    .chain(value_options)
+                    .find(|o| **o == flag)
+                {
+                    return (option, None);
+                }
+            }
+        }
+    }
+    (arg, None)
+}
+
+/// Whether an argument of an unknown command is probably a path.
+fn looks_like_path(arg: &str, cwd: &Path) -> bool {
+    if arg.contains("://") {
+        return false;
+    }
+    arg.contains('/')
+        || arg.contains('\\')
+        || arg.starts_with('~')
+        || arg.starts_with('.')
+        || arg.starts_with("$HOME")
+        || cwd.join(arg).exists()
+}
+
+/// Terminal streams, `/dev/null` and process substitutions aren't files.
+pub(crate) fn is_stream(raw: &str) -> bool {
+    matches!(
+        raw,
+        "/dev/null" | "/dev/stdout" | "/dev/stderr" | "/dev/stdin" | "/dev/tty" | "NUL" | "nul"
+    ) || raw.starts_with("/dev/fd/")
+}
+
+/// Resolve `raw` against `cwd`, expanding `~` and `$HOME`.
+pub(crate) fn resolve(raw: &str, cwd: &Path) -> PathBuf {
+    let home = || dirs::home_dir().unwrap_or_else(|| cwd.to_path_buf());
+    let path = if raw == "~" || raw == "$HOME" || raw == "${HOME}" {
+        home()
+    } else if let Some(rest) = raw
+        .strip_prefix("~/")
+        .or_else(|| raw.strip_prefix("$HOME/"))
+        .or_else(|| raw.strip_prefix("${HOME}/"))
+    {
+        home().join(rest)
+    } else {
+        PathBuf::from(raw)
+    };
+    if path.is_absolute() {
+        path
+    } else {
+        cwd.join(path)
+    }
+}
+
+/// Resolve `raw`, expanding glob patterns to the files they match. A
+/// pattern with no matches resolves to the deepest directory before the
+/// first wildcard, which is where the shell would have looked.
+fn expand(raw: &str, cwd: &Path) -> Vec<CommandPath> {
+    let path = resolve(raw, cwd);
+    let (matches, base) = if raw.contains(['*', '?', '[']) {
+        expand_glob(&path)
+    } else {
+        (vec![path.clone()], None)
+    };
+    let matches = if matches.is_empty() {
+        vec![base.unwrap_or(path)]
+    } else {
+        matches
+    };
+    matches
+        .into_iter()
+        .map(|path| CommandPath {
+            raw: raw.to_string(),
+            path,
+        })
+        .collect()
+}
+
+/// Resolve `raw` to the paths the shell would pass: the glob's matches
+/// (none if nothing matches), or the path itself when it isn't a glob.
+pub(crate) fn glob_paths(raw: &str, cwd: &Path) -> Vec<PathBuf> {
+    let path = resolve(raw, cwd);
+    if raw.contains(['*', '?', '[']) {
+        expand_glob(&path).0
+    } else {
+        vec![path]
+    }
+}
+
+/// The paths matching a glob in sorted order, and the directory before its
+/// first wildcard.
+fn expand_glob(path: &Path) -> (Vec<PathBuf>, Option<PathBuf>) {
+    let mut matches = vec![PathBuf::new()];
+    let mut base: Option<PathBuf> = None;
+    for component in path.components() {
+        let part = component.as_os_str().to_string_lossy();
+        if !matches!(component, Component::Normal(_)) || !part.contains(['*', '?', '[']) {
+            for m in &mut matches {
+                m.push(component.as_os_str());
+            }
+            continue;
+        }
+        base.get_or_insert_with(|| matches[0].clone());
+        let mut next = Vec::new();
+        for dir in &matches {
+            let Ok(entries) = std::fs::read_dir(dir) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                if glob_match(&part, &name) && next.len() < MAX_GLOB_MATCHES {
+                    next.push(dir.join(&name));
+                }
+            }
+        }
+        // Sorted, like the shell's expansion.
+        next.sort();
+        matches = next;
+    }
+    (matches, base)
+}
+
+/// Shell wildcard matching for one path component: `*`, `?` and `[...]`
+/// (with `!`/`^` negation and ranges). Like the shell, wildcards don't
+/// match a leading `.`.
+fn glob_match(pattern: &str, name: &str) -> bool {
+    if name.starts_with('.') && !pattern.starts_with('.') {
+        return false;
+    }
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    match_from(&pattern, &name)
+}
+
+fn match_from(pattern: &[char], name: &[char]) -> bool {
+    match pattern.first() {
+        None => name.is_empty(),
+        Some('*') => (0..=name.len()).any(|i| match_from(&pattern[1..], &name[i..])),
+        Some('?') => !name.is_empty() && match_from(&pattern[1..], &name[1..]),
+        Some('[') => {
+            let Some(close) = pattern.iter().skip(2).position(|c| *c == ']') else {
+                return name.first() == Some(&'[') && match_from(&pattern[1..], &name[1..]);
+            };
+            let class = &pattern[1..close + 2];
+            let Some(c) = name.first() else { return false };
+            let (negated, class) = match class.first() {
+                Some('!') | Some('^') => (true, &class[1..]),
+                _ => (false, class),
+            };
+            let mut hit = false;
+            let mut i = 0;
+            while i < class.len() {
+                if class.get(i + 1) == Some(&'-') && i + 2 < class.len() {
+                    hit |= (class[i]..=class[i + 2]).contains(c);
+                    i += 3;
+                } else {
+                    hit |= class[i] == *c;
+                    i += 1;
+                }
+            }
+            hit != negated && match_from(&pattern[close + 3..], &name[1..])
+        }
+        Some(p) => name.first() == Some(p) && match_from(&pattern[1..], &name[1..]),
+    }
+}
+
+/// Fallback for command lines the parser can't handle: whitespace-split
+/// tokens that look like paths.
+fn guess_tokens(cmd: &str, cwd: &Path) -> Vec<CommandPath> {
+    cmd.split_whitespace()
+        .map(|t| t.trim_matches(|c| c == '"' || c == '\''))
+        .filter(|t| !t.starts_with('-') && looks_like_path(t, cwd))
+        .flat_map(|t| expand(t, cwd))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw_paths(cmd: &str, cwd: &Path) -> Vec<String> {
+        command_paths(cmd, cwd).into_iter().map(|p| p.raw).collect()
+    }
+
+    #[test]
+    fn test_known_commands_and_options() {
+        let cwd = Path::new("/work");
+        assert_eq!(
+            raw_paths("cat notes.txt 'my file.txt'", cwd),
+            vec!["notes.txt", "my file.txt"]
+        );
+        assert_eq!(raw_paths("grep -rn TODO src", cwd), vec!["src"]);
+        assert_eq!(
+            raw_paths("grep -e TODO -f pats src", cwd),
+            vec!["pats", "src"]
+        );
+        assert_eq!(
+            raw_paths("tar -czf out.tgz dir", cwd),
+            vec!["out.tgz", "dir"]
+        );
+        assert_eq!(raw_paths("sort -o/tmp/s data", cwd), vec!["/tmp/s", "data"]);
+        assert_eq!(
+            raw_paths("tool --config=/etc/x --level=3", cwd),
+            vec!["/etc/x"]
+        );
+        assert_eq!(
+            raw_paths("curl -d @/etc/passwd https://x.y/z", cwd),
+            vec!["/etc/passwd"]
+        );
+        assert_eq!(
+            raw_paths("echo a/b > out.txt 2>/dev/null", cwd),
+            vec!["out.txt"]
+        );
+        assert_eq!(
+            raw_paths("dd if=/dev/sda of=disk.img", cwd),
+            vec!["/dev/sda", "disk.img"]
+        );
+        assert_eq!(raw_paths("sudo chmod 600 key", cwd), vec!["key"]);
+        assert_eq!(
+            raw_paths("find . -name '*.rs' -newer ref", cwd),
+            vec![".", "ref"]
+        );
+    }
+
+    #[test]
+    fn test_cd_moves_the_working_directory() {
+        let paths = command_paths("cd .. && cat passwd", Path::new("/work/a"));
+        let resolved: Vec<_> = paths.iter().map(|p| p.path.clone()).collect();
+        assert_eq!(
+            resolved,
+            vec![
+                PathBuf::from("/work/a/.."),
+                PathBuf::from("/work/a/../passwd")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_inline_code_and_find_exec() {
+        let cwd = Path::new("/work");
+        assert_eq!(
+            raw_paths("bash -c \"cat /etc/passwd\"", cwd),
+            vec!["/etc/passwd"]
+        );
+        assert_eq!(
+            raw_paths("sh -c 'cd /tmp && cp /etc/shadow .'", cwd),
+            vec!["/tmp", "/etc/shadow", "."]
+        );
+        assert_eq!(
+            command_paths("sh -c 'cd /tmp && cp /etc/shadow .'", cwd)[2].path,
+            PathBuf::from("/tmp/.")
+        );
+        assert_eq!(raw_paths("sudo bash -lc 'rm -rf ~/x'", cwd), vec!["~/x"]);
+        assert_eq!(raw_paths("eval 'rm notes.txt'", cwd), vec!["notes.txt"]);
+        assert_eq!(
+            raw_paths("find . -exec cat /etc/passwd {} +", cwd),
+            vec!["/etc/passwd", "."]
+        );
+        assert_eq!(
+            raw_paths(
+                "find src -name '*.o' -execdir rm {} \\; -exec sh -c 'cat /etc/hosts' \\;",
+                cwd
+            ),
+            vec!["/etc/hosts", "src"]
+        );
+        assert_eq!(
+            raw_paths("python3 -c \"print(open('/etc/passwd').read())\"", cwd),
+            vec!["/etc/passwd"]
+        );
+        assert_eq!(
+            raw_paths("python3 ./script.py -c x", cwd),
+            vec!["./script.py"]
+        );
+        assert_eq!(
+            raw_paths("bash <<< 'cat /etc/passwd'", cwd),
+            vec!["/etc/passwd"]
+        );
+        assert_eq!(raw_paths("trap 'rm -rf ~/x' EXIT", cwd), vec!["~/x"]);
+        assert_eq!(raw_paths("alias ll='ls /etc'", cwd), vec!["/etc"]);
+
+        assert!(runs_inline_code("ls && sudo sh -c 'rm x'"));
+        assert!(runs_inline_code("find . -exec bash -c 'rm {}' \\;"));
+        assert!(!runs_inline_code("find . -exec rm {} +"));
+        assert!(!runs_inline_code("python3 script.py"));
+    }
+
+    #[test]
+    fn test_globs_expand_against_cwd() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["a.txt", "b.txt", "c.md", ".hidden.txt"] {
+            std::fs::write(dir.path().join(name), "").unwrap();
+        }
+        let mut found: Vec<_> = command_paths("cat *.txt", dir.path())
+            .into_iter()
+            .map(|p| p.path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        found.sort();
+        assert_eq!(found, vec!["a.txt", "b.txt"]);
+
+        // No match: the directory the shell would have searched.
+        let paths = command_paths("cat /nonexistent-dir/*.txt", dir.path());
+        assert_eq!(paths[0].path, PathBuf::from("/nonexistent-dir"));
+
+        assert!(glob_match("[a-c]?.t*", "b1.txt"));
+        assert!(!glob_match("[!a-c]*", "cat"));
+    }
+}
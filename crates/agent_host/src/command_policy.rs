@@ -0,0 +1,519 @@
+//! User-editable command policy.
+//!
+//! The executor's built-in lists (`SAFE_COMMANDS`, `DANGEROUS_COMMANDS`, ...)
+//! are the default policy. A [`CommandPolicy`] file layers the user's own
+//! rules on top: each rule matches simple commands by glob or regex, can be
+//! limited to some directories or modes, and allows, confirms, requires 2FA
+//! for, or denies what it matches. Exact command lines the user picked
+//! "Always allow" for in the confirmation dialog are kept in the same file.
+//!
+//! Rules are tried in file order against every simple command of a line
+//! (the segments `explain_command` reports); the first rule that applies
+//! sets that segment's level. A rule matches a segment as written or with
+//! its wrappers and program directory stripped, so `rm *` also catches
+//! `command rm x`, `env rm x` and `/bin/rm x`. Segments no rule matches keep their built-in
+//! level. Allowing can't waive what the built-in lists block, or their
+//! sudo and 2FA requirements -- a rule can only relax confirmation prompts.
+//!
+//! The file is JSON, e.g.:
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "rules": [
+//!     { "name": "no pushes", "glob": "git push*", "action": "deny" },
+//!     { "glob": "cargo *", "action": "allow", "dirs": ["~/code"], "modes": ["Build"] },
+//!     { "regex": "^npm (ci|install)\\b", "action": "confirm" }
+//!   ],
+//!   "always_allow": ["make test"]
+//! }
+//! ```
+
+use anyhow::{bail, Context, Result};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use shared::skill::Mode;
+use std::path::{Path, PathBuf};
+
+use crate::executor::{explain_command, CommandClassification, DangerLevel};
+use crate::shell_syntax::{self, program_name, unwrap_command};
+
+/// Format version written by this build. Files from a newer build are
+/// refused rather than half-understood.
+pub const POLICY_VERSION: u32 = 1;
+
+/// What a rule does with the commands it matches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PolicyAction {
+    /// Run without asking.
+    Allow,
+    /// Ask before running.
+    Confirm,
+    /// Run only with an active 2FA session.
+    RequireAuth,
+    /// Never run.
+    Deny,
+}
+
+impl PolicyAction {
+    pub fn level(self) -> DangerLevel {
+        match self {
+            PolicyAction::Allow => DangerLevel::Safe,
+            PolicyAction::Confirm => DangerLevel::NeedsConfirmation,
+            PolicyAction::RequireAuth => DangerLevel::NeedsAuth,
+            PolicyAction::Deny => DangerLevel::Blocked,
+        }
+    }
+
+    fn verb(self) -> &'static str {
+        match self {
+            PolicyAction::Allow => "allowed",
+            PolicyAction::Confirm => "needs confirmation",
+            PolicyAction::RequireAuth => "needs 2FA",
+            PolicyAction::Deny => "denied",
+        }
+    }
+}
+
+/// One allow/deny rule. Exactly one of `glob` and `regex` is set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PolicyRule {
+    /// Label shown when explaining a decision; the pattern if empty.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub name: String,
+    /// Shell-style pattern for the whole simple command: `*` matches any
+    /// run of characters, `?` any one. Case-insensitive.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub glob: Option<String>,
+    /// Regular expression searched for in the simple command.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub regex: Option<String>,
+    pub action: PolicyAction,
+    /// Only applies when the working directory is inside one of these
+    /// (`~` allowed). Empty means everywhere.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dirs: Vec<String>,
+    /// Only applies in these modes. Empty means every mode.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub modes: Vec<Mode>,
+    #[serde(skip)]
+    matcher: Option<Regex>,
+}
+
+impl PolicyRule {
+    /// A rule matching simple commands against a shell-style `pattern`.
+    pub fn glob(pattern: &str, action: PolicyAction) -> Result<Self> {
+        Self {
+            name: String::new(),
+            glob: Some(pattern.to_string()),
+            regex: None,
+            action,
+            dirs: Vec::new(),
+            modes: Vec::new(),
+            matcher: None,
+        }
+        .compiled()
+    }
+
+    /// A rule matching simple commands against a regular expression.
+    pub fn regex(pattern: &str, action: PolicyAction) -> Result<Self> {
+        Self {
+            name: String::new(),
+            glob: None,
+            regex: Some(pattern.to_string()),
+            action,
+            dirs: Vec::new(),
+            modes: Vec::new(),
+            matcher: None,
+        }
+        .compiled()
+    }
+
+    pub fn named(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    pub fn with_dirs(mut self, dirs: Vec<String>) -> Self {
+        self.dirs = dirs;
+        self
+    }
+
+    pub fn with_modes(mut self, modes: Vec<Mode>) -> Self {
+        self.modes = modes;
+        self
+    }
+
+    /// The name, or the pattern for unnamed rules.
+    pub fn label(&self) -> &str {
+        if !self.name.is_empty() {
+            &self.name
+        } else {
+            self.glob.as_deref().or(self.regex.as_deref()).unwrap_or("")
+        }
+    }
+
+    fn compiled(mut self) -> Result<Self> {
+        let matcher = match (&self.glob, &self.regex) {
+            (Some(glob), None) => RegexBuilder::new(&glob_to_regex(glob))
+                .case_insensitive(true)
+                .build()?,
+            (None, Some(regex)) => Regex::new(regex)?,
+            _ => bail!("a rule needs exactly one of \"glob\" and \"regex\""),
+        };
+        self.matcher = Some(matcher);
+        Ok(self)
+    }
+
+    fn applies(&self, command: &str, scope: &PolicyScope) -> bool {
+        if !self.modes.is_empty() && !scope.mode.is_some_and(|m| self.modes.contains(&m)) {
+            return false;
+        }
+        if !self.dirs.is_empty() {
+            let Some(cwd) = scope.cwd else {
+                return false;
+            };
+            if !self.dirs.iter().any(|d| cwd.starts_with(expand_home(d))) {
+                return false;
+            }
+        }
+        let Some(matcher) = &self.matcher else {
+            return false;
+        };
+        match_forms(command)
+            .iter()
+            .any(|form| matcher.is_match(form))
+    }
+}
+
+/// The texts a rule is matched against: the segment as written and, when
+/// it differs, the wrapped command (`sudo`, `env`, `command`, ... peeled
+/// off) with its program name bare (`/bin/rm` as `rm`).
+fn match_forms(command: &str) -> Vec<String> {
+    let mut forms = vec![command.trim().to_string()];
+    let Some(parsed) = shell_syntax::parse(command)
+        .ok()
+        .and_then(|commands| commands.into_iter().next())
+    else {
+        return forms;
+    };
+    let mut words: &[String] = &parsed.words;
+    while let Some((_, inner)) = unwrap_command(words) {
+        if inner.is_empty() {
+            break;
+        }
+        words = inner;
+    }
+    if let Some((name, args)) = words.split_first() {
+        let form = std::iter::once(program_name(name))
+            .chain(args.iter().cloned())
+            .collect::<Vec<_>>()
+            .join(" ");
+        if form != forms[0] {
+            forms.push(form);
+        }
+    }
+    forms
+}
+
+/// Where a command is about to run, for rules limited to some directories
+/// or modes. Rules with such limits never apply to an unknown scope.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PolicyScope<'a> {
+    pub cwd: Option<&'a Path>,
+    pub mode: Option<Mode>,
+}
+
+/// The user's rules and always-allowed commands, on top of the built-in
+/// lists. The default policy has neither, so the built-in lists decide.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandPolicy {
+    pub version: u32,
+    #[serde(default)]
+    pub rules: Vec<PolicyRule>,
+    /// Exact command lines the user chose to always allow.
+    #[serde(default)]
+    pub always_allow: Vec<String>,
+}
+
+impl Default for CommandPolicy {
+    fn default() -> Self {
+        Self {
+            version: POLICY_VERSION,
+            rules: Vec::new(),
+            always_allow: Vec::new(),
+        }
+    }
+}
+
+impl CommandPolicy {
+    /// Parse a policy file, compiling every rule's pattern. Errors name the
+    /// first bad rule.
+    pub fn from_json(json: &str) -> Result<Self> {
+        let mut policy: Self = serde_json::from_str(json)?;
+        if policy.version > POLICY_VERSION {
+            bail!(
+                "policy version {} is newer than this app understands ({})",
+                policy.version,
+                POLICY_VERSION
+            );
+        }
+        policy.rules = std::mem::take(&mut policy.rules)
+            .into_iter()
+            .enumerate()
+            .map(|(i, rule)| {
+                let label = rule.label().to_string();
+                rule.compiled()
+                    .with_context(|| format!("rule {} (`{}`)", i + 1, label))
+            })
+            .collect::<Result<_>>()?;
+        Ok(policy)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        Self::from_json(&contents).with_context(|| format!("in {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Remember `cmd` (the exact line) as always allowed.
+    pub fn allow_always(&mut self, cmd: &str) {
+        let cmd = cmd.trim();
+        if !self.always_allow.iter().any(|c| c == cmd) {
+            self.always_allow.push(cmd.to_string());
+        }
+    }
+
+    /// Whether "Always allow" would let `cmd` run unasked: the built-in
+    /// lists don't block it or require sudo or 2FA for it.
+    pub fn can_always_allow(&self, cmd: &str) -> bool {
+        explain_command(cmd).level <= DangerLevel::Dangerous
+    }
+
+    /// Classify `cmd` under this policy. Shorthand for
+    /// `explain_policy(cmd, scope).level`.
+    pub fn classify(&self, cmd: &str, scope: &PolicyScope) -> DangerLevel {
+        self.explain_policy(cmd, scope).level
+    }
+
+    /// Classify `cmd` like `explain_command`, then apply the always-allowed
+    /// list and the first matching rule of each segment. Each segment's
+    /// reason says which rule decided it, or gives the built-in reason.
+    pub fn explain_policy(&self, cmd: &str, scope: &PolicyScope) -> CommandClassification {
+        let mut classification = explain_command(cmd);
+
+        if self.always_allow.iter().any(|c| c == cmd.trim()) {
+            if classification.level <= DangerLevel::Dangerous {
+                return CommandClassification::single(
+                    cmd,
+                    DangerLevel::Safe,
+                    "you chose to always allow this exact command".to_string(),
+                );
+            }
+            for segment in &mut classification.segments {
+                if segment.level > DangerLevel::Dangerous {
+                    segment.reason = format!(
+                        "{} (always allowed, but that can't waive this)",
+                        segment.reason
+                    );
+                }
+            }
+        }
+
+        for segment in &mut classification.segments {
+            let Some(rule) = self
+                .rules
+                .iter()
+                .find(|r| r.applies(&segment.command, scope))
+            else {
+                continue;
+            };
+            let level = rule.action.level();
+            if level < segment.level && segment.level > DangerLevel::Dangerous {
+                segment.reason = format!(
+                    "{} (policy rule `{}` would allow it, but can't waive this)",
+                    segment.reason,
+                    rule.label()
+                );
+                continue;
+            }
+            segment.level = level;
+            segment.reason = format!("{} by policy rule `{}`", rule.action.verb(), rule.label());
+        }
+        classification.level = classification
+            .segments
+            .iter()
+            .map(|s| s.level)
+            .max()
+            .unwrap_or(DangerLevel::NeedsConfirmation);
+        classification
+    }
+}
+
+/// Anchored regex for a shell-style glob.
+pub(crate) fn glob_to_regex(glob: &str) -> String {
+    let mut out = String::from("^");
+    for c in glob.chars() {
+        match c {
+            '*' => out.push_str(".*"),
+            '?' => out.push('.'),
+            c => out.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    out.push('$');
+    out
+}
+
+pub(crate) fn expand_home(dir: &str) -> PathBuf {
+    match (dir.strip_prefix('~'), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest.trim_start_matches('/')),
+        _ => PathBuf::from(dir),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rules_override_builtin_levels() {
+        let policy = CommandPolicy {
+            rules: vec![
+                PolicyRule::glob("git push*", PolicyAction::Deny)
+                    .unwrap()
+                    .named("no pushes"),
+                PolicyRule::regex(r"^cargo (build|test)\b", PolicyAction::Allow).unwrap(),
+                PolicyRule::glob("rm *", PolicyAction::Allow).unwrap(),
+            ],
+            ..Default::default()
+        };
+        let scope = PolicyScope::default();
+
+        let push = policy.explain_policy("git status && git push origin main", &scope);
+        assert_eq!(push.level, DangerLevel::Blocked);
+        assert_eq!(push.segments[0].level, DangerLevel::Safe);
+        assert!(push.segments[1].reason.contains("`no pushes`"));
+
+        assert_eq!(
+            policy.classify("cargo test --all", &scope),
+            DangerLevel::Safe
+        );
+        assert_eq!(
+            crate::executor::classify_command("cargo test --all"),
+            DangerLevel::NeedsConfirmation
+        );
+
+        // Allowing can't waive the 2FA requirement on rm.
+        let rm = policy.explain_policy("rm old.txt", &scope);
+        assert_eq!(rm.level, DangerLevel::NeedsAuth);
+        assert!(rm.segments[0].reason.contains("can't waive"));
+    }
+
+    #[test]
+    fn test_scoped_rules_and_always_allow() {
+        let mut policy = CommandPolicy {
+            rules: vec![PolicyRule::glob("make *", PolicyAction::Allow)
+                .unwrap()
+                .with_dirs(vec!["/work".to_string()])
+                .with_modes(vec![Mode::Build])],
+            ..Default::default()
+        };
+        let build = PolicyScope {
+            cwd: Some(Path::new("/work/app")),
+            mode: Some(Mode::Build),
+        };
+        let fix = PolicyScope {
+            mode: Some(Mode::Fix),
+            ..build
+        };
+        assert_eq!(policy.classify("make all", &build), DangerLevel::Safe);
+        assert_eq!(
+            policy.classify("make all", &fix),
+            DangerLevel::NeedsConfirmation
+        );
+        assert_eq!(
+            policy.classify("make all", &PolicyScope::default()),
+            DangerLevel::NeedsConfirmation
+        );
+
+        policy.allow_always(" npm run deploy ");
+        policy.allow_always("npm run deploy");
+        assert_eq!(policy.always_allow.len(), 1);
+        assert_eq!(policy.classify("npm run deploy", &fix), DangerLevel::Safe);
+        assert!(!policy.can_always_allow("rm -rf build"));
+    }
+
+    #[test]
+    fn test_rules_see_through_wrappers_and_paths() {
+        let policy = CommandPolicy {
+            rules: vec![PolicyRule::glob("rm *", PolicyAction::Deny).unwrap()],
+            ..Default::default()
+        };
+        let scope = PolicyScope::default();
+        for cmd in [
+            "rm -rf build",
+            "command rm -rf build",
+            "env rm -rf build",
+            "env FOO=1 rm -rf build",
+            "/bin/rm -rf build",
+            "sudo /usr/bin/rm -rf build",
+            "nohup rm -rf build",
+            "ls && command rm -rf build",
+        ] {
+            let explained = policy.explain_policy(cmd, &scope);
+            assert_eq!(explained.level, DangerLevel::Blocked, "{cmd}");
+            assert!(
+                explained
+                    .segments
+                    .iter()
+                    .any(|s| s.reason.contains("`rm *`")),
+                "{cmd}"
+            );
+        }
+        assert_ne!(policy.classify("ls -la", &scope), DangerLevel::Blocked);
+
+        assert_eq!(
+            match_forms("env FOO=1 /bin/rm -rf build"),
+            vec!["env FOO=1 /bin/rm -rf build", "rm -rf build"]
+        );
+        assert_eq!(match_forms("rm -rf build"), vec!["rm -rf build"]);
+    }
+
+    #[test]
+    fn test_policy_file_round_trip() {
+        let json = r#"{
+            "version": 1,
+            "rules": [
+                { "name": "no curl", "glob": "curl *", "action": "deny" },
+                { "regex": "^docker ", "action": "require_auth", "modes": ["Fix"] }
+            ],
+            "always_allow": ["make test"]
+        }"#;
+        let policy = CommandPolicy::from_json(json).unwrap();
+        assert_eq!(policy.rules.len(), 2);
+        assert_eq!(
+            policy.classify("curl https://x", &PolicyScope::default()),
+            DangerLevel::Blocked
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("command_policy.json");
+        policy.save(&path).unwrap();
+        let loaded = CommandPolicy::load(&path).unwrap();
+        assert_eq!(loaded.rules[1].modes, vec![Mode::Fix]);
+        assert_eq!(loaded.always_allow, vec!["make test".to_string()]);
+
+        let bad = r#"{ "version": 1, "rules": [{ "regex": "(", "action": "deny" }] }"#;
+        let err = CommandPolicy::from_json(bad).unwrap_err();
+        assert!(format!("{:#}", err).contains("rule 1"));
+        assert!(CommandPolicy::from_json(r#"{ "version": 2 }"#).is_err());
+    }
+}
@@ -12,7 +12,12 @@
 //! - **LRU auto-pruning**: when the Critical threshold is hit, the least
 //!   valuable documents (fewest references + oldest access) are evicted
 //!   automatically to bring usage back under the target budget.
+//!
+//! Budgets come from the model's real context window via
+//! [`TokenBudget::for_model`], which reads the provider's model catalog;
+//! the fixed presets remain for callers that know the window up front.
 
+use providers::catalog::ModelCatalogs;
 use std::collections::HashMap;
 use std::time::{Duration, Instant};
 
@@ -30,34 +35,42 @@ pub struct TokenBudget {
 }
 
 impl TokenBudget {
-    /// Conservative budget for GPT-4 (8k context)
-    pub fn gpt4_8k() -> Self {
+    /// Budget for a model with `max_context_tokens` of context. Small
+    /// windows need most of their space for context; large ones keep usage
+    /// (and cost) well below the limit.
+    pub fn for_context_window(max_context_tokens: usize) -> Self {
+        let (target, warning, prune) = match max_context_tokens {
+            0..=8_192 => (0.7, 0.8, 0.9), // Use 70%, warn at 80%, prune at 90%
+            8_193..=32_768 => (0.75, 0.85, 0.95),
+            _ => (0.5, 0.7, 0.8), // Can use more but keep it reasonable
+        };
         Self {
-            max_context_tokens: 8192,
-            target_usage_percent: 0.7,         // Use 70% for context
-            warning_threshold_percent: 0.8,    // Warn at 80%
-            auto_prune_threshold_percent: 0.9, // Prune at 90%
+            max_context_tokens,
+            target_usage_percent: target,
+            warning_threshold_percent: warning,
+            auto_prune_threshold_percent: prune,
         }
     }
 
+    /// Budget for `model` on `provider`, sized to the context window in the
+    /// provider's model catalog (or a name-based guess if it isn't listed).
+    pub fn for_model(catalogs: &ModelCatalogs, provider: &str, model: &str) -> Self {
+        Self::for_context_window(catalogs.context_window(provider, model))
+    }
+
+    /// Conservative budget for GPT-4 (8k context)
+    pub fn gpt4_8k() -> Self {
+        Self::for_context_window(8192)
+    }
+
     /// Budget for GPT-3.5 (16k context)
     pub fn gpt35_16k() -> Self {
-        Self {
-            max_context_tokens: 16384,
-            target_usage_percent: 0.75,
-            warning_threshold_percent: 0.85,
-            auto_prune_threshold_percent: 0.95,
-        }
+        Self::for_context_window(16384)
     }
 
     /// Budget for Claude (200k context - very generous)
     pub fn claude() -> Self {
-        Self {
-            max_context_tokens: 200000,
-            target_usage_percent: 0.5, // Can use more but keep it reasonable
-            warning_threshold_percent: 0.7,
-            auto_prune_threshold_percent: 0.8,
-        }
+        Self::for_context_window(200000)
     }
 
     /// Calculate actual token limits
@@ -445,6 +458,27 @@ mod tests {
         assert_eq!(budget.warning_tokens(), 6553); // 8192 * 0.8
     }
 
+    #[test]
+    fn test_budget_for_model_uses_catalog() {
+        use providers::catalog::{ModelCatalog, ModelInfo};
+
+        let mut catalogs = ModelCatalogs::default();
+        catalogs.providers.insert(
+            "openai".to_string(),
+            ModelCatalog {
+                fetched_at: 0,
+                models: vec![ModelInfo::new("openai", "kimi-k2.5", Some(262_144))],
+            },
+        );
+        let budget = TokenBudget::for_model(&catalogs, "openai", "kimi-k2.5");
+        assert_eq!(budget.max_context_tokens, 262_144);
+        assert_eq!(budget.target_tokens(), 131_072);
+        // Not listed: falls back to the name-based window.
+        let local = TokenBudget::for_model(&catalogs, "local", "llama3.2:3b");
+        assert_eq!(local.max_context_tokens, 8_192);
+        assert_eq!(local.prune_tokens(), TokenBudget::gpt4_8k().prune_tokens());
+    }
+
     #[test]
     fn test_load_and_access() {
         let mut tracker = ContextUsageTracker::new(TokenBudget::gpt4_8k());
@@ -0,0 +1,784 @@
+//! Dry-run planning for file-changing shell commands.
+//!
+//! A command that needs confirmation used to be shown as its raw text
+//! only. [`plan_command`] works out what it would do to the filesystem
+//! instead: each simple command of the line is read with `shell_syntax`,
+//! globs are expanded in the virtual working directory (as
+//! `command_paths` does for the sandbox), and the concrete files that
+//! would be created, changed, moved, copied, deleted or re-permissioned
+//! are listed. The UI renders the plan like a cleanup plan.
+//!
+//! Well-known commands are understood: `mv`, `cp`, `ln`, `install`, `rm`,
+//! `rmdir`, `mkdir`, `touch`, `sed -i`, `chmod`/`chown`/`chgrp`, `tee`,
+//! `truncate`, `dd of=`, plus output redirections. Read-only commands add
+//! nothing; anything else is listed as unplanned, so a preview never looks
+//! more complete than it is.
+//!
+//! [`CommandPlan::snapshot`] copies the existing files a plan would change
+//! into a snapshot store in the app's data directory before the real run,
+//! and [`restore_snapshots`] puts them back. The store is never inside a
+//! path the plan touches, so `rm -r docs` can't delete its own backup.
+
+use anyhow::{bail, Result};
+use shared::preview_types::{PlannedChange, PlannedChangeKind, PreviewContent};
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use walkdir::WalkDir;
+
+use crate::command_paths::{glob_paths, is_stream, resolve, unwrapped};
+use crate::executor::{classify_command, DangerLevel};
+use crate::shell_syntax::{self, program_name};
+
+/// Most changes listed for one command; recursive operations on big trees
+/// stop here and set [`CommandPlan::truncated`].
+const MAX_PLANNED_CHANGES: usize = 2000;
+
+/// Files larger than this aren't snapshotted.
+const MAX_SNAPSHOT_BYTES: u64 = 50 * 1024 * 1024;
+
+/// Most bytes snapshotted for one command; the files after that are run
+/// without a backup rather than holding up the command.
+const MAX_SNAPSHOT_TOTAL_BYTES: u64 = 200 * 1024 * 1024;
+
+/// Snapshots older than this are deleted when a new one is taken.
+const SNAPSHOT_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// What a command line would do to the filesystem.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandPlan {
+    pub command: String,
+    /// The working directory the plan was made for.
+    pub cwd: PathBuf,
+    pub changes: Vec<PlannedChange>,
+    /// Simple commands that may change files in ways not worked out here.
+    pub unplanned: Vec<String>,
+    /// Whether `changes` stopped at [`MAX_PLANNED_CHANGES`].
+    pub truncated: bool,
+}
+
+/// A file saved before a command ran, and where its copy is kept.
+#[derive(Debug, Clone)]
+pub struct FileSnapshot {
+    pub path: PathBuf,
+    pub saved: PathBuf,
+}
+
+impl CommandPlan {
+    /// One line for the confirmation prompt, e.g. "moves 3 files, deletes 1".
+    pub fn summary(&self) -> String {
+        let count =
+            |f: fn(&PlannedChangeKind) -> bool| self.changes.iter().filter(|c| f(&c.kind)).count();
+        let counts = [
+            ("creates", count(|k| *k == PlannedChangeKind::Create)),
+            ("changes", count(|k| *k == PlannedChangeKind::Modify)),
+            (
+                "moves",
+                count(|k| matches!(k, PlannedChangeKind::Move { .. })),
+            ),
+            (
+                "copies",
+                count(|k| matches!(k, PlannedChangeKind::Copy { .. })),
+            ),
+            ("deletes", count(|k| *k == PlannedChangeKind::Delete)),
+            (
+                "changes permissions of",
+                count(|k| *k == PlannedChangeKind::Permissions),
+            ),
+        ];
+        let mut parts: Vec<String> = counts
+            .iter()
+            .filter(|(_, n)| *n > 0)
+            .map(|(verb, n)| format!("{} {} {}", verb, n, if *n == 1 { "file" } else { "files" }))
+            .collect();
+        if self.truncated {
+            parts.push("and more".to_string());
+        }
+        let mut summary = if parts.is_empty() {
+            "changes no files I can see".to_string()
+        } else {
+            parts.join(", ")
+        };
+        if !self.unplanned.is_empty() {
+            summary.push_str(&format!(
+                " (can't preview {})",
+                self.unplanned
+                    .iter()
+                    .map(|c| format!("`{}`", c))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+        summary
+    }
+
+    /// The plan as preview panel content.
+    pub fn preview(&self) -> PreviewContent {
+        PreviewContent::CommandPlan {
+            command: self.command.clone(),
+            folder: self.cwd.clone(),
+            changes: self.changes.clone(),
+            unplanned: self.unplanned.clone(),
+        }
+    }
+
+    /// Existing files whose current contents the plan would lose or move:
+    /// everything it changes, deletes or moves, and copy or move
+    /// destinations it would overwrite.
+    pub fn files_at_risk(&self) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = Vec::new();
+        for change in &self.changes {
+            let candidates = match &change.kind {
+                PlannedChangeKind::Create => vec![],
+                PlannedChangeKind::Modify
+                | PlannedChangeKind::Delete
+                | PlannedChangeKind::Permissions => vec![&change.path],
+                PlannedChangeKind::Move { to } => vec![&change.path, to],
+                PlannedChangeKind::Copy { to } => vec![to],
+            };
+            for path in candidates {
+                if path.is_file() && !files.contains(path) {
+                    files.push(path.clone());
+                }
+            }
+        }
+        files
+    }
+
+    /// Copy every file in [`files_at_risk`](Self::files_at_risk) into the
+    /// snapshot store (`little-helper/command-snapshots` in the data
+    /// directory, or the temp directory if the plan touches that). Files
+    /// that can't be saved (too big, unreadable, past the total size cap)
+    /// are skipped with a warning rather than holding up the command.
+    pub fn snapshot(&self) -> Vec<FileSnapshot> {
+        let stores = [
+            dirs::data_dir().map(|d| d.join("little-helper")),
+            Some(std::env::temp_dir()),
+        ];
+        let Some(store) = stores
+            .into_iter()
+            .flatten()
+            .map(|d| d.join("command-snapshots"))
+            .find(|store| !self.touches(store))
+        else {
+            tracing::warn!(
+                "no snapshot store outside the paths `{}` changes",
+                self.command
+            );
+            return Vec::new();
+        };
+        prune_store(&store);
+        self.snapshot_in(&store.join(uuid::Uuid::new_v4().to_string()))
+    }
+
+    /// [`snapshot`](Self::snapshot) into `store`, which must not be inside
+    /// a path the plan touches.
+    pub fn snapshot_in(&self, store: &Path) -> Vec<FileSnapshot> {
+        if self.touches(store) {
+            tracing::warn!(
+                "not snapshotting into {}, which `{}` changes",
+                store.display(),
+                self.command
+            );
+            return Vec::new();
+        }
+        let mut total = 0;
+        let mut snapshots = Vec::new();
+        for (i, path) in self.files_at_risk().into_iter().enumerate() {
+            let Some(size) = snapshot_size(&path, store) else {
+                continue;
+            };
+            if total + size > MAX_SNAPSHOT_TOTAL_BYTES {
+                tracing::warn!(
+                    "not snapshotting {} or later files: over {} MB",
+                    path.display(),
+                    MAX_SNAPSHOT_TOTAL_BYTES / (1024 * 1024)
+                );
+                break;
+            }
+            let saved = store.join(i.to_string());
+            let copied = std::fs::create_dir_all(store).and_then(|()| std::fs::copy(&path, &saved));
+            match copied {
+                Ok(_) => {
+                    total += size;
+                    snapshots.push(FileSnapshot { path, saved });
+                }
+                Err(e) => tracing::warn!("couldn't snapshot {}: {}", path.display(), e),
+            }
+        }
+        snapshots
+    }
+
+    /// Whether `path` is, or is inside, something the plan changes, moves
+    /// or writes to.
+    fn touches(&self, path: &Path) -> bool {
+        let path = tidy(path);
+        self.changes.iter().any(|change| {
+            let to = match &change.kind {
+                PlannedChangeKind::Move { to } | PlannedChangeKind::Copy { to } => Some(to),
+                _ => None,
+            };
+            std::iter::once(&change.path)
+                .chain(to)
+                .any(|touched| path.starts_with(touched))
+        })
+    }
+
+    fn push(&mut self, path: PathBuf, kind: PlannedChangeKind) {
+        if self.changes.len() >= MAX_PLANNED_CHANGES {
+            self.truncated = true;
+            return;
+        }
+        let kind = match kind {
+            PlannedChangeKind::Move { to } => PlannedChangeKind::Move { to: tidy(&to) },
+            PlannedChangeKind::Copy { to } => PlannedChangeKind::Copy { to: tidy(&to) },
+            other => other,
+        };
+        let change = PlannedChange {
+            path: tidy(&path),
+            kind,
+        };
+        if !self.changes.contains(&change) {
+            self.changes.push(change);
+        }
+    }
+
+    /// A file written by a redirection, `tee` or similar.
+    fn write(&mut self, path: PathBuf) {
+        if path.is_dir() {
+            return;
+        }
+        let kind = if path.exists() {
+            PlannedChangeKind::Modify
+        } else {
+            PlannedChangeKind::Create
+        };
+        self.push(path, kind);
+    }
+}
+
+/// `path` with `.` and `..` components folded away, without touching the
+/// filesystem, so previews show `/a/c` rather than `/a/b/../c`.
+fn tidy(path: &Path) -> PathBuf {
+    let mut tidy = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if tidy.file_name().is_some() => {
+                tidy.pop();
+            }
+            other => tidy.push(other.as_os_str()),
+        }
+    }
+    tidy
+}
+
+/// Put snapshotted files back as they were. Moved or deleted files are
+/// recreated; files the command created are left alone. Returns how many
+/// were restored, or an error naming the ones that couldn't be.
+pub fn restore_snapshots(snapshots: &[FileSnapshot]) -> Result<usize> {
+    let mut restored = 0;
+    let mut failed = Vec::new();
+    for snapshot in snapshots {
+        let result = (|| {
+            if let Some(parent) = snapshot.path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::copy(&snapshot.saved, &snapshot.path).map(|_| ())
+        })();
+        match result {
+            Ok(()) => restored += 1,
+            Err(e) => failed.push(format!("{} ({})", snapshot.path.display(), e)),
+        }
+    }
+    if !failed.is_empty() {
+        bail!("couldn't restore {}", failed.join(", "));
+    }
+    Ok(restored)
+}
+
+/// The size of `path` if it should be snapshotted: not in a version or
+/// snapshot store, and not over [`MAX_SNAPSHOT_BYTES`].
+fn snapshot_size(path: &Path, store: &Path) -> Option<u64> {
+    let in_store =
+        path.starts_with(store) || path.components().any(|c| c.as_os_str() == ".little-helper");
+    let size = std::fs::metadata(path).ok()?.len();
+    (!in_store && size <= MAX_SNAPSHOT_BYTES).then_some(size)
+}
+
+/// Delete snapshots older than [`SNAPSHOT_MAX_AGE`].
+fn prune_store(store: &Path) {
+    let Ok(entries) = std::fs::read_dir(store) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let old = entry
+            .metadata()
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+            .is_some_and(|age| age > SNAPSHOT_MAX_AGE);
+        if old {
+            let _ = std::fs::remove_dir_all(entry.path());
+        }
+    }
+}
+
+/// Work out what `cmd` would do to files when run from `cwd`.
+pub fn plan_command(cmd: &str, cwd: &Path) -> CommandPlan {
+    let mut plan = CommandPlan {
+        command: cmd.trim().to_string(),
+        cwd: cwd.to_path_buf(),
+        changes: Vec::new(),
+        unplanned: Vec::new(),
+        truncated: false,
+    };
+    let commands = match shell_syntax::parse(cmd) {
+        Ok(commands) => commands,
+        Err(_) => {
+            plan.unplanned.push(plan.command.clone());
+            return plan;
+        }
+    };
+
+    let mut cwd = cwd.to_path_buf();
+    for command in &commands {
+        for redirect in command.redirects.iter().filter(|r| r.writes_file()) {
+            if !is_stream(&redirect.target) {
+                plan.write(resolve(&redirect.target, &cwd));
+            }
+        }
+        let Some((name, args)) = unwrapped(command).split_first() else {
+            continue;
+        };
+        let name = program_name(name);
+        if matches!(name.as_str(), "cd" | "pushd") {
+            cwd = match args.iter().find(|a| !a.starts_with('-')) {
+                Some(dir) => tidy(&resolve(dir, &cwd)),
+                None => dirs::home_dir().unwrap_or(cwd),
+            };
+            continue;
+        }
+        if !plan_known(&mut plan, &name, args, &cwd) {
+            let line = shell_quote(unwrapped(command));
+            if classify_command(&line) != DangerLevel::Safe {
+                plan.unplanned.push(command.text.clone());
+            }
+        }
+    }
+    plan
+}
+
+/// Add the changes of one known command; false if `name` isn't known.
+fn plan_known(plan: &mut CommandPlan, name: &str, args: &[String], cwd: &Path) -> bool {
+    match name {
+        "mv" | "cp" | "ln" | "install" => {
+            let args = Args::parse(
+                args,
+                &[
+                    "-t",
+                    "--target-directory",
+                    "-S",
+                    "--suffix",
+                    "-m",
+                    "--mode",
+                    "-o",
+                    "-g",
+                ],
+            );
+            plan_transfer(plan, name, &args, cwd);
+        }
+        "rm" | "unlink" | "shred" => {
+            let args = Args::parse(args, &["-n", "--iterations", "-s", "--size"]);
+            let recursive = args.flag('r', "--recursive") || args.flag('R', "--recursive");
+            for path in args.paths(&args.operands, cwd) {
+                if path.is_dir() {
+                    if recursive && name == "rm" {
+                        plan_tree(plan, &path, PlannedChangeKind::Delete);
+                    }
+                } else if path.exists() {
+                    plan.push(path, PlannedChangeKind::Delete);
+                }
+            }
+        }
+        "rmdir" => {
+            let args = Args::parse(args, &[]);
+            for path in args.paths(&args.operands, cwd) {
+                if path.is_dir() {
+                    plan.push(path, PlannedChangeKind::Delete);
+                }
+            }
+        }
+        "mkdir" => {
+            let args = Args::parse(args, &["-m", "--mode"]);
+            for path in args.paths(&args.operands, cwd) {
+                if !path.exists() {
+                    plan.push(path, PlannedChangeKind::Create);
+                }
+            }
+        }
+        "touch" => {
+            let args = Args::parse(args, &["-d", "--date", "-t", "-r", "--reference"]);
+            for path in args.paths(&args.operands, cwd) {
+                plan.write(path);
+            }
+        }
+        "tee" | "truncate" => {
+            let args = Args::parse(args, &["-s", "--size", "-r", "--reference"]);
+            for path in args.paths(&args.operands, cwd) {
+                if !is_stream(&path.to_string_lossy()) {
+                    plan.write(path);
+                }
+            }
+        }
+        "dd" => {
+            for target in args.iter().filter_map(|a| a.strip_prefix("of=")) {
+                if !is_stream(target) {
+                    plan.write(resolve(target, cwd));
+                }
+            }
+        }
+        "sed" => {
+            let args = Args::parse(
+                args,
+                &["-e", "--expression", "-f", "--file", "-l", "--line-length"],
+            );
+            let in_place = args.flags.iter().any(|f| {
+                f.starts_with("--in-place")
+                    || (!f.starts_with("--") && f.starts_with('-') && f[1..].contains('i'))
+            });
+            if in_place {
+                // Without -e/-f the first operand is the script.
+                let scripted = args.value("-e").is_some()
+                    || args.value("--expression").is_some()
+                    || args.value("-f").is_some()
+                    || args.value("--file").is_some();
+                let files = &args.operands[usize::from(!scripted).min(args.operands.len())..];
+                for path in args.paths(files, cwd) {
+                    if path.is_file() {
+                        plan.push(path, PlannedChangeKind::Modify);
+                    }
+                }
+            }
+        }
+        "chmod" | "chown" | "chgrp" => {
+            let args = Args::parse(args, &["--reference", "--from"]);
+            let recursive = args.flag('R', "--recursive");
+            // The mode or owner comes first unless --reference gave it.
+            let skip = usize::from(args.value("--reference").is_none());
+            let files = &args.operands[skip.min(args.operands.len())..];
+            for path in args.paths(files, cwd) {
+                if recursive && path.is_dir() {
+                    plan_tree(plan, &path, PlannedChangeKind::Permissions);
+                } else if path.exists() {
+                    plan.push(path, PlannedChangeKind::Permissions);
+                }
+            }
+        }
+        _ => return false,
+    }
+    true
+}
+
+/// `mv`, `cp`, `ln` and `install`: sources into a target file or directory.
+fn plan_transfer(plan: &mut CommandPlan, name: &str, args: &Args, cwd: &Path) {
+    let target_dir = args
+        .value("-t")
+        .or_else(|| args.value("--target-directory"))
+        .map(|t| resolve(t, cwd));
+    let (sources, target) = match &target_dir {
+        Some(dir) => (&args.operands[..], dir.clone()),
+        None if name == "ln" && args.operands.len() == 1 => (&args.operands[..], cwd.to_path_buf()),
+        None => match args.operands.split_last() {
+            Some((target, sources)) if !sources.is_empty() => (sources, resolve(target, cwd)),
+            _ => {
+                plan.unplanned
+                    .push(format!("{} {}", name, args.operands.join(" ")));
+                return;
+            }
+        },
+    };
+    let sources = args.paths(sources, cwd);
+    let into_dir = target_dir.is_some() || sources.len() > 1 || target.is_dir();
+    let recursive = args.flag('r', "--recursive")
+        || args.flag('R', "--recursive")
+        || args.flag('a', "--archive");
+
+    for source in sources {
+        let dest = match (into_dir, source.file_name()) {
+            (true, Some(file_name)) => target.join(file_name),
+            _ => target.clone(),
+        };
+        match name {
+            "mv" => {
+                if !source.exists() {
+                    continue;
+                }
+                plan.push(source, PlannedChangeKind::Move { to: dest });
+            }
+            "ln" => plan.write(dest),
+            _ if source.is_dir() => {
+                if !recursive {
+                    continue;
+                }
+                for file in walk_files(&source) {
+                    let relative = file.strip_prefix(&source).unwrap_or(&file);
+                    let to = dest.join(relative);
+                    plan.push(file, PlannedChangeKind::Copy { to });
+                    if plan.truncated {
+                        return;
+                    }
+                }
+            }
+            _ => {
+                if source.exists() {
+                    plan.push(source, PlannedChangeKind::Copy { to: dest });
+                }
+            }
+        }
+    }
+}
+
+/// `dir` itself, then every file under it, with the same change. The
+/// directory goes first so a truncated plan still shows (and
+/// [`CommandPlan::snapshot`] still avoids) the whole tree.
+fn plan_tree(plan: &mut CommandPlan, dir: &Path, kind: PlannedChangeKind) {
+    plan.push(dir.to_path_buf(), kind.clone());
+    for file in walk_files(dir) {
+        plan.push(file, kind.clone());
+        if plan.truncated {
+            return;
+        }
+    }
+}
+
+fn walk_files(dir: &Path) -> impl Iterator<Item = PathBuf> {
+    WalkDir::new(dir)
+        .follow_links(false)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| !e.file_type().is_dir())
+        .map(|e| e.into_path())
+        .take(MAX_PLANNED_CHANGES)
+}
+
+/// A command's arguments split into flags, option values and operands.
+struct Args {
+    flags: Vec<String>,
+    values: Vec<(String, String)>,
+    operands: Vec<String>,
+}
+
+impl Args {
+    /// `value_options` take a value, attached (`--opt=x`, `-tDIR`) or as
+    /// the next word.
+    fn parse(args: &[String], value_options: &[&str]) -> Self {
+        let mut parsed = Args {
+            flags: Vec::new(),
+            values: Vec::new(),
+            operands: Vec::new(),
+        };
+        let mut options_done = false;
+        let mut words = args.iter();
+        while let Some(arg) = words.next() {
+            if options_done || !arg.starts_with('-') || arg.len() == 1 {
+                parsed.operands.push(arg.clone());
+                continue;
+            }
+            if arg == "--" {
+                options_done = true;
+                continue;
+            }
+            if let Some((option, value)) = arg.split_once('=').filter(|_| arg.starts_with("--")) {
+                if value_options.contains(&option) {
+                    parsed.values.push((option.to_string(), value.to_string()));
+                } else {
+                    parsed.flags.push(arg.clone());
+                }
+                continue;
+            }
+            if value_options.contains(&arg.as_str()) {
+                let value = words.next().cloned().unwrap_or_default();
+                parsed.values.push((arg.clone(), value));
+                continue;
+            }
+            let short = arg
+                .get(..2)
+                .filter(|_| !arg.starts_with("--") && arg.len() > 2);
+            match short.filter(|o| value_options.contains(o)) {
+                Some(option) => parsed
+                    .values
+                    .push((option.to_string(), arg[2..].to_string())),
+                None => parsed.flags.push(arg.clone()),
+            }
+        }
+        parsed
+    }
+
+    /// Whether the short flag (alone or in a cluster) or the long one is set.
+    fn flag(&self, short: char, long: &str) -> bool {
+        self.flags.iter().any(|f| {
+            f == long || (!f.starts_with("--") && f.starts_with('-') && f[1..].contains(short))
+        })
+    }
+
+    fn value(&self, option: &str) -> Option<&str> {
+        self.values
+            .iter()
+            .find(|(o, _)| o == option)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Resolve operands, expanding globs to what they match.
+    fn paths(&self, operands: &[String], cwd: &Path) -> Vec<PathBuf> {
+        operands.iter().flat_map(|o| glob_paths(o, cwd)).collect()
+    }
+}
+
+/// Words joined back into a command line, quoting where needed.
+fn shell_quote(words: &[String]) -> String {
+    words
+        .iter()
+        .map(|w| {
+            if !w.is_empty()
+                && w.chars()
+                    .all(|c| c.is_alphanumeric() || "-_./=:,+@%~".contains(c))
+            {
+                w.clone()
+            } else {
+                format!("'{}'", w.replace('\'', r"'\''"))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn kinds(plan: &CommandPlan, root: &Path) -> Vec<(String, PlannedChangeKind)> {
+        plan.changes
+            .iter()
+            .map(|c| {
+                let relative = |p: &Path| p.strip_prefix(root).unwrap_or(p).to_path_buf();
+                let kind = match &c.kind {
+                    PlannedChangeKind::Move { to } => PlannedChangeKind::Move { to: relative(to) },
+                    PlannedChangeKind::Copy { to } => PlannedChangeKind::Copy { to: relative(to) },
+                    other => other.clone(),
+                };
+                (relative(&c.path).to_string_lossy().into_owned(), kind)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_plans_known_commands_with_globs() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir(root.join("docs")).unwrap();
+        fs::create_dir(root.join("old")).unwrap();
+        fs::write(root.join("a.txt"), "a").unwrap();
+        fs::write(root.join("b.txt"), "b").unwrap();
+        fs::write(root.join("docs/c.md"), "c").unwrap();
+        fs::write(root.join("old/a.txt"), "older").unwrap();
+
+        let plan = plan_command("mv *.txt old && sed -i 's/x/y/' docs/c.md > log", root);
+        assert_eq!(
+            kinds(&plan, root),
+            vec![
+                (
+                    "a.txt".into(),
+                    PlannedChangeKind::Move {
+                        to: "old/a.txt".into()
+                    }
+                ),
+                (
+                    "b.txt".into(),
+                    PlannedChangeKind::Move {
+                        to: "old/b.txt".into()
+                    }
+                ),
+                ("log".into(), PlannedChangeKind::Create),
+                ("docs/c.md".into(), PlannedChangeKind::Modify),
+            ]
+        );
+        assert!(plan.unplanned.is_empty());
+        assert_eq!(
+            plan.summary(),
+            "creates 1 file, changes 1 file, moves 2 files"
+        );
+
+        let plan = plan_command("cd docs && cp -r . ../backup; chmod -R 600 ../old", root);
+        let changes = kinds(&plan, root);
+        assert!(changes.contains(&(
+            "docs/c.md".into(),
+            PlannedChangeKind::Copy {
+                to: "backup/c.md".into()
+            }
+        )));
+        assert!(changes.contains(&("old/a.txt".into(), PlannedChangeKind::Permissions)));
+
+        // No matches means nothing to delete, not the directory.
+        assert!(plan_command("rm -f *.log", root).changes.is_empty());
+        let plan = plan_command("make install && rm -r docs", root);
+        assert_eq!(plan.unplanned, vec!["make install".to_string()]);
+        assert_eq!(kinds(&plan, root).len(), 2);
+        assert!(plan_command("ls -la | grep txt", root)
+            .summary()
+            .contains("no files"));
+    }
+
+    #[test]
+    fn test_snapshot_and_restore() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::write(root.join("notes.txt"), "original").unwrap();
+        fs::write(root.join("keep.txt"), "keep").unwrap();
+
+        let plan = plan_command("sed -i s/original/edited/ notes.txt; rm keep.txt", root);
+        let snapshots = plan.snapshot_in(&root.join("store"));
+        assert_eq!(snapshots.len(), 2);
+
+        fs::write(root.join("notes.txt"), "edited").unwrap();
+        fs::remove_file(root.join("keep.txt")).unwrap();
+
+        assert_eq!(restore_snapshots(&snapshots).unwrap(), 2);
+        assert_eq!(
+            fs::read_to_string(root.join("notes.txt")).unwrap(),
+            "original"
+        );
+        assert_eq!(fs::read_to_string(root.join("keep.txt")).unwrap(), "keep");
+    }
+
+    #[test]
+    fn test_restore_after_recursive_delete() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("docs/notes")).unwrap();
+        fs::write(root.join("docs/a.md"), "a").unwrap();
+        fs::write(root.join("docs/notes/b.md"), "b").unwrap();
+
+        let plan = plan_command("rm -r docs", root);
+        // A store inside the deleted folder would go with it.
+        assert!(plan.snapshot_in(&root.join("docs/.store")).is_empty());
+        let snapshots = plan.snapshot_in(&root.join("store/1"));
+        assert_eq!(snapshots.len(), 2);
+        assert!(snapshots
+            .iter()
+            .all(|s| s.saved.starts_with(root.join("store"))));
+
+        fs::remove_dir_all(root.join("docs")).unwrap();
+        assert_eq!(restore_snapshots(&snapshots).unwrap(), 2);
+        assert_eq!(fs::read_to_string(root.join("docs/a.md")).unwrap(), "a");
+        assert_eq!(
+            fs::read_to_string(root.join("docs/notes/b.md")).unwrap(),
+            "b"
+        );
+
+        // Moving the folder doesn't take the store along either.
+        let plan = plan_command("mv docs archive", root);
+        assert!(plan.touches(&root.join("archive/x")));
+        assert!(!plan.touches(&root.join("store")));
+    }
+}
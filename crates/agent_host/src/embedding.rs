@@ -7,12 +7,27 @@
 //!
 //! The `Arc<TextEmbedding>` handle is `Send + Sync`, so the service can be
 //! shared across async tasks without additional locking.
+//!
+//! [`CrossEncoderReranker`] wraps a `fastembed` cross-encoder (BGE reranker
+//! base) for optional re-ranking of retrieved passages; it is a larger
+//! download, so nothing loads it unless asked to.
 
+use crate::retrieval::Reranker;
 use anyhow::Result;
-use fastembed::{EmbeddingModel, InitOptions, TextEmbedding};
+use fastembed::{
+    EmbeddingModel, InitOptions, RerankInitOptions, RerankerModel, TextEmbedding, TextRerank,
+};
 use std::path::PathBuf;
 use std::sync::Arc;
 
+/// Where downloaded models are cached: `$XDG_CACHE_HOME/little-helper/fastembed`.
+fn model_cache_dir() -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("little-helper")
+        .join("fastembed")
+}
+
 /// Thin wrapper around `fastembed::TextEmbedding` that manages model
 /// caching and exposes single-text and batch embedding methods.
 pub struct EmbeddingService {
@@ -25,13 +40,8 @@ impl EmbeddingService {
     /// The model weights are cached under `$XDG_CACHE_HOME/little-helper/fastembed`
     /// to keep them out of the project directory.
     pub fn new() -> Result<Self> {
-        let cache_dir = dirs::cache_dir()
-            .unwrap_or_else(|| PathBuf::from("."))
-            .join("little-helper")
-            .join("fastembed");
-
         let options = InitOptions::new(EmbeddingModel::AllMiniLML6V2)
-            .with_cache_dir(cache_dir)
+            .with_cache_dir(model_cache_dir())
             .with_show_download_progress(false);
 
         let model = TextEmbedding::try_new(options)?;
@@ -58,3 +68,40 @@ impl EmbeddingService {
         Ok(embeddings)
     }
 }
+
+/// A local cross-encoder that scores query and passage together.
+pub struct CrossEncoderReranker {
+    model: Arc<TextRerank>,
+}
+
+impl CrossEncoderReranker {
+    /// Load (or download on first run) the re-ranking model, cached next
+    /// to the embedding model.
+    pub fn new() -> Result<Self> {
+        let options = RerankInitOptions::new(RerankerModel::BGERerankerBase)
+            .with_cache_dir(model_cache_dir())
+            .with_show_download_progress(false);
+
+        let model = TextRerank::try_new(options)?;
+
+        Ok(Self {
+            model: Arc::new(model),
+        })
+    }
+}
+
+impl Reranker for CrossEncoderReranker {
+    fn score(&self, query: &str, passages: &[String]) -> Result<Vec<f32>> {
+        let documents: Vec<&str> = passages.iter().map(String::as_str).collect();
+        let results = self.model.rerank(query, documents, false, None)?;
+
+        // Results come back best first; put the scores in passage order.
+        let mut scores = vec![f32::MIN; passages.len()];
+        for result in results {
+            if let Some(score) = scores.get_mut(result.index) {
+                *score = result.score;
+            }
+        }
+        Ok(scores)
+    }
+}
@@ -0,0 +1,550 @@
+//! Entity and relation extraction for the knowledge graph.
+//!
+//! Ingested documents and finished conversations are read for the people,
+//! projects, tools, places and preferences they mention and the typed
+//! relations between them, which [`ContextManager::apply_extraction`]
+//! merges into the graph. Two extractors run:
+//!
+//! - **Local model**: the text, a chunk at a time, goes to the local Ollama
+//!   model with a JSON schema for its reply. Only the local model is used:
+//!   documents and conversations are extracted in the background, and
+//!   nothing should leave the machine without the user asking.
+//! - **Rules**: regexes for dates, emails, paths and URLs. They always
+//!   run, and are all there is when no local model is configured or it
+//!   can't be reached.
+//!
+//! Every entity and relation keeps the byte span it was found at, so graph
+//! nodes and edges can point back at their source. The model is asked to
+//! quote its evidence; entities it names but the text doesn't contain are
+//! dropped rather than trusted.
+//!
+//! [`ContextManager::apply_extraction`]: crate::context_manager::ContextManager::apply_extraction
+
+use std::ops::Range;
+use std::sync::{Arc, LazyLock};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use shared::agent_api::ChatMessage;
+use shared::settings::ModelProvider;
+
+use crate::chunking::{chunk_document, ChunkerConfig};
+use crate::graph_store::Mode;
+
+/// Categories the rules assign.
+pub const DATE: &str = "Date";
+pub const EMAIL: &str = "Email";
+pub const PATH: &str = "Path";
+pub const URL: &str = "URL";
+
+/// Categories the model may assign.
+const ENTITY_TYPES: &[&str] = &[
+    "Person",
+    "Organization",
+    "Project",
+    "Technology",
+    "Place",
+    "Event",
+    "Preference",
+    "Concept",
+];
+
+/// Confidence of a relation the model doesn't rate.
+const DEFAULT_CONFIDENCE: f32 = 0.8;
+
+const SYSTEM_PROMPT: &str = "You extract a knowledge graph from text. List the people, organizations, projects, technologies, places, events and personal preferences the text mentions, and the relationships between them that the text states. \
+Copy `mention` and `evidence` verbatim from the text. Name relations with short lowercase verb phrases such as \"works on\", \"depends on\", \"prefers\", \"located in\". \
+Do not infer anything the text does not say. Reply with JSON only.";
+
+/// An entity found in a text.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entity {
+    pub label: String,
+    pub category: String,
+    /// Byte span of the mention in the text
+    pub start: usize,
+    pub end: usize,
+}
+
+/// A relation stated in a text.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Relation {
+    pub source: String,
+    pub relation: String,
+    pub target: String,
+    /// 0.0 - 1.0
+    pub confidence: f32,
+    /// Byte span of the statement in the text
+    pub start: usize,
+    pub end: usize,
+}
+
+/// What was extracted from one text.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Extraction {
+    pub entities: Vec<Entity>,
+    pub relations: Vec<Relation>,
+}
+
+impl Extraction {
+    /// Add `other`, found in the part of the text starting at `offset`.
+    fn extend_at(&mut self, other: Extraction, offset: usize) {
+        for mut entity in other.entities {
+            entity.start += offset;
+            entity.end += offset;
+            if !self
+                .entities
+                .iter()
+                .any(|e| e.label == entity.label && e.start == entity.start)
+            {
+                self.entities.push(entity);
+            }
+        }
+        for mut relation in other.relations {
+            relation.start += offset;
+            relation.end += offset;
+            self.relations.push(relation);
+        }
+    }
+}
+
+/// A text waiting to be extracted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtractionJob {
+    /// The document ID, or `thread/<id>` for a conversation
+    pub source_id: String,
+    pub text: String,
+    /// The mode new nodes are filed under
+    pub mode: Mode,
+    /// Content hash of `text`, so unchanged sources aren't extracted twice
+    pub hash: u64,
+}
+
+impl ExtractionJob {
+    pub fn new(source_id: impl Into<String>, text: impl Into<String>, mode: Mode) -> Self {
+        let text = text.into();
+        Self {
+            source_id: source_id.into(),
+            hash: crate::context_manager::content_hash(&text),
+            text,
+            mode,
+        }
+    }
+}
+
+/// A model that answers in JSON following a schema.
+#[async_trait]
+pub trait JsonModel: Send + Sync {
+    /// Reply to `messages` with JSON text matching `schema`.
+    async fn generate_json(
+        &self,
+        messages: Vec<ChatMessage>,
+        schema: &serde_json::Value,
+    ) -> Result<String>;
+}
+
+#[async_trait]
+impl JsonModel for providers::ollama::OllamaClient {
+    async fn generate_json(
+        &self,
+        messages: Vec<ChatMessage>,
+        schema: &serde_json::Value,
+    ) -> Result<String> {
+        Ok(self.generate_structured(messages, schema).await?.0)
+    }
+}
+
+/// Extracts entities and relations with the rules and, if it has one,
+/// a model.
+#[derive(Clone)]
+pub struct Extractor {
+    model: Option<Arc<dyn JsonModel>>,
+    /// How texts are cut up for the model
+    chunker: ChunkerConfig,
+}
+
+impl Default for Extractor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Extractor {
+    /// Rules only.
+    pub fn new() -> Self {
+        Self {
+            model: None,
+            chunker: ChunkerConfig::default()
+                .with_max_tokens(1024)
+                .with_overlap_tokens(0),
+        }
+    }
+
+    pub fn with_model(mut self, model: Arc<dyn JsonModel>) -> Self {
+        self.model = Some(model);
+        self
+    }
+
+    /// Use the local model if the user has "local" among their providers.
+    pub fn for_settings(settings: &ModelProvider) -> Self {
+        let extractor = Self::new();
+        if settings.provider_preference.iter().any(|p| p == "local") {
+            let client = providers::ollama::OllamaClient::new(settings.local_model.clone());
+            extractor.with_model(Arc::new(client))
+        } else {
+            extractor
+        }
+    }
+
+    /// Extract from `job`. Never fails: if the model errors, what the
+    /// rules (and the model, up to the failing chunk) found is returned.
+    pub async fn extract(&self, job: &ExtractionJob) -> Extraction {
+        let mut extraction = extract_with_rules(&job.text);
+        let Some(model) = &self.model else {
+            return extraction;
+        };
+        for chunk in chunk_document(&job.text, &self.chunker) {
+            match extract_with_model(model.as_ref(), &chunk.text).await {
+                Ok(found) => extraction.extend_at(found, chunk.start),
+                Err(e) => {
+                    tracing::warn!(
+                        "Entity extraction for {} fell back to rules: {}",
+                        job.source_id,
+                        e
+                    );
+                    break;
+                }
+            }
+        }
+        extraction
+    }
+}
+
+/// Dates, emails, paths and URLs in `text`.
+pub fn extract_with_rules(text: &str) -> Extraction {
+    static URL_RE: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r#"\bhttps?://[^\s<>()\[\]"'`]+"#).unwrap());
+    static EMAIL_RE: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"\b[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}\b").unwrap()
+    });
+    static DATE_RE: LazyLock<Regex> = LazyLock::new(|| {
+        let month = "(?:Jan(?:uary)?|Feb(?:ruary)?|Mar(?:ch)?|Apr(?:il)?|May|June?|July?|Aug(?:ust)?|Sep(?:t(?:ember)?)?|Oct(?:ober)?|Nov(?:ember)?|Dec(?:ember)?)";
+        Regex::new(&format!(
+            r"\b(?:\d{{4}}-\d{{2}}-\d{{2}}|\d{{1,2}}/\d{{1,2}}/\d{{2,4}}|\d{{1,2}} {m},? \d{{4}}|{m} \d{{1,2}}(?:st|nd|rd|th)?,? \d{{4}})\b",
+            m = month
+        ))
+        .unwrap()
+    });
+    // Unix paths need two segments ("/etc/hosts") unless they start at
+    // home or the working directory ("~/notes", "./build"); the
+    // lookbehind-free prefix group keeps "and/or" out.
+    static PATH_RE: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"(?:^|[\s(\[`'])((?:~|\.{1,2})/[\w.-]+(?:/[\w.-]+)*/?|/[\w.-]+(?:/[\w.-]+)+/?|[A-Za-z]:\\[\w .-]+(?:\\[\w .-]+)*)").unwrap()
+    });
+
+    let mut extraction = Extraction::default();
+    let mut taken: Vec<Range<usize>> = Vec::new();
+    let mut push = |category: &str, span: Range<usize>, extraction: &mut Extraction| {
+        if taken
+            .iter()
+            .any(|t| span.start < t.end && t.start < span.end)
+        {
+            return;
+        }
+        extraction.entities.push(Entity {
+            label: text[span.clone()].to_string(),
+            category: category.to_string(),
+            start: span.start,
+            end: span.end,
+        });
+        taken.push(span);
+    };
+
+    // URLs first, so the paths and emails inside them aren't counted again.
+    for m in URL_RE.find_iter(text) {
+        let trimmed = m.as_str().trim_end_matches(['.', ',', ';', ':', '!', '?']);
+        push(URL, m.start()..m.start() + trimmed.len(), &mut extraction);
+    }
+    for m in EMAIL_RE.find_iter(text) {
+        push(EMAIL, m.range(), &mut extraction);
+    }
+    for m in DATE_RE.find_iter(text) {
+        push(DATE, m.range(), &mut extraction);
+    }
+    for caps in PATH_RE.captures_iter(text) {
+        let m = caps.get(1).unwrap();
+        let trimmed = m.as_str().trim_end_matches(['.', ',', ';', ':']);
+        push(PATH, m.start()..m.start() + trimmed.len(), &mut extraction);
+    }
+
+    extraction.entities.sort_by_key(|e| e.start);
+    extraction
+}
+
+/// The model's reply, as the schema asks for it.
+#[derive(Debug, Deserialize)]
+struct ModelReply {
+    #[serde(default)]
+    entities: Vec<ModelEntity>,
+    #[serde(default)]
+    relations: Vec<ModelRelation>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ModelEntity {
+    name: String,
+    #[serde(rename = "type")]
+    category: String,
+    #[serde(default)]
+    mention: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ModelRelation {
+    source: String,
+    relation: String,
+    target: String,
+    #[serde(default)]
+    evidence: String,
+    #[serde(default)]
+    confidence: Option<f32>,
+}
+
+/// The schema the model's reply must follow.
+fn reply_schema() -> serde_json::Value {
+    serde_json::json!({
+        "type": "object",
+        "properties": {
+            "entities": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "name": {"type": "string"},
+                        "type": {"type": "string", "enum": ENTITY_TYPES},
+                        "mention": {"type": "string"}
+                    },
+                    "required": ["name", "type", "mention"]
+                }
+            },
+            "relations": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "source": {"type": "string"},
+                        "relation": {"type": "string"},
+                        "target": {"type": "string"},
+                        "evidence": {"type": "string"},
+                        "confidence": {"type": "number", "minimum": 0, "maximum": 1}
+                    },
+                    "required": ["source", "relation", "target", "evidence"]
+                }
+            }
+        },
+        "required": ["entities", "relations"]
+    })
+}
+
+/// Ask `model` for the entities and relations in `text`, and locate each
+/// in it.
+async fn extract_with_model(model: &dyn JsonModel, text: &str) -> Result<Extraction> {
+    let messages = vec![
+        ChatMessage::system(SYSTEM_PROMPT.to_string()),
+        ChatMessage::user(text.to_string()),
+    ];
+    let json = model.generate_json(messages, &reply_schema()).await?;
+    let reply: ModelReply = serde_json::from_str(&json)?;
+
+    let mut extraction = Extraction::default();
+    for entity in reply.entities {
+        let label = entity.name.trim();
+        if label.is_empty() {
+            continue;
+        }
+        let Some(span) = find_span(text, &entity.mention).or_else(|| find_span(text, label)) else {
+            continue;
+        };
+        let category = ENTITY_TYPES
+            .iter()
+            .find(|t| t.eq_ignore_ascii_case(entity.category.trim()))
+            .unwrap_or(&"Concept");
+        extraction.entities.push(Entity {
+            label: label.to_string(),
+            category: category.to_string(),
+            start: span.start,
+            end: span.end,
+        });
+    }
+
+    for relation in reply.relations {
+        let name = normalize_relation(&relation.relation);
+        let (source, target) = (relation.source.trim(), relation.target.trim());
+        if name.is_empty() || source.is_empty() || target.is_empty() || source == target {
+            continue;
+        }
+        // Both ends must be in the text; the statement's span is the quoted
+        // evidence, or else the stretch from one end's mention to the other's.
+        let (Some(s), Some(t)) = (
+            entity_span(&extraction, text, source),
+            entity_span(&extraction, text, target),
+        ) else {
+            continue;
+        };
+        let span =
+            find_span(text, &relation.evidence).unwrap_or(s.start.min(t.start)..s.end.max(t.end));
+        extraction.relations.push(Relation {
+            source: source.to_string(),
+            relation: name,
+            target: target.to_string(),
+            confidence: relation
+                .confidence
+                .unwrap_or(DEFAULT_CONFIDENCE)
+                .clamp(0.0, 1.0),
+            start: span.start,
+            end: span.end,
+        });
+    }
+    Ok(extraction)
+}
+
+/// Where `label` is mentioned: an extracted entity's span, or the label
+/// itself in the text.
+fn entity_span(extraction: &Extraction, text: &str, label: &str) -> Option<Range<usize>> {
+    extraction
+        .entities
+        .iter()
+        .find(|e| e.label.eq_ignore_ascii_case(label))
+        .map(|e| e.start..e.end)
+        .or_else(|| find_span(text, label))
+}
+
+/// The span of `needle` in `text`, matched exactly or else ignoring ASCII
+/// case (which keeps byte offsets the same).
+fn find_span(text: &str, needle: &str) -> Option<Range<usize>> {
+    let needle = needle.trim();
+    if needle.is_empty() {
+        return None;
+    }
+    let start = text
+        .find(needle)
+        .or_else(|| text.to_ascii_lowercase().find(&needle.to_ascii_lowercase()))?;
+    Some(start..start + needle.len())
+}
+
+/// "Works_On " -> "works on".
+fn normalize_relation(relation: &str) -> String {
+    relation
+        .replace('_', " ")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Replies with canned JSON, or fails.
+    struct CannedModel(Option<String>);
+
+    #[async_trait]
+    impl JsonModel for CannedModel {
+        async fn generate_json(
+            &self,
+            _messages: Vec<ChatMessage>,
+            _schema: &serde_json::Value,
+        ) -> Result<String> {
+            self.0
+                .clone()
+                .ok_or_else(|| anyhow::anyhow!("connection refused"))
+        }
+    }
+
+    #[test]
+    fn test_rules_find_dates_emails_paths_and_urls() {
+        let text = "Send the report to alice@example.com by 2024-03-01 (or March 5, 2024).\n\
+                    It lives in ~/reports/q1.md and /srv/share/q1.pdf, see https://wiki.example.com/q1/report.\n\
+                    Pros and/or cons.";
+        let extraction = extract_with_rules(text);
+        let found: Vec<(&str, &str)> = extraction
+            .entities
+            .iter()
+            .map(|e| (e.category.as_str(), e.label.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (EMAIL, "alice@example.com"),
+                (DATE, "2024-03-01"),
+                (DATE, "March 5, 2024"),
+                (PATH, "~/reports/q1.md"),
+                (PATH, "/srv/share/q1.pdf"),
+                (URL, "https://wiki.example.com/q1/report"),
+            ]
+        );
+        for entity in &extraction.entities {
+            assert_eq!(&text[entity.start..entity.end], entity.label);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_model_output_is_located_in_the_text() {
+        let text = "Alice leads the Atlas migration. Atlas depends on Postgres 16.";
+        let reply = serde_json::json!({
+            "entities": [
+                {"name": "Alice", "type": "person", "mention": "Alice"},
+                {"name": "Atlas", "type": "Project", "mention": "Atlas migration"},
+                {"name": "Postgres", "type": "Technology", "mention": "Postgres 16"},
+                {"name": "Bob", "type": "Person", "mention": "Bob"}
+            ],
+            "relations": [
+                {"source": "Alice", "relation": "Leads", "target": "Atlas", "evidence": "Alice leads the Atlas migration", "confidence": 0.9},
+                {"source": "Atlas", "relation": "depends_on", "target": "Postgres", "evidence": "not quoted right"},
+                {"source": "Bob", "relation": "manages", "target": "Alice", "evidence": ""}
+            ]
+        });
+        let extractor = Extractor::new().with_model(Arc::new(CannedModel(Some(reply.to_string()))));
+        let job = ExtractionJob::new("doc/atlas.md", text, Mode::General);
+        let extraction = extractor.extract(&job).await;
+
+        // Bob isn't in the text, so neither he nor his relation is kept.
+        let labels: Vec<&str> = extraction
+            .entities
+            .iter()
+            .map(|e| e.label.as_str())
+            .collect();
+        assert_eq!(labels, vec!["Alice", "Atlas", "Postgres"]);
+        assert_eq!(extraction.entities[0].category, "Person");
+        assert_eq!(extraction.relations.len(), 2);
+
+        let leads = &extraction.relations[0];
+        assert_eq!(leads.relation, "leads");
+        assert_eq!(
+            &text[leads.start..leads.end],
+            "Alice leads the Atlas migration"
+        );
+
+        // Unquotable evidence falls back to the span between the mentions.
+        let depends = &extraction.relations[1];
+        assert_eq!(depends.relation, "depends on");
+        assert_eq!(depends.confidence, DEFAULT_CONFIDENCE);
+        assert_eq!(
+            &text[depends.start..depends.end],
+            "Atlas migration. Atlas depends on Postgres 16"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_model_failure_falls_back_to_rules() {
+        let extractor = Extractor::new().with_model(Arc::new(CannedModel(None)));
+        let job = ExtractionJob::new("thread/1", "Ping bob@example.com tomorrow", Mode::General);
+        let extraction = extractor.extract(&job).await;
+        assert_eq!(extraction.entities.len(), 1);
+        assert_eq!(extraction.entities[0].category, EMAIL);
+        assert!(extraction.relations.is_empty());
+    }
+}
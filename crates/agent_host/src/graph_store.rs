@@ -12,15 +12,30 @@
 //!   so queries can be scoped to the current agent personality.
 //! - **Reinforcement signals**: `usage_count` and `feedback_score` let the
 //!   memory optimiser prune low-value nodes and consolidate near-duplicates
-//!   via Jaro-Winkler similarity.
-//! - **Hybrid search**: both BFS traversal (`find_related`) and brute-force
-//!   cosine vector search (`vector_search`) are supported.
-
+//!   via Jaro-Winkler similarity ([`same_entity`]).
+//! - **Provenance**: nodes and edges extracted from text (`extraction.rs`)
+//!   record every source and byte span they were found at, and new
+//!   mentions are matched to existing nodes with the same test the
+//!   optimiser merges on ([`GraphStore::find_entity`]).
+//! - **Typed queries**: walks constrained by relation type and direction,
+//!   with results filtered by category or `Mode` ([`Traversal`],
+//!   [`NodeFilter`]), rank reached nodes by the weights of the edges that
+//!   lead to them. `shortest_path` returns the connecting edges so a
+//!   relationship can be explained, and `subgraph` extracts the
+//!   neighbourhood of a node for display.
+//! - **Hybrid search**: both BFS traversal (`find_related`) and cosine
+//!   vector search (`vector_search`) are supported. With a `VectorIndex`
+//!   attached (`set_vector_index`), embeddings live in the index rather
+//!   than on the nodes, so the JSON graph file stays small and search is
+//!   approximate nearest-neighbour; without one, search is brute force.
+
+use crate::vector_index::SharedVectorIndex;
 use anyhow::Result;
-use petgraph::graph::{DiGraph, NodeIndex};
-use petgraph::visit::Bfs;
+use petgraph::graph::{DiGraph, EdgeIndex, NodeIndex};
+use petgraph::visit::{Bfs, EdgeRef};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, HashSet};
 use std::fs;
 use std::path::PathBuf;
 use std::time::{SystemTime, UNIX_EPOCH};
@@ -39,6 +54,19 @@ pub enum Mode {
     General,
 }
 
+impl From<shared::skill::Mode> for Mode {
+    fn from(mode: shared::skill::Mode) -> Self {
+        match mode {
+            shared::skill::Mode::Find => Mode::Find,
+            shared::skill::Mode::Fix => Mode::Fix,
+            shared::skill::Mode::Research => Mode::Research,
+            shared::skill::Mode::Build => Mode::Build,
+            shared::skill::Mode::Data => Mode::Data,
+            shared::skill::Mode::Content => Mode::Content,
+        }
+    }
+}
+
 /// A node in the knowledge graph representing an entity or concept
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct NodeData {
@@ -60,9 +88,13 @@ pub struct NodeData {
     /// Last accessed timestamp (Unix seconds)
     #[serde(default = "default_timestamp")]
     pub last_accessed: u64,
-    /// Vector embedding for semantic search
+    /// Vector embedding for semantic search, unless the store keeps
+    /// embeddings in a vector index
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub embedding: Option<Vec<f32>>,
+    /// Where the entity was mentioned, oldest first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub provenance: Vec<Provenance>,
 }
 
 fn default_timestamp() -> u64 {
@@ -79,8 +111,315 @@ pub struct EdgeData {
     pub relation: String,
     /// Confidence score (0.0 - 1.0)
     pub weight: f32,
+    /// Where the relationship was stated, oldest first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub provenance: Vec<Provenance>,
+}
+
+/// Where a node or edge was extracted from: a document ID (or
+/// `thread/<id>` for a conversation) and the byte span of the mention in
+/// that text.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Provenance {
+    pub source_id: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Most provenance records kept per node or edge; an entity mentioned
+/// on every page of a long document doesn't need every span.
+const MAX_PROVENANCE: usize = 16;
+
+/// Add `provenance` to `records` unless it is already there or the list
+/// is full.
+fn record_provenance(records: &mut Vec<Provenance>, provenance: Provenance) {
+    if records.len() < MAX_PROVENANCE && !records.contains(&provenance) {
+        records.push(provenance);
+    }
+}
+
+/// Whether two labels name the same entity: the Jaro-Winkler similarity of
+/// the lowercased labels is at least `threshold`. Labels with digits, `@`
+/// or path separators (dates, emails, paths, versions) must match exactly
+/// apart from case, since "2024-03-01" and "2024-03-02" are close in
+/// spelling but not the same thing.
+pub fn same_entity(a: &str, b: &str, threshold: f64) -> bool {
+    let (a, b) = (a.trim().to_lowercase(), b.trim().to_lowercase());
+    let exact_only = |l: &str| l.contains(|c: char| c.is_ascii_digit() || "@/\\".contains(c));
+    if exact_only(&a) || exact_only(&b) {
+        return a == b;
+    }
+    jaro_winkler(&a, &b) >= threshold
+}
+
+/// Which nodes a query returns. Empty lists accept anything; categories
+/// match ignoring case.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NodeFilter {
+    pub categories: Vec<String>,
+    pub modes: Vec<Mode>,
+}
+
+impl NodeFilter {
+    pub fn with_category(mut self, category: impl Into<String>) -> Self {
+        self.categories.push(category.into());
+        self
+    }
+
+    pub fn with_mode(mut self, mode: Mode) -> Self {
+        self.modes.push(mode);
+        self
+    }
+
+    pub fn matches(&self, node: &NodeData) -> bool {
+        let category_ok = self.categories.is_empty()
+            || node.category.as_deref().is_some_and(|c| {
+                self.categories
+                    .iter()
+                    .any(|wanted| wanted.eq_ignore_ascii_case(c))
+            });
+        category_ok && (self.modes.is_empty() || self.modes.contains(&node.mode))
+    }
 }
 
+/// Which way a traversal may follow an edge.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Direction {
+    /// Source to target only
+    Outgoing,
+    /// Target to source only
+    Incoming,
+    /// Either way
+    #[default]
+    Both,
+}
+
+/// Each hop after the first multiplies a path's score by this, so of two
+/// equally confident connections the more direct ranks first.
+const HOP_DECAY: f32 = 0.5;
+
+/// Constraints on a walk through the graph.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Traversal {
+    /// Relations that may be followed, matched ignoring case; empty
+    /// follows any
+    pub relations: Vec<String>,
+    pub direction: Direction,
+    pub max_hops: usize,
+    /// Edges less confident than this aren't followed
+    pub min_weight: f32,
+    /// Which reached nodes are returned; the walk passes through others
+    pub filter: NodeFilter,
+}
+
+impl Default for Traversal {
+    fn default() -> Self {
+        Self {
+            relations: Vec::new(),
+            direction: Direction::Both,
+            max_hops: 3,
+            min_weight: 0.0,
+            filter: NodeFilter::default(),
+        }
+    }
+}
+
+impl Traversal {
+    pub fn with_relation(mut self, relation: impl Into<String>) -> Self {
+        self.relations.push(relation.into());
+        self
+    }
+
+    pub fn with_direction(mut self, direction: Direction) -> Self {
+        self.direction = direction;
+        self
+    }
+
+    pub fn with_max_hops(mut self, max_hops: usize) -> Self {
+        self.max_hops = max_hops;
+        self
+    }
+
+    pub fn with_min_weight(mut self, min_weight: f32) -> Self {
+        self.min_weight = min_weight;
+        self
+    }
+
+    pub fn with_filter(mut self, filter: NodeFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Whether the walk may follow `edge`.
+    fn follows(&self, edge: &EdgeData) -> bool {
+        edge.weight >= self.min_weight
+            && (self.relations.is_empty()
+                || self
+                    .relations
+                    .iter()
+                    .any(|r| r.eq_ignore_ascii_case(&edge.relation)))
+    }
+}
+
+/// An edge on a [`GraphPath`], in the edge's own direction (which may be
+/// against the direction of the walk).
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathStep {
+    pub edge: EdgeIndex,
+    pub source: NodeIndex,
+    pub target: NodeIndex,
+    pub relation: String,
+    pub weight: f32,
+}
+
+/// A path found by a query: the nodes from start to end and the edges
+/// between them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GraphPath {
+    pub nodes: Vec<NodeIndex>,
+    pub steps: Vec<PathStep>,
+    /// Product of the edge weights, decayed by `HOP_DECAY` per hop after
+    /// the first; 1.0 for the empty path
+    pub score: f32,
+}
+
+impl GraphPath {
+    fn start(node: NodeIndex) -> Self {
+        Self {
+            nodes: vec![node],
+            steps: Vec::new(),
+            score: 1.0,
+        }
+    }
+
+    pub fn hops(&self) -> usize {
+        self.steps.len()
+    }
+
+    /// The node the path leads to.
+    pub fn end(&self) -> NodeIndex {
+        *self.nodes.last().expect("a path has at least its start")
+    }
+
+    /// The path continued along `edge` to `next`.
+    fn extended(
+        &self,
+        graph: &DiGraph<NodeData, EdgeData>,
+        edge: EdgeIndex,
+        next: NodeIndex,
+    ) -> Self {
+        let (source, target) = graph.edge_endpoints(edge).expect("edge from this graph");
+        let data = &graph[edge];
+        let decay = if self.steps.is_empty() {
+            1.0
+        } else {
+            HOP_DECAY
+        };
+        let mut path = self.clone();
+        path.nodes.push(next);
+        path.steps.push(PathStep {
+            edge,
+            source,
+            target,
+            relation: data.relation.clone(),
+            weight: data.weight,
+        });
+        path.score *= data.weight.clamp(0.0, 1.0) * decay;
+        path
+    }
+
+    /// The path as the relations it's made of, e.g. "Alice works on
+    /// Atlas; Atlas depends on Postgres".
+    pub fn explain(&self, store: &GraphStore) -> String {
+        self.steps
+            .iter()
+            .map(|step| {
+                format!(
+                    "{} {} {}",
+                    store.graph[step.source].label, step.relation, store.graph[step.target].label
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+}
+
+/// The neighbourhood of a node, by label, for display.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Subgraph {
+    pub center: String,
+    /// The center first, then by score
+    pub nodes: Vec<SubgraphNode>,
+    pub edges: Vec<SubgraphEdge>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SubgraphNode {
+    pub label: String,
+    pub category: Option<String>,
+    pub mode: Mode,
+    /// Hops from the center
+    pub hops: usize,
+    /// Score of the best path from the center
+    pub score: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SubgraphEdge {
+    pub source: String,
+    pub target: String,
+    pub relation: String,
+    pub weight: f32,
+    /// Where the relation was stated
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sources: Vec<String>,
+}
+
+/// A node waiting in a best-first search, best first out of the heap.
+struct Candidate {
+    node: NodeIndex,
+    hops: usize,
+    score: f32,
+    fewest_hops: bool,
+}
+
+/// Order paths best first: by score then hops, or with `fewest_hops` by
+/// hops then score.
+fn path_order(a: (usize, f32), b: (usize, f32), fewest_hops: bool) -> Ordering {
+    let by_hops = b.0.cmp(&a.0);
+    let by_score = a.1.total_cmp(&b.1);
+    if fewest_hops {
+        by_hops.then(by_score)
+    } else {
+        by_score.then(by_hops)
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        path_order(
+            (self.hops, self.score),
+            (other.hops, other.score),
+            self.fewest_hops,
+        )
+        .then(other.node.cmp(&self.node))
+    }
+}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
 /// Serializable knowledge graph backed by a directed graph.
 ///
 /// `node_map` provides O(1) label-to-index lookups and must be kept in
@@ -90,6 +429,9 @@ pub struct EdgeData {
 pub struct GraphStore {
     pub graph: DiGraph<NodeData, EdgeData>,
     node_map: HashMap<String, NodeIndex>,
+    /// Node embeddings keyed by label, when attached
+    #[serde(skip)]
+    vectors: Option<SharedVectorIndex>,
 }
 
 impl Default for GraphStore {
@@ -104,6 +446,47 @@ impl GraphStore {
         Self {
             graph: DiGraph::new(),
             node_map: HashMap::new(),
+            vectors: None,
+        }
+    }
+
+    /// Keep node embeddings in `index` from now on. Embeddings already on
+    /// nodes (e.g. from an older graph file) move into it.
+    pub fn set_vector_index(&mut self, index: SharedVectorIndex) {
+        {
+            let mut vectors = index.write();
+            for node in self.graph.node_weights_mut() {
+                if let Some(embedding) = node.embedding.take() {
+                    if let Err(e) = vectors.insert(&node.label, &embedding) {
+                        tracing::warn!("Failed to index embedding for {}: {}", node.label, e);
+                    }
+                }
+            }
+        }
+        self.vectors = Some(index);
+    }
+
+    /// Store a node's embedding: in the index if there is one, otherwise
+    /// on the node.
+    fn store_embedding(&mut self, idx: NodeIndex, embedding: Vec<f32>) {
+        match &self.vectors {
+            Some(vectors) => {
+                let label = &self.graph[idx].label;
+                if let Err(e) = vectors.write().insert(label, &embedding) {
+                    tracing::warn!("Failed to index embedding for {}: {}", label, e);
+                }
+            }
+            None => self.graph[idx].embedding = Some(embedding),
+        }
+    }
+
+    /// Drop removed nodes' embeddings from the index.
+    fn forget_embeddings<'a>(&self, labels: impl IntoIterator<Item = &'a String>) {
+        if let Some(vectors) = &self.vectors {
+            let mut vectors = vectors.write();
+            for label in labels {
+                vectors.remove(label);
+            }
         }
     }
 
@@ -119,9 +502,9 @@ impl GraphStore {
     ) -> NodeIndex {
         if let Some(&idx) = self.node_map.get(label) {
             // Update embedding if missing and provided
-            if let Some(node) = self.graph.node_weight_mut(idx) {
-                if node.embedding.is_none() && embedding.is_some() {
-                    node.embedding = embedding;
+            if let Some(embedding) = embedding {
+                if !self.has_embedding(label) {
+                    self.store_embedding(idx, embedding);
                 }
             }
             return idx;
@@ -135,11 +518,15 @@ impl GraphStore {
             usage_count: 0,
             feedback_score: 0.0,
             last_accessed: default_timestamp(),
-            embedding,
+            embedding: None,
+            provenance: Vec::new(),
         };
 
         let idx = self.graph.add_node(node);
         self.node_map.insert(label.to_string(), idx);
+        if let Some(embedding) = embedding {
+            self.store_embedding(idx, embedding);
+        }
         idx
     }
 
@@ -147,7 +534,11 @@ impl GraphStore {
     pub fn has_embedding(&self, label: &str) -> bool {
         if let Some(&idx) = self.node_map.get(label) {
             if let Some(node) = self.graph.node_weight(idx) {
-                return node.embedding.is_some();
+                return node.embedding.is_some()
+                    || self
+                        .vectors
+                        .as_ref()
+                        .is_some_and(|v| v.read().contains(label));
             }
         }
         false
@@ -164,10 +555,79 @@ impl GraphStore {
         let edge_data = EdgeData {
             relation: relation.to_string(),
             weight: 1.0,
+            provenance: Vec::new(),
         };
         self.graph.add_edge(source, target, edge_data);
     }
 
+    /// Index of the node labelled `label`.
+    pub fn node_index(&self, label: &str) -> Option<NodeIndex> {
+        self.node_map.get(label).copied()
+    }
+
+    /// The node that `label` names: the one with exactly that label, else
+    /// the most similar one that [`same_entity`] accepts at `threshold`.
+    pub fn find_entity(&self, label: &str, threshold: f64) -> Option<NodeIndex> {
+        if let Some(&idx) = self.node_map.get(label) {
+            return Some(idx);
+        }
+        let lower = label.trim().to_lowercase();
+        self.graph
+            .node_indices()
+            .filter(|&idx| same_entity(label, &self.graph[idx].label, threshold))
+            .max_by(|&a, &b| {
+                let sim =
+                    |idx: NodeIndex| jaro_winkler(&lower, &self.graph[idx].label.to_lowercase());
+                sim(a)
+                    .partial_cmp(&sim(b))
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            })
+    }
+
+    /// Record that the node at `idx` was mentioned at `provenance`.
+    pub fn add_node_provenance(&mut self, idx: NodeIndex, provenance: Provenance) {
+        if let Some(node) = self.graph.node_weight_mut(idx) {
+            record_provenance(&mut node.provenance, provenance);
+        }
+    }
+
+    /// Add a typed relationship stated at `provenance`. Unlike
+    /// [`Self::add_edge`], nodes may be linked by several relations; a
+    /// relation stated again gains the new provenance and keeps the
+    /// higher of the two weights.
+    pub fn add_relation(
+        &mut self,
+        source: NodeIndex,
+        target: NodeIndex,
+        relation: &str,
+        weight: f32,
+        provenance: Provenance,
+    ) {
+        let existing = self
+            .graph
+            .edges_connecting(source, target)
+            .find(|e| e.weight().relation == relation)
+            .map(|e| e.id());
+        match existing {
+            Some(edge) => {
+                let data = &mut self.graph[edge];
+                data.weight = data.weight.max(weight);
+                record_provenance(&mut data.provenance, provenance);
+            }
+            None => {
+                self.graph.add_edge(
+                    source,
+                    target,
+                    EdgeData {
+                        relation: relation.to_string(),
+                        weight,
+                        provenance: vec![provenance],
+                    },
+                );
+            }
+        }
+    }
+
     /// Find related nodes up to `depth` hops away
     pub fn find_related(&self, start_label: &str, max_depth: usize) -> Vec<(String, String)> {
         let mut related = Vec::new();
@@ -222,7 +682,6 @@ impl GraphStore {
 
         // Outgoing
         for edge in self.graph.edges(idx) {
-            use petgraph::visit::EdgeRef;
             related.push((
                 edge.target(),
                 edge.weight().relation.clone(),
@@ -249,6 +708,179 @@ impl GraphStore {
         related
     }
 
+    /// Nodes that `filter` accepts.
+    pub fn nodes_where(&self, filter: &NodeFilter) -> Vec<NodeIndex> {
+        self.graph
+            .node_indices()
+            .filter(|&idx| filter.matches(&self.graph[idx]))
+            .collect()
+    }
+
+    /// Every node reachable from `start` under `traversal` that its filter
+    /// accepts, each with its best-scoring path, best first.
+    pub fn walk(&self, start: NodeIndex, traversal: &Traversal) -> Vec<GraphPath> {
+        let mut paths: Vec<GraphPath> = self
+            .best_paths(start, traversal, false, None)
+            .into_values()
+            .filter(|p| p.end() != start && traversal.filter.matches(&self.graph[p.end()]))
+            .collect();
+        paths.sort_by(|a, b| {
+            path_order((a.hops(), a.score), (b.hops(), b.score), false)
+                .reverse()
+                .then_with(|| self.graph[a.end()].label.cmp(&self.graph[b.end()].label))
+        });
+        paths
+    }
+
+    /// The path from `from` to `to` with the fewest hops under `traversal`,
+    /// the best-scoring of those if there are several. The traversal's
+    /// filter doesn't apply.
+    pub fn shortest_path(
+        &self,
+        from: NodeIndex,
+        to: NodeIndex,
+        traversal: &Traversal,
+    ) -> Option<GraphPath> {
+        self.best_paths(from, traversal, true, Some(to)).remove(&to)
+    }
+
+    /// `center` and up to `max_nodes` of the best nodes `walk` reaches from
+    /// it, with the edges between them that the traversal would follow.
+    pub fn subgraph(&self, center: NodeIndex, traversal: &Traversal, max_nodes: usize) -> Subgraph {
+        let mut paths = vec![GraphPath::start(center)];
+        paths.extend(self.walk(center, traversal).into_iter().take(max_nodes));
+        let included: HashSet<NodeIndex> = paths.iter().map(GraphPath::end).collect();
+
+        let nodes = paths
+            .iter()
+            .map(|path| {
+                let node = &self.graph[path.end()];
+                SubgraphNode {
+                    label: node.label.clone(),
+                    category: node.category.clone(),
+                    mode: node.mode,
+                    hops: path.hops(),
+                    score: path.score,
+                }
+            })
+            .collect();
+        let edges = self
+            .graph
+            .edge_references()
+            .filter(|e| {
+                included.contains(&e.source())
+                    && included.contains(&e.target())
+                    && traversal.follows(e.weight())
+            })
+            .map(|e| {
+                let mut sources: Vec<String> = Vec::new();
+                for p in &e.weight().provenance {
+                    if !sources.contains(&p.source_id) {
+                        sources.push(p.source_id.clone());
+                    }
+                }
+                SubgraphEdge {
+                    source: self.graph[e.source()].label.clone(),
+                    target: self.graph[e.target()].label.clone(),
+                    relation: e.weight().relation.clone(),
+                    weight: e.weight().weight,
+                    sources,
+                }
+            })
+            .collect();
+
+        Subgraph {
+            center: self.graph[center].label.clone(),
+            nodes,
+            edges,
+        }
+    }
+
+    /// Best-first search from `start`: the best path (see `path_order`)
+    /// to every node reachable under `traversal`, stopping early once
+    /// `goal` is settled.
+    fn best_paths(
+        &self,
+        start: NodeIndex,
+        traversal: &Traversal,
+        fewest_hops: bool,
+        goal: Option<NodeIndex>,
+    ) -> HashMap<NodeIndex, GraphPath> {
+        let mut best = HashMap::new();
+        if self.graph.node_weight(start).is_none() {
+            return best;
+        }
+        best.insert(start, GraphPath::start(start));
+        let mut settled = HashSet::new();
+        let mut frontier = BinaryHeap::new();
+        frontier.push(Candidate {
+            node: start,
+            hops: 0,
+            score: 1.0,
+            fewest_hops,
+        });
+
+        while let Some(Candidate { node, .. }) = frontier.pop() {
+            if !settled.insert(node) {
+                continue;
+            }
+            if goal == Some(node) {
+                break;
+            }
+            let path = best[&node].clone();
+            if path.hops() >= traversal.max_hops {
+                continue;
+            }
+            for (edge, next) in self.steps_from(node, traversal) {
+                if settled.contains(&next) {
+                    continue;
+                }
+                let candidate = path.extended(&self.graph, edge, next);
+                let improves = best.get(&next).is_none_or(|old: &GraphPath| {
+                    path_order(
+                        (candidate.hops(), candidate.score),
+                        (old.hops(), old.score),
+                        fewest_hops,
+                    ) == Ordering::Greater
+                });
+                if improves {
+                    frontier.push(Candidate {
+                        node: next,
+                        hops: candidate.hops(),
+                        score: candidate.score,
+                        fewest_hops,
+                    });
+                    best.insert(next, candidate);
+                }
+            }
+        }
+        best
+    }
+
+    /// The edges `traversal` may follow out of `node`, with the node each
+    /// leads to.
+    fn steps_from(&self, node: NodeIndex, traversal: &Traversal) -> Vec<(EdgeIndex, NodeIndex)> {
+        let mut steps = Vec::new();
+        if traversal.direction != Direction::Incoming {
+            steps.extend(
+                self.graph
+                    .edges_directed(node, petgraph::Direction::Outgoing)
+                    .filter(|e| traversal.follows(e.weight()))
+                    .map(|e| (e.id(), e.target())),
+            );
+        }
+        if traversal.direction != Direction::Outgoing {
+            steps.extend(
+                self.graph
+                    .edges_directed(node, petgraph::Direction::Incoming)
+                    .filter(|e| traversal.follows(e.weight()))
+                    .map(|e| (e.id(), e.source())),
+            );
+        }
+        steps.retain(|&(_, next)| next != node);
+        steps
+    }
+
     /// Update feedback for a node
     pub fn update_node_feedback(&mut self, label: &str, score_delta: f32) {
         if let Some(&idx) = self.node_map.get(label) {
@@ -306,11 +938,7 @@ impl GraphStore {
 
                 let label_j = &self.graph[j].label;
 
-                // Check similarity
-                // Normalize slightly by lowercasing for the check
-                let sim = jaro_winkler(&label_i.to_lowercase(), &label_j.to_lowercase());
-
-                if sim >= threshold {
+                if same_entity(&label_i, label_j, threshold) {
                     // Merge j into i (keep i as canonical)
                     // Logic: Keep the one with higher usage, or if equal, keep i
                     let (keep, discard) = if self.graph[j].usage_count > self.graph[i].usage_count {
@@ -330,11 +958,13 @@ impl GraphStore {
                         if self.graph[discard].feedback_score > 0.0 {
                             self.graph[keep].feedback_score += 0.1;
                         }
+                        for provenance in self.graph[discard].provenance.clone() {
+                            record_provenance(&mut self.graph[keep].provenance, provenance);
+                        }
 
                         // Collect edges to remap
                         // Outgoing from discard -> target
                         for edge in self.graph.edges(discard) {
-                            use petgraph::visit::EdgeRef;
                             edges_to_add.push((keep, edge.target(), edge.weight().clone()));
                         }
 
@@ -396,6 +1026,13 @@ impl GraphStore {
             }
         }
 
+        let removed: Vec<String> = nodes_to_remove
+            .iter()
+            .filter_map(|idx| self.graph.node_weight(*idx))
+            .map(|node| node.label.trim_start_matches("__DELETED__").to_string())
+            .collect();
+        self.forget_embeddings(&removed);
+
         self.graph
             .retain_nodes(|g, ix| !g[ix].label.starts_with("__DELETED__"));
 
@@ -437,6 +1074,15 @@ impl GraphStore {
 
         // Rebuild map
         if self.graph.node_count() != before_count {
+            let kept: std::collections::HashSet<&String> =
+                self.graph.node_weights().map(|n| &n.label).collect();
+            let removed: Vec<String> = self
+                .node_map
+                .keys()
+                .filter(|label| !kept.contains(label))
+                .cloned()
+                .collect();
+            self.forget_embeddings(&removed);
             self.node_map.clear();
             for ix in self.graph.node_indices() {
                 let label = self.graph[ix].label.clone();
@@ -447,10 +1093,18 @@ impl GraphStore {
         before_count - self.graph.node_count()
     }
 
-    /// Serialize graph to JSON
+    /// Serialize graph to JSON, and flush the vector index if attached
     pub fn save_to_file(&self, path: PathBuf) -> Result<()> {
         let json = serde_json::to_string_pretty(self)?;
         fs::write(path, json)?;
+        self.flush_vector_index()
+    }
+
+    /// Persist the attached vector index's pending changes, if any.
+    pub fn flush_vector_index(&self) -> Result<()> {
+        if let Some(vectors) = &self.vectors {
+            vectors.write().flush()?;
+        }
         Ok(())
     }
 
@@ -463,16 +1117,28 @@ impl GraphStore {
         let store: Self = serde_json::from_str(&json)?;
         Ok(store)
     }
-    /// Brute-force cosine similarity search over all nodes that carry an
-    /// embedding. Returns up to `limit` results above `min_score`, sorted
-    /// by descending similarity. For small graphs this is fast enough; a
-    /// future optimisation could use an approximate nearest-neighbour index.
+    /// Cosine similarity search over all nodes that carry an embedding.
+    /// Returns up to `limit` results above `min_score`, sorted by
+    /// descending similarity. Uses the vector index when attached, else
+    /// compares against every node.
     pub fn vector_search(
         &self,
         query_vec: &[f32],
         limit: usize,
         min_score: f32,
     ) -> Vec<(NodeIndex, f32)> {
+        if let Some(vectors) = &self.vectors {
+            // The index may hold labels of nodes this graph doesn't have
+            // (it outlives the in-memory graph), so ask for a few extra.
+            return vectors
+                .read()
+                .search(query_vec, limit * 2, min_score)
+                .into_iter()
+                .filter_map(|(label, score)| self.node_map.get(&label).map(|&idx| (idx, score)))
+                .take(limit)
+                .collect();
+        }
+
         let mut results = Vec::new();
 
         for idx in self.graph.node_indices() {
@@ -493,7 +1159,7 @@ impl GraphStore {
     }
 }
 
-fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+pub(crate) fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
     let dot_product: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
     let norm_a: f32 = a.iter().map(|x| x * x).sum::<f32>().sqrt();
     let norm_b: f32 = b.iter().map(|x| x * x).sum::<f32>().sqrt();
@@ -534,6 +1200,55 @@ mod tests {
         assert_eq!(store.graph[idx].usage_count, 15); // Summed
     }
 
+    #[test]
+    fn test_consolidate_keeps_distinct_dates_and_merges_provenance() {
+        let mut store = GraphStore::new();
+        let at = |source_id: &str| Provenance {
+            source_id: source_id.to_string(),
+            start: 0,
+            end: 5,
+        };
+
+        let n1 = store.add_node("Atlas", None, "a", Mode::General, None);
+        store.graph[n1].usage_count = 2;
+        store.add_node_provenance(n1, at("a"));
+        let n2 = store.add_node("atlas", None, "b", Mode::General, None);
+        store.add_node_provenance(n2, at("b"));
+        store.add_node("2024-03-01", None, "a", Mode::General, None);
+        store.add_node("2024-03-02", None, "a", Mode::General, None);
+
+        assert_eq!(store.consolidate_nodes(0.9), 1);
+        let atlas = store.find_entity("ATLAS", 0.9).unwrap();
+        assert_eq!(store.graph[atlas].provenance, vec![at("a"), at("b")]);
+        assert!(store.find_entity("2024-03-03", 0.5).is_none());
+    }
+
+    #[test]
+    fn test_add_relation_keeps_each_relation_type() {
+        let mut store = GraphStore::new();
+        let at = |start: usize| Provenance {
+            source_id: "doc".to_string(),
+            start,
+            end: start + 10,
+        };
+        let alice = store.add_node("Alice", None, "doc", Mode::General, None);
+        let atlas = store.add_node("Atlas", None, "doc", Mode::General, None);
+
+        store.add_relation(alice, atlas, "owns", 0.6, at(0));
+        store.add_relation(alice, atlas, "owns", 0.9, at(20));
+        store.add_relation(alice, atlas, "works on", 0.8, at(40));
+
+        let edges: Vec<&EdgeData> = store
+            .graph
+            .edges_connecting(alice, atlas)
+            .map(|e| e.weight())
+            .collect();
+        assert_eq!(edges.len(), 2);
+        let owns = edges.iter().find(|e| e.relation == "owns").unwrap();
+        assert_eq!(owns.weight, 0.9);
+        assert_eq!(owns.provenance, vec![at(0), at(20)]);
+    }
+
     #[test]
     fn test_prune_nodes() {
         let mut store = GraphStore::new();
@@ -616,4 +1331,131 @@ mod tests {
             && rel == "depends_on"
             && role == "referenced by"));
     }
+
+    /// Alice works on Atlas (0.9) and Beacon (0.4); Atlas depends on
+    /// Postgres; Bob reviews Atlas.
+    fn project_graph() -> GraphStore {
+        let mut store = GraphStore::new();
+        let at = Provenance {
+            source_id: "notes".to_string(),
+            start: 0,
+            end: 1,
+        };
+        let mut node = |label: &str, category: &str, mode: Mode| {
+            store.add_node(label, Some(category.to_string()), "notes", mode, None)
+        };
+        let alice = node("Alice", "Person", Mode::General);
+        let bob = node("Bob", "Person", Mode::General);
+        let atlas = node("Atlas", "Project", Mode::Build);
+        let beacon = node("Beacon", "Project", Mode::Content);
+        let postgres = node("Postgres", "Technology", Mode::Build);
+        store.add_relation(alice, atlas, "works on", 0.9, at.clone());
+        store.add_relation(alice, beacon, "works on", 0.4, at.clone());
+        store.add_relation(atlas, postgres, "depends on", 1.0, at.clone());
+        store.add_relation(bob, atlas, "reviews", 1.0, at);
+        store
+    }
+
+    #[test]
+    fn test_walk_ranks_by_edge_weight_and_filters() {
+        let store = project_graph();
+        let alice = store.node_index("Alice").unwrap();
+        let labels = |paths: Vec<GraphPath>| -> Vec<String> {
+            paths
+                .iter()
+                .map(|p| store.graph[p.end()].label.clone())
+                .collect()
+        };
+
+        // Bob and Postgres, two hops through Atlas (0.9 * 1.0 * 0.5),
+        // outrank the weakly related Beacon (0.4)
+        let all = store.walk(alice, &Traversal::default());
+        assert_eq!(labels(all), vec!["Atlas", "Bob", "Postgres", "Beacon"]);
+
+        let projects =
+            Traversal::default().with_filter(NodeFilter::default().with_category("project"));
+        assert_eq!(
+            labels(store.walk(alice, &projects)),
+            vec!["Atlas", "Beacon"]
+        );
+
+        let build = Traversal::default().with_filter(NodeFilter::default().with_mode(Mode::Build));
+        assert_eq!(labels(store.walk(alice, &build)), vec!["Atlas", "Postgres"]);
+
+        let works_on = Traversal::default().with_relation("Works On");
+        assert_eq!(
+            labels(store.walk(alice, &works_on)),
+            vec!["Atlas", "Beacon"]
+        );
+
+        let confident = Traversal::default()
+            .with_direction(Direction::Outgoing)
+            .with_min_weight(0.5);
+        assert_eq!(
+            labels(store.walk(alice, &confident)),
+            vec!["Atlas", "Postgres"]
+        );
+    }
+
+    #[test]
+    fn test_shortest_path_explains_relationship() {
+        let store = project_graph();
+        let postgres = store.node_index("Postgres").unwrap();
+        let bob = store.node_index("Bob").unwrap();
+
+        let path = store
+            .shortest_path(bob, postgres, &Traversal::default())
+            .unwrap();
+        assert_eq!(path.hops(), 2);
+        assert_eq!(
+            path.explain(&store),
+            "Bob reviews Atlas; Atlas depends on Postgres"
+        );
+
+        // Against the edges, or too far, there is no path
+        let forward_only = Traversal::default().with_direction(Direction::Incoming);
+        assert!(store.shortest_path(bob, postgres, &forward_only).is_none());
+        let one_hop = Traversal::default().with_max_hops(1);
+        assert!(store.shortest_path(bob, postgres, &one_hop).is_none());
+    }
+
+    #[test]
+    fn test_subgraph_around_node() {
+        let store = project_graph();
+        let atlas = store.node_index("Atlas").unwrap();
+
+        let subgraph = store.subgraph(atlas, &Traversal::default().with_max_hops(1), 2);
+        let labels: Vec<&str> = subgraph.nodes.iter().map(|n| n.label.as_str()).collect();
+        assert_eq!(labels, vec!["Atlas", "Bob", "Postgres"]);
+        assert_eq!(subgraph.nodes[0].hops, 0);
+        assert_eq!(subgraph.edges.len(), 2);
+        assert!(subgraph.edges.iter().all(|e| e.sources == vec!["notes"]));
+    }
+
+    #[test]
+    fn test_vector_search_with_index() {
+        let mut store = GraphStore::new();
+        let _old = store.add_node("Old", None, "math", Mode::General, Some(vec![0.0, 1.0]));
+
+        let index =
+            crate::vector_index::shared(crate::vector_index::HnswIndex::new(Default::default()));
+        store.set_vector_index(index.clone());
+        let x = store.add_node("X-Axis", None, "math", Mode::General, Some(vec![1.0, 0.0]));
+        let near = store.add_node("Near X", None, "math", Mode::General, Some(vec![0.9, 0.1]));
+
+        // Embeddings moved off the nodes and into the index.
+        assert!(store.graph.node_weights().all(|n| n.embedding.is_none()));
+        assert_eq!(index.read().len(), 3);
+        assert!(store.has_embedding("Old"));
+
+        let results = store.vector_search(&[1.0, 0.0], 5, 0.5);
+        assert_eq!(
+            results.iter().map(|(idx, _)| *idx).collect::<Vec<_>>(),
+            vec![x, near]
+        );
+
+        store.graph[near].feedback_score = -1.0;
+        assert_eq!(store.prune_nodes(-0.5, 30), 1);
+        assert!(!index.read().contains("Near X"));
+    }
 }
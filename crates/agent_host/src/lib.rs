@@ -11,31 +11,64 @@
 //!
 //! 2. **Skill system** (`skills/`, `skill_executor.rs`) -- a registry of
 //!    typed, permission-gated skills (Find, Fix, Research, Data, Content,
-//!    Build) that the agent can invoke via `<skill>` tags.
+//!    Build) that the agent can invoke as native tools (`tools.rs`) or,
+//!    for models without tool support, via `<skill>` tags.
 //!
-//! 3. **Context & memory** (`context_manager.rs`, `graph_store.rs`,
-//!    `embedding.rs`, `daily_log.rs`, `context_token_manager.rs`,
-//!    `token_tracker.rs`) -- RAG pipeline with a petgraph knowledge
-//!    graph, fastembed vector embeddings, token-budget management, and
-//!    daily log archival.
+//! 3. **Context & memory** (`context_manager.rs`, `chunking.rs`, `graph_store.rs`,
+//!    `extraction.rs`, `embedding.rs`, `vector_index.rs`, `retrieval.rs`,
+//!    `daily_log.rs`, `context_token_manager.rs`, `token_tracker.rs`) -- RAG
+//!    pipeline with a petgraph knowledge graph fed by entity and relation
+//!    extraction, fastembed vector embeddings in an on-disk
+//!    nearest-neighbour index, hybrid BM25 / embedding / graph retrieval
+//!    fused by reciprocal rank, token-budget management, and daily log
+//!    archival.
 //!
-//! Security is enforced at two boundaries:
+//! Provider selection per request (cost, prompt size, capabilities, mode)
+//! lives in `routing.rs`, on top of the fallback router in `providers`.
+//!
+//! Security is enforced at these boundaries:
 //! - `security.rs` provides path sandboxing and time-boxed 2FA context.
+//! - Secrets (`shared::secrets`) are blocked in commands and redacted from
+//!   command output, context documents and every LLM request.
 //! - The executor layer scans commands for leaked secrets and validates
-//!   every path token against the sandbox before execution.
-
+//!   every path a command touches (`command_paths.rs`) against the sandbox
+//!   before execution.
+//! - With hardened execution on (Linux), `process_sandbox.rs` also confines
+//!   the spawned process: filesystem, network and resource limits.
+//! - The user's command policy file (`command_policy.rs`) can allow, confirm
+//!   or deny commands on top of the built-in safety lists.
+//! - Before a file-changing command is confirmed, `dry_run.rs` lists the
+//!   files it would touch and snapshots them so the run can be undone.
+
+pub mod chunking;
+pub mod command_paths;
+pub mod command_policy;
 pub mod context_manager;
 pub mod context_token_manager;
+pub mod dry_run;
 pub mod embedding;
 pub mod executor;
+pub mod extraction;
 use std::sync::Arc;
 use tokio::sync::Mutex as AsyncMutex;
 pub mod daily_log;
+#[cfg(test)]
+mod executor_harness;
+pub mod process_sandbox;
 pub mod prompts;
+pub mod retrieval;
+#[cfg(test)]
+mod retrieval_eval;
+pub mod routing;
 pub mod security;
+pub mod shell_session;
+pub mod shell_syntax;
 pub mod skill_executor;
 pub mod skills;
+pub mod spawner;
 pub mod token_tracker;
+pub mod tools;
+pub mod vector_index;
 
 pub use prompts::{
     get_mode_introduction, get_mode_prompt, get_system_prompt, ModeIntroduction, ModePrompt,
@@ -43,13 +76,16 @@ pub use prompts::{
 };
 
 use anyhow::Result;
+use providers::catalog::ModelCatalogs;
 use regex::Regex;
 use shared::agent_api::ChatMessage;
-use shared::settings::AppSettings;
+use shared::secrets::SecretScanner;
+use shared::settings::{AppSettings, ModelProvider};
 
 pub use executor::{
-    classify_command, execute_command, needs_elevation, parse_progress, web_search, CommandResult,
-    DangerLevel, SessionState,
+    classify_command, execute_command, explain_command, needs_elevation, parse_progress,
+    web_search, CommandClassification, CommandResult, DangerLevel, SegmentClassification,
+    SessionState,
 };
 
 #[cfg(not(windows))]
@@ -58,6 +94,26 @@ pub use executor::execute_with_sudo;
 #[cfg(windows)]
 pub use executor::execute_with_elevation;
 
+/// Longest a single provider attempt may take before the router falls back
+/// to the next provider.
+pub const LLM_REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(90);
+
+/// Longest one user turn (every LLM call and command it triggers) may take.
+pub const TURN_DEADLINE: std::time::Duration = std::time::Duration::from_secs(5 * 60);
+
+/// Note appended to whatever was produced before a turn was stopped.
+/// `by_user` distinguishes the Stop button from [`TURN_DEADLINE`].
+pub fn stopped_note(by_user: bool) -> String {
+    if by_user {
+        "_(Stopped before finishing.)_".to_string()
+    } else {
+        format!(
+            "_(Stopped: this took longer than {} minutes.)_",
+            TURN_DEADLINE.as_secs() / 60
+        )
+    }
+}
+
 /// Pairs a command string with its execution result, used to accumulate
 /// tool invocations across the multi-turn agent loop.
 #[derive(Debug, Clone)]
@@ -66,13 +122,20 @@ pub struct ToolResult {
     pub result: CommandResult,
 }
 
-/// Top-level orchestrator: routes user messages to the LLM, extracts
-/// `<command>` / `<search>` / `[RUN]` tags from the response, executes
-/// them subject to safety classification, and feeds results back for
-/// further reasoning (up to 10 turns).
+/// Top-level orchestrator: routes user messages to the LLM, collects
+/// `run_command` tool calls (or `<command>` / `[RUN]` tags from models
+/// without tool support), executes them subject to safety classification,
+/// and feeds results back for further reasoning (up to 10 turns).
 pub struct AgentHost {
     pub settings: AppSettings,
+    /// Its `mode` is also the mode requests are routed for.
     pub session_state: Arc<AsyncMutex<SessionState>>,
+    /// Built from `settings.secret_scanning`; redacts tool output and requests
+    pub secrets: Arc<SecretScanner>,
+    /// Spending so far, so routing can keep within the budget
+    pub token_tracker: Option<Arc<token_tracker::TokenTracker>>,
+    /// Listed models, for real context windows and capabilities in routing
+    pub model_catalogs: Option<Arc<ModelCatalogs>>,
 }
 
 impl AgentHost {
@@ -85,31 +148,89 @@ impl AgentHost {
 
         let allowed = settings.allowed_dirs.iter().map(PathBuf::from).collect();
         let sandbox = PathSandbox::new(allowed);
+        let secrets = Arc::new(SecretScanner::from_settings(&settings.secret_scanning));
+        let mut session_state = SessionState::new()
+            .with_sandbox(sandbox)
+            .with_secret_scanner(secrets.clone());
+        if let Some(process_sandbox) = process_sandbox::ProcessSandbox::for_settings(&settings) {
+            session_state = session_state.with_process_sandbox(process_sandbox);
+        }
 
         Self {
             settings,
-            session_state: Arc::new(AsyncMutex::new(SessionState::new().with_sandbox(sandbox))),
+            session_state: Arc::new(AsyncMutex::new(session_state)),
+            secrets,
+            token_tracker: None,
+            model_catalogs: None,
         }
     }
 
+    pub fn with_token_tracker(mut self, tracker: Arc<token_tracker::TokenTracker>) -> Self {
+        self.token_tracker = Some(tracker);
+        self
+    }
+
+    pub fn with_model_catalogs(mut self, catalogs: Arc<ModelCatalogs>) -> Self {
+        self.model_catalogs = Some(catalogs);
+        self
+    }
+
     /// Simple chat - just AI response, no command execution
     pub async fn chat(&self, messages: Vec<ChatMessage>) -> Result<String> {
         use providers::router::ProviderRouter;
-        let router = ProviderRouter::new(self.settings.model.clone());
+        let config = self.route(&self.settings.model, &messages, false).await;
+        let router = ProviderRouter::new(config).with_secret_scanner(self.secrets.clone());
         router.generate(messages).await
     }
 
-    /// Multi-turn agent loop: sends the conversation to the LLM, parses
-    /// any `<command>`, `[RUN]`, or `[EXECUTE]` markers from the response,
-    /// and auto-executes commands classified as Safe. Blocked commands are
-    /// reported back to the model so it can adjust. The loop runs for at
-    /// most 10 iterations to prevent runaway tool use.
+    /// `config` ordered by the routing policy for `messages`: the session's
+    /// mode, the budget left and the model catalog all count.
+    async fn route(
+        &self,
+        config: &ModelProvider,
+        messages: &[ChatMessage],
+        tools: bool,
+    ) -> ModelProvider {
+        let mode = self.session_state.lock().await.mode;
+        let plan = routing::plan_route(
+            config,
+            &routing::RouteRequest {
+                messages,
+                mode,
+                tools,
+                remaining_budget_usd: self
+                    .token_tracker
+                    .as_ref()
+                    .and_then(|tracker| tracker.remaining_budget_usd()),
+                catalog: self.model_catalogs.as_deref(),
+            },
+        );
+        for note in &plan.notes {
+            tracing::info!("routing: {}", note);
+        }
+        plan.config
+    }
+
+    /// Multi-turn agent loop: sends the conversation to the LLM with the
+    /// `run_command` tool, falls back to parsing `<command>`, `[RUN]`, or
+    /// `[EXECUTE]` markers when the model answers in prose, and auto-executes
+    /// commands classified as Safe. Blocked commands are reported back to the
+    /// model so it can adjust. Commands awaiting confirmation are returned as
+    /// `<command>` tags in the response text. The loop runs for at most 10
+    /// iterations to prevent runaway tool use. Command output has secrets
+    /// redacted before it is added to the conversation.
+    ///
+    /// Cancelling `cancel` (or exceeding [`TURN_DEADLINE`]) aborts the
+    /// pending LLM request or kills the running command; the commands run so
+    /// far are returned with a [`stopped_note`] instead of an error.
     pub async fn agent_chat(
         &self,
         messages: Vec<ChatMessage>,
         auto_execute_safe: bool,
         use_fast_model: bool,
+        cancel: providers::cancel::CancellationToken,
     ) -> Result<(String, Vec<ToolResult>)> {
+        use providers::cancel::{is_cancelled, with_deadline};
         use providers::router::ProviderRouter;
 
         let mut model_settings = self.settings.model.clone();
@@ -118,26 +239,82 @@ impl AgentHost {
             model_settings.anthropic_model = model_settings.anthropic_fast_model.clone();
             model_settings.gemini_model = model_settings.gemini_fast_model.clone();
         }
-
-        let router = ProviderRouter::new(model_settings);
+        let config = self.route(&model_settings, &messages, true).await;
+
+        let turn = with_deadline(&cancel, TURN_DEADLINE);
+        let router = ProviderRouter::new(config)
+            .with_tools(tools::builtin_tool_specs(true, false))
+            .with_cancel(turn.clone())
+            .with_request_timeout(LLM_REQUEST_TIMEOUT)
+            .with_secret_scanner(self.secrets.clone());
+        let stopped = || stopped_note(cancel.is_cancelled());
         let mut all_messages = messages.clone();
         let mut tool_results = Vec::new();
 
         // Add agent system prompt
         let system_prompt = self.get_agent_system_prompt();
-        all_messages.insert(
-            0,
-            ChatMessage {
-                role: "system".to_string(),
-                content: system_prompt,
-            },
-        );
+        all_messages.insert(0, ChatMessage::system(system_prompt));
 
         // Loop for multi-turn command execution (max 10 iterations)
         for _ in 0..10 {
-            let response = router.generate(all_messages.clone()).await?;
+            let gen = match router.generate_with_meta(all_messages.clone()).await {
+                Ok(gen) => gen,
+                Err(e) if is_cancelled(&e) => return Ok((stopped(), tool_results)),
+                Err(e) => return Err(e),
+            };
+            let mut response = gen.text.clone();
+
+            // Structured tool calls: every call gets a tool result message.
+            if !gen.tool_calls.is_empty() {
+                all_messages.push(gen.to_message());
+                let mut pending = Vec::new();
+                for call in &gen.tool_calls {
+                    let output = match tools::tool_action(call) {
+                        tools::ToolAction::Command(cmd) => match self.classify(&cmd).await {
+                            DangerLevel::Safe if auto_execute_safe => {
+                                let mut state = self.session_state.lock().await;
+                                state.cancel = turn.clone();
+                                let result = execute_command(&cmd, 30, &mut state).await?;
+                                let output = format!(
+                                    "$ {}\n{}\nExit code: {}",
+                                    cmd, result.output, result.exit_code
+                                );
+                                tool_results.push(ToolResult {
+                                    command: cmd,
+                                    result,
+                                });
+                                if turn.is_cancelled() {
+                                    return Ok((stopped(), tool_results));
+                                }
+                                output
+                            }
+                            DangerLevel::Blocked => format!(
+                                "[Command Blocked]\n$ {}\nThis command is blocked for safety reasons.",
+                                cmd
+                            ),
+                            _ => {
+                                let output = format!("[Command '{}' needs user confirmation]", cmd);
+                                pending.push(cmd);
+                                output
+                            }
+                        },
+                        tools::ToolAction::Invalid(reason) => reason,
+                        _ => format!("Tool '{}' is not available here.", call.name),
+                    };
+                    let output = self.secrets.redact(&output).into_owned();
+                    all_messages.push(ChatMessage::tool_result(call, output));
+                }
+                if !pending.is_empty() {
+                    // Surface pending commands as tags, the format callers already handle.
+                    for cmd in pending {
+                        response.push_str(&format!("\n<command>{}</command>", cmd));
+                    }
+                    return Ok((response, tool_results));
+                }
+                continue;
+            }
 
-            // Extract commands from response
+            // Fallback: extract commands from tags in the response text
             let commands = self.extract_commands(&response);
 
             if commands.is_empty() {
@@ -148,7 +325,7 @@ impl AgentHost {
             // Process each command
             let mut executed_any = false;
             for cmd in commands {
-                let danger = classify_command(&cmd);
+                let danger = self.classify(&cmd).await;
 
                 // Only auto-execute safe commands if enabled
                 let should_execute = match danger {
@@ -159,39 +336,33 @@ impl AgentHost {
 
                 if should_execute {
                     let mut state = self.session_state.lock().await;
+                    state.cancel = turn.clone();
                     let result = execute_command(&cmd, 30, &mut state).await?;
 
                     // Add result to conversation
-                    all_messages.push(ChatMessage {
-                        role: "assistant".to_string(),
-                        content: response.clone(),
-                    });
-                    all_messages.push(ChatMessage {
-                        role: "user".to_string(),
-                        content: format!(
-                            "[Command Output]\n$ {}\n{}\nExit code: {}",
-                            cmd, result.output, result.exit_code
-                        ),
-                    });
+                    all_messages.push(ChatMessage::assistant(response.clone()));
+                    all_messages.push(ChatMessage::user(format!(
+                        "[Command Output]\n$ {}\n{}\nExit code: {}",
+                        cmd,
+                        self.secrets.redact(&result.output),
+                        result.exit_code
+                    )));
 
                     tool_results.push(ToolResult {
                         command: cmd.clone(),
                         result,
                     });
+                    if turn.is_cancelled() {
+                        return Ok((stopped(), tool_results));
+                    }
                     executed_any = true;
                 } else if danger == DangerLevel::Blocked {
                     // Inform AI the command is blocked
-                    all_messages.push(ChatMessage {
-                        role: "assistant".to_string(),
-                        content: response.clone(),
-                    });
-                    all_messages.push(ChatMessage {
-                        role: "user".to_string(),
-                        content: format!(
-                            "[Command Blocked]\n$ {}\nThis command is blocked for safety reasons.",
-                            cmd
-                        ),
-                    });
+                    all_messages.push(ChatMessage::assistant(response.clone()));
+                    all_messages.push(ChatMessage::user(format!(
+                        "[Command Blocked]\n$ {}\nThis command is blocked for safety reasons.",
+                        cmd
+                    )));
                     executed_any = true;
                 }
             }
@@ -299,7 +470,7 @@ ALWAYS search the web when:
 - User asks about products, prices, or availability
 
 ## How to Run Commands
-When you need to run a command, use:
+If you have a `run_command` tool, call it. Otherwise, when you need to run a command, use:
    <command>your command here</command>
 
 Example:
@@ -336,8 +507,8 @@ The file will automatically open in the preview panel.
     }
 
     /// Check if a command needs confirmation
-    pub fn needs_confirmation(&self, cmd: &str) -> bool {
-        let danger = classify_command(cmd);
+    pub async fn needs_confirmation(&self, cmd: &str) -> bool {
+        let danger = self.classify(cmd).await;
         matches!(
             danger,
             DangerLevel::NeedsConfirmation | DangerLevel::Dangerous | DangerLevel::NeedsSudo
@@ -345,8 +516,35 @@ The file will automatically open in the preview panel.
     }
 
     /// Get danger level for a command
-    pub fn get_danger_level(&self, cmd: &str) -> DangerLevel {
-        classify_command(cmd)
+    pub async fn get_danger_level(&self, cmd: &str) -> DangerLevel {
+        self.classify(cmd).await
+    }
+
+    /// Classify under the session's command policy, if one is attached.
+    async fn classify(&self, cmd: &str) -> DangerLevel {
+        self.session_state.lock().await.classify(cmd)
     }
 }
 pub mod graph_store;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shared::skill::Mode;
+
+    #[tokio::test]
+    async fn test_requests_are_routed_for_the_session_mode() {
+        let mut settings = AppSettings::default();
+        settings.model.provider_preference = vec!["openai".to_string(), "local".to_string()];
+        settings.model.routing.local_only_modes = vec![Mode::Data];
+        let host = AgentHost::new(settings);
+        let messages = vec![ChatMessage::user("hi")];
+
+        let config = host.route(&host.settings.model, &messages, true).await;
+        assert_eq!(config.provider_preference, vec!["openai", "local"]);
+
+        host.session_state.lock().await.mode = Some(Mode::Data);
+        let config = host.route(&host.settings.model, &messages, true).await;
+        assert_eq!(config.provider_preference, vec!["local"]);
+    }
+}
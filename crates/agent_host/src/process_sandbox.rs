@@ -0,0 +1,698 @@
+//! OS-level sandbox for executed commands (Linux, opt-in).
+//!
+//! `PathSandbox` only inspects the command string. With hardened execution
+//! turned on, the executor also confines the process itself, so a command
+//! that slips past the string check still can't reach outside the approved
+//! folders:
+//!
+//! - **Filesystem** -- the allowed directories are read-write, system
+//!   directories are read-only, and the home directory (apart from them and
+//!   a few toolchain folders) is hidden.
+//! - **Network** -- denied unless the command needs it (`curl`, `git clone`,
+//!   `pip install`, ...; see [`needs_network`]).
+//! - **Resources** -- CPU time, address space and process count are capped
+//!   with rlimits.
+//!
+//! Confinement uses bubblewrap (`bwrap`) when it is installed: a private
+//! mount, PID and network namespace built from bind mounts. Without it the
+//! child restricts itself before `exec` with Landlock (filesystem, and TCP
+//! on kernels that support it) and a seccomp filter that refuses IPv4/IPv6
+//! sockets. When neither is available (no `bwrap`, and a kernel that
+//! doesn't enforce Landlock) the command fails rather than run unconfined.
+//!
+//! When a sandboxed command fails, [`ProcessSandbox::restriction`] works
+//! out from its exit status and stderr which restriction stopped it, and
+//! the executor reports that in `CommandResult::restricted_by`.
+
+use crate::shell_syntax::{self, program_name, unwrap_command};
+use serde::{Deserialize, Serialize};
+use shared::settings::AppSettings;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+/// Resource caps applied to every sandboxed command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResourceLimits {
+    /// CPU seconds before the command gets `SIGXCPU`.
+    pub cpu_secs: u64,
+    /// Largest address space, in bytes.
+    pub memory_bytes: u64,
+    /// Processes and threads the command may have running at once.
+    pub max_processes: u64,
+}
+
+impl Default for ResourceLimits {
+    fn default() -> Self {
+        Self {
+            cpu_secs: 300,
+            memory_bytes: 4 * 1024 * 1024 * 1024,
+            max_processes: 256,
+        }
+    }
+}
+
+/// The sandbox restriction that stopped a command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SandboxRestriction {
+    /// Ran out of CPU time.
+    CpuTime,
+    /// Hit the memory cap.
+    Memory,
+    /// Couldn't start more processes.
+    ProcessLimit,
+    /// Tried to use the network.
+    Network,
+    /// Tried to write or read outside the approved folders.
+    Filesystem,
+}
+
+impl SandboxRestriction {
+    /// Short explanation for the UI.
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Self::CpuTime => "used more CPU time than allowed",
+            Self::Memory => "used more memory than allowed",
+            Self::ProcessLimit => "started more processes than allowed",
+            Self::Network => "tried to use the network, which is off for this command",
+            Self::Filesystem => "tried to touch files outside the approved folders",
+        }
+    }
+}
+
+/// Folders under the home directory that stay readable so toolchains
+/// installed there keep working.
+const HOME_READABLE: &[&str] = &[
+    ".cargo",
+    ".rustup",
+    ".local",
+    ".nvm",
+    ".pyenv",
+    ".gitconfig",
+];
+
+/// Directories hidden from sandboxed commands besides the home directory.
+/// `/run/user` holds the session's D-Bus and keyring sockets.
+const HIDDEN_DIRS: &[&str] = &["/run/user"];
+
+/// Confines executed commands with OS-level isolation. Built from the
+/// user's allowed directories; attach it with
+/// `SessionState::with_process_sandbox`.
+#[derive(Debug, Clone)]
+pub struct ProcessSandbox {
+    writable_dirs: Vec<PathBuf>,
+    readable_dirs: Vec<PathBuf>,
+    limits: ResourceLimits,
+}
+
+impl ProcessSandbox {
+    /// A sandbox where `writable_dirs` are the only writable locations
+    /// (besides a scratch `/tmp`).
+    pub fn new(writable_dirs: Vec<PathBuf>) -> Self {
+        let home = dirs::home_dir();
+        Self {
+            writable_dirs: writable_dirs
+                .into_iter()
+                .map(|p| p.canonicalize().unwrap_or(p))
+                .collect(),
+            readable_dirs: home
+                .map(|home| HOME_READABLE.iter().map(|d| home.join(d)).collect())
+                .unwrap_or_default(),
+            limits: ResourceLimits::default(),
+        }
+    }
+
+    /// The sandbox for `settings`, or `None` when hardened execution is off
+    /// or unsupported on this platform.
+    pub fn for_settings(settings: &AppSettings) -> Option<Self> {
+        if !settings.hardened_execution || !cfg!(target_os = "linux") {
+            return None;
+        }
+        Some(Self::new(
+            settings.allowed_dirs.iter().map(PathBuf::from).collect(),
+        ))
+    }
+
+    pub fn with_limits(mut self, limits: ResourceLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Extra directories to leave readable (they stay read-only).
+    pub fn with_readable_dirs(mut self, dirs: Vec<PathBuf>) -> Self {
+        self.readable_dirs.extend(dirs);
+        self
+    }
+
+    pub fn limits(&self) -> ResourceLimits {
+        self.limits
+    }
+
+    /// A command that runs `shell shell_arg cmd` in `cwd` inside the
+    /// sandbox, with network access only if `network` is set.
+    pub fn command(
+        &self,
+        shell: &str,
+        shell_arg: &str,
+        cmd: &str,
+        cwd: &Path,
+        network: bool,
+    ) -> anyhow::Result<Command> {
+        #[cfg(target_os = "linux")]
+        {
+            linux::command(self, shell, shell_arg, cmd, cwd, network)
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            let _ = network;
+            tracing::warn!("process sandbox is only available on Linux; running unconfined");
+            let mut command = Command::new(shell);
+            command.arg(shell_arg).arg(cmd).current_dir(cwd);
+            Ok(command)
+        }
+    }
+
+    /// Which restriction, if any, made a sandboxed command fail. `signal`
+    /// is the signal that killed it, if any; shells report a child killed
+    /// by signal N as exit code 128 + N, so that is checked too.
+    pub fn restriction(
+        &self,
+        network: bool,
+        exit_code: i32,
+        signal: Option<i32>,
+        stderr: &str,
+    ) -> Option<SandboxRestriction> {
+        const SIGXCPU: i32 = 24;
+        if signal == Some(SIGXCPU) || exit_code == 128 + SIGXCPU {
+            return Some(SandboxRestriction::CpuTime);
+        }
+        if exit_code == 0 && signal.is_none() {
+            return None;
+        }
+        let err = stderr.to_lowercase();
+        let any = |needles: &[&str]| needles.iter().any(|n| err.contains(n));
+        if any(&[
+            "cannot allocate memory",
+            "out of memory",
+            "memoryerror",
+            "memory allocation of",
+            "bad_alloc",
+        ]) {
+            return Some(SandboxRestriction::Memory);
+        }
+        if any(&[
+            "fork: resource temporarily unavailable",
+            "cannot fork",
+            "fork: retry",
+        ]) {
+            return Some(SandboxRestriction::ProcessLimit);
+        }
+        if !network
+            && (any(&[
+                "network is unreachable",
+                "could not resolve host",
+                "temporary failure in name resolution",
+                "name or service not known",
+                "failed to establish a new connection",
+                "couldn't connect to server",
+            ]) || (err.contains("socket") && err.contains("permission denied")))
+        {
+            return Some(SandboxRestriction::Network);
+        }
+        if any(&["read-only file system", "permission denied"]) {
+            return Some(SandboxRestriction::Filesystem);
+        }
+        None
+    }
+
+    /// `bwrap` arguments that set up the sandbox for a command run in
+    /// `cwd`, up to the `--` before the command itself.
+    fn bwrap_args(&self, cwd: &Path, network: bool, home: Option<&Path>) -> Vec<String> {
+        let mut args: Vec<String> = ["--die-with-parent", "--new-session", "--unshare-all"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        if network {
+            args.push("--share-net".to_string());
+        }
+        let mut push = |flag: &str, paths: &[&Path]| {
+            args.push(flag.to_string());
+            args.extend(paths.iter().map(|p| p.display().to_string()));
+        };
+        push("--ro-bind", &[Path::new("/"), Path::new("/")]);
+        push("--dev", &[Path::new("/dev")]);
+        push("--proc", &[Path::new("/proc")]);
+        push("--tmpfs", &[Path::new("/tmp")]);
+        if let Some(home) = home {
+            push("--tmpfs", &[home]);
+        }
+        for dir in HIDDEN_DIRS {
+            if Path::new(dir).exists() {
+                push("--tmpfs", &[Path::new(dir)]);
+            }
+        }
+        for dir in &self.readable_dirs {
+            push("--ro-bind-try", &[dir, dir]);
+        }
+        for dir in &self.writable_dirs {
+            push("--bind-try", &[dir, dir]);
+        }
+        push("--chdir", &[cwd]);
+        args.push("--".to_string());
+        args
+    }
+}
+
+/// Commands that talk to the network. `git` only needs it for the
+/// subcommands in `GIT_NETWORK_SUBCOMMANDS`.
+const NETWORK_COMMANDS: &[&str] = &[
+    "curl",
+    "wget",
+    "ssh",
+    "scp",
+    "sftp",
+    "rsync",
+    "ftp",
+    "telnet",
+    "nc",
+    "ping",
+    "nslookup",
+    "dig",
+    "host",
+    "traceroute",
+    "pip",
+    "pip3",
+    "pipx",
+    "uv",
+    "npm",
+    "npx",
+    "yarn",
+    "pnpm",
+    "cargo",
+    "go",
+    "gem",
+    "bundle",
+    "brew",
+    "apt",
+    "apt-get",
+    "dnf",
+    "docker",
+    "podman",
+    "ollama",
+    "gh",
+    "http",
+    "yt-dlp",
+];
+
+const GIT_NETWORK_SUBCOMMANDS: &[&str] = &[
+    "clone",
+    "fetch",
+    "pull",
+    "push",
+    "ls-remote",
+    "submodule",
+    "remote",
+];
+
+/// Whether any command in `cmd` needs network access. Unparseable command
+/// lines get none.
+pub fn needs_network(cmd: &str) -> bool {
+    let Ok(commands) = shell_syntax::parse(cmd) else {
+        return false;
+    };
+    commands.iter().any(|command| {
+        let mut words: &[String] = &command.words;
+        while let Some((_, inner)) = unwrap_command(words) {
+            words = inner;
+        }
+        let Some((first, rest)) = words.split_first() else {
+            return false;
+        };
+        let name = program_name(first);
+        if name == "git" {
+            return rest
+                .iter()
+                .find(|w| !w.starts_with('-'))
+                .is_some_and(|sub| GIT_NETWORK_SUBCOMMANDS.contains(&sub.as_str()));
+        }
+        NETWORK_COMMANDS.contains(&name.as_str())
+    })
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use super::{ProcessSandbox, ResourceLimits, HIDDEN_DIRS};
+    use landlock::{
+        path_beneath_rules, Access, AccessFs, AccessNet, Ruleset, RulesetAttr, RulesetCreatedAttr,
+        RulesetStatus, ABI,
+    };
+    use seccompiler::{
+        BpfProgram, SeccompAction, SeccompCmpArgLen, SeccompCmpOp, SeccompCondition, SeccompFilter,
+        SeccompRule,
+    };
+    use std::os::unix::fs::MetadataExt;
+    use std::path::{Path, PathBuf};
+    use std::sync::OnceLock;
+    use tokio::process::Command;
+
+    /// System directories readable under Landlock.
+    const SYSTEM_READABLE: &[&str] = &[
+        "/usr", "/bin", "/sbin", "/lib", "/lib32", "/lib64", "/etc", "/opt", "/var", "/run",
+        "/proc", "/sys", "/dev", "/nix", "/snap",
+    ];
+
+    /// Writable everywhere under Landlock: scratch space and the
+    /// character devices shells write to.
+    const ALWAYS_WRITABLE: &[&str] = &[
+        "/tmp",
+        "/var/tmp",
+        "/dev/null",
+        "/dev/zero",
+        "/dev/full",
+        "/dev/tty",
+        "/dev/pts",
+        "/dev/shm",
+    ];
+
+    pub(super) fn command(
+        sandbox: &ProcessSandbox,
+        shell: &str,
+        shell_arg: &str,
+        cmd: &str,
+        cwd: &Path,
+        network: bool,
+    ) -> anyhow::Result<Command> {
+        let limits = rlimits(sandbox.limits);
+        let mut command = match find_bwrap() {
+            Some(bwrap) => {
+                let home = dirs::home_dir();
+                let mut command = Command::new(bwrap);
+                command
+                    .args(sandbox.bwrap_args(cwd, network, home.as_deref()))
+                    .arg(shell)
+                    .arg(shell_arg)
+                    .arg(cmd);
+                // SAFETY: the closure only calls `setrlimit`, which is
+                // async-signal-safe.
+                unsafe {
+                    command.pre_exec(move || apply_rlimits(&limits));
+                }
+                command
+            }
+            None => {
+                let mut ruleset = Some(landlock_ruleset(sandbox, network)?);
+                let filter = if network {
+                    None
+                } else {
+                    match socket_filter() {
+                        Ok(filter) => Some(filter),
+                        Err(e) => {
+                            tracing::warn!("seccomp network filter unavailable: {}", e);
+                            None
+                        }
+                    }
+                };
+                let mut command = Command::new(shell);
+                command.arg(shell_arg).arg(cmd);
+                // SAFETY: the closure makes only raw syscalls (`setrlimit`,
+                // `prctl`, `landlock_restrict_self`, `seccomp`) on state
+                // built before the fork; nothing is allocated or locked.
+                unsafe {
+                    command.pre_exec(move || {
+                        apply_rlimits(&limits)?;
+                        if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0 {
+                            return Err(std::io::Error::last_os_error());
+                        }
+                        if let Some(ruleset) = ruleset.take() {
+                            ruleset.restrict_self().map_err(std::io::Error::other)?;
+                        }
+                        if let Some(filter) = &filter {
+                            seccompiler::apply_filter(filter).map_err(std::io::Error::other)?;
+                        }
+                        Ok(())
+                    });
+                }
+                command
+            }
+        };
+        command.current_dir(cwd);
+        Ok(command)
+    }
+
+    fn find_bwrap() -> Option<PathBuf> {
+        let path = std::env::var_os("PATH")?;
+        std::env::split_paths(&path)
+            .map(|dir| dir.join("bwrap"))
+            .find(|candidate| candidate.is_file())
+    }
+
+    /// Landlock rules: system directories read-only, the allowed folders
+    /// and scratch space read-write, everything else (the home directory
+    /// and `HIDDEN_DIRS`) inaccessible. Without network, TCP bind and
+    /// connect are refused too. Fails when the kernel doesn't enforce
+    /// Landlock, since nothing would confine the command.
+    fn landlock_ruleset(
+        sandbox: &ProcessSandbox,
+        network: bool,
+    ) -> anyhow::Result<landlock::RulesetCreated> {
+        if !landlock_supported() {
+            anyhow::bail!(
+                "neither bubblewrap nor Landlock is available to confine commands on this system"
+            );
+        }
+        let abi = ABI::V5;
+        let mut ruleset = Ruleset::default().handle_access(AccessFs::from_all(abi))?;
+        if !network {
+            ruleset = ruleset.handle_access(AccessNet::BindTcp | AccessNet::ConnectTcp)?;
+        }
+        let readable = readable_except(SYSTEM_READABLE, HIDDEN_DIRS)
+            .into_iter()
+            .chain(sandbox.readable_dirs.iter().cloned());
+        let writable = ALWAYS_WRITABLE
+            .iter()
+            .map(PathBuf::from)
+            .chain(sandbox.writable_dirs.iter().cloned());
+        let created = ruleset
+            .create()?
+            .add_rules(path_beneath_rules(readable, AccessFs::from_read(abi)))?
+            .add_rules(path_beneath_rules(writable, AccessFs::from_all(abi)))?;
+        Ok(created)
+    }
+
+    /// `dirs` with `hidden` cut out. Landlock rules can only grant access,
+    /// so a directory containing a hidden one is replaced by its other
+    /// entries.
+    pub(super) fn readable_except(dirs: &[&str], hidden: &[&str]) -> Vec<PathBuf> {
+        fn add(dir: PathBuf, hidden: &[&str], out: &mut Vec<PathBuf>) {
+            if hidden.iter().any(|h| dir == Path::new(h)) {
+                return;
+            }
+            if !hidden.iter().any(|h| Path::new(h).starts_with(&dir)) {
+                out.push(dir);
+                return;
+            }
+            if let Ok(entries) = std::fs::read_dir(&dir) {
+                for entry in entries.flatten() {
+                    add(entry.path(), hidden, out);
+                }
+            }
+        }
+        let mut out = Vec::new();
+        for dir in dirs {
+            add(PathBuf::from(dir), hidden, &mut out);
+        }
+        out
+    }
+
+    /// Whether the kernel enforces Landlock, checked once by restricting a
+    /// throwaway thread.
+    fn landlock_supported() -> bool {
+        static SUPPORTED: OnceLock<bool> = OnceLock::new();
+        *SUPPORTED.get_or_init(|| {
+            std::thread::spawn(|| {
+                Ruleset::default()
+                    .handle_access(AccessFs::Execute)
+                    .and_then(|r| r.create())
+                    .and_then(|r| r.restrict_self())
+                    .map(|status| status.ruleset != RulesetStatus::NotEnforced)
+                    .unwrap_or(false)
+            })
+            .join()
+            .unwrap_or(false)
+        })
+    }
+
+    /// A seccomp filter that fails `socket(AF_INET | AF_INET6, ...)` with
+    /// `EACCES` and allows everything else.
+    fn socket_filter() -> anyhow::Result<BpfProgram> {
+        let rule = |family: i32| {
+            SeccompCondition::new(0, SeccompCmpArgLen::Dword, SeccompCmpOp::Eq, family as u64)
+                .and_then(|c| SeccompRule::new(vec![c]))
+        };
+        let filter = SeccompFilter::new(
+            [(
+                libc::SYS_socket,
+                vec![rule(libc::AF_INET)?, rule(libc::AF_INET6)?],
+            )]
+            .into_iter()
+            .collect(),
+            SeccompAction::Allow,
+            SeccompAction::Errno(libc::EACCES as u32),
+            std::env::consts::ARCH.try_into()?,
+        )?;
+        Ok(filter.try_into()?)
+    }
+
+    type Rlimits = [(libc::__rlimit_resource_t, libc::rlimit); 3];
+
+    /// The rlimits for `limits`. `RLIMIT_NPROC` counts every process of
+    /// the user, so the cap is added to what the user already runs.
+    fn rlimits(limits: ResourceLimits) -> Rlimits {
+        let limit = |soft: u64, hard: u64| libc::rlimit {
+            rlim_cur: soft as libc::rlim_t,
+            rlim_max: hard as libc::rlim_t,
+        };
+        let processes = user_task_count() + limits.max_processes;
+        [
+            // The hard limit sends SIGKILL if SIGXCPU is ignored.
+            (
+                libc::RLIMIT_CPU,
+                limit(limits.cpu_secs, limits.cpu_secs + 5),
+            ),
+            (
+                libc::RLIMIT_AS,
+                limit(limits.memory_bytes, limits.memory_bytes),
+            ),
+            (libc::RLIMIT_NPROC, limit(processes, processes)),
+        ]
+    }
+
+    fn apply_rlimits(limits: &Rlimits) -> std::io::Result<()> {
+        for (resource, limit) in limits {
+            // SAFETY: `limit` points to a valid `rlimit`.
+            if unsafe { libc::setrlimit(*resource, limit) } != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+
+    /// Threads currently owned by this user, from `/proc`.
+    fn user_task_count() -> u64 {
+        // SAFETY: `getuid` has no preconditions.
+        let uid = unsafe { libc::getuid() };
+        let Ok(entries) = std::fs::read_dir("/proc") else {
+            return 0;
+        };
+        entries
+            .flatten()
+            .filter(|e| {
+                e.file_name()
+                    .to_string_lossy()
+                    .bytes()
+                    .all(|b| b.is_ascii_digit())
+            })
+            .filter(|e| e.metadata().is_ok_and(|m| m.uid() == uid))
+            .map(|e| {
+                std::fs::read_dir(e.path().join("task"))
+                    .map(|tasks| tasks.count() as u64)
+                    .unwrap_or(1)
+            })
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_needs_network() {
+        assert!(needs_network("curl -s https://example.com"));
+        assert!(needs_network("cd repo && git pull --rebase"));
+        assert!(needs_network("sudo pip install requests"));
+        assert!(!needs_network("git status"));
+        assert!(!needs_network("ls -la | grep txt"));
+    }
+
+    #[test]
+    fn test_bwrap_args_hide_home_and_bind_allowed_dirs() {
+        let sandbox = ProcessSandbox {
+            writable_dirs: vec![PathBuf::from("/home/u/work")],
+            readable_dirs: vec![PathBuf::from("/home/u/.cargo")],
+            limits: ResourceLimits::default(),
+        };
+        let args = sandbox
+            .bwrap_args(Path::new("/home/u/work"), false, Some(Path::new("/home/u")))
+            .join(" ");
+        assert!(!args.contains("--share-net"));
+        assert!(args.contains("--ro-bind / / "));
+        let hide = args.find("--tmpfs /home/u ").unwrap();
+        let ro = args
+            .find("--ro-bind-try /home/u/.cargo /home/u/.cargo")
+            .unwrap();
+        let rw = args.find("--bind-try /home/u/work /home/u/work").unwrap();
+        assert!(hide < ro && ro < rw);
+        assert!(args.ends_with("--chdir /home/u/work --"));
+
+        let args = sandbox.bwrap_args(Path::new("/home/u/work"), true, None);
+        assert!(args.contains(&"--share-net".to_string()));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_landlock_readable_dirs_leave_out_hidden() {
+        let dir = tempfile::tempdir().unwrap();
+        let run = dir.path().join("run");
+        for sub in ["lock", "user/1000", "systemd"] {
+            std::fs::create_dir_all(run.join(sub)).unwrap();
+        }
+        let hidden = run.join("user");
+        let mut readable = linux::readable_except(
+            &[run.to_str().unwrap(), "/usr"],
+            &[hidden.to_str().unwrap()],
+        );
+        readable.sort();
+        assert_eq!(
+            readable,
+            vec![run.join("lock"), run.join("systemd"), PathBuf::from("/usr")]
+        );
+    }
+
+    #[test]
+    fn test_restriction_from_exit() {
+        let sandbox = ProcessSandbox::new(Vec::new());
+        assert_eq!(
+            sandbox.restriction(false, 152, None, ""),
+            Some(SandboxRestriction::CpuTime)
+        );
+        assert_eq!(
+            sandbox.restriction(false, 6, None, "memory allocation of 1024 bytes failed"),
+            Some(SandboxRestriction::Memory)
+        );
+        assert_eq!(
+            sandbox.restriction(false, 6, None, "curl: (6) Could not resolve host: x.y"),
+            Some(SandboxRestriction::Network)
+        );
+        assert_eq!(
+            sandbox.restriction(true, 6, None, "Could not resolve host"),
+            None
+        );
+        assert_eq!(
+            sandbox.restriction(false, 1, None, "touch: /etc/x: Read-only file system"),
+            Some(SandboxRestriction::Filesystem)
+        );
+        assert_eq!(
+            sandbox.restriction(
+                false,
+                1,
+                None,
+                "socket.py: PermissionError: Permission denied"
+            ),
+            Some(SandboxRestriction::Network)
+        );
+        assert_eq!(
+            sandbox.restriction(false, 0, None, "permission denied"),
+            None
+        );
+    }
+}
@@ -71,6 +71,9 @@ Common Skills:
 - memory_optimize (consolidate, prune)
 - web_search (query)
 - write_file (path, content)
+
+If these actions are offered to you as native tools (`run_command`, `search`,
+or a skill ID), call the tool directly instead of writing tags.
 "#;
 
     format!(
@@ -0,0 +1,605 @@
+//! Hybrid retrieval: lexical, semantic and graph signals fused into one
+//! ranking.
+//!
+//! Each signal ranks passages on its own:
+//!
+//! - **Lexical**: BM25 over the passage text and heading plus the
+//!   document's name, tags and description ([`Bm25Index`]). Catches exact
+//!   names, identifiers and rare words that embeddings blur together.
+//! - **Semantic**: cosine similarity of passage embeddings to the query,
+//!   from the passage vector index.
+//! - **Graph**: documents reached from the graph nodes a query names or
+//!   resembles, walking up to [`GRAPH_HOPS`] relationships out
+//!   ([`graph_seeds`], [`graph_walk`]).
+//!
+//! The rankings are merged with reciprocal rank fusion ([`fuse`]): a
+//! passage scores `Σ 1 / (k + rank)` over the signals that ranked it. RRF
+//! only looks at ranks, so BM25 scores, cosines and walk strengths never
+//! have to be calibrated against each other, and a passage two signals
+//! agree on beats one that a single signal loves. Every fused result keeps
+//! each signal's rank and raw score ([`SignalScore`]), so a result can say
+//! why it matched.
+//!
+//! An optional [`Reranker`] (e.g. the local cross-encoder in
+//! `embedding.rs`) then re-orders the head of the fused list by reading
+//! query and passage together, which is slower but sharper than comparing
+//! separately computed vectors.
+//!
+//! `retrieval_eval.rs` measures all of this against a labelled query set.
+
+use crate::graph_store::GraphStore;
+use anyhow::Result;
+use petgraph::graph::NodeIndex;
+use petgraph::visit::EdgeRef;
+use petgraph::Direction;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+
+/// The `k` of reciprocal rank fusion. 60 is the value from the original
+/// RRF paper; it flattens the gap between the first few ranks so no one
+/// signal dominates.
+pub const RRF_K: f32 = 60.0;
+
+/// How many relationships the graph signal follows from a seed node.
+pub const GRAPH_HOPS: usize = 2;
+
+/// BM25 term-frequency saturation and length normalisation, at the usual
+/// defaults.
+const BM25_K1: f32 = 1.2;
+const BM25_B: f32 = 0.75;
+
+/// Words too common to say anything about relevance.
+const STOP_WORDS: &[&str] = &[
+    "about", "an", "and", "are", "as", "at", "be", "but", "by", "can", "do", "does", "for", "from",
+    "how", "in", "is", "it", "my", "of", "on", "or", "that", "the", "this", "to", "was", "what",
+    "when", "where", "which", "who", "why", "with", "you", "your",
+];
+
+/// A source of evidence for a result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Signal {
+    /// BM25 over passage text and document metadata
+    Lexical,
+    /// Embedding similarity to the query
+    Semantic,
+    /// Closeness in the knowledge graph to what the query mentions
+    Graph,
+    /// Cross-encoder relevance
+    Rerank,
+}
+
+impl Signal {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Signal::Lexical => "lexical",
+            Signal::Semantic => "semantic",
+            Signal::Graph => "graph",
+            Signal::Rerank => "rerank",
+        }
+    }
+}
+
+/// One signal's verdict on a result.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SignalScore {
+    pub signal: Signal,
+    /// 1-based rank in the signal's own ranking
+    pub rank: usize,
+    /// The signal's raw score: BM25, cosine similarity, walk strength or
+    /// cross-encoder logit. Only comparable within one signal.
+    pub score: f32,
+    /// What the signal matched on, when the score alone doesn't say (the
+    /// graph path that reached the document)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+impl fmt::Display for SignalScore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} #{} ({:.2}",
+            self.signal.name(),
+            self.rank,
+            self.score
+        )?;
+        if let Some(reason) = &self.reason {
+            write!(f, ": {}", reason)?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// An entry in one signal's ranking.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hit {
+    pub key: String,
+    pub score: f32,
+    pub reason: Option<String>,
+}
+
+impl Hit {
+    pub fn new(key: impl Into<String>, score: f32) -> Self {
+        Self {
+            key: key.into(),
+            score,
+            reason: None,
+        }
+    }
+
+    pub fn with_reason(mut self, reason: impl Into<String>) -> Self {
+        self.reason = Some(reason.into());
+        self
+    }
+}
+
+/// One signal's ranking, best first, each key at most once.
+#[derive(Debug, Clone)]
+pub struct Ranking {
+    pub signal: Signal,
+    pub hits: Vec<Hit>,
+}
+
+impl Ranking {
+    pub fn new(signal: Signal, hits: Vec<Hit>) -> Self {
+        Self { signal, hits }
+    }
+}
+
+/// A result of [`fuse`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fused {
+    pub key: String,
+    /// Reciprocal rank fusion score
+    pub score: f32,
+    /// How each signal that found the key ranked it
+    pub signals: Vec<SignalScore>,
+}
+
+/// Merge rankings by reciprocal rank fusion, best first.
+///
+/// Hits with equal scores share a rank and the next score takes the next
+/// rank, so a signal that scores whole documents (every passage alike)
+/// doesn't push its second document down by the first one's length. Ties
+/// in the fused score are broken by key, keeping results stable.
+pub fn fuse(rankings: &[Ranking], k: f32) -> Vec<Fused> {
+    let mut fused: HashMap<&str, Fused> = HashMap::new();
+    for ranking in rankings {
+        let mut rank = 0;
+        let mut previous = None;
+        for hit in &ranking.hits {
+            if previous != Some(hit.score) {
+                rank += 1;
+                previous = Some(hit.score);
+            }
+            let entry = fused.entry(&hit.key).or_insert_with(|| Fused {
+                key: hit.key.clone(),
+                score: 0.0,
+                signals: Vec::new(),
+            });
+            entry.score += 1.0 / (k + rank as f32);
+            entry.signals.push(SignalScore {
+                signal: ranking.signal,
+                rank,
+                score: hit.score,
+                reason: hit.reason.clone(),
+            });
+        }
+    }
+
+    let mut fused: Vec<Fused> = fused.into_values().collect();
+    fused.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| a.key.cmp(&b.key))
+    });
+    fused
+}
+
+/// Scores passages against a query by reading them together, e.g. a
+/// cross-encoder.
+pub trait Reranker: Send + Sync {
+    /// Relevance of each of `passages` to `query`, in order; higher is
+    /// more relevant.
+    fn score(&self, query: &str, passages: &[String]) -> Result<Vec<f32>>;
+}
+
+/// Re-order `results` by `reranker`'s scores of `texts` (one per result),
+/// recording its verdict as a [`Signal::Rerank`] score on each.
+pub fn rerank(
+    reranker: &dyn Reranker,
+    query: &str,
+    results: &mut [Fused],
+    texts: &[String],
+) -> Result<()> {
+    let scores = reranker.score(query, texts)?;
+    anyhow::ensure!(
+        scores.len() == results.len(),
+        "re-ranker returned {} scores for {} passages",
+        scores.len(),
+        results.len()
+    );
+
+    let mut order: Vec<usize> = (0..results.len()).collect();
+    order.sort_by(|&a, &b| {
+        scores[b]
+            .partial_cmp(&scores[a])
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    for (rank, &i) in order.iter().enumerate() {
+        results[i].signals.push(SignalScore {
+            signal: Signal::Rerank,
+            rank: rank + 1,
+            score: scores[i],
+            reason: None,
+        });
+    }
+    let reordered: Vec<Fused> = order.iter().map(|&i| results[i].clone()).collect();
+    results.clone_from_slice(&reordered);
+    Ok(())
+}
+
+/// An in-memory BM25 index over short texts, updated one key at a time.
+#[derive(Debug, Default)]
+pub struct Bm25Index {
+    /// Term -> key -> occurrences of the term in the key's text
+    postings: HashMap<String, HashMap<String, u32>>,
+    /// Key -> (length in terms, distinct terms)
+    entries: HashMap<String, (usize, Vec<String>)>,
+    /// Sum of all lengths, for the average
+    total_len: usize,
+}
+
+impl Bm25Index {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Index `text` under `key`, replacing whatever was there.
+    pub fn insert(&mut self, key: &str, text: &str) {
+        self.remove(key);
+        let tokens = tokenize(text);
+        let mut counts: HashMap<String, u32> = HashMap::new();
+        for token in &tokens {
+            *counts.entry(token.clone()).or_default() += 1;
+        }
+        for (term, count) in &counts {
+            self.postings
+                .entry(term.clone())
+                .or_default()
+                .insert(key.to_string(), *count);
+        }
+        self.total_len += tokens.len();
+        self.entries.insert(
+            key.to_string(),
+            (tokens.len(), counts.into_keys().collect()),
+        );
+    }
+
+    pub fn remove(&mut self, key: &str) -> bool {
+        let Some((len, terms)) = self.entries.remove(key) else {
+            return false;
+        };
+        self.total_len -= len;
+        for term in terms {
+            if let Some(posting) = self.postings.get_mut(&term) {
+                posting.remove(key);
+                if posting.is_empty() {
+                    self.postings.remove(&term);
+                }
+            }
+        }
+        true
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Keys whose text shares a term with `query`, best first, at most
+    /// `limit`.
+    pub fn search(&self, query: &str, limit: usize) -> Vec<(String, f32)> {
+        if self.entries.is_empty() {
+            return Vec::new();
+        }
+        let n = self.entries.len() as f32;
+        let average_len = (self.total_len as f32 / n).max(1.0);
+
+        let mut terms = tokenize(query);
+        terms.sort();
+        terms.dedup();
+
+        let mut scores: HashMap<&str, f32> = HashMap::new();
+        for term in &terms {
+            let Some(posting) = self.postings.get(term) else {
+                continue;
+            };
+            let df = posting.len() as f32;
+            let idf = ((n - df + 0.5) / (df + 0.5) + 1.0).ln();
+            for (key, &count) in posting {
+                let len = self.entries[key].0 as f32;
+                let tf = count as f32;
+                let norm = BM25_K1 * (1.0 - BM25_B + BM25_B * len / average_len);
+                *scores.entry(key).or_default() += idf * tf * (BM25_K1 + 1.0) / (tf + norm);
+            }
+        }
+
+        let mut results: Vec<(String, f32)> = scores
+            .into_iter()
+            .map(|(key, score)| (key.to_string(), score))
+            .collect();
+        results.sort_by(|a, b| {
+            b.1.partial_cmp(&a.1)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| a.0.cmp(&b.0))
+        });
+        results.truncate(limit);
+        results
+    }
+}
+
+/// The terms BM25 matches on: lowercased alphanumeric words, without
+/// one-letter words and stop words, with plurals folded ("backups" ->
+/// "backup", "entries" -> "entry").
+pub fn tokenize(text: &str) -> Vec<String> {
+    words(text)
+        .filter(|w| w.chars().count() > 1 && !STOP_WORDS.contains(&w.as_str()))
+        .map(|w| fold_plural(&w))
+        .collect()
+}
+
+fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+}
+
+fn fold_plural(word: &str) -> String {
+    if word.len() > 4 && word.ends_with("ies") {
+        format!("{}y", &word[..word.len() - 3])
+    } else if word.len() > 3
+        && word.ends_with('s')
+        && !word.ends_with("ss")
+        && !word.ends_with("us")
+        && !word.ends_with("is")
+    {
+        word[..word.len() - 1].to_string()
+    } else {
+        word.to_string()
+    }
+}
+
+/// Where a graph walk starts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Seed {
+    pub node: NodeIndex,
+    /// 1.0 for a node the query names, else its similarity to the query
+    pub strength: f32,
+    pub reason: String,
+}
+
+/// The nodes a query points at: those whose label appears in it as a
+/// whole phrase, and, given the query's embedding, the few most similar
+/// to it.
+pub fn graph_seeds(graph: &GraphStore, query: &str, query_vec: Option<&[f32]>) -> Vec<Seed> {
+    let query_words: Vec<String> = words(query).collect();
+    let mut seeds: Vec<Seed> = Vec::new();
+
+    for idx in graph.graph.node_indices() {
+        let label = &graph.graph[idx].label;
+        let label_words: Vec<String> = words(label).collect();
+        let meaningful = label_words
+            .iter()
+            .any(|w| w.chars().count() > 2 && !STOP_WORDS.contains(&w.as_str()));
+        if meaningful
+            && query_words
+                .windows(label_words.len())
+                .any(|window| window == label_words.as_slice())
+        {
+            seeds.push(Seed {
+                node: idx,
+                strength: 1.0,
+                reason: format!("query names {}", label),
+            });
+        }
+    }
+
+    if let Some(query_vec) = query_vec {
+        for (idx, similarity) in graph.vector_search(query_vec, 5, 0.5) {
+            if !seeds.iter().any(|s| s.node == idx) {
+                seeds.push(Seed {
+                    node: idx,
+                    strength: similarity,
+                    reason: format!("query resembles {}", graph.graph[idx].label),
+                });
+            }
+        }
+    }
+    seeds
+}
+
+/// A node reached by [`graph_walk`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reached {
+    pub node: NodeIndex,
+    /// Seed strength times the weight of each edge on the way, halved per
+    /// hop
+    pub strength: f32,
+    pub hops: usize,
+    /// The seed's reason, then each relationship followed
+    pub path: Vec<String>,
+}
+
+impl Reached {
+    pub fn explain(&self) -> String {
+        self.path.join(" → ")
+    }
+}
+
+/// Walk up to `max_hops` relationships out from `seeds`, along edges in
+/// either direction. Seeds are reached at hop 0; a node reached several
+/// ways keeps its strongest path. Strongest first.
+pub fn graph_walk(graph: &GraphStore, seeds: &[Seed], max_hops: usize) -> Vec<Reached> {
+    let mut best: HashMap<NodeIndex, Reached> = HashMap::new();
+
+    for seed in seeds {
+        let mut queue = VecDeque::from([Reached {
+            node: seed.node,
+            strength: seed.strength,
+            hops: 0,
+            path: vec![seed.reason.clone()],
+        }]);
+        while let Some(current) = queue.pop_front() {
+            if best
+                .get(&current.node)
+                .is_some_and(|b| b.strength >= current.strength)
+            {
+                continue;
+            }
+            if current.hops < max_hops {
+                let label = &graph.graph[current.node].label;
+                let outgoing = graph
+                    .graph
+                    .edges_directed(current.node, Direction::Outgoing)
+                    .map(|e| (e.target(), e.weight(), true));
+                let incoming = graph
+                    .graph
+                    .edges_directed(current.node, Direction::Incoming)
+                    .map(|e| (e.source(), e.weight(), false));
+                for (next, edge, forward) in outgoing.chain(incoming) {
+                    let next_label = &graph.graph[next].label;
+                    let step = if forward {
+                        format!("{} {} {}", label, edge.relation, next_label)
+                    } else {
+                        format!("{} {} {}", next_label, edge.relation, label)
+                    };
+                    let mut path = current.path.clone();
+                    path.push(step);
+                    queue.push_back(Reached {
+                        node: next,
+                        strength: current.strength * edge.weight * 0.5,
+                        hops: current.hops + 1,
+                        path,
+                    });
+                }
+            }
+            best.insert(current.node, current);
+        }
+    }
+
+    let mut reached: Vec<Reached> = best.into_values().collect();
+    reached.sort_by(|a, b| {
+        b.strength
+            .partial_cmp(&a.strength)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| a.hops.cmp(&b.hops))
+            .then_with(|| a.node.cmp(&b.node))
+    });
+    reached
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph_store::Mode;
+
+    #[test]
+    fn test_bm25_prefers_rare_terms_and_forgets_removed_keys() {
+        let mut index = Bm25Index::new();
+        index.insert("a", "The router forwards port 22 to the backup server");
+        index.insert("b", "Backups run nightly; the server keeps thirty days");
+        index.insert("c", "Sourdough needs a warm kitchen and a lively starter");
+
+        let results = index.search("router backups", 10);
+        let keys: Vec<&str> = results.iter().map(|(k, _)| k.as_str()).collect();
+        // "router" is rarer than "backup", which both a and b have.
+        assert_eq!(keys, vec!["a", "b"]);
+
+        assert!(index.remove("a"));
+        assert!(!index.contains("a"));
+        let results = index.search("router", 10);
+        assert!(results.is_empty());
+        assert_eq!(index.len(), 2);
+    }
+
+    #[test]
+    fn test_fusion_rewards_agreement_and_explains_itself() {
+        let lexical = Ranking::new(
+            Signal::Lexical,
+            vec![Hit::new("x", 9.0), Hit::new("y", 4.0), Hit::new("z", 1.0)],
+        );
+        let semantic = Ranking::new(
+            Signal::Semantic,
+            vec![Hit::new("y", 0.8), Hit::new("z", 0.7)],
+        );
+        let graph = Ranking::new(
+            Signal::Graph,
+            vec![
+                Hit::new("z", 0.5).with_reason("Alice works on Atlas"),
+                Hit::new("y", 0.5).with_reason("Alice works on Atlas"),
+            ],
+        );
+
+        let fused = fuse(&[lexical, semantic, graph], RRF_K);
+        let keys: Vec<&str> = fused.iter().map(|f| f.key.as_str()).collect();
+        assert_eq!(keys, vec!["y", "z", "x"]);
+
+        // Equal graph scores share rank 1.
+        let z = &fused[1];
+        let graph = z
+            .signals
+            .iter()
+            .find(|s| s.signal == Signal::Graph)
+            .unwrap();
+        assert_eq!(graph.rank, 1);
+        assert_eq!(graph.to_string(), "graph #1 (0.50: Alice works on Atlas)");
+        assert!((z.score - (1.0 / 63.0 + 1.0 / 62.0 + 1.0 / 61.0)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_graph_walk_follows_relations_both_ways() {
+        let mut graph = GraphStore::new();
+        let alice = graph.add_node(
+            "Alice",
+            Some("Person".into()),
+            "system",
+            Mode::General,
+            None,
+        );
+        let atlas = graph.add_node(
+            "Atlas",
+            Some("Project".into()),
+            "doc/atlas",
+            Mode::General,
+            None,
+        );
+        let db = graph.add_node("Postgres", None, "doc/db", Mode::General, None);
+        let far = graph.add_node("Kyoto", None, "doc/travel", Mode::General, None);
+        graph.add_edge(alice, atlas, "works on");
+        graph.add_edge(atlas, db, "depends on");
+        graph.add_edge(far, db, "unrelated to");
+
+        let seeds = graph_seeds(&graph, "what is alice doing", None);
+        assert_eq!(seeds.len(), 1);
+        let reached = graph_walk(&graph, &seeds, GRAPH_HOPS);
+
+        let nodes: Vec<NodeIndex> = reached.iter().map(|r| r.node).collect();
+        assert_eq!(nodes, vec![alice, atlas, db]);
+        assert_eq!(
+            reached[2].explain(),
+            "query names Alice → Alice works on Atlas → Atlas depends on Postgres"
+        );
+        assert!((reached[2].strength - 0.25).abs() < 1e-6);
+    }
+}
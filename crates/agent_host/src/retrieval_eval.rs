@@ -0,0 +1,198 @@
+//! Offline evaluation of context retrieval.
+//!
+//! Loads a small labelled corpus (`tests/fixtures/retrieval/`) into a
+//! throwaway [`ContextManager`], runs each query through
+//! [`ContextManager::search`] and scores where the relevant documents
+//! land: the mean reciprocal rank of the first relevant one, and recall in
+//! the top `k`. When changing how retrieval ranks, run
+//!
+//! ```text
+//! cargo test -p agent_host retrieval_eval -- --nocapture
+//! ```
+//!
+//! to see the report; the test fails if quality drops below the recorded
+//! baseline. Without the embedding model (as in CI) only the lexical and
+//! graph signals run, so the baseline is theirs.
+
+use std::fmt;
+
+use serde::Deserialize;
+use tempfile::TempDir;
+
+use crate::context_manager::{ContextManager, ContextType};
+use crate::graph_store::Mode;
+
+const CORPUS: &str = include_str!(concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/../../tests/fixtures/retrieval/corpus.json"
+));
+
+const QUERIES: &str = include_str!(concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/../../tests/fixtures/retrieval/queries.json"
+));
+
+/// `corpus.json`: documents, and relations between them and the people
+/// and things they mention.
+#[derive(Deserialize)]
+struct Corpus {
+    documents: Vec<CorpusDocument>,
+    /// `[source, relation, target]`
+    relations: Vec<[String; 3]>,
+}
+
+#[derive(Deserialize)]
+struct CorpusDocument {
+    name: String,
+    #[serde(rename = "type")]
+    context_type: ContextType,
+    tags: Vec<String>,
+    description: String,
+    content: String,
+}
+
+/// An entry of `queries.json`.
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct LabelledQuery {
+    pub query: String,
+    /// Names of the documents that answer it
+    pub relevant: Vec<String>,
+}
+
+/// How well a set of queries was answered.
+#[derive(Debug, Clone)]
+pub(crate) struct EvalReport {
+    pub k: usize,
+    pub queries: usize,
+    /// Mean over queries of 1 / rank of the first relevant document
+    /// (0 if none is returned)
+    pub mrr: f32,
+    /// Mean over queries of the share of relevant documents in the top `k`
+    pub recall: f32,
+    /// Queries whose first result wasn't relevant, with what came first
+    pub misses: Vec<String>,
+}
+
+impl fmt::Display for EvalReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} queries: MRR {:.3}, recall@{} {:.3}",
+            self.queries, self.mrr, self.k, self.recall
+        )?;
+        for miss in &self.misses {
+            writeln!(f, "  miss: {}", miss)?;
+        }
+        Ok(())
+    }
+}
+
+/// A context manager in a temporary directory holding the fixture corpus
+/// and its relations.
+pub(crate) fn load_corpus() -> (TempDir, ContextManager) {
+    let corpus: Corpus = serde_json::from_str(CORPUS).unwrap();
+    let dir = TempDir::new().unwrap();
+    let mut manager = ContextManager::new(dir.path().to_path_buf()).unwrap();
+    for doc in corpus.documents {
+        manager
+            .add_document(
+                &doc.name,
+                doc.context_type,
+                &doc.content,
+                &doc.description,
+                doc.tags,
+            )
+            .unwrap();
+    }
+    for [source, relation, target] in &corpus.relations {
+        manager.add_graph_edge(source, target, relation, Mode::General);
+    }
+    (dir, manager)
+}
+
+pub(crate) fn labelled_queries() -> Vec<LabelledQuery> {
+    serde_json::from_str(QUERIES).unwrap()
+}
+
+/// Run `queries` through `manager` and score the rankings.
+pub(crate) fn evaluate(
+    manager: &mut ContextManager,
+    queries: &[LabelledQuery],
+    k: usize,
+) -> EvalReport {
+    let mut reciprocal_ranks = 0.0;
+    let mut recall = 0.0;
+    let mut misses = Vec::new();
+
+    for labelled in queries {
+        let ranked: Vec<String> = manager
+            .search(&labelled.query, None)
+            .into_iter()
+            .map(|r| r.document.name)
+            .collect();
+        let is_relevant = |name: &String| labelled.relevant.contains(name);
+
+        if let Some(rank) = ranked.iter().position(is_relevant) {
+            reciprocal_ranks += 1.0 / (rank + 1) as f32;
+        }
+        let found = ranked.iter().take(k).filter(|n| is_relevant(n)).count();
+        recall += found as f32 / labelled.relevant.len() as f32;
+        if !ranked.first().is_some_and(is_relevant) {
+            misses.push(format!(
+                "{:?} -> {}",
+                labelled.query,
+                ranked.first().map(String::as_str).unwrap_or("(nothing)")
+            ));
+        }
+    }
+
+    let n = queries.len().max(1) as f32;
+    EvalReport {
+        k,
+        queries: queries.len(),
+        mrr: reciprocal_ranks / n,
+        recall: recall / n,
+        misses,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retrieval_quality_baseline() {
+        let (_dir, mut manager) = load_corpus();
+        let report = evaluate(&mut manager, &labelled_queries(), 3);
+        println!("{}", report);
+
+        assert!(report.mrr >= 0.9, "MRR regressed:\n{}", report);
+        assert!(report.recall >= 0.9, "recall@3 regressed:\n{}", report);
+    }
+
+    #[test]
+    fn test_results_explain_their_signals() {
+        let (_dir, mut manager) = load_corpus();
+
+        // "Alice" is only a graph node and a word in the meeting notes;
+        // the migration plan is found through the relation.
+        let passages = manager.search_passages("what is Alice responsible for", None, 10);
+        let plan = passages
+            .iter()
+            .find(|p| p.document.name == "Atlas Migration Plan")
+            .expect("graph relation should surface the plan");
+        let explanation = plan.explain();
+        assert!(
+            explanation.contains("graph #1")
+                && explanation.contains("Alice owns Atlas Migration Plan"),
+            "{}",
+            explanation
+        );
+
+        let notes = passages
+            .iter()
+            .find(|p| p.document.name == "Quarterly Planning Notes")
+            .unwrap();
+        assert!(notes.explain().contains("lexical #"));
+    }
+}
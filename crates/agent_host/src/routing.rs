@@ -0,0 +1,447 @@
+//! Per-request provider and model selection.
+//!
+//! `provider_preference` alone sends every request down the same fallback
+//! chain. [`plan_route`] applies the [`RoutingPolicy`](shared::settings::RoutingPolicy) stored in
+//! [`ModelProvider`] to one request and returns a config whose preference
+//! order and model slots suit it, ready for `ProviderRouter::new`.
+//!
+//! Rules are applied in this order:
+//! 1. **Hard rules** remove providers: modes in `local_only_modes` and an
+//!    exhausted budget (when `respect_budget` is set) leave only `local`.
+//! 2. **Strategy**: `Cheapest` sorts by estimated cost from
+//!    [`get_model_pricing`] (local models are free); `Preference` keeps the
+//!    configured order.
+//! 3. Models without native tool calling move behind those with it.
+//! 4. `local_max_prompt_tokens` moves `local` to the front for small prompts
+//!    and to the back for large ones.
+//! 5. Models whose context window can't hold the prompt, or that can't see
+//!    attached images, move to the back. They stay as a last resort because
+//!    trimming and text fallbacks still give a usable answer.
+//!
+//! Context windows and capabilities come from the model catalog when the
+//! request carries one (see [`providers::catalog`]), and from name-based
+//! guesses otherwise.
+//!
+//! Provider profiles take part like the built-in providers, using their
+//! capability flags instead of name-based guesses. Profiles marked `local`
+//! count as local: free, and kept by the local-only rules.
+
+use crate::context_token_manager::ContextUsageTracker;
+use crate::token_tracker::get_model_pricing;
+use providers::capabilities::{context_window, supports_tools, supports_vision};
+use providers::catalog::ModelCatalogs;
+use shared::agent_api::{Attachment, ChatMessage};
+use shared::settings::{ModelProvider, ProviderProfile, RoutingStrategy};
+use shared::skill::Mode;
+
+/// Reply length assumed when estimating cost and context fit.
+const ESTIMATED_OUTPUT_TOKENS: usize = 500;
+/// Rough prompt cost of one attached image.
+const IMAGE_TOKENS: usize = 1_000;
+const KNOWN_PROVIDERS: [&str; 4] = ["local", "openai", "anthropic", "gemini"];
+
+/// What the router needs to know about one request.
+#[derive(Debug, Clone, Copy)]
+pub struct RouteRequest<'a> {
+    pub messages: &'a [ChatMessage],
+    /// The mode the conversation is in, if any.
+    pub mode: Option<Mode>,
+    /// Whether tools will be offered with the request.
+    pub tools: bool,
+    /// Budget left this period (see `TokenTracker::remaining_budget_usd`).
+    pub remaining_budget_usd: Option<f64>,
+    /// Listed models, for real context windows and capabilities.
+    pub catalog: Option<&'a ModelCatalogs>,
+}
+
+/// One provider the router may try.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouteCandidate {
+    pub provider: String,
+    pub model: String,
+    pub estimated_cost_usd: f64,
+    /// The prompt plus an average reply fits the model's context window.
+    pub fits_context: bool,
+    /// The model can see attached images (true if there are none).
+    pub vision: bool,
+    /// The model supports native tool calling (true if no tools are offered).
+    pub tools: bool,
+}
+
+/// The outcome of [`plan_route`].
+#[derive(Debug, Clone)]
+pub struct RoutePlan {
+    /// Config for `ProviderRouter::new`: preference reordered, models chosen.
+    pub config: ModelProvider,
+    /// Providers in the order they will be tried.
+    pub candidates: Vec<RouteCandidate>,
+    pub prompt_tokens: usize,
+    /// Why providers were removed, for logs.
+    pub notes: Vec<String>,
+}
+
+/// Estimated prompt size of `messages` in tokens.
+pub fn estimate_prompt_tokens(messages: &[ChatMessage]) -> usize {
+    messages
+        .iter()
+        .map(|m| {
+            let images = m
+                .attachments
+                .iter()
+                .filter(|a| matches!(a, Attachment::Image { .. }))
+                .count();
+            ContextUsageTracker::estimate_tokens(&m.content) + images * IMAGE_TOKENS
+        })
+        .sum()
+}
+
+/// Pick the provider order and models for one request.
+pub fn plan_route(config: &ModelProvider, req: &RouteRequest<'_>) -> RoutePlan {
+    let policy = &config.routing;
+    let fast = req.mode.is_some_and(|m| policy.fast_modes.contains(&m));
+    let prompt_tokens = estimate_prompt_tokens(req.messages);
+    let has_images = req.messages.iter().any(|m| {
+        m.attachments
+            .iter()
+            .any(|a| matches!(a, Attachment::Image { .. }))
+    });
+    let mut notes = Vec::new();
+
+    let mut providers: Vec<&str> = Vec::new();
+    for p in &config.provider_preference {
+        if !KNOWN_PROVIDERS.contains(&p.as_str()) && config.profile(p).is_none() {
+            notes.push(format!("ignoring unknown provider {}", p));
+        } else if !providers.contains(&p.as_str()) {
+            providers.push(p);
+        }
+    }
+
+    let local_only = |providers: &mut Vec<&str>| {
+        providers.retain(|p| is_local(config, p));
+        if providers.is_empty() {
+            providers.push("local");
+        }
+    };
+    if let Some(mode) = req.mode.filter(|m| policy.local_only_modes.contains(m)) {
+        local_only(&mut providers);
+        notes.push(format!(
+            "{} mode is set to stay on this computer",
+            mode.display_name()
+        ));
+    } else if policy.respect_budget && req.remaining_budget_usd.is_some_and(|r| r <= 0.0) {
+        local_only(&mut providers);
+        notes.push("budget used up; using the local model only".to_string());
+    }
+
+    let mut candidates: Vec<RouteCandidate> = providers
+        .into_iter()
+        .map(|provider| {
+            let model = model_for(config, provider, fast);
+            let estimated_cost_usd = if is_local(config, provider) {
+                0.0
+            } else {
+                get_model_pricing(&model)
+                    .calculate_cost(prompt_tokens as u32, ESTIMATED_OUTPUT_TOKENS as u32)
+            };
+            let listed = req.catalog.and_then(|c| c.model(provider, &model));
+            let (window, vision, tools) = match (config.profile(provider), listed) {
+                (Some(profile), _) => (
+                    profile
+                        .context_window
+                        .or(listed.map(|m| m.context_window))
+                        .unwrap_or_else(|| context_window("openai", &model)),
+                    profile.supports_vision,
+                    profile.supports_tools,
+                ),
+                (None, Some(info)) => (
+                    info.context_window,
+                    info.supports_vision,
+                    info.supports_tools,
+                ),
+                (None, None) => (
+                    context_window(provider, &model),
+                    supports_vision(provider, &model),
+                    supports_tools(provider, &model),
+                ),
+            };
+            RouteCandidate {
+                fits_context: window >= prompt_tokens + ESTIMATED_OUTPUT_TOKENS,
+                vision: !has_images || vision,
+                tools: !req.tools || tools,
+                provider: provider.to_string(),
+                model,
+                estimated_cost_usd,
+            }
+        })
+        .collect();
+
+    if policy.strategy == RoutingStrategy::Cheapest {
+        candidates.sort_by(|a, b| a.estimated_cost_usd.total_cmp(&b.estimated_cost_usd));
+    }
+    candidates.sort_by_key(|c| !c.tools);
+    if let Some(limit) = policy.local_max_prompt_tokens {
+        if let Some(i) = candidates.iter().position(|c| c.provider == "local") {
+            let local = candidates.remove(i);
+            if prompt_tokens <= limit {
+                candidates.insert(0, local);
+            } else {
+                candidates.push(local);
+            }
+        }
+    }
+    candidates.sort_by_key(|c| (!c.fits_context, !c.vision));
+
+    let mut routed = config.clone();
+    routed.provider_preference = candidates.iter().map(|c| c.provider.clone()).collect();
+    if fast {
+        routed.openai_model = config.openai_fast_model.clone();
+        routed.anthropic_model = config.anthropic_fast_model.clone();
+        routed.gemini_model = config.gemini_fast_model.clone();
+        for profile in &mut routed.profiles {
+            use_fast_model(profile);
+        }
+    }
+
+    RoutePlan {
+        config: routed,
+        candidates,
+        prompt_tokens,
+        notes,
+    }
+}
+
+/// The built-in local provider, or a profile marked `local`.
+fn is_local(config: &ModelProvider, provider: &str) -> bool {
+    provider == "local" || config.profile(provider).is_some_and(|p| p.local)
+}
+
+/// Move the profile's fast model to the front so it becomes the default.
+fn use_fast_model(profile: &mut ProviderProfile) {
+    let fast = profile.fast_model().to_string();
+    profile.models.retain(|m| *m != fast);
+    profile.models.insert(0, fast);
+}
+
+fn model_for(config: &ModelProvider, provider: &str, fast: bool) -> String {
+    match (provider, fast) {
+        ("local", _) => config.local_model.clone(),
+        ("openai", false) => config.openai_model.clone(),
+        ("openai", true) => config.openai_fast_model.clone(),
+        ("anthropic", false) => config.anthropic_model.clone(),
+        ("anthropic", true) => config.anthropic_fast_model.clone(),
+        ("gemini", false) => config.gemini_model.clone(),
+        ("gemini", true) => config.gemini_fast_model.clone(),
+        _ => match config.profile(provider) {
+            Some(profile) if fast => profile.fast_model().to_string(),
+            Some(profile) => profile.model().to_string(),
+            None => String::new(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shared::settings::AppSettings;
+
+    fn config() -> ModelProvider {
+        let mut config = AppSettings::default().model;
+        config.provider_preference = vec![
+            "anthropic".to_string(),
+            "openai".to_string(),
+            "local".to_string(),
+        ];
+        config.openai_model = "gpt-4o".to_string();
+        config.anthropic_model = "claude-3-opus-20240229".to_string();
+        config
+    }
+
+    fn order(plan: &RoutePlan) -> Vec<&str> {
+        plan.candidates
+            .iter()
+            .map(|c| c.provider.as_str())
+            .collect()
+    }
+
+    fn request(messages: &[ChatMessage]) -> RouteRequest<'_> {
+        RouteRequest {
+            messages,
+            mode: None,
+            tools: false,
+            remaining_budget_usd: None,
+            catalog: None,
+        }
+    }
+
+    #[test]
+    fn test_preference_order_is_kept_by_default() {
+        let messages = vec![ChatMessage::user("hi")];
+        let plan = plan_route(&config(), &request(&messages));
+        assert_eq!(order(&plan), vec!["anthropic", "openai", "local"]);
+        assert_eq!(
+            plan.config.provider_preference,
+            vec!["anthropic", "openai", "local"]
+        );
+    }
+
+    #[test]
+    fn test_cheapest_strategy_and_fast_modes() {
+        let mut config = config();
+        config.routing.strategy = RoutingStrategy::Cheapest;
+        let messages = vec![ChatMessage::user("hi")];
+        let plan = plan_route(&config, &request(&messages));
+        assert_eq!(order(&plan), vec!["local", "openai", "anthropic"]);
+
+        let plan = plan_route(
+            &config,
+            &RouteRequest {
+                mode: Some(Mode::Find),
+                ..request(&messages)
+            },
+        );
+        assert_eq!(plan.config.anthropic_model, config.anthropic_fast_model);
+        // Haiku is far cheaper than gpt-4o-mini in the pricing table.
+        assert_eq!(order(&plan), vec!["local", "anthropic", "openai"]);
+    }
+
+    #[test]
+    fn test_local_threshold() {
+        let mut config = config();
+        config.routing.local_max_prompt_tokens = Some(100);
+        let small = vec![ChatMessage::user("hi")];
+        assert_eq!(order(&plan_route(&config, &request(&small)))[0], "local");
+
+        let large = vec![ChatMessage::user("x".repeat(4_000))];
+        assert_eq!(order(&plan_route(&config, &request(&large)))[2], "local");
+    }
+
+    #[test]
+    fn test_hard_rules_keep_data_local() {
+        let mut config = config();
+        config.routing.local_only_modes = vec![Mode::Data];
+        let messages = vec![ChatMessage::user("hi")];
+        let plan = plan_route(
+            &config,
+            &RouteRequest {
+                mode: Some(Mode::Data),
+                ..request(&messages)
+            },
+        );
+        assert_eq!(order(&plan), vec!["local"]);
+
+        let plan = plan_route(
+            &config,
+            &RouteRequest {
+                remaining_budget_usd: Some(-0.5),
+                ..request(&messages)
+            },
+        );
+        assert_eq!(order(&plan), vec!["local"]);
+        assert_eq!(plan.notes.len(), 1);
+    }
+
+    #[test]
+    fn test_profiles_route_with_their_own_flags() {
+        use shared::settings::{AuthHeaderStyle, ProviderAuth};
+
+        let profile = |name: &str, local: bool| ProviderProfile {
+            name: name.to_string(),
+            base_url: "http://localhost:1234".to_string(),
+            auth_header: AuthHeaderStyle::None,
+            auth: ProviderAuth::default(),
+            models: vec!["big".to_string(), "small".to_string()],
+            fast_model: Some("small".to_string()),
+            supports_tools: false,
+            supports_vision: false,
+            context_window: Some(4_096),
+            local,
+        };
+        let mut config = config();
+        config.profiles = vec![profile("lmstudio", true), profile("openrouter", false)];
+        config.provider_preference = vec![
+            "lmstudio".to_string(),
+            "openrouter".to_string(),
+            "openai".to_string(),
+        ];
+        config.routing.local_only_modes = vec![Mode::Data];
+        let messages = vec![ChatMessage::user("hi")];
+
+        let plan = plan_route(
+            &config,
+            &RouteRequest {
+                tools: true,
+                ..request(&messages)
+            },
+        );
+        assert_eq!(order(&plan), vec!["openai", "lmstudio", "openrouter"]);
+
+        let plan = plan_route(
+            &config,
+            &RouteRequest {
+                mode: Some(Mode::Data),
+                ..request(&messages)
+            },
+        );
+        assert_eq!(order(&plan), vec!["lmstudio"]);
+        assert_eq!(plan.candidates[0].estimated_cost_usd, 0.0);
+
+        let plan = plan_route(
+            &config,
+            &RouteRequest {
+                mode: Some(Mode::Find),
+                ..request(&messages)
+            },
+        );
+        assert_eq!(plan.candidates[0].model, "small");
+        assert_eq!(plan.config.profiles[0].model(), "small");
+    }
+
+    #[test]
+    fn test_capabilities_demote_unfit_models() {
+        // ~40k tokens overflows the local 8k window.
+        let long = vec![ChatMessage::user("x".repeat(160_000))];
+        let mut config = config();
+        config.provider_preference = vec!["local".to_string(), "openai".to_string()];
+        assert_eq!(
+            order(&plan_route(&config, &request(&long))),
+            vec!["openai", "local"]
+        );
+
+        let image = vec![ChatMessage::user("what is this?")
+            .with_attachment(Attachment::image("image/png", b"png"))];
+        assert_eq!(
+            order(&plan_route(&config, &request(&image))),
+            vec!["openai", "local"]
+        );
+    }
+
+    #[test]
+    fn test_catalog_context_window_overrides_guess() {
+        use providers::catalog::{ModelCatalog, ModelInfo};
+
+        let long = vec![ChatMessage::user("x".repeat(160_000))];
+        let mut config = config();
+        config.provider_preference = vec!["local".to_string(), "openai".to_string()];
+        config.local_model = "qwen2.5:14b-128k".to_string();
+        let mut catalogs = ModelCatalogs::default();
+        catalogs.providers.insert(
+            "local".to_string(),
+            ModelCatalog {
+                fetched_at: 0,
+                models: vec![ModelInfo {
+                    context_window: 131_072,
+                    ..ModelInfo::new("local", "qwen2.5:14b-128k", None)
+                }],
+            },
+        );
+        let plan = plan_route(
+            &config,
+            &RouteRequest {
+                catalog: Some(&catalogs),
+                ..request(&long)
+            },
+        );
+        assert!(plan.candidates[0].fits_context);
+        assert_eq!(order(&plan), vec!["local", "openai"]);
+    }
+}
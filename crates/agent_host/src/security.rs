@@ -3,60 +3,265 @@
 //! Two independent mechanisms live here:
 //!
 //! - **`PathSandbox`** -- restricts all file-touching commands to a set of
-//!   user-approved directories.  Every path token in a command is resolved
-//!   and checked *before* the shell is spawned, preventing directory
-//!   traversal even when the LLM crafts creative paths.
+//!   user-approved directories.  Every path a command would touch (found by
+//!   `command_paths.rs`) is resolved and checked *before* the shell is
+//!   spawned, preventing directory traversal even when the LLM crafts
+//!   creative paths. Credential stores (`~/.ssh`, `~/.aws`, `.env`, ...)
+//!   are refused even inside an allowed directory.
 //!
-//! - **`SecurityContext`** -- time-boxed TOTP session gate.  Destructive
-//!   commands (rm, chmod, kill, ...) require an active authentication
-//!   window before the executor will proceed. The window expires after a
-//!   configurable timeout (default 15 minutes) to limit blast radius.
+//! - **`SecurityContext`** -- TOTP step-up grants.  Destructive commands
+//!   (rm, chmod, kill, ...) need an active grant before the executor will
+//!   proceed. A grant can be scoped to a command pattern and/or a
+//!   directory, expire after a few minutes, be used once, or be revoked;
+//!   the executor checks the parsed command against the grant's scope.
+//!   An unscoped grant for the configured timeout (default 15 minutes)
+//!   gives the old unlock-everything window.
 
+use crate::command_paths::{command_paths, runs_inline_code, unwrapped};
+use crate::command_policy::{expand_home, glob_to_regex};
+use crate::executor::{CommandClassification, DangerLevel};
+use crate::shell_syntax::{self, program_name};
+use parking_lot::Mutex;
+use regex::RegexBuilder;
+use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
-use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-/// Tracks whether a TOTP verification has occurred recently enough to
-/// permit destructive operations. Uses an atomic timestamp so the check
-/// is lock-free and safe to share across async tasks.
+/// What a 2FA grant unlocks. The default scope unlocks every command that
+/// needs 2FA.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GrantScope {
+    /// Which commands: a bare program name (`rm`) matches any invocation
+    /// of it, anything else is a shell-style glob over the whole simple
+    /// command (`git push *`), as in command policy rules.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+    /// Where: every path the command touches -- or its working directory,
+    /// if it touches none -- must be inside this directory (`~` allowed).
+    /// Commands that run inline shell or interpreter code (`sh -c`,
+    /// `eval`, `python -c`) are never covered, as what they touch can't be
+    /// known for sure.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dir: Option<PathBuf>,
+}
+
+impl GrantScope {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_pattern(mut self, pattern: impl Into<String>) -> Self {
+        self.pattern = Some(pattern.into());
+        self
+    }
+
+    pub fn with_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.dir = Some(dir.into());
+        self
+    }
+
+    /// Whether this scope covers `cmd`, run from `cwd`: each of its
+    /// simple commands that needs 2FA (including those in inline code)
+    /// matches the pattern, and everything it touches, inline code
+    /// included, is inside the directory.
+    pub fn covers(&self, classification: &CommandClassification, cmd: &str, cwd: &Path) -> bool {
+        if let Some(pattern) = &self.pattern {
+            let covered = classification
+                .segments
+                .iter()
+                .filter(|s| s.level == DangerLevel::NeedsAuth)
+                .all(|s| pattern_matches(pattern, &s.command));
+            if !covered {
+                return false;
+            }
+        }
+        if let Some(dir) = &self.dir {
+            if runs_inline_code(cmd) {
+                return false;
+            }
+            let sandbox = PathSandbox::new(vec![expand_home(&dir.to_string_lossy())]);
+            let paths = command_paths(cmd, cwd);
+            let inside = if paths.is_empty() {
+                sandbox.is_allowed(cwd)
+            } else {
+                paths.iter().all(|p| sandbox.is_allowed(&p.path))
+            };
+            if !inside {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// How specific the scope is, for preferring narrow grants.
+    fn specificity(&self) -> u8 {
+        self.pattern.is_some() as u8 + self.dir.is_some() as u8
+    }
+}
+
+impl std::fmt::Display for GrantScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.pattern, &self.dir) {
+            (None, None) => write!(f, "any command"),
+            (Some(pattern), None) => write!(f, "`{}`", pattern),
+            (None, Some(dir)) => write!(f, "any command under {}", dir.display()),
+            (Some(pattern), Some(dir)) => write!(f, "`{}` under {}", pattern, dir.display()),
+        }
+    }
+}
+
+/// Whether the simple command `command` matches a grant's `pattern`.
+fn pattern_matches(pattern: &str, command: &str) -> bool {
+    let pattern = pattern.trim();
+    if !pattern.contains(|c: char| c.is_whitespace() || c == '*' || c == '?') {
+        let name = shell_syntax::parse(command)
+            .ok()
+            .and_then(|commands| commands.into_iter().next())
+            .and_then(|c| unwrapped(&c).first().map(|w| program_name(w)));
+        return name.is_some_and(|n| n.eq_ignore_ascii_case(pattern));
+    }
+    RegexBuilder::new(&glob_to_regex(pattern))
+        .case_insensitive(true)
+        .build()
+        .is_ok_and(|re| re.is_match(command.trim()))
+}
+
+/// A successful 2FA verification, limited in scope and time.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuthGrant {
+    pub id: u64,
+    pub scope: GrantScope,
+    /// Unix epoch seconds after which the grant no longer applies.
+    pub expires_at: i64,
+    /// Revoked by the first command it lets through.
+    pub single_use: bool,
+}
+
+impl AuthGrant {
+    fn is_active(&self, now: i64) -> bool {
+        now < self.expires_at
+    }
+
+    /// e.g. "#3: `rm` under ~/Projects/tmp, single use, 4 min left".
+    pub fn describe(&self) -> String {
+        let left = (self.expires_at - now_secs()).max(0);
+        let mut text = format!("#{}: {}", self.id, self.scope);
+        if self.single_use {
+            text.push_str(", single use");
+        }
+        text.push_str(&format!(", {} min left", (left + 59) / 60));
+        text
+    }
+}
+
+fn now_secs() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64
+}
+
+/// The 2FA grants in force. Destructive commands run only when a grant
+/// covers them; expired grants are dropped as they are found. Shared
+/// between the security skill, which adds grants, and the executor, which
+/// uses them.
 #[derive(Debug)]
 pub struct SecurityContext {
-    /// Unix epoch seconds of last successful verification; 0 means never.
-    last_auth_time: AtomicI64,
-    /// How long a successful verification remains valid.
+    grants: Mutex<Vec<AuthGrant>>,
+    next_id: AtomicU64,
+    /// How long an unscoped `authenticate()` grant remains valid.
     auth_timeout: Duration,
 }
 
 impl SecurityContext {
     pub fn new(timeout_mins: u64) -> Self {
         Self {
-            last_auth_time: AtomicI64::new(0),
+            grants: Mutex::new(Vec::new()),
+            next_id: AtomicU64::new(1),
             auth_timeout: Duration::from_secs(timeout_mins * 60),
         }
     }
 
-    /// Mark the session as authenticated NOW.
-    pub fn authenticate(&self) {
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs() as i64;
-        self.last_auth_time.store(now, Ordering::Relaxed);
+    /// How long a grant lasts unless the user asks for less.
+    pub fn timeout(&self) -> Duration {
+        self.auth_timeout
+    }
+
+    /// Grant every 2FA command for the default timeout, starting NOW.
+    pub fn authenticate(&self) -> AuthGrant {
+        self.grant(GrantScope::default(), self.auth_timeout, false)
+    }
+
+    /// Add a grant for commands in `scope`, valid for `duration`.
+    pub fn grant(&self, scope: GrantScope, duration: Duration, single_use: bool) -> AuthGrant {
+        let grant = AuthGrant {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            scope,
+            expires_at: now_secs() + duration.as_secs() as i64,
+            single_use,
+        };
+        self.grants.lock().push(grant.clone());
+        grant
     }
 
-    /// Check if the session is currently authenticated (within timeout).
+    /// Revoke one grant. Returns whether it was in force.
+    pub fn revoke(&self, id: u64) -> bool {
+        let now = now_secs();
+        let mut grants = self.grants.lock();
+        grants.retain(|g| g.is_active(now));
+        let before = grants.len();
+        grants.retain(|g| g.id != id);
+        grants.len() < before
+    }
+
+    /// Revoke every grant. Returns how many were in force.
+    pub fn revoke_all(&self) -> usize {
+        let now = now_secs();
+        let mut grants = self.grants.lock();
+        let active = grants.iter().filter(|g| g.is_active(now)).count();
+        grants.clear();
+        active
+    }
+
+    /// The grants in force.
+    pub fn active_grants(&self) -> Vec<AuthGrant> {
+        let now = now_secs();
+        let mut grants = self.grants.lock();
+        grants.retain(|g| g.is_active(now));
+        grants.clone()
+    }
+
+    /// Check if an unscoped grant is in force.
     pub fn is_authenticated(&self) -> bool {
-        let last = self.last_auth_time.load(Ordering::Relaxed);
-        if last == 0 {
-            return false;
+        self.active_grants()
+            .iter()
+            .any(|g| g.scope == GrantScope::default())
+    }
+
+    /// Find a grant covering `cmd`, run from `cwd`, so it can run now.
+    /// The narrowest covering grant is used, single-use ones first, and a
+    /// single-use grant is consumed. `None` means the command must not run.
+    pub fn authorize(
+        &self,
+        classification: &CommandClassification,
+        cmd: &str,
+        cwd: &Path,
+    ) -> Option<AuthGrant> {
+        let now = now_secs();
+        let mut grants = self.grants.lock();
+        grants.retain(|g| g.is_active(now));
+        let index = grants
+            .iter()
+            .enumerate()
+            .filter(|(_, g)| g.scope.covers(classification, cmd, cwd))
+            .max_by_key(|(_, g)| (g.scope.specificity(), g.single_use))
+            .map(|(i, _)| i)?;
+        let grant = grants[index].clone();
+        if grant.single_use {
+            grants.remove(index);
         }
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs() as i64;
-        let elapsed = now - last;
-        // Check if elapsed is positive and within timeout
-        elapsed >= 0 && elapsed < self.auth_timeout.as_secs() as i64
+        Some(grant)
     }
 }
 
@@ -114,59 +319,211 @@ impl PathSandbox {
             .any(|allowed| canonical_path.starts_with(allowed))
     }
 
-    /// Heuristic scan of a command string for path tokens that escape the
-    /// sandbox. Not a full shell parser -- it splits on whitespace, skips
-    /// flag tokens, and treats anything with a path separator (or `cd`
-    /// arguments) as a candidate path to validate. This catches the vast
-    /// majority of real-world commands without needing a proper shell AST.
-    pub fn validate_command(&self, cmd: &str, cwd: &Path) -> Result<(), String> {
-        let tokens: Vec<&str> = cmd.split_whitespace().collect();
-
-        let mut prev: Option<&str> = None;
-        for token in tokens {
-            // Skip flags
-            if token.starts_with('-') {
-                prev = Some(token);
-                continue;
+    /// Check every path `cmd` would touch, run from `cwd`, against the
+    /// sandbox. Paths come from `command_paths`, which parses the command
+    /// line, knows which arguments of common commands are paths, follows
+    /// `cd`, and expands globs. Credential stores are refused wherever they
+    /// are. Returns every offending path, not just the first.
+    pub fn validate_command(&self, cmd: &str, cwd: &Path) -> Result<(), SandboxViolation> {
+        let mut paths: Vec<String> = Vec::new();
+        for found in command_paths(cmd, cwd) {
+            let refused = !self.is_allowed(&found.path) || is_sensitive_path(&found.path);
+            if refused && !paths.contains(&found.raw) {
+                paths.push(found.raw);
             }
+        }
+        if paths.is_empty() {
+            Ok(())
+        } else {
+            Err(SandboxViolation { paths })
+        }
+    }
+}
 
-            let is_cd_arg = prev == Some("cd");
-
-            // Heuristic: If it has a separator, it MIGHT be a path
-            // Special-case: `cd <arg>` should always be treated as a path.
-            if is_cd_arg
-                || token.contains('/')
-                || token.contains('\\')
-                || token == "~"
-                || token.starts_with("~/")
-            {
-                // Try to resolve it (lightweight; not a shell parser).
-                let path = if token == "~" {
-                    dirs::home_dir().unwrap_or_else(|| cwd.to_path_buf())
-                } else if let Some(rest) = token.strip_prefix("~/") {
-                    dirs::home_dir()
-                        .unwrap_or_else(|| cwd.to_path_buf())
-                        .join(rest)
-                } else {
-                    PathBuf::from(token)
-                };
-                let abs_path = if path.is_absolute() {
-                    path
-                } else {
-                    cwd.join(path)
-                };
-
-                if !self.is_allowed(&abs_path) {
-                    return Err(format!(
-                        "Access denied: Path '{}' is outside allowed directories.",
-                        token
-                    ));
-                }
-            }
+/// Whether `path` is, or is inside, a well-known credential store: SSH
+/// keys, AWS credentials, GPG keyrings, macOS keychains, `.npmrc` and
+/// `.env` files.
+pub fn is_sensitive_path(path: &Path) -> bool {
+    let lower = path.to_string_lossy().to_lowercase().replace('\\', "/");
+    let lower = Path::new(&lower);
+    lower
+        .components()
+        .any(|c| matches!(c.as_os_str().to_str(), Some(".ssh" | ".aws" | ".gnupg")))
+        || lower
+            .to_str()
+            .is_some_and(|s| s.contains("/library/keychains"))
+        || lower
+            .file_name()
+            .is_some_and(|name| name == ".npmrc" || name == ".env")
+}
+
+/// Paths in a command that fall outside the sandbox, or are credential
+/// stores, as written in the command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SandboxViolation {
+    pub paths: Vec<String>,
+}
+
+impl std::fmt::Display for SandboxViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let quoted: Vec<String> = self.paths.iter().map(|p| format!("'{}'", p)).collect();
+        if let [path] = quoted.as_slice() {
+            write!(
+                f,
+                "Access denied: Path {} is outside allowed directories or protected.",
+                path
+            )
+        } else {
+            write!(
+                f,
+                "Access denied: Paths {} are outside allowed directories or protected.",
+                quoted.join(", ")
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_command_reports_every_path() {
+        let root = tempfile::tempdir().unwrap();
+        let allowed = root.path().join("work");
+        std::fs::create_dir_all(allowed.join("src")).unwrap();
+        std::fs::write(root.path().join("secret.txt"), "").unwrap();
+        let sandbox = PathSandbox::new(vec![allowed.clone()]);
+
+        assert!(sandbox
+            .validate_command("grep -rn TODO src > src/todo.txt", &allowed)
+            .is_ok());
 
-            prev = Some(token);
+        // No separators, but `cd ..` leaves the sandbox first.
+        let err = sandbox
+            .validate_command("cd .. && cat secret.txt", &allowed)
+            .unwrap_err();
+        assert_eq!(err.paths, vec!["..", "secret.txt"]);
+
+        let err = sandbox
+            .validate_command(
+                "cp \"src\" --target-directory=../out; ls ../*.txt",
+                &allowed,
+            )
+            .unwrap_err();
+        assert_eq!(err.paths, vec!["../out", "../*.txt"]);
+        assert!(err.to_string().contains("Paths '../out', '../*.txt'"));
+    }
+
+    #[test]
+    fn test_validate_command_reads_inline_code() {
+        let root = tempfile::tempdir().unwrap();
+        let sandbox = PathSandbox::new(vec![root.path().to_path_buf()]);
+        let cwd = root.path();
+
+        for (cmd, outside) in [
+            ("bash -c \"cat /etc/passwd\"", "/etc/passwd"),
+            ("sh -c 'cp /etc/shadow .'", "/etc/shadow"),
+            ("find . -exec cat /etc/passwd {} +", "/etc/passwd"),
+        ] {
+            let err = sandbox.validate_command(cmd, cwd).unwrap_err();
+            assert_eq!(err.paths, vec![outside], "{}", cmd);
         }
+        assert!(sandbox.validate_command("sh -c 'ls .'", cwd).is_ok());
+    }
+
+    #[test]
+    fn test_validate_command_refuses_credential_stores() {
+        let root = tempfile::tempdir().unwrap();
+        let sandbox = PathSandbox::new(vec![root.path().to_path_buf()]);
+        let cwd = root.path();
+        std::fs::create_dir_all(cwd.join(".ssh")).unwrap();
+        std::fs::create_dir_all(cwd.join("notes")).unwrap();
+
+        for (cmd, refused) in [
+            ("cat .ssh/id_rsa", ".ssh/id_rsa"),
+            ("ls .ssh", ".ssh"),
+            ("cp .env backup.txt", ".env"),
+            ("cat app/.npmrc", "app/.npmrc"),
+        ] {
+            let err = sandbox.validate_command(cmd, cwd).unwrap_err();
+            assert_eq!(err.paths, vec![refused], "{}", cmd);
+        }
+        assert!(sandbox
+            .validate_command("cat .env.example notes/ssh.txt", cwd)
+            .is_ok());
+        assert!(is_sensitive_path(Path::new(
+            "/Users/me/Library/Keychains/login.keychain-db"
+        )));
+    }
+
+    #[test]
+    fn test_grants_are_scoped_single_use_and_revocable() {
+        use crate::executor::explain_command;
+
+        let root = tempfile::tempdir().unwrap();
+        let scratch = root.path().join("scratch");
+        std::fs::create_dir_all(&scratch).unwrap();
+        std::fs::write(scratch.join("a.txt"), "").unwrap();
+        std::fs::write(root.path().join("keep.txt"), "").unwrap();
+        let cwd = root.path();
+        let check =
+            |ctx: &SecurityContext, cmd: &str| ctx.authorize(&explain_command(cmd), cmd, cwd);
+
+        let ctx = SecurityContext::new(15);
+        assert!(check(&ctx, "rm scratch/a.txt").is_none());
+
+        // `rm` under scratch/ only, once.
+        let grant = ctx.grant(
+            GrantScope::new().with_pattern("rm").with_dir(&scratch),
+            Duration::from_secs(300),
+            true,
+        );
+        assert!(check(&ctx, "rm keep.txt").is_none());
+        assert!(check(&ctx, "chmod 600 scratch/a.txt").is_none());
+        assert!(check(&ctx, "ls; rm scratch/*.txt && rm keep.txt").is_none());
+        // Run from inside scratch/, inline code still has its paths checked.
+        let check_in_scratch =
+            |ctx: &SecurityContext, cmd: &str| ctx.authorize(&explain_command(cmd), cmd, &scratch);
+        assert!(check_in_scratch(&ctx, "sh -c \"rm $HOME/notes.txt\"").is_none());
+        assert!(check_in_scratch(&ctx, "sh -c 'rm a.txt'").is_none());
+        assert_eq!(check(&ctx, "echo hi; rm scratch/*.txt"), Some(grant));
+        assert!(check(&ctx, "rm scratch/a.txt").is_none());
+
+        // A directory alone doesn't cover code run inline.
+        let grant = ctx.grant(
+            GrantScope::new().with_dir(&scratch),
+            Duration::from_secs(300),
+            false,
+        );
+        assert!(check_in_scratch(&ctx, "bash -c 'chmod 777 /etc/passwd'").is_none());
+        assert!(check_in_scratch(&ctx, "bash -c 'chmod 600 a.txt'").is_none());
+        assert!(check_in_scratch(&ctx, "find . -exec chmod 777 /etc/passwd +").is_none());
+        assert!(check(&ctx, "chmod 600 scratch/a.txt").is_some());
+        assert!(ctx.revoke(grant.id));
+
+        // A glob pattern, until revoked.
+        let grant = ctx.grant(
+            GrantScope::new().with_pattern("chmod 6?? *"),
+            Duration::from_secs(300),
+            false,
+        );
+        assert!(check(&ctx, "chmod 600 keep.txt").is_some());
+        assert!(check(&ctx, "chmod 777 keep.txt").is_none());
+        assert!(check(&ctx, "chmod 644 scratch/a.txt").is_some());
+        assert!(!ctx.is_authenticated());
+        assert!(ctx.revoke(grant.id));
+        assert!(!ctx.revoke(grant.id));
+        assert!(check(&ctx, "chmod 600 keep.txt").is_none());
 
-        Ok(())
+        // Unscoped grants cover everything; expired ones nothing.
+        ctx.grant(GrantScope::new(), Duration::ZERO, false);
+        assert!(check(&ctx, "rm keep.txt").is_none());
+        ctx.authenticate();
+        assert!(ctx.is_authenticated());
+        assert!(check(&ctx, "rm keep.txt").is_some());
+        assert_eq!(ctx.active_grants().len(), 1);
+        assert_eq!(ctx.revoke_all(), 1);
+        assert!(check(&ctx, "rm keep.txt").is_none());
     }
 }
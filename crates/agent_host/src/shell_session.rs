@@ -0,0 +1,572 @@
+//! Persistent interactive shell on a pseudo-terminal.
+//!
+//! `execute_command` normally spawns a fresh `sh -c` per command, so only
+//! the state `SessionState` emulates (`cd`, env vars) carries over. A
+//! [`ShellSession`] keeps one `bash` running on a PTY for the whole agent
+//! session instead: activated virtualenvs, aliases, functions and exported
+//! variables persist between commands, output arrives while the command
+//! runs, and prompts can be answered through [`ShellSession::send_input`].
+//!
+//! Each command is written to the shell as one `{ ...; }` group. The shell's
+//! `PROMPT_COMMAND` prints a marker carrying `$?` and `$PWD` when the
+//! command finishes; [`ShellSession::run`] reads output up to that marker,
+//! forwarding it as [`CommandEvent`]s on the way. A PTY merges stdout and
+//! stderr, as a terminal does, so a run's output is one stream.
+//!
+//! The prompt variables are readonly, and the marker also carries a
+//! checksum of the shell's aliases, functions, traps and `PATH`. Once that
+//! differs from the fresh shell's, [`ShellSession::is_customised`] turns
+//! true: `ls` may no longer run `ls`, so callers stop running "safe"
+//! commands unasked.
+//!
+//! Stopping a command sends Ctrl-C through the terminal, which interrupts
+//! the foreground job and leaves the shell running; if the shell doesn't
+//! come back, it is killed and [`ShellSession::is_alive`] turns false.
+
+use crate::executor::parse_progress;
+use anyhow::{bail, Context, Result};
+use parking_lot::Mutex;
+use portable_pty::{native_pty_system, Child, ChildKiller, CommandBuilder, MasterPty, PtySize};
+use providers::cancel::CancellationToken;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::time::Duration;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+
+/// Live output from a running command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandEvent {
+    /// Output as it arrives.
+    Output(String),
+    /// A completion percentage found in the output (see `parse_progress`).
+    Progress(u8),
+}
+
+/// How a [`ShellSession::run`] ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunEnd {
+    Finished,
+    TimedOut,
+    Cancelled,
+    /// The shell exited or was killed mid-command.
+    ShellExited,
+}
+
+/// Output and status of one command run in the shell.
+#[derive(Debug, Clone)]
+pub struct ShellRun {
+    pub output: String,
+    /// `$?` after the command; -1 when it didn't finish.
+    pub exit_code: i32,
+    /// The shell's working directory afterwards.
+    pub cwd: Option<PathBuf>,
+    pub ended: RunEnd,
+}
+
+/// How long to wait for the prompt after Ctrl-C before killing the shell.
+const INTERRUPT_GRACE: Duration = Duration::from_secs(2);
+
+/// How often Ctrl-C is repeated while waiting for the prompt.
+const INTERRUPT_REPEAT: Duration = Duration::from_millis(250);
+
+/// How long the shell may take to start and print its first prompt.
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A long-lived `bash` on a PTY. One command runs at a time; share it
+/// between callers with an `Arc`.
+pub struct ShellSession {
+    writer: Mutex<Box<dyn Write + Send>>,
+    output: tokio::sync::Mutex<UnboundedReceiver<Vec<u8>>>,
+    killer: Mutex<Box<dyn ChildKiller + Send + Sync>>,
+    // Held so the shell and its terminal live as long as the session.
+    _child: Mutex<Box<dyn Child + Send + Sync>>,
+    _master: Mutex<Box<dyn MasterPty + Send>>,
+    /// Start of the marker `PROMPT_COMMAND` prints, unique to this session.
+    marker: String,
+    /// Working directory reported by the last prompt.
+    cwd: Mutex<PathBuf>,
+    /// Checksum of aliases, functions, traps and `PATH` at the first prompt.
+    baseline: Mutex<Option<String>>,
+    customised: AtomicBool,
+    busy: AtomicBool,
+    alive: AtomicBool,
+}
+
+impl std::fmt::Debug for ShellSession {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ShellSession")
+            .field("busy", &self.is_busy())
+            .field("alive", &self.is_alive())
+            .finish()
+    }
+}
+
+impl ShellSession {
+    /// Start `bash` in `cwd` with `env` added to the environment, and wait
+    /// for its first prompt. Fails when `bash` isn't installed.
+    pub async fn start(cwd: &Path, env: &HashMap<String, String>) -> Result<Self> {
+        let bash = find_program("bash").context("the persistent shell needs bash")?;
+        // By path, so a shell function can't stand in for it.
+        let cksum = find_program("cksum").context("the persistent shell needs cksum")?;
+        let pair = native_pty_system().openpty(PtySize {
+            rows: 24,
+            cols: 200,
+            pixel_width: 0,
+            pixel_height: 0,
+        })?;
+        let mut builder = CommandBuilder::new(bash);
+        builder.args(["--noprofile", "--norc", "--noediting", "-i"]);
+        builder.cwd(cwd);
+        builder.env("TERM", "dumb");
+        for (key, value) in env {
+            builder.env(key, value);
+        }
+        let child = pair.slave.spawn_command(builder)?;
+        drop(pair.slave);
+
+        let mut reader = pair.master.try_clone_reader()?;
+        let writer = pair.master.take_writer()?;
+        let (tx, rx) = unbounded_channel();
+        std::thread::spawn(move || {
+            let mut buf = [0u8; 4096];
+            loop {
+                match reader.read(&mut buf) {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => {
+                        if tx.send(buf[..n].to_vec()).is_err() {
+                            break;
+                        }
+                    }
+                }
+            }
+        });
+
+        let marker = format!("\u{1e}LH{}:", uuid::Uuid::new_v4().simple());
+        let session = Self {
+            writer: Mutex::new(writer),
+            output: tokio::sync::Mutex::new(rx),
+            killer: Mutex::new(child.clone_killer()),
+            _child: Mutex::new(child),
+            _master: Mutex::new(pair.master),
+            marker,
+            cwd: Mutex::new(cwd.to_path_buf()),
+            baseline: Mutex::new(None),
+            customised: AtomicBool::new(false),
+            busy: AtomicBool::new(false),
+            alive: AtomicBool::new(true),
+        };
+        // No echo or prompts, and a marker after every command that
+        // commands can't replace.
+        let state = format!(
+            "$( {{ builtin alias; builtin declare -f; builtin trap -p; builtin printf %s \"$PATH\"; }} | {})",
+            cksum.display()
+        );
+        let setup = format!(
+            "stty -echo 2>/dev/null; PS1=''; PS2=''; PROMPT_COMMAND='builtin printf \"\\036{}%s:%s:%s\\036\\n\" \"$?\" \"{}\" \"$PWD\"'; readonly PS1 PS2 PROMPT_COMMAND\n",
+            &session.marker[1..],
+            state,
+        );
+        session.write(setup.as_bytes())?;
+        let ready = session
+            .read_until_marker(STARTUP_TIMEOUT, &CancellationToken::new(), None)
+            .await;
+        if ready.ended != RunEnd::Finished {
+            session.kill();
+            bail!("the shell did not start: {}", ready.output.trim());
+        }
+        Ok(session)
+    }
+
+    /// Run `cmd` and wait for it to finish, forwarding output to `events`.
+    /// On timeout or cancellation the command is interrupted with Ctrl-C.
+    pub async fn run(
+        &self,
+        cmd: &str,
+        timeout: Duration,
+        cancel: &CancellationToken,
+        events: Option<&Sender<CommandEvent>>,
+    ) -> Result<ShellRun> {
+        if !self.is_alive() {
+            bail!("the shell has exited");
+        }
+        self.busy.store(true, Ordering::SeqCst);
+        // Braces make a multi-line command one unit, so one marker follows.
+        let written = self.write(format!("{{ {}\n}}\n", cmd).as_bytes());
+        let run = match written {
+            Ok(()) => Ok(self.read_until_marker(timeout, cancel, events).await),
+            Err(e) => Err(e),
+        };
+        self.busy.store(false, Ordering::SeqCst);
+        run
+    }
+
+    /// Type `text` into the running command (include the `\n`).
+    pub fn send_input(&self, text: &str) -> Result<()> {
+        self.write(text.as_bytes())
+    }
+
+    /// Interrupt the running command (Ctrl-C).
+    pub fn interrupt(&self) -> Result<()> {
+        self.write(b"\x03")
+    }
+
+    /// Kill the shell and everything running in it.
+    pub fn kill(&self) {
+        self.alive.store(false, Ordering::SeqCst);
+        let _ = self.killer.lock().kill();
+    }
+
+    /// The shell's working directory as of the last finished command; it
+    /// carries over between commands, so callers seed their own cwd from it.
+    pub fn cwd(&self) -> PathBuf {
+        self.cwd.lock().clone()
+    }
+
+    /// Whether aliases, functions, traps or `PATH` have changed since the
+    /// shell started (or [`mark_customised`](Self::mark_customised) was
+    /// called), so a command name may not run what it usually does.
+    pub fn is_customised(&self) -> bool {
+        self.customised.load(Ordering::SeqCst)
+    }
+
+    /// Treat the shell as customised from now on, for changes the
+    /// checksum can't see (`hash -p`, `enable`, a function named `builtin`).
+    pub fn mark_customised(&self) {
+        self.customised.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether a command is running.
+    pub fn is_busy(&self) -> bool {
+        self.busy.load(Ordering::SeqCst)
+    }
+
+    /// Whether the shell is still usable.
+    pub fn is_alive(&self) -> bool {
+        self.alive.load(Ordering::SeqCst)
+    }
+
+    fn write(&self, bytes: &[u8]) -> Result<()> {
+        let mut writer = self.writer.lock();
+        writer.write_all(bytes)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Collect output until the next prompt marker, interrupting the
+    /// command when `timeout` passes or `cancel` fires.
+    async fn read_until_marker(
+        &self,
+        timeout: Duration,
+        cancel: &CancellationToken,
+        events: Option<&Sender<CommandEvent>>,
+    ) -> ShellRun {
+        let mut rx = self.output.lock().await;
+        let mut scanner = MarkerScanner::new(&self.marker);
+        let mut progress = None;
+        let deadline = tokio::time::sleep(timeout);
+        tokio::pin!(deadline);
+        let mut ended = RunEnd::Finished;
+        let mut grace: Option<std::pin::Pin<Box<tokio::time::Sleep>>> = None;
+
+        loop {
+            let chunk = tokio::select! {
+                chunk = rx.recv() => chunk,
+                _ = &mut deadline, if grace.is_none() => {
+                    ended = RunEnd::TimedOut;
+                    let _ = self.interrupt();
+                    grace = Some(Box::pin(tokio::time::sleep(INTERRUPT_GRACE)));
+                    continue;
+                }
+                _ = cancel.cancelled(), if grace.is_none() => {
+                    ended = RunEnd::Cancelled;
+                    let _ = self.interrupt();
+                    grace = Some(Box::pin(tokio::time::sleep(INTERRUPT_GRACE)));
+                    continue;
+                }
+                _ = async { grace.as_mut().unwrap().await }, if grace.is_some() => {
+                    self.kill();
+                    None
+                }
+                // A Ctrl-C that lands while bash is still parsing the
+                // command is lost, so keep sending until the prompt is back.
+                _ = tokio::time::sleep(INTERRUPT_REPEAT), if grace.is_some() => {
+                    let _ = self.interrupt();
+                    continue;
+                }
+            };
+            let Some(chunk) = chunk else {
+                self.alive.store(false, Ordering::SeqCst);
+                if ended == RunEnd::Finished {
+                    ended = RunEnd::ShellExited;
+                }
+                return ShellRun {
+                    output: scanner.finish(),
+                    exit_code: -1,
+                    cwd: None,
+                    ended,
+                };
+            };
+            let (text, done) = scanner.push(&chunk);
+            if !text.is_empty() {
+                if let Some(events) = events {
+                    let _ = events.send(CommandEvent::Output(text));
+                    if let Some(percent) = parse_progress(scanner.tail()) {
+                        if progress != Some(percent) {
+                            progress = Some(percent);
+                            let _ = events.send(CommandEvent::Progress(percent));
+                        }
+                    }
+                }
+            }
+            if let Some((exit_code, state, cwd)) = done {
+                if cwd.is_dir() {
+                    *self.cwd.lock() = cwd.clone();
+                }
+                let mut baseline = self.baseline.lock();
+                match baseline.as_deref() {
+                    None => *baseline = Some(state),
+                    Some(first) if first != state => self.mark_customised(),
+                    Some(_) => {}
+                }
+                return ShellRun {
+                    output: scanner.finish(),
+                    exit_code: if ended == RunEnd::Finished {
+                        exit_code
+                    } else {
+                        -1
+                    },
+                    cwd: Some(cwd),
+                    ended,
+                };
+            }
+        }
+    }
+}
+
+impl Drop for ShellSession {
+    fn drop(&mut self) {
+        self.kill();
+    }
+}
+
+fn find_program(name: &str) -> Option<PathBuf> {
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path)
+        .flat_map(|dir| [dir.join(name), dir.join(format!("{}.exe", name))])
+        .find(|candidate| candidate.is_file())
+}
+
+/// Splits PTY output into command output and the end-of-command marker
+/// (`marker` `code:state:pwd` `\x1e`), decoding UTF-8 across chunk
+/// boundaries.
+struct MarkerScanner<'a> {
+    marker: &'a str,
+    /// Bytes of an incomplete UTF-8 sequence at the end of the last chunk.
+    partial: Vec<u8>,
+    /// Decoded text not yet forwarded, in case it starts a marker.
+    pending: String,
+    output: String,
+}
+
+impl<'a> MarkerScanner<'a> {
+    fn new(marker: &'a str) -> Self {
+        Self {
+            marker,
+            partial: Vec::new(),
+            pending: String::new(),
+            output: String::new(),
+        }
+    }
+
+    /// Add a chunk. Returns the new output that is safe to show and, once
+    /// the marker is complete, the exit code, state checksum and working
+    /// directory.
+    fn push(&mut self, chunk: &[u8]) -> (String, Option<(i32, String, PathBuf)>) {
+        self.partial.extend_from_slice(chunk);
+        let valid = match std::str::from_utf8(&self.partial) {
+            Ok(s) => s.len(),
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(_) => self.partial.len(),
+        };
+        let bytes: Vec<u8> = self.partial.drain(..valid).collect();
+        self.pending
+            .push_str(&String::from_utf8_lossy(&bytes).replace('\r', ""));
+
+        if let Some(start) = self.pending.find(self.marker) {
+            let rest = &self.pending[start + self.marker.len()..];
+            if let Some(end) = rest.find('\u{1e}') {
+                let mut fields = rest[..end].splitn(3, ':');
+                let code = fields.next().unwrap_or("-1");
+                let state = fields.next().unwrap_or("");
+                let cwd = fields.next().unwrap_or("");
+                let done = (
+                    code.trim().parse().unwrap_or(-1),
+                    state.to_string(),
+                    PathBuf::from(cwd),
+                );
+                let text = self.pending[..start].to_string();
+                self.pending.clear();
+                return (self.emit(text), Some(done));
+            }
+            let text = self.pending[..start].to_string();
+            self.pending.drain(..start);
+            return (self.emit(text), None);
+        }
+        // Hold back a trailing `\x1e` that may start a marker.
+        let keep = self.pending.rfind('\u{1e}').unwrap_or(self.pending.len());
+        let text = self.pending[..keep].to_string();
+        self.pending.drain(..keep);
+        (self.emit(text), None)
+    }
+
+    fn emit(&mut self, text: String) -> String {
+        self.output.push_str(&text);
+        text
+    }
+
+    /// The last few lines of output, for progress parsing.
+    fn tail(&self) -> &str {
+        let start = self.output.len().saturating_sub(200);
+        let start = (start..=self.output.len())
+            .find(|i| self.output.is_char_boundary(*i))
+            .unwrap_or(0);
+        &self.output[start..]
+    }
+
+    /// All output, without the trailing newline the marker adds.
+    fn finish(mut self) -> String {
+        let pending = std::mem::take(&mut self.pending);
+        self.output.push_str(&pending);
+        self.output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_marker_scanner_splits_output_and_status() {
+        let mut scanner = MarkerScanner::new("\u{1e}LHx:");
+        let (text, done) = scanner.push(b"Downloading 45%\r\n\xe2\x9c");
+        assert_eq!(text, "Downloading 45%\n");
+        assert!(done.is_none());
+        assert_eq!(parse_progress(scanner.tail()), Some(45));
+
+        // The rest of a split `✓`, then a marker split across chunks.
+        let (text, done) = scanner.push(b"\x93 done\n\x1eLH");
+        assert_eq!(text, "✓ done\n");
+        assert!(done.is_none());
+        let (text, done) = scanner.push(b"x:3:42 7:/tmp/a:b\x1e\n");
+        assert_eq!(text, "");
+        assert_eq!(
+            done,
+            Some((3, "42 7".to_string(), PathBuf::from("/tmp/a:b")))
+        );
+        assert_eq!(scanner.finish(), "Downloading 45%\n✓ done\n");
+    }
+
+    #[tokio::test]
+    async fn test_shell_keeps_state_between_commands() {
+        if find_program("bash").is_none() {
+            return;
+        }
+        let dir = tempfile::tempdir().unwrap();
+        let shell = std::sync::Arc::new(
+            ShellSession::start(dir.path(), &HashMap::new())
+                .await
+                .unwrap(),
+        );
+        let cancel = CancellationToken::new();
+        let timeout = Duration::from_secs(10);
+
+        shell
+            .run(
+                "greet() { echo \"hi $1\"; }; export LH_TEST=1; mkdir sub && cd sub",
+                timeout,
+                &cancel,
+                None,
+            )
+            .await
+            .unwrap();
+        let (tx, rx) = std::sync::mpsc::channel();
+        let run = shell
+            .run(
+                "greet there; echo $LH_TEST; false",
+                timeout,
+                &cancel,
+                Some(&tx),
+            )
+            .await
+            .unwrap();
+        assert_eq!(run.output, "hi there\n1\n");
+        assert_eq!(run.exit_code, 1);
+        assert!(run.cwd.unwrap().ends_with("sub"));
+        assert!(shell.cwd().ends_with("sub"));
+        assert!(rx.try_iter().any(|e| matches!(e, CommandEvent::Output(_))));
+
+        // A prompt answered through stdin.
+        let running = {
+            let shell = shell.clone();
+            let cancel = cancel.clone();
+            tokio::spawn(async move {
+                shell
+                    .run("read answer; echo got $answer", timeout, &cancel, None)
+                    .await
+            })
+        };
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert!(shell.is_busy());
+        shell.send_input("yes\n").unwrap();
+        assert_eq!(running.await.unwrap().unwrap().output, "got yes\n");
+
+        // Stopping interrupts the command but keeps the shell.
+        let stop = CancellationToken::new();
+        stop.cancel();
+        let run = shell.run("sleep 30", timeout, &stop, None).await.unwrap();
+        assert_eq!(run.ended, RunEnd::Cancelled);
+        assert!(shell.is_alive());
+    }
+
+    #[tokio::test]
+    async fn test_shell_notices_redefined_commands() {
+        if find_program("bash").is_none() || find_program("cksum").is_none() {
+            return;
+        }
+        let dir = tempfile::tempdir().unwrap();
+        let cancel = CancellationToken::new();
+        let timeout = Duration::from_secs(10);
+        for change in [
+            "alias ls='echo gotcha'",
+            "ls() { echo gotcha; }",
+            "trap 'echo gotcha' DEBUG",
+            "export PATH=\"$PWD:$PATH\"",
+        ] {
+            let shell = ShellSession::start(dir.path(), &HashMap::new())
+                .await
+                .unwrap();
+            let run = shell
+                .run("export LH_TEST=1; cd . && echo ok", timeout, &cancel, None)
+                .await
+                .unwrap();
+            assert_eq!(run.output, "ok\n");
+            assert!(!shell.is_customised(), "{change}");
+
+            // The prompt can't be replaced to hide the change.
+            let run = shell
+                .run(
+                    &format!("{change}; PROMPT_COMMAND=true"),
+                    timeout,
+                    &cancel,
+                    None,
+                )
+                .await
+                .unwrap();
+            assert_eq!(run.ended, RunEnd::Finished, "{change}");
+            assert!(shell.is_customised(), "{change}");
+        }
+    }
+}
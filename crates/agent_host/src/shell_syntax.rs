@@ -0,0 +1,745 @@
+//! Shell command-line parsing for safety classification.
+//!
+//! `executor::classify_command` needs to know every program a command line
+//! would start, not just the first word: `ls; rm -rf ~/x` runs `rm`, and
+//! `cat foo | sh` hands a file to a shell. This module splits a POSIX-style
+//! command line into its simple commands:
+//!
+//! - pipelines (`|`, `|&`) and lists (`;`, `&&`, `||`, `&`, newlines),
+//! - subshells `( ... )` and `{ ...; }` groups,
+//! - command substitution (`$(...)`, backticks) and process substitution
+//!   (`<(...)`, `>(...)`), whose commands are returned alongside the outer one,
+//! - redirections, so callers can tell which commands write files.
+//!
+//! Quotes and backslashes are removed from words the way the shell would.
+//! Parameter expansions (`$HOME`, `${x}`) are kept as written and flagged as
+//! dynamic, since their value isn't known until the command runs.
+//!
+//! This is a parser for classification, not a shell: it doesn't expand
+//! anything and gives up (returns an error) on input it can't make sense of,
+//! such as unterminated quotes or `case` statements. Callers should treat an
+//! error as "unknown, ask the user".
+
+use anyhow::{bail, Result};
+
+/// One simple command: optional `NAME=value` assignments, the words of the
+/// command, and its redirections.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimpleCommand {
+    /// The command as written (trimmed), for explanations.
+    pub text: String,
+    /// Leading `NAME=value` assignments.
+    pub assignments: Vec<String>,
+    /// Words after quote removal. Reserved words (`if`, `then`, `{`, ...)
+    /// are left in; callers decide what to skip.
+    pub words: Vec<String>,
+    /// Redirections in the order written.
+    pub redirects: Vec<Redirect>,
+    /// Whether stdin comes from the previous stage of a pipeline.
+    pub piped_input: bool,
+    /// Whether the command name comes from an expansion (`$CMD`, `$(...)`).
+    pub dynamic_name: bool,
+}
+
+/// A redirection such as `> out.txt` or `2>&1`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Redirect {
+    pub kind: RedirectKind,
+    /// File name, fd number (for duplications) or here-doc delimiter.
+    pub target: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedirectKind {
+    /// `<`, `<>`
+    Read,
+    /// `>`, `>|`, `&>`
+    Write,
+    /// `>>`, `&>>`
+    Append,
+    /// `>&2`, `2>&1`, `<&0`, `>&-`
+    Duplicate,
+    /// `<<EOF`, `<<-EOF`
+    HereDoc,
+    /// `<<< text`; the target is the text itself
+    HereString,
+}
+
+impl Redirect {
+    /// Whether this redirection creates or changes a file. Writes to
+    /// `/dev/null` and the terminal streams don't count.
+    pub fn writes_file(&self) -> bool {
+        matches!(self.kind, RedirectKind::Write | RedirectKind::Append)
+            && !matches!(
+                self.target.as_str(),
+                "/dev/null" | "/dev/stdout" | "/dev/stderr" | "/dev/tty" | "NUL" | "nul"
+            )
+    }
+}
+
+/// Split `input` into the simple commands it would run, including those
+/// inside substitutions and subshells.
+pub fn parse(input: &str) -> Result<Vec<SimpleCommand>> {
+    let mut parser = Parser {
+        chars: input.chars().collect(),
+        pos: 0,
+        commands: Vec::new(),
+        heredocs: Vec::new(),
+    };
+    parser.parse_list(None)?;
+    if parser.pos < parser.chars.len() {
+        bail!("unexpected '{}'", parser.chars[parser.pos]);
+    }
+    if !parser.heredocs.is_empty() {
+        bail!("here-document without a body");
+    }
+    Ok(parser.commands)
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    commands: Vec<SimpleCommand>,
+    /// Here-doc delimiters (and whether `<<-` strips tabs) whose bodies
+    /// start after the next newline.
+    heredocs: Vec<(String, bool)>,
+}
+
+/// Characters that end an unquoted word.
+fn is_meta(c: char) -> bool {
+    c.is_whitespace() || matches!(c, ';' | '&' | '|' | '<' | '>' | '(' | ')')
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).copied()
+    }
+
+    fn starts_with(&self, s: &str) -> bool {
+        s.chars()
+            .enumerate()
+            .all(|(i, c)| self.peek_at(i) == Some(c))
+    }
+
+    /// Skip blanks (not newlines) and comments.
+    fn skip_blanks(&mut self) {
+        while let Some(c) = self.peek() {
+            if c == '\n' || !c.is_whitespace() {
+                break;
+            }
+            self.pos += 1;
+        }
+        if self.peek() == Some('#') {
+            while !matches!(self.peek(), None | Some('\n')) {
+                self.pos += 1;
+            }
+        }
+    }
+
+    /// Parse `;`/`&&`/`||`/`&`/newline-separated pipelines until `end` (or
+    /// the end of input). The closing character is left for the caller.
+    fn parse_list(&mut self, end: Option<char>) -> Result<()> {
+        loop {
+            self.skip_blanks();
+            match self.peek() {
+                None => {
+                    if let Some(end) = end {
+                        bail!("missing '{}'", end);
+                    }
+                    return Ok(());
+                }
+                Some(c) if Some(c) == end => return Ok(()),
+                Some('\n') => {
+                    self.pos += 1;
+                    self.skip_heredoc_bodies()?;
+                    continue;
+                }
+                Some(c @ (';' | '&' | '|' | ')')) => {
+                    // Empty command: `; ls`, `&& ls`, `ls )`.
+                    bail!("unexpected '{}'", c);
+                }
+                _ => {}
+            }
+            self.parse_pipeline(end)?;
+            self.skip_blanks();
+            if self.starts_with("&&") || self.starts_with("||") {
+                self.pos += 2;
+            } else if self.starts_with(";;") {
+                bail!("unexpected ';;'");
+            } else if matches!(self.peek(), Some(';') | Some('&')) {
+                self.pos += 1;
+            }
+        }
+    }
+
+    fn parse_pipeline(&mut self, end: Option<char>) -> Result<()> {
+        let mut piped = false;
+        loop {
+            self.parse_command(piped, end)?;
+            self.skip_blanks();
+            if self.peek() == Some('|') && self.peek_at(1) != Some('|') {
+                self.pos += if self.peek_at(1) == Some('&') { 2 } else { 1 };
+                piped = true;
+                // A pipeline may continue on the next line.
+                self.skip_blanks();
+                while self.peek() == Some('\n') {
+                    self.pos += 1;
+                    self.skip_blanks();
+                }
+                if matches!(self.peek(), None | Some(';') | Some('&') | Some('|')) {
+                    bail!("pipeline ends without a command");
+                }
+                continue;
+            }
+            return Ok(());
+        }
+    }
+
+    fn parse_command(&mut self, piped: bool, end: Option<char>) -> Result<()> {
+        self.skip_blanks();
+        if self.peek() == Some('(') {
+            self.pos += 1;
+            let first = self.commands.len();
+            self.parse_list(Some(')'))?;
+            self.pos += 1;
+            if let Some(cmd) = self.commands.get_mut(first) {
+                cmd.piped_input |= piped;
+            }
+            // Redirections after a subshell apply to all of it.
+            let mut redirects = Vec::new();
+            let mut nested = Vec::new();
+            loop {
+                self.skip_blanks();
+                match self.parse_redirect(&mut nested)? {
+                    Some(redirect) => redirects.push(redirect),
+                    None => break,
+                }
+            }
+            for cmd in &mut self.commands[first..] {
+                cmd.redirects.extend(redirects.iter().cloned());
+            }
+            self.commands.extend(nested);
+            return Ok(());
+        }
+
+        let start = self.pos;
+        let mut cmd = SimpleCommand {
+            text: String::new(),
+            assignments: Vec::new(),
+            words: Vec::new(),
+            redirects: Vec::new(),
+            piped_input: piped,
+            dynamic_name: false,
+        };
+        // Substitutions inside this command are parsed first; they are pushed
+        // after it so the outer command comes first in the output.
+        let mut nested = Vec::new();
+        loop {
+            self.skip_blanks();
+            let Some(c) = self.peek() else { break };
+            if c == '\n' || Some(c) == end || matches!(c, ';' | '|' | ')') {
+                break;
+            }
+            if c == '&' && self.peek_at(1) != Some('>') {
+                break;
+            }
+            if let Some(redirect) = self.parse_redirect(&mut nested)? {
+                cmd.redirects.push(redirect);
+                continue;
+            }
+            if c == '(' {
+                // `name() { ...; }` defines a function; mark it the way
+                // `function name { ...; }` is written.
+                let named = match cmd.words.as_slice() {
+                    [_] => true,
+                    [keyword, _] => keyword == "function",
+                    _ => false,
+                };
+                if named && self.peek_at(1) == Some(')') {
+                    self.pos += 2;
+                    if cmd.words.len() == 1 {
+                        cmd.words.insert(0, "function".to_string());
+                    }
+                    continue;
+                }
+                bail!("unexpected '('");
+            }
+            let (word, dynamic) = self.parse_word(&mut nested)?;
+            if cmd.words.is_empty() && is_assignment(&word) {
+                cmd.assignments.push(word);
+            } else {
+                if cmd.words.is_empty() {
+                    cmd.dynamic_name = dynamic;
+                }
+                cmd.words.push(word);
+            }
+        }
+        cmd.text = self.chars[start..self.pos]
+            .iter()
+            .collect::<String>()
+            .trim()
+            .to_string();
+        if !(cmd.words.is_empty() && cmd.assignments.is_empty() && cmd.redirects.is_empty()) {
+            self.commands.push(cmd);
+        } else if piped {
+            bail!("pipeline ends without a command");
+        }
+        self.commands.extend(nested);
+        Ok(())
+    }
+
+    /// Parse a redirection at the current position, if there is one.
+    /// Commands from substitutions in its target are appended to `nested`.
+    fn parse_redirect(&mut self, nested: &mut Vec<SimpleCommand>) -> Result<Option<Redirect>> {
+        let save = self.pos;
+        // Optional fd number: `2>`, `1>>`.
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        let has_fd = self.pos > save;
+        let kind = if !has_fd && self.starts_with("&>>") {
+            self.pos += 3;
+            RedirectKind::Append
+        } else if !has_fd && self.starts_with("&>") {
+            self.pos += 2;
+            RedirectKind::Write
+        } else if self.starts_with(">>") {
+            self.pos += 2;
+            RedirectKind::Append
+        } else if self.starts_with(">&") || self.starts_with("<&") {
+            self.pos += 2;
+            RedirectKind::Duplicate
+        } else if self.starts_with(">|") {
+            self.pos += 2;
+            RedirectKind::Write
+        } else if self.starts_with("<<<") {
+            self.pos += 3;
+            RedirectKind::HereString
+        } else if self.starts_with("<<") {
+            self.pos += 2;
+            RedirectKind::HereDoc
+        } else if self.starts_with("<>") {
+            self.pos += 2;
+            RedirectKind::Read
+        } else if (self.starts_with(">") || self.starts_with("<")) && self.peek_at(1) != Some('(') {
+            let write = self.peek() == Some('>');
+            self.pos += 1;
+            if write {
+                RedirectKind::Write
+            } else {
+                RedirectKind::Read
+            }
+        } else {
+            self.pos = save;
+            return Ok(None);
+        };
+
+        let strip_tabs = kind == RedirectKind::HereDoc && self.peek() == Some('-');
+        if strip_tabs {
+            self.pos += 1;
+        }
+        self.skip_blanks();
+        if self.peek().is_none_or(is_meta) {
+            bail!("redirection without a target");
+        }
+        let (target, _) = self.parse_word(nested)?;
+
+        let kind = match kind {
+            // Bash's `>&file` is a write; only fd numbers and `-` duplicate.
+            RedirectKind::Duplicate
+                if !(target == "-" || target.chars().all(|c| c.is_ascii_digit())) =>
+            {
+                RedirectKind::Write
+            }
+            kind => kind,
+        };
+        if kind == RedirectKind::HereDoc {
+            self.heredocs.push((target.clone(), strip_tabs));
+        }
+        Ok(Some(Redirect { kind, target }))
+    }
+
+    /// Skip the bodies of pending here-documents, which start at the
+    /// current position (just after a newline).
+    fn skip_heredoc_bodies(&mut self) -> Result<()> {
+        for (delimiter, strip_tabs) in std::mem::take(&mut self.heredocs) {
+            loop {
+                if self.pos >= self.chars.len() {
+                    bail!("here-document '{}' is not terminated", delimiter);
+                }
+                let line_start = self.pos;
+                while !matches!(self.peek(), None | Some('\n')) {
+                    self.pos += 1;
+                }
+                let line: String = self.chars[line_start..self.pos].iter().collect();
+                if self.peek() == Some('\n') {
+                    self.pos += 1;
+                }
+                let line = if strip_tabs {
+                    line.trim_start_matches('\t')
+                } else {
+                    &line
+                };
+                if line == delimiter {
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Parse one word, removing quotes. Commands from substitutions inside it
+    /// are appended to `nested`. Returns the word and whether any part of it
+    /// is an expansion.
+    fn parse_word(&mut self, nested: &mut Vec<SimpleCommand>) -> Result<(String, bool)> {
+        let mut word = String::new();
+        let mut dynamic = false;
+        while let Some(c) = self.peek() {
+            match c {
+                '\'' => {
+                    self.pos += 1;
+                    loop {
+                        match self.peek() {
+                            None => bail!("unterminated single quote"),
+                            Some('\'') => break,
+                            Some(c) => word.push(c),
+                        }
+                        self.pos += 1;
+                    }
+                    self.pos += 1;
+                }
+                '"' => {
+                    self.pos += 1;
+                    loop {
+                        match self.peek() {
+                            None => bail!("unterminated double quote"),
+                            Some('"') => break,
+                            Some('\\') => {
+                                match self.peek_at(1) {
+                                    Some(next @ ('$' | '`' | '"' | '\\')) => word.push(next),
+                                    Some('\n') => {}
+                                    Some(next) => {
+                                        word.push('\\');
+                                        word.push(next);
+                                    }
+                                    None => bail!("unterminated double quote"),
+                                }
+                                self.pos += 2;
+                                continue;
+                            }
+                            Some('$') | Some('`') => {
+                                dynamic = true;
+                                self.parse_expansion(&mut word, nested)?;
+                                continue;
+                            }
+                            Some(c) => word.push(c),
+                        }
+                        self.pos += 1;
+                    }
+                    self.pos += 1;
+                }
+                '\\' => {
+                    match self.peek_at(1) {
+                        Some('\n') => {}
+                        Some(next) => word.push(next),
+                        None => word.push('\\'),
+                    }
+                    self.pos += 2;
+                }
+                '$' | '`' => {
+                    dynamic = true;
+                    self.parse_expansion(&mut word, nested)?;
+                }
+                '<' | '>' if self.peek_at(1) == Some('(') => {
+                    // Process substitution: `<(cmd)`, `>(cmd)`.
+                    dynamic = true;
+                    self.pos += 2;
+                    nested.extend(self.parse_nested(')')?);
+                    word.push_str("/dev/fd/N");
+                }
+                c if is_meta(c) => break,
+                c => {
+                    word.push(c);
+                    self.pos += 1;
+                }
+            }
+        }
+        Ok((word, dynamic))
+    }
+
+    /// Parse a `$...` or backtick expansion at the current position,
+    /// appending its text (as written) to `word`.
+    fn parse_expansion(
+        &mut self,
+        word: &mut String,
+        nested: &mut Vec<SimpleCommand>,
+    ) -> Result<()> {
+        let start = self.pos;
+        if self.peek() == Some('`') {
+            self.pos += 1;
+            let mut inner = String::new();
+            loop {
+                match self.peek() {
+                    None => bail!("unterminated backquote"),
+                    Some('`') => break,
+                    Some('\\') if matches!(self.peek_at(1), Some('`' | '\\' | '$')) => {
+                        inner.push(self.peek_at(1).unwrap_or_default());
+                        self.pos += 2;
+                        continue;
+                    }
+                    Some(c) => inner.push(c),
+                }
+                self.pos += 1;
+            }
+            self.pos += 1;
+            nested.extend(parse(&inner)?);
+        } else if self.starts_with("$((") {
+            // Arithmetic: no commands, but may nest parentheses.
+            self.pos += 3;
+            let mut depth = 2;
+            while depth > 0 {
+                match self.peek() {
+                    None => bail!("unterminated arithmetic expansion"),
+                    Some('(') => depth += 1,
+                    Some(')') => depth -= 1,
+                    _ => {}
+                }
+                self.pos += 1;
+            }
+        } else if self.starts_with("$(") {
+            self.pos += 2;
+            nested.extend(self.parse_nested(')')?);
+        } else if self.starts_with("${") {
+            self.pos += 2;
+            let mut depth = 1;
+            while depth > 0 {
+                match self.peek() {
+                    None => bail!("unterminated '${{'"),
+                    Some('{') => depth += 1,
+                    Some('}') => depth -= 1,
+                    _ => {}
+                }
+                self.pos += 1;
+            }
+        } else {
+            // `$name`, `$1`, `$?`; a lone `$` is literal.
+            self.pos += 1;
+            match self.peek() {
+                Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+                    while self
+                        .peek()
+                        .is_some_and(|c| c.is_ascii_alphanumeric() || c == '_')
+                    {
+                        self.pos += 1;
+                    }
+                }
+                Some(c) if c.is_ascii_digit() || "?#@*!$-".contains(c) => self.pos += 1,
+                _ => {}
+            }
+        }
+        word.extend(&self.chars[start..self.pos]);
+        Ok(())
+    }
+
+    /// Parse a command list up to `close` (consuming it) into its own set of
+    /// commands.
+    fn parse_nested(&mut self, close: char) -> Result<Vec<SimpleCommand>> {
+        let outer = std::mem::take(&mut self.commands);
+        let result = self.parse_list(Some(close));
+        let inner = std::mem::replace(&mut self.commands, outer);
+        result?;
+        self.pos += 1;
+        Ok(inner)
+    }
+}
+
+/// `NAME=value` (the name must be a valid shell identifier).
+fn is_assignment(word: &str) -> bool {
+    match word.split_once('=') {
+        Some((name, _)) => {
+            !name.is_empty()
+                && !name.starts_with(|c: char| c.is_ascii_digit())
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        None => false,
+    }
+}
+
+/// The program a word names, without its directory or `.exe`:
+/// `/usr/bin/rm` and `rm.exe` are both `rm`.
+pub fn program_name(word: &str) -> String {
+    let base = word.rsplit(['/', '\\']).next().unwrap_or(word);
+    let lower = base.to_lowercase();
+    lower.strip_suffix(".exe").unwrap_or(&lower).to_string()
+}
+
+/// If `words` starts with a wrapper that runs another command (`sudo`,
+/// `env`, `nohup`, `timeout`, `xargs`, `setsid`, `busybox`, ...), the
+/// wrapper's name and the wrapped command's words, which may be empty
+/// (`sudo` alone). Returns `None` when the wrapper is itself the command
+/// (`env` listing the environment, plain `xargs`, `chroot dir` starting a
+/// shell). `watch` and `flock -c` take the command as one string of shell
+/// code, which is returned as a single word.
+pub fn unwrap_command(words: &[String]) -> Option<(String, &[String])> {
+    let name = program_name(words.first()?);
+    let rest = &words[1..];
+    let inner = match name.as_str() {
+        "sudo" | "doas" => skip_options(rest, &["-u", "-g", "-C", "-h", "-p"]),
+        "env" => {
+            let after = skip_options(rest, &["-u", "-C", "-S"]);
+            let skip = after.iter().take_while(|w| w.contains('=')).count();
+            if skip == after.len() {
+                return None;
+            }
+            &after[skip..]
+        }
+        "nohup" | "time" | "command" | "builtin" | "exec" | "setsid" | "unbuffer" | "busybox" => {
+            if rest.is_empty() {
+                return None;
+            }
+            skip_options(rest, &[])
+        }
+        "nice" => skip_options(rest, &["-n"]),
+        "stdbuf" => skip_options(rest, &["-i", "-o", "-e"]),
+        "ionice" => skip_options(rest, &["-c", "-n", "-p", "-P", "-u"]),
+        "watch" => skip_options(rest, &["-n", "--interval", "-q", "--equexit"]),
+        "flock" => {
+            // `flock [options] FILE COMMAND...` or `flock FILE -c CODE`;
+            // `flock FD` locks a descriptor and runs nothing.
+            let after = skip_options(rest, &["-w", "--timeout", "-E", "--conflict-exit-code"]);
+            skip_options(after.get(1..).unwrap_or_default(), &[])
+        }
+        "chroot" => skip_options(rest, &[]).get(1..).unwrap_or_default(),
+        "timeout" => skip_options(rest, &["-s", "-k"])
+            .get(1..)
+            .unwrap_or_default(),
+        "xargs" => {
+            let after = skip_options(rest, &["-n", "-I", "-L", "-P", "-d", "-s", "-E", "-a"]);
+            if after.is_empty() {
+                // Plain `xargs` runs `echo`.
+                return None;
+            }
+            after
+        }
+        _ => return None,
+    };
+    if inner.is_empty()
+        && matches!(
+            name.as_str(),
+            "flock" | "chroot" | "watch" | "ionice" | "stdbuf"
+        )
+    {
+        return None;
+    }
+    Some((name, inner))
+}
+
+/// Skip leading options. Options in `with_value` take the next word as
+/// their value; `--` ends the options.
+fn skip_options<'a>(words: &'a [String], with_value: &[&str]) -> &'a [String] {
+    let mut i = 0;
+    while let Some(word) = words.get(i) {
+        if word == "--" {
+            return &words[i + 1..];
+        }
+        if !word.starts_with('-') || word == "-" {
+            break;
+        }
+        i += if with_value.contains(&word.as_str()) {
+            2
+        } else {
+            1
+        };
+    }
+    words.get(i..).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(input: &str) -> Vec<Vec<String>> {
+        parse(input).unwrap().into_iter().map(|c| c.words).collect()
+    }
+
+    #[test]
+    fn test_splits_lists_pipelines_and_substitutions() {
+        assert_eq!(
+            words("ls -la; rm -rf ~/x && echo 'a; b' | sh"),
+            vec![
+                vec!["ls", "-la"],
+                vec!["rm", "-rf", "~/x"],
+                vec!["echo", "a; b"],
+                vec!["sh"],
+            ]
+        );
+        assert_eq!(
+            words("echo \"$(whoami)\" `id -u`"),
+            vec![
+                vec!["echo", "$(whoami)", "`id -u`"],
+                vec!["whoami"],
+                vec!["id", "-u"]
+            ]
+        );
+        assert_eq!(
+            words("(cd /tmp; ls) > out.txt"),
+            vec![vec!["cd", "/tmp"], vec!["ls"]]
+        );
+
+        assert_eq!(
+            words("f() { rm x; }; function g() { ls; }"),
+            vec![
+                vec!["function", "f", "{", "rm", "x"],
+                vec!["}"],
+                vec!["function", "g", "{", "ls"],
+                vec!["}"],
+            ]
+        );
+
+        let cmds = parse("cat foo | sh").unwrap();
+        assert!(!cmds[0].piped_input);
+        assert!(cmds[1].piped_input);
+    }
+
+    #[test]
+    fn test_redirects() {
+        let cmds = parse("FOO=1 make 2>&1 >>build.log < in.txt > /dev/null").unwrap();
+        assert_eq!(cmds[0].assignments, vec!["FOO=1"]);
+        assert_eq!(cmds[0].words, vec!["make"]);
+        let writes: Vec<_> = cmds[0]
+            .redirects
+            .iter()
+            .filter(|r| r.writes_file())
+            .map(|r| r.target.as_str())
+            .collect();
+        assert_eq!(writes, vec!["build.log"]);
+
+        let cmds = parse("bash <<< 'rm x' < in.txt").unwrap();
+        assert_eq!(cmds[0].redirects[0].kind, RedirectKind::HereString);
+        assert_eq!(cmds[0].redirects[0].target, "rm x");
+        assert_eq!(cmds[0].redirects[1].kind, RedirectKind::Read);
+
+        let cmds = parse("cat <<EOF > notes.txt\nrm -rf /\nEOF\nls").unwrap();
+        assert_eq!(cmds.len(), 2);
+        assert!(cmds[0].redirects.iter().any(Redirect::writes_file));
+        assert_eq!(cmds[1].words, vec!["ls"]);
+    }
+
+    #[test]
+    fn test_rejects_malformed_input() {
+        for input in [
+            "echo 'unterminated",
+            "ls |",
+            "(ls",
+            "ls )",
+            "echo $(ls",
+            "; ls",
+        ] {
+            assert!(parse(input).is_err(), "{input}");
+        }
+    }
+}
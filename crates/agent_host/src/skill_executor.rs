@@ -11,8 +11,12 @@
 //! 3. **Batch execution** -- sequential (`execute_batch`) and concurrent
 //!    (`execute_concurrent`) execution of skill lists with bounded
 //!    parallelism.
+//!
+//! An executor built `with_cancel` stops a running skill (dropping its
+//! future) as soon as the token is cancelled and reports it as Cancelled.
 
 use anyhow::Result;
+use providers::cancel::CancellationToken;
 use shared::events::SkillEvent;
 use shared::skill::{ExecutionStatus, Skill, SkillContext, SkillError, SkillExecution, SkillInput};
 use std::sync::Arc;
@@ -29,6 +33,8 @@ pub struct SkillExecutor {
     default_timeout: Duration,
     /// Channel for sending skill events
     event_sender: Option<mpsc::UnboundedSender<SkillEvent>>,
+    /// Stops running skills when cancelled
+    cancel: CancellationToken,
 }
 
 impl SkillExecutor {
@@ -37,6 +43,7 @@ impl SkillExecutor {
         Self {
             default_timeout: DEFAULT_TIMEOUT,
             event_sender: None,
+            cancel: CancellationToken::new(),
         }
     }
 
@@ -45,6 +52,7 @@ impl SkillExecutor {
         Self {
             default_timeout: DEFAULT_TIMEOUT,
             event_sender: Some(event_sender),
+            cancel: CancellationToken::new(),
         }
     }
 
@@ -54,6 +62,12 @@ impl SkillExecutor {
         self
     }
 
+    /// Stop running skills when `cancel` is cancelled.
+    pub fn with_cancel(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
     /// Execute a skill with timeout handling.
     pub async fn execute(
         &self,
@@ -97,8 +111,18 @@ impl SkillExecutor {
 
         let start = Instant::now();
 
-        // Execute with timeout
-        let result = tokio::time::timeout(timeout, skill.execute(input, ctx)).await;
+        // Execute with timeout, unless cancelled first
+        let result = tokio::select! {
+            result = tokio::time::timeout(timeout, skill.execute(input, ctx)) => result,
+            _ = self.cancel.cancelled() => {
+                let duration_ms = start.elapsed().as_millis() as u64;
+                self.send_event(SkillEvent::Cancelled {
+                    execution_id,
+                    duration_ms,
+                });
+                return Ok(execution.cancel(duration_ms));
+            }
+        };
 
         let duration_ms = start.elapsed().as_millis() as u64;
 
@@ -156,7 +180,17 @@ impl SkillExecutor {
         });
 
         let start = Instant::now();
-        let result = skill.execute(input, ctx).await;
+        let result = tokio::select! {
+            result = skill.execute(input, ctx) => result,
+            _ = self.cancel.cancelled() => {
+                let duration_ms = start.elapsed().as_millis() as u64;
+                self.send_event(SkillEvent::Cancelled {
+                    execution_id,
+                    duration_ms,
+                });
+                return Ok(execution.cancel(duration_ms));
+            }
+        };
         let duration_ms = start.elapsed().as_millis() as u64;
 
         match result {
@@ -389,6 +423,22 @@ mod tests {
         assert_eq!(execution.status, ExecutionStatus::Timeout);
     }
 
+    #[tokio::test]
+    async fn test_execute_cancelled() {
+        let cancel = CancellationToken::new();
+        let executor = SkillExecutor::new().with_cancel(cancel.clone());
+        let skill: Arc<dyn Skill> = Arc::new(SlowSkill);
+        let ctx = SkillContext::new(Mode::Find, PathBuf::from("/tmp"));
+        let input = SkillInput::from_query("test");
+
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            cancel.cancel();
+        });
+        let execution = executor.execute(&skill, input, &ctx).await.unwrap();
+        assert_eq!(execution.status, ExecutionStatus::Cancelled);
+    }
+
     #[tokio::test]
     async fn test_event_sending() {
         let (tx, mut rx) = mpsc::unbounded_channel();
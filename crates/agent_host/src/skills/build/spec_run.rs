@@ -32,6 +32,16 @@ impl Skill for SpecRunSkill {
         &[Mode::Build]
     }
 
+    fn input_schema(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "spec": { "type": "string", "description": "Spec to run (defaults to the current one)" },
+                "folder": { "type": "string", "description": "Project folder" }
+            }
+        })
+    }
+
     async fn execute(&self, input: SkillInput, _ctx: &SkillContext) -> Result<SkillOutput> {
         let description = if input.query.is_empty() {
             input
@@ -1,4 +1,4 @@
-//! Audit logging for skill executions and file operations.
+//! Audit logging for skill executions, file operations and security events.
 //!
 //! Provides JSON-based audit logging with automatic file rotation.
 //! Logs are accessible to the primary user through the settings panel.
@@ -6,7 +6,7 @@
 use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
 use parking_lot::RwLock;
-use shared::events::{AuditEntry, AuditFilter, EventType};
+use shared::events::{AuditEntry, AuditFilter, EventType, SecurityEvent};
 use shared::skill::FileAction;
 use std::collections::VecDeque;
 use std::fs::{self, File, OpenOptions};
@@ -32,6 +32,14 @@ pub struct AuditLogger {
     cache: RwLock<VecDeque<AuditEntry>>,
 }
 
+impl std::fmt::Debug for AuditLogger {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AuditLogger")
+            .field("current_log", &self.current_log)
+            .finish_non_exhaustive()
+    }
+}
+
 impl AuditLogger {
     /// Create a new audit logger with the given log directory.
     pub fn new(log_dir: PathBuf) -> Result<Self> {
@@ -96,6 +104,12 @@ impl AuditLogger {
         self.log_entry(entry)
     }
 
+    /// Log a security event (blocked command, sandbox violation, secret,
+    /// 2FA step-up).
+    pub fn log_security_event(&self, event: SecurityEvent) -> Result<()> {
+        self.log_entry(AuditEntry::security(event))
+    }
+
     /// Log a raw audit entry.
     pub fn log_entry(&self, entry: AuditEntry) -> Result<()> {
         // Check if rotation is needed
@@ -185,6 +199,34 @@ impl AuditLogger {
             .collect()
     }
 
+    /// Get security events, most recent first.
+    pub fn security_events(&self, limit: usize) -> Vec<AuditEntry> {
+        let cache = self.cache.read();
+        cache
+            .iter()
+            .rev()
+            .filter(|e| e.security.is_some())
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    /// Get security events from one agent session, most recent first.
+    pub fn security_events_for_session(&self, session_id: &str, limit: usize) -> Vec<AuditEntry> {
+        let cache = self.cache.read();
+        cache
+            .iter()
+            .rev()
+            .filter(|e| {
+                e.security
+                    .as_ref()
+                    .is_some_and(|s| s.session_id.as_deref() == Some(session_id))
+            })
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
     /// Get the current log file path.
     pub fn log_file(&self) -> &Path {
         &self.current_log
@@ -234,6 +276,17 @@ impl AuditLogger {
             }
         }
 
+        // Session filter
+        if let Some(ref session_id) = filter.session_id {
+            let session = entry
+                .security
+                .as_ref()
+                .and_then(|s| s.session_id.as_deref());
+            if session != Some(session_id.as_str()) {
+                return false;
+            }
+        }
+
         // User visible filter
         if filter.user_visible_only && !entry.user_visible {
             return false;
@@ -329,6 +382,7 @@ pub struct AuditStats {
     pub file_operations: usize,
     pub permission_changes: usize,
     pub errors: usize,
+    pub security_events: usize,
     pub oldest_entry: Option<DateTime<Utc>>,
     pub newest_entry: Option<DateTime<Utc>>,
 }
@@ -348,6 +402,7 @@ impl AuditLogger {
                 EventType::FileOp => stats.file_operations += 1,
                 EventType::PermChange => stats.permission_changes += 1,
                 EventType::Error => stats.errors += 1,
+                EventType::Security => stats.security_events += 1,
             }
         }
 
@@ -480,4 +535,52 @@ mod tests {
         assert_eq!(stats.file_operations, 1);
         assert_eq!(stats.errors, 1);
     }
+
+    #[test]
+    fn test_security_events() {
+        use shared::events::SecurityEventKind;
+
+        let (temp_dir, logger) = setup();
+
+        logger.log_skill_execution("skill", "exec", None).unwrap();
+        logger
+            .log_security_event(
+                SecurityEvent::new(SecurityEventKind::CommandBlocked)
+                    .with_command("rm -rf /")
+                    .with_rule("matches the blocked pattern `rm -rf /`")
+                    .with_session_id("a"),
+            )
+            .unwrap();
+        logger
+            .log_security_event(
+                SecurityEvent::new(SecurityEventKind::SecretDetected)
+                    .with_command("echo [REDACTED:github-token]")
+                    .with_rule("github-token")
+                    .with_session_id("b"),
+            )
+            .unwrap();
+
+        let events = logger.security_events(10);
+        assert_eq!(events.len(), 2);
+        assert_eq!(
+            events[0].security.as_ref().unwrap().kind,
+            SecurityEventKind::SecretDetected
+        );
+
+        let session_a = logger.security_events_for_session("a", 10);
+        assert_eq!(session_a.len(), 1);
+        assert_eq!(session_a[0].action, "Command blocked: `rm -rf /`");
+
+        let filtered = logger
+            .query(AuditFilter::new().security().session("b"))
+            .unwrap();
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(logger.stats().security_events, 2);
+
+        // Security events survive a reload from disk
+        let reloaded = AuditLogger::new(temp_dir.path().to_path_buf()).unwrap();
+        let reloaded_events = reloaded.security_events(10);
+        assert_eq!(reloaded_events.len(), 2);
+        assert_eq!(reloaded_events[1].security, events[1].security);
+    }
 }
@@ -187,6 +187,17 @@ impl Skill for VersionHistory {
         }
         Ok(())
     }
+
+    fn input_schema(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string", "description": "File whose version history to show" },
+                "query": { "type": "string", "description": "Extra instructions, if any" }
+            },
+            "required": ["path"]
+        })
+    }
 }
 
 #[cfg(test)]
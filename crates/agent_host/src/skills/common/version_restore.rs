@@ -239,6 +239,17 @@ impl Skill for VersionRestore {
         }
         Ok(())
     }
+
+    fn input_schema(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string", "description": "File to restore" },
+                "query": { "type": "string", "description": "Extra instructions, if any" }
+            },
+            "required": ["path"]
+        })
+    }
 }
 
 /// Parse restore query for path and version number
@@ -8,8 +8,10 @@
 //! - Use personas: "Switch to the Tech Savvy Early Adopter persona"
 //! - Apply templates: "Use the weekly status template"
 //! - Reference research: "What do I know about file organization?"
+//! - Ask the knowledge graph: "How is Project X related to Alice?"
 
 use crate::context_manager::{ContextManager, ContextType, DistributionLevel};
+use crate::graph_store::{NodeFilter, Traversal};
 use anyhow::Result;
 use async_trait::async_trait;
 use shared::skill::{
@@ -243,6 +245,88 @@ impl ContextBrowser {
         }
     }
 
+    /// Explain how two entities in the knowledge graph are related
+    fn handle_relate(&self, from: &str, to: &str, traversal: &Traversal) -> Result<SkillOutput> {
+        let Some(path) = self.manager.graph_path(from, to, traversal) else {
+            return Ok(SkillOutput {
+                result_type: shared::skill::ResultType::Text,
+                text: Some(format!(
+                    "I don't know of a connection between '{}' and '{}' yet.",
+                    from, to
+                )),
+                files: Vec::new(),
+                data: None,
+                citations: Vec::new(),
+                suggested_actions: Vec::new(),
+            });
+        };
+
+        let graph = &self.manager.graph;
+        let mut output = format!("## 🕸️ How {} relates to {}\n\n", from, to);
+        for step in &path.steps {
+            output.push_str(&format!(
+                "• **{}** {} **{}**\n",
+                graph.graph[step.source].label, step.relation, graph.graph[step.target].label
+            ));
+        }
+
+        // Show the neighbourhood of where the path starts
+        let subgraph = self.manager.graph_subgraph(from, traversal, 20);
+        let mut params = HashMap::new();
+        params.insert("action".to_string(), serde_json::json!("graph"));
+        params.insert("entity".to_string(), serde_json::json!(from));
+
+        Ok(SkillOutput {
+            result_type: shared::skill::ResultType::Mixed,
+            text: Some(output),
+            files: Vec::new(),
+            data: Some(serde_json::json!({
+                "explanation": path.explain(graph),
+                "subgraph": subgraph,
+            })),
+            citations: Vec::new(),
+            suggested_actions: vec![SuggestedAction {
+                label: format!("🕸️ Show everything connected to {}", from),
+                skill_id: "context_browser".to_string(),
+                params,
+            }],
+        })
+    }
+
+    /// Show the part of the knowledge graph around an entity
+    fn handle_graph(&self, entity: &str, traversal: &Traversal) -> Result<SkillOutput> {
+        let Some(subgraph) = self.manager.graph_subgraph(entity, traversal, 20) else {
+            return Ok(SkillOutput {
+                result_type: shared::skill::ResultType::Text,
+                text: Some(format!("'{}' isn't in your knowledge graph yet.", entity)),
+                files: Vec::new(),
+                data: None,
+                citations: Vec::new(),
+                suggested_actions: Vec::new(),
+            });
+        };
+
+        let mut output = format!("## 🕸️ Connected to {}\n\n", subgraph.center);
+        for edge in &subgraph.edges {
+            output.push_str(&format!(
+                "• **{}** {} **{}**\n",
+                edge.source, edge.relation, edge.target
+            ));
+        }
+        if subgraph.edges.is_empty() {
+            output.push_str("Nothing is connected to it yet.\n");
+        }
+
+        Ok(SkillOutput {
+            result_type: shared::skill::ResultType::Mixed,
+            text: Some(output),
+            files: Vec::new(),
+            data: Some(serde_json::to_value(&subgraph)?),
+            citations: Vec::new(),
+            suggested_actions: Vec::new(),
+        })
+    }
+
     /// Browse by category
     fn handle_browse_categories(&self) -> Result<SkillOutput> {
         let mut output = String::new();
@@ -391,6 +475,19 @@ impl Skill for ContextBrowser {
                 browser.handle_view(doc_id)
             }
             "browse_categories" => browser.handle_browse_categories(),
+            "relate" => {
+                let param =
+                    |key: &str| input.params.get(key).and_then(|v| v.as_str()).unwrap_or("");
+                browser.handle_relate(param("from"), param("to"), &traversal_from_params(&input))
+            }
+            "graph" => {
+                let entity = input
+                    .params
+                    .get("entity")
+                    .and_then(|e| e.as_str())
+                    .unwrap_or(&input.query);
+                browser.handle_graph(entity, &traversal_from_params(&input))
+            }
             _ => {
                 // Default to search with the input query
                 browser.handle_search(&input.query, None)
@@ -398,3 +495,29 @@ impl Skill for ContextBrowser {
         }
     }
 }
+
+/// Graph query constraints from skill params: `relations` (list of
+/// relation names), `max_hops`, `category` and `mode`.
+fn traversal_from_params(input: &SkillInput) -> Traversal {
+    let mut traversal = Traversal::default();
+    if let Some(relations) = input.params.get("relations").and_then(|r| r.as_array()) {
+        for relation in relations.iter().filter_map(|r| r.as_str()) {
+            traversal = traversal.with_relation(relation);
+        }
+    }
+    if let Some(max_hops) = input.params.get("max_hops").and_then(|h| h.as_u64()) {
+        traversal = traversal.with_max_hops(max_hops as usize);
+    }
+    let mut filter = NodeFilter::default();
+    if let Some(category) = input.params.get("category").and_then(|c| c.as_str()) {
+        filter = filter.with_category(category);
+    }
+    if let Some(mode) = input
+        .params
+        .get("mode")
+        .and_then(|m| serde_json::from_value::<Mode>(m.clone()).ok())
+    {
+        filter = filter.with_mode(mode.into());
+    }
+    traversal.with_filter(filter)
+}
@@ -139,6 +139,17 @@ impl Skill for DriveIndex {
         }
         Ok(())
     }
+
+    fn input_schema(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string", "description": "Directory to index" },
+                "query": { "type": "string", "description": "Extra instructions, if any" }
+            },
+            "required": ["path"]
+        })
+    }
 }
 
 /// Get default directories to index based on platform
@@ -230,6 +230,17 @@ impl Skill for FilePreview {
         }
         Ok(())
     }
+
+    fn input_schema(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string", "description": "File to preview" },
+                "query": { "type": "string", "description": "Extra instructions, if any" }
+            },
+            "required": ["path"]
+        })
+    }
 }
 
 /// Format file size in human-readable form
@@ -23,6 +23,7 @@ use std::sync::Arc;
 use std::time::Instant;
 
 use anyhow::Result;
+use shared::agent_api::ToolSpec;
 use shared::skill::{
     Mode, Permission, PermissionLevel, Skill, SkillContext, SkillError, SkillExecution, SkillInput,
 };
@@ -206,6 +207,7 @@ impl SkillRegistry {
             permission_level: skill.permission_level(),
             modes: skill.modes().to_vec(),
             user_permission: self.get_permission(skill_id),
+            input_schema: skill.input_schema(),
         })
     }
 
@@ -220,9 +222,23 @@ impl SkillRegistry {
                 permission_level: skill.permission_level(),
                 modes: skill.modes().to_vec(),
                 user_permission: self.get_permission(skill.id()),
+                input_schema: skill.input_schema(),
             })
             .collect()
     }
+
+    /// Native tool definitions for every skill the user has not disabled in
+    /// `mode`. The tool name is the skill ID.
+    pub fn tool_specs_for_mode(&self, mode: Mode) -> Vec<ToolSpec> {
+        let mut specs: Vec<ToolSpec> = self
+            .skills_info_for_mode(mode)
+            .into_iter()
+            .filter(|info| info.user_permission != Permission::Disabled)
+            .map(|info| info.tool_spec())
+            .collect();
+        specs.sort_by(|a, b| a.name.cmp(&b.name));
+        specs
+    }
 }
 
 impl Default for SkillRegistry {
@@ -240,6 +256,19 @@ pub struct SkillInfo {
     pub permission_level: PermissionLevel,
     pub modes: Vec<Mode>,
     pub user_permission: Permission,
+    /// JSON Schema for the skill's tool-call arguments.
+    pub input_schema: serde_json::Value,
+}
+
+impl SkillInfo {
+    /// Describe this skill as a tool the model can call natively.
+    pub fn tool_spec(&self) -> ToolSpec {
+        ToolSpec {
+            name: self.id.to_string(),
+            description: format!("{}: {}", self.name, self.description),
+            parameters: self.input_schema.clone(),
+        }
+    }
 }
 
 use services::file_index::FileIndexService;
@@ -374,4 +403,19 @@ mod tests {
         assert_eq!(execution.skill_id, "test_skill");
         assert!(execution.output.is_some());
     }
+
+    #[test]
+    fn test_tool_specs_for_mode() {
+        let mut registry = SkillRegistry::new();
+        registry.register(Arc::new(TestSkill));
+
+        let specs = registry.tool_specs_for_mode(Mode::Find);
+        assert_eq!(specs.len(), 1);
+        assert_eq!(specs[0].name, "test_skill");
+        assert_eq!(specs[0].parameters["properties"]["query"]["type"], "string");
+        assert!(registry.tool_specs_for_mode(Mode::Fix).is_empty());
+
+        registry.set_permission("test_skill", Permission::Disabled);
+        assert!(registry.tool_specs_for_mode(Mode::Find).is_empty());
+    }
 }
@@ -1,21 +1,29 @@
 //! TOTP-based two-factor authentication skill.
 //!
-//! Provides `setup_2fa` (generate secret + QR code) and `verify_2fa`
-//! (validate a 6-digit code) actions. The TOTP secret is stored in the
-//! OS keyring via the `keyring` crate, so it never touches disk in
-//! plaintext. On successful verification the shared `SecurityContext`
-//! is marked as authenticated, which unlocks destructive commands in
-//! the executor for the configured timeout window.
+//! Provides `setup_2fa` (generate secret + QR code), `verify_2fa`
+//! (validate a 6-digit code), `list_grants` and `revoke_2fa` actions. The
+//! TOTP secret is stored in the OS keyring via the `keyring` crate, so it
+//! never touches disk in plaintext. On successful verification a grant is
+//! added to the shared `SecurityContext`, which unlocks destructive
+//! commands in the executor. The grant can be limited to a command
+//! pattern and/or directory, a shorter window, or a single use; without
+//! limits it covers every command for the configured timeout. Every
+//! verification attempt and revocation is recorded as a security event in
+//! the audit log.
 
 use anyhow::{Context, Result};
 use async_trait::async_trait;
 use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
 use keyring::Entry;
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 use totp_rs::{Algorithm, TOTP};
 
+use crate::security::GrantScope;
 use crate::skills::common::CommonInfrastructure;
 use crate::skills::{Skill, SkillContext, SkillInput};
+use shared::events::{SecurityEvent, SecurityEventKind};
 use shared::skill::{Mode, PermissionLevel, SkillOutput};
 
 /// 2FA management skill (setup and verification).
@@ -23,6 +31,41 @@ pub struct SecuritySkill {
     infra: Arc<CommonInfrastructure>,
 }
 
+/// What a successful `verify_2fa` unlocks, from the skill's parameters.
+struct GrantRequest {
+    scope: GrantScope,
+    /// Shorter than the configured timeout, if given.
+    minutes: Option<u64>,
+    single_use: bool,
+}
+
+impl GrantRequest {
+    fn from_params(params: &HashMap<String, serde_json::Value>) -> Self {
+        let text = |key: &str| {
+            params
+                .get(key)
+                .and_then(|v| v.as_str())
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+        };
+        let mut scope = GrantScope::new();
+        if let Some(pattern) = text("pattern") {
+            scope = scope.with_pattern(pattern);
+        }
+        if let Some(dir) = text("dir") {
+            scope = scope.with_dir(dir);
+        }
+        Self {
+            scope,
+            minutes: params.get("minutes").and_then(|v| v.as_u64()),
+            single_use: params
+                .get("single_use")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
+        }
+    }
+}
+
 impl SecuritySkill {
     pub fn new(infra: Arc<CommonInfrastructure>) -> Self {
         Self { infra }
@@ -76,7 +119,13 @@ impl SecuritySkill {
     }
 
     /// Verify a code
-    async fn verify_2fa(&self, user: &str, code: &str) -> Result<SkillOutput> {
+    async fn verify_2fa(
+        &self,
+        user: &str,
+        code: &str,
+        request: GrantRequest,
+        session_id: Option<&str>,
+    ) -> Result<SkillOutput> {
         let entry = Entry::new("little-helper-2fa", user)?;
         let secret_str = entry
             .get_password()
@@ -96,13 +145,78 @@ impl SecuritySkill {
         )
         .unwrap();
 
-        if totp.check_current(code).unwrap_or(false) {
-            self.infra.security_context.authenticate();
-            Ok(SkillOutput::text(
-                "✅ 2FA Code Verified! Session checks passed.",
-            ))
-        } else {
-            Ok(SkillOutput::error("❌ Invalid 2FA Code. Please try again."))
+        if !totp.check_current(code).unwrap_or(false) {
+            self.audit(
+                SecurityEventKind::TwoFactorFailed,
+                format!("TOTP for {}", user),
+                session_id,
+            );
+            return Ok(SkillOutput::error("❌ Invalid 2FA Code. Please try again."));
+        }
+
+        let context = &self.infra.security_context;
+        let timeout = context.timeout();
+        let duration = request
+            .minutes
+            .map(|m| Duration::from_secs(m.max(1) * 60).min(timeout))
+            .unwrap_or(timeout);
+        let grant = context.grant(request.scope, duration, request.single_use);
+        self.audit(
+            SecurityEventKind::TwoFactorGranted,
+            grant.describe(),
+            session_id,
+        );
+        Ok(SkillOutput::text(format!(
+            "✅ 2FA Code Verified! Unlocked {}.",
+            grant.describe()
+        )))
+    }
+
+    /// List the grants in force.
+    fn list_grants(&self) -> SkillOutput {
+        let grants = self.infra.security_context.active_grants();
+        if grants.is_empty() {
+            return SkillOutput::text("No 2FA grants are active.");
+        }
+        let lines: Vec<String> = grants
+            .iter()
+            .map(|g| format!("- {}", g.describe()))
+            .collect();
+        SkillOutput::text(format!("Active 2FA grants:\n{}", lines.join("\n")))
+    }
+
+    /// Revoke one grant by id, or all of them.
+    fn revoke(&self, id: Option<u64>, session_id: Option<&str>) -> SkillOutput {
+        let context = &self.infra.security_context;
+        match id {
+            Some(id) => {
+                if !context.revoke(id) {
+                    return SkillOutput::error(format!("No active 2FA grant #{}.", id));
+                }
+                self.audit(
+                    SecurityEventKind::GrantRevoked,
+                    format!("#{}", id),
+                    session_id,
+                );
+                SkillOutput::text(format!("Revoked 2FA grant #{}.", id))
+            }
+            None => {
+                let count = context.revoke_all();
+                self.audit(
+                    SecurityEventKind::GrantRevoked,
+                    format!("all ({})", count),
+                    session_id,
+                );
+                SkillOutput::text(format!("Revoked {} 2FA grant(s).", count))
+            }
+        }
+    }
+
+    fn audit(&self, kind: SecurityEventKind, rule: String, session_id: Option<&str>) {
+        let mut event = SecurityEvent::new(kind).with_rule(rule);
+        event.session_id = session_id.map(str::to_string);
+        if let Err(e) = self.infra.audit_logger.log_security_event(event) {
+            tracing::warn!("failed to record 2FA event: {:#}", e);
         }
     }
 }
@@ -118,7 +232,7 @@ impl Skill for SecuritySkill {
     }
 
     fn description(&self) -> &'static str {
-        "Manage 2FA setup and verification."
+        "Manage 2FA setup and verification, and the grants that unlock destructive commands."
     }
 
     fn permission_level(&self) -> PermissionLevel {
@@ -135,7 +249,32 @@ impl Skill for SecuritySkill {
         ]
     }
 
-    async fn execute(&self, input: SkillInput, _ctx: &SkillContext) -> Result<SkillOutput> {
+    fn input_schema(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "action": {
+                    "type": "string",
+                    "enum": ["setup_2fa", "verify_2fa", "list_grants", "revoke_2fa"]
+                },
+                "code": { "type": "string", "description": "6-digit code, for verify_2fa" },
+                "pattern": {
+                    "type": "string",
+                    "description": "Only unlock commands matching this program name or glob, e.g. `rm`"
+                },
+                "dir": {
+                    "type": "string",
+                    "description": "Only unlock commands that stay inside this directory"
+                },
+                "minutes": { "type": "integer", "description": "Unlock for fewer minutes than the default" },
+                "single_use": { "type": "boolean", "description": "Unlock a single command" },
+                "id": { "type": "integer", "description": "Grant to revoke; all grants if omitted" }
+            },
+            "required": ["action"]
+        })
+    }
+
+    async fn execute(&self, input: SkillInput, ctx: &SkillContext) -> Result<SkillOutput> {
         let action = input
             .params
             .get("action")
@@ -156,10 +295,17 @@ impl Skill for SecuritySkill {
                     .get("code")
                     .and_then(|v| v.as_str())
                     .context("Missing code")?;
-                self.verify_2fa(user, code).await
+                let request = GrantRequest::from_params(&input.params);
+                self.verify_2fa(user, code, request, ctx.session_id.as_deref())
+                    .await
+            }
+            "list_grants" => Ok(self.list_grants()),
+            "revoke_2fa" => {
+                let id = input.params.get("id").and_then(|v| v.as_u64());
+                Ok(self.revoke(id, ctx.session_id.as_deref()))
             }
             _ => Ok(SkillOutput::text(
-                "Unknown action. Use setup_2fa or verify_2fa.",
+                "Unknown action. Use setup_2fa, verify_2fa, list_grants or revoke_2fa.",
             )),
         }
     }
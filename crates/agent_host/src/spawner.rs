@@ -0,0 +1,409 @@
+//! Process spawning behind `execute_command`.
+//!
+//! Once a command has passed every check (secret scan, sandbox,
+//! classification, 2FA), the executor hands it to the session's
+//! [`ProcessSpawner`]. [`SystemSpawner`], the default, runs it with `sh -c`
+//! (`cmd /C` on Windows) in its own process group, confined by the process
+//! sandbox when one is attached, and kills the whole group on timeout or
+//! cancellation.
+//!
+//! The other two spawners let the executor be exercised without running
+//! anything real:
+//!
+//! - **`RecordingSpawner`** wraps another spawner and keeps every command
+//!   it ran with its outcome; `save` writes them as a JSON fixture file.
+//! - **`ReplaySpawner`** answers from such fixtures and notes which
+//!   commands reached it, so a test can check both the result of an
+//!   allowed command and that a refused one was never spawned.
+//!
+//! A fixture file is a JSON list, e.g.:
+//!
+//! ```json
+//! [
+//!   { "command": "ls", "outcome": { "outcome": "finished", "stdout": "a.txt\n", "stderr": "", "exit_code": 0 } },
+//!   { "command": "sleep 60", "outcome": { "outcome": "timed_out" } }
+//! ]
+//! ```
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::process::Command;
+
+use crate::process_sandbox::ProcessSandbox;
+use providers::cancel::CancellationToken;
+
+/// What the executor asks a spawner to run.
+#[derive(Debug, Clone, Copy)]
+pub struct SpawnRequest<'a> {
+    pub command: &'a str,
+    pub cwd: &'a Path,
+    pub env: &'a HashMap<String, String>,
+    pub timeout: Duration,
+    /// Whether the command needs the network, for the process sandbox.
+    pub network: bool,
+    pub sandbox: Option<&'a ProcessSandbox>,
+    pub cancel: &'a CancellationToken,
+}
+
+/// Output of a process that ran to completion.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProcessOutput {
+    pub stdout: String,
+    pub stderr: String,
+    /// `None` when the process was killed by a signal.
+    pub exit_code: Option<i32>,
+    /// The signal that killed the process (Unix).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signal: Option<i32>,
+}
+
+impl ProcessOutput {
+    pub fn success(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+
+    fn from_output(output: std::process::Output) -> Self {
+        #[cfg(unix)]
+        let signal = std::os::unix::process::ExitStatusExt::signal(&output.status);
+        #[cfg(not(unix))]
+        let signal = None;
+        Self {
+            stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+            stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+            exit_code: output.status.code(),
+            signal,
+        }
+    }
+}
+
+/// How a spawned command ended.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum SpawnOutcome {
+    Finished(ProcessOutput),
+    /// The process could not be started.
+    Failed {
+        error: String,
+    },
+    /// The process sandbox could not be set up, so nothing ran.
+    SandboxUnavailable {
+        error: String,
+    },
+    TimedOut,
+    Cancelled,
+}
+
+/// Runs commands that passed the executor's checks.
+#[async_trait]
+pub trait ProcessSpawner: std::fmt::Debug + Send + Sync {
+    async fn spawn(&self, request: SpawnRequest<'_>) -> SpawnOutcome;
+}
+
+/// Spawns real processes. The default for every session.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemSpawner;
+
+impl SystemSpawner {
+    /// `SystemSpawner` as the session's shared spawner.
+    pub fn shared() -> Arc<dyn ProcessSpawner> {
+        Arc::new(SystemSpawner)
+    }
+}
+
+#[async_trait]
+impl ProcessSpawner for SystemSpawner {
+    async fn spawn(&self, request: SpawnRequest<'_>) -> SpawnOutcome {
+        // Determine shell based on OS
+        let (shell, shell_arg) = if cfg!(windows) {
+            ("cmd", "/C")
+        } else {
+            ("sh", "-c")
+        };
+
+        // Hardened execution: confine the process itself, not just the string.
+        let mut command = match request.sandbox {
+            Some(sandbox) => match sandbox.command(
+                shell,
+                shell_arg,
+                request.command,
+                request.cwd,
+                request.network,
+            ) {
+                Ok(command) => command,
+                Err(e) => {
+                    return SpawnOutcome::SandboxUnavailable {
+                        error: e.to_string(),
+                    }
+                }
+            },
+            None => {
+                let mut command = Command::new(shell);
+                command
+                    .arg(shell_arg)
+                    .arg(request.command)
+                    .current_dir(request.cwd);
+                command
+            }
+        };
+        command
+            .envs(request.env)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        #[cfg(unix)]
+        command.process_group(0);
+
+        let child = match command.spawn() {
+            Ok(child) => child,
+            Err(e) => {
+                return SpawnOutcome::Failed {
+                    error: e.to_string(),
+                }
+            }
+        };
+        let pid = child.id();
+        let outcome = tokio::select! {
+            out = tokio::time::timeout(request.timeout, child.wait_with_output()) => match out {
+                Ok(Ok(output)) => SpawnOutcome::Finished(ProcessOutput::from_output(output)),
+                Ok(Err(e)) => SpawnOutcome::Failed { error: e.to_string() },
+                Err(_) => SpawnOutcome::TimedOut,
+            },
+            _ = request.cancel.cancelled() => SpawnOutcome::Cancelled,
+        };
+        if matches!(outcome, SpawnOutcome::TimedOut | SpawnOutcome::Cancelled) {
+            kill_process_tree(pid);
+        }
+        outcome
+    }
+}
+
+/// Kill a command's process group: the shell and everything it started.
+fn kill_process_tree(pid: Option<u32>) {
+    let Some(pid) = pid else {
+        return;
+    };
+    #[cfg(unix)]
+    let _ = std::process::Command::new("kill")
+        .args(["-KILL", "--", &format!("-{}", pid)])
+        .output();
+    #[cfg(windows)]
+    let _ = std::process::Command::new("taskkill")
+        .args(["/T", "/F", "/PID", &pid.to_string()])
+        .output();
+}
+
+/// One command and how it ended.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommandFixture {
+    pub command: String,
+    pub outcome: SpawnOutcome,
+}
+
+impl CommandFixture {
+    /// A command that ran to completion with `exit_code`.
+    pub fn finished(command: &str, stdout: &str, stderr: &str, exit_code: i32) -> Self {
+        Self {
+            command: command.to_string(),
+            outcome: SpawnOutcome::Finished(ProcessOutput {
+                stdout: stdout.to_string(),
+                stderr: stderr.to_string(),
+                exit_code: Some(exit_code),
+                signal: None,
+            }),
+        }
+    }
+
+    /// Parse a fixture file's contents.
+    pub fn from_json(json: &str) -> Result<Vec<Self>> {
+        serde_json::from_str(json).context("Invalid command fixtures")
+    }
+
+    pub fn load(path: &Path) -> Result<Vec<Self>> {
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read command fixtures {:?}", path))?;
+        Self::from_json(&json)
+    }
+}
+
+/// Runs commands with another spawner and records their outcomes.
+#[derive(Debug)]
+pub struct RecordingSpawner {
+    inner: Arc<dyn ProcessSpawner>,
+    recorded: Mutex<Vec<CommandFixture>>,
+}
+
+impl RecordingSpawner {
+    pub fn new(inner: Arc<dyn ProcessSpawner>) -> Self {
+        Self {
+            inner,
+            recorded: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Everything recorded so far, in the order it ran.
+    pub fn fixtures(&self) -> Vec<CommandFixture> {
+        self.recorded.lock().clone()
+    }
+
+    /// Write the recording as a fixture file for `ReplaySpawner`.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(&*self.recorded.lock())?;
+        std::fs::write(path, json)
+            .with_context(|| format!("Failed to write command fixtures {:?}", path))
+    }
+}
+
+#[async_trait]
+impl ProcessSpawner for RecordingSpawner {
+    async fn spawn(&self, request: SpawnRequest<'_>) -> SpawnOutcome {
+        let outcome = self.inner.spawn(request).await;
+        self.recorded.lock().push(CommandFixture {
+            command: request.command.to_string(),
+            outcome: outcome.clone(),
+        });
+        outcome
+    }
+}
+
+/// Answers from recorded fixtures instead of running anything.
+///
+/// Each fixture answers one request for its command, in order; the last
+/// one for a command keeps answering once the others are used up. A
+/// command with no fixture fails to start.
+#[derive(Debug, Default)]
+pub struct ReplaySpawner {
+    fixtures: Mutex<Vec<CommandFixture>>,
+    spawned: Mutex<Vec<String>>,
+}
+
+impl ReplaySpawner {
+    pub fn new(fixtures: Vec<CommandFixture>) -> Self {
+        Self {
+            fixtures: Mutex::new(fixtures),
+            spawned: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        Ok(Self::new(CommandFixture::load(path)?))
+    }
+
+    /// Every command that reached the spawner, in order.
+    pub fn spawned(&self) -> Vec<String> {
+        self.spawned.lock().clone()
+    }
+}
+
+#[async_trait]
+impl ProcessSpawner for ReplaySpawner {
+    async fn spawn(&self, request: SpawnRequest<'_>) -> SpawnOutcome {
+        self.spawned.lock().push(request.command.to_string());
+        if request.cancel.is_cancelled() {
+            return SpawnOutcome::Cancelled;
+        }
+        let mut fixtures = self.fixtures.lock();
+        let matching: Vec<usize> = fixtures
+            .iter()
+            .enumerate()
+            .filter(|(_, f)| f.command == request.command)
+            .map(|(i, _)| i)
+            .collect();
+        match matching.as_slice() {
+            [] => SpawnOutcome::Failed {
+                error: format!("no recorded outcome for `{}`", request.command),
+            },
+            [only] => fixtures[*only].outcome.clone(),
+            [first, ..] => fixtures.remove(*first).outcome,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request<'a>(
+        command: &'a str,
+        env: &'a HashMap<String, String>,
+        cancel: &'a CancellationToken,
+    ) -> SpawnRequest<'a> {
+        SpawnRequest {
+            command,
+            cwd: Path::new("."),
+            env,
+            timeout: Duration::from_secs(10),
+            network: false,
+            sandbox: None,
+            cancel,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_record_then_replay() {
+        let env = HashMap::from([("GREETING".to_string(), "hello".to_string())]);
+        let cancel = CancellationToken::new();
+        let recorder = RecordingSpawner::new(SystemSpawner::shared());
+        let live = recorder
+            .spawn(request("echo $GREETING; exit 3", &env, &cancel))
+            .await;
+        match &live {
+            SpawnOutcome::Finished(output) => {
+                assert_eq!(output.stdout.trim(), "hello");
+                assert_eq!(output.exit_code, Some(3));
+            }
+            other => panic!("unexpected outcome {:?}", other),
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fixtures.json");
+        recorder.save(&path).unwrap();
+
+        let replay = ReplaySpawner::load(&path).unwrap();
+        let replayed = replay
+            .spawn(request("echo $GREETING; exit 3", &env, &cancel))
+            .await;
+        assert_eq!(replayed, live);
+        assert!(matches!(
+            replay.spawn(request("rm -rf ~", &env, &cancel)).await,
+            SpawnOutcome::Failed { .. }
+        ));
+        assert_eq!(replay.spawned(), vec!["echo $GREETING; exit 3", "rm -rf ~"]);
+    }
+
+    #[tokio::test]
+    async fn test_replay_answers_in_order() {
+        let env = HashMap::new();
+        let cancel = CancellationToken::new();
+        let replay = ReplaySpawner::new(vec![
+            CommandFixture::finished("git pull", "", "network down", 1),
+            CommandFixture::finished("git pull", "Already up to date.", "", 0),
+        ]);
+        let stderr = |outcome: SpawnOutcome| match outcome {
+            SpawnOutcome::Finished(output) => output.stderr,
+            other => panic!("unexpected outcome {:?}", other),
+        };
+        assert_eq!(
+            stderr(replay.spawn(request("git pull", &env, &cancel)).await),
+            "network down"
+        );
+        for _ in 0..2 {
+            assert_eq!(
+                stderr(replay.spawn(request("git pull", &env, &cancel)).await),
+                ""
+            );
+        }
+
+        cancel.cancel();
+        assert_eq!(
+            replay.spawn(request("git pull", &env, &cancel)).await,
+            SpawnOutcome::Cancelled
+        );
+    }
+}
@@ -263,6 +263,31 @@ pub enum BudgetAlert {
     },
 }
 
+impl BudgetAlert {
+    /// A short, friendly notice for the chat.
+    pub fn message(&self) -> String {
+        let (period, used, budget) = match self {
+            BudgetAlert::DailyWarning { used, budget } => ("today's", used, budget),
+            BudgetAlert::WeeklyWarning { used, budget } => ("this week's", used, budget),
+            BudgetAlert::MonthlyWarning { used, budget } => ("this month's", used, budget),
+            BudgetAlert::AutoSwitchSuggestion {
+                current_model,
+                suggested_model,
+                current_cost,
+            } => {
+                return format!(
+                    "This conversation has cost ${:.2} so far on {}. {} would be much cheaper for everyday questions.",
+                    current_cost, current_model, suggested_model
+                )
+            }
+        };
+        format!(
+            "Heads up: you've used ${:.2} of {} ${:.2} AI budget.",
+            used, period, budget
+        )
+    }
+}
+
 /// Cost optimization suggestion
 #[derive(Debug, Clone)]
 pub struct CostOptimization {
@@ -286,8 +311,10 @@ pub struct TokenTracker {
     budget_settings: Arc<Mutex<BudgetSettings>>,
     /// Current conversation ID
     current_conversation: Arc<Mutex<Option<String>>>,
-    /// Last budget check time
-    last_budget_check: Arc<Mutex<Instant>>,
+    /// Last budget check time (`None` until the first check)
+    last_budget_check: Arc<Mutex<Option<Instant>>>,
+    /// Alerts raised since the UI last called `take_alerts`
+    pending_alerts: Arc<Mutex<Vec<BudgetAlert>>>,
 }
 
 impl TokenTracker {
@@ -297,7 +324,8 @@ impl TokenTracker {
             usage_history: Arc::new(Mutex::new(Vec::new())),
             budget_settings: Arc::new(Mutex::new(BudgetSettings::default())),
             current_conversation: Arc::new(Mutex::new(None)),
-            last_budget_check: Arc::new(Mutex::new(Instant::now())),
+            last_budget_check: Arc::new(Mutex::new(None)),
+            pending_alerts: Arc::new(Mutex::new(Vec::new())),
         }
     }
 
@@ -315,30 +343,57 @@ impl TokenTracker {
 
     /// Record token usage from a message
     pub fn record_usage(&self, input_tokens: u32, output_tokens: u32, model: &str) {
-        let pricing = get_model_pricing(model);
-        let cost = pricing.calculate_cost(input_tokens, output_tokens);
-
         let conversation_id = self
             .current_conversation
             .lock()
             .unwrap()
             .clone()
             .unwrap_or_else(|| "unknown".to_string());
+        self.record_usage_for(&conversation_id, None, input_tokens, output_tokens, model);
+    }
+
+    /// Record usage reported by `provider` for a specific conversation.
+    /// Local models are free. Any budget alerts this triggers are queued
+    /// for [`take_alerts`](Self::take_alerts).
+    pub fn record_usage_for(
+        &self,
+        conversation_id: &str,
+        provider: Option<&str>,
+        input_tokens: u32,
+        output_tokens: u32,
+        model: &str,
+    ) {
+        let pricing = get_model_pricing(model);
+        let (provider, cost) = match provider {
+            Some("local") => ("Local".to_string(), 0.0),
+            _ => (
+                pricing.provider.clone(),
+                pricing.calculate_cost(input_tokens, output_tokens),
+            ),
+        };
 
         let usage = TokenUsage {
             timestamp: Utc::now(),
             input_tokens,
             output_tokens,
             model: model.to_string(),
-            provider: pricing.provider,
+            provider,
             cost_usd: cost,
-            conversation_id,
+            conversation_id: conversation_id.to_string(),
         };
 
         self.usage_history.lock().unwrap().push(usage);
 
-        // Check if we should trigger budget alerts
-        self.check_budget_alerts();
+        // Free usage can't push a budget over, so don't spend the check on it
+        if cost > 0.0 {
+            let alerts = self.check_budget_alerts(conversation_id);
+            self.pending_alerts.lock().unwrap().extend(alerts);
+        }
+    }
+
+    /// Drain the budget alerts raised since the last call.
+    pub fn take_alerts(&self) -> Vec<BudgetAlert> {
+        std::mem::take(&mut *self.pending_alerts.lock().unwrap())
     }
 
     /// Get usage stats for today
@@ -387,30 +442,47 @@ impl TokenTracker {
 
     /// Get current conversation cost
     pub fn get_conversation_cost(&self) -> f64 {
-        let current_id = match self.current_conversation.lock().unwrap().clone() {
-            Some(id) => id,
-            None => return 0.0,
-        };
+        match self.current_conversation.lock().unwrap().clone() {
+            Some(id) => self.conversation_cost(&id),
+            None => 0.0,
+        }
+    }
 
+    /// Total cost recorded for `conversation_id`
+    pub fn conversation_cost(&self, conversation_id: &str) -> f64 {
         let history = self.usage_history.lock().unwrap();
         history
             .iter()
-            .filter(|u| u.conversation_id == current_id)
+            .filter(|u| u.conversation_id == conversation_id)
             .map(|u| u.cost_usd)
             .sum()
     }
 
+    /// Smallest amount left across the configured budget periods, or `None`
+    /// if no budget is set. Negative once a period is overspent.
+    pub fn remaining_budget_usd(&self) -> Option<f64> {
+        let settings = self.get_budget_settings();
+        [
+            (settings.daily_budget_usd, self.get_daily_stats()),
+            (settings.weekly_budget_usd, self.get_weekly_stats()),
+            (settings.monthly_budget_usd, self.get_monthly_stats()),
+        ]
+        .into_iter()
+        .filter_map(|(budget, stats)| budget.map(|b| b - stats.total_cost_usd))
+        .reduce(f64::min)
+    }
+
     /// Check budget and return any alerts
-    fn check_budget_alerts(&self) -> Vec<BudgetAlert> {
+    fn check_budget_alerts(&self, conversation_id: &str) -> Vec<BudgetAlert> {
         let mut alerts = Vec::new();
         let settings = self.budget_settings.lock().unwrap();
 
         // Only check every 5 minutes to avoid spam
         let mut last_check = self.last_budget_check.lock().unwrap();
-        if last_check.elapsed() < Duration::from_secs(300) {
+        if last_check.is_some_and(|t| t.elapsed() < Duration::from_secs(300)) {
             return alerts;
         }
-        *last_check = Instant::now();
+        *last_check = Some(Instant::now());
         drop(last_check);
 
         // Check daily budget
@@ -454,34 +526,31 @@ impl TokenTracker {
 
         // Check auto-switch threshold
         if let Some(threshold) = settings.auto_switch_threshold_usd {
-            let conversation_cost = self.get_conversation_cost();
+            let conversation_cost = self.conversation_cost(conversation_id);
             if conversation_cost >= threshold {
-                // Get most expensive model in current conversation
+                // Get most expensive model in this conversation
                 let history = self.usage_history.lock().unwrap();
-                let current_id = self.current_conversation.lock().unwrap().clone();
-
-                if let Some(id) = current_id {
-                    let expensive_model = history
-                        .iter()
-                        .filter(|u| u.conversation_id == id)
-                        .max_by(|a, b| {
-                            let a_price = get_model_pricing(&a.model);
-                            let b_price = get_model_pricing(&b.model);
-                            a_price
-                                .input_cost_per_1k
-                                .partial_cmp(&b_price.input_cost_per_1k)
-                                .unwrap()
-                        })
-                        .map(|u| u.model.clone());
-
-                    if let Some(model) = expensive_model {
-                        let suggestion = self.get_cheaper_alternative(&model);
-                        alerts.push(BudgetAlert::AutoSwitchSuggestion {
-                            current_model: model,
-                            suggested_model: suggestion.suggested_model,
-                            current_cost: conversation_cost,
-                        });
-                    }
+
+                let expensive_model = history
+                    .iter()
+                    .filter(|u| u.conversation_id == conversation_id)
+                    .max_by(|a, b| {
+                        let a_price = get_model_pricing(&a.model);
+                        let b_price = get_model_pricing(&b.model);
+                        a_price
+                            .input_cost_per_1k
+                            .partial_cmp(&b_price.input_cost_per_1k)
+                            .unwrap()
+                    })
+                    .map(|u| u.model.clone());
+
+                if let Some(model) = expensive_model {
+                    let suggestion = self.get_cheaper_alternative(&model);
+                    alerts.push(BudgetAlert::AutoSwitchSuggestion {
+                        current_model: model,
+                        suggested_model: suggestion.suggested_model,
+                        current_cost: conversation_cost,
+                    });
                 }
             }
         }
@@ -623,6 +692,24 @@ impl Default for TokenTracker {
     }
 }
 
+impl providers::usage::UsageRecorder for TokenTracker {
+    fn record_usage(
+        &self,
+        conversation_id: &str,
+        provider: &str,
+        model: &str,
+        usage: providers::usage::Usage,
+    ) {
+        self.record_usage_for(
+            conversation_id,
+            Some(provider),
+            usage.input_tokens,
+            usage.output_tokens,
+            model,
+        );
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -671,4 +758,48 @@ mod tests {
         assert_eq!(stats.message_count, 1);
         assert_eq!(stats.total_cost_usd, 0.001);
     }
+
+    #[test]
+    fn test_remaining_budget() {
+        let tracker = TokenTracker::new();
+        tracker.update_budget_settings(BudgetSettings {
+            daily_budget_usd: Some(1.0),
+            ..Default::default()
+        });
+        tracker.record_usage(1000, 1000, "gpt-4");
+        let remaining = tracker.remaining_budget_usd().unwrap();
+        assert!((remaining - 0.91).abs() < 1e-9);
+
+        tracker.update_budget_settings(BudgetSettings {
+            daily_budget_usd: None,
+            weekly_budget_usd: None,
+            monthly_budget_usd: None,
+            ..Default::default()
+        });
+        assert_eq!(tracker.remaining_budget_usd(), None);
+    }
+
+    #[test]
+    fn test_provider_usage_is_recorded_per_conversation() {
+        use providers::usage::{Usage, UsageRecorder};
+
+        let tracker = TokenTracker::new();
+        tracker.update_budget_settings(BudgetSettings {
+            daily_budget_usd: Some(0.05),
+            ..Default::default()
+        });
+        UsageRecorder::record_usage(&tracker, "a", "local", "llama3", Usage::new(5000, 5000));
+        assert_eq!(tracker.conversation_cost("a"), 0.0);
+        assert!(tracker.take_alerts().is_empty());
+
+        UsageRecorder::record_usage(&tracker, "b", "openai", "gpt-4", Usage::new(1000, 1000));
+        assert!((tracker.conversation_cost("b") - 0.09).abs() < 1e-9);
+        assert_eq!(tracker.get_daily_stats().input_tokens, 6000);
+
+        // The first check happens straight away and is queued for the UI.
+        let alerts = tracker.take_alerts();
+        assert!(matches!(alerts[0], BudgetAlert::DailyWarning { .. }));
+        assert!(alerts[0].message().contains("today's"));
+        assert!(tracker.take_alerts().is_empty());
+    }
 }
@@ -0,0 +1,163 @@
+//! Native tool definitions and tool-call dispatch.
+//!
+//! Models with structured tool calling are offered two built-in tools --
+//! [`RUN_COMMAND_TOOL`] and [`SEARCH_TOOL`] -- plus one tool per skill (see
+//! [`SkillRegistry::tool_specs_for_mode`](crate::skills::SkillRegistry::tool_specs_for_mode)).
+//! [`tool_action`] maps the calls they return onto the same actions the
+//! `<command>` / `<search>` / `<skill>` tag parser produces, so the executor
+//! and permission checks are shared. Models without tool support keep using
+//! the tags.
+
+use serde_json::{json, Value};
+use shared::agent_api::{ToolCall, ToolSpec};
+use shared::skill::SkillInput;
+
+/// Built-in tool that runs a shell command (subject to safety classification).
+pub const RUN_COMMAND_TOOL: &str = "run_command";
+
+/// Built-in tool that searches the web. Not `web_search`, which is a skill ID.
+pub const SEARCH_TOOL: &str = "search";
+
+/// Built-in tool definitions, limited to what the user has allowed.
+pub fn builtin_tool_specs(allow_terminal: bool, allow_web: bool) -> Vec<ToolSpec> {
+    let mut specs = Vec::new();
+    if allow_terminal {
+        specs.push(ToolSpec {
+            name: RUN_COMMAND_TOOL.to_string(),
+            description: "Run a shell command on the user's computer and return its output. \
+                Safe read-only commands run immediately; anything that changes the system \
+                waits for the user's approval."
+                .to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "command": { "type": "string", "description": "The command line to run" }
+                },
+                "required": ["command"]
+            }),
+        });
+    }
+    if allow_web {
+        specs.push(ToolSpec {
+            name: SEARCH_TOOL.to_string(),
+            description: "Search the web and return the top results.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "query": { "type": "string", "description": "Search query" }
+                },
+                "required": ["query"]
+            }),
+        });
+    }
+    specs
+}
+
+/// What a tool call asks the agent to do.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ToolAction {
+    Search(String),
+    Command(String),
+    /// Invoke the skill with this ID; `params` holds the raw arguments.
+    Skill {
+        id: String,
+        params: Value,
+    },
+    /// The call was malformed; the message is sent back as the tool result.
+    Invalid(String),
+}
+
+/// Map a structured tool call onto an agent action.
+pub fn tool_action(call: &ToolCall) -> ToolAction {
+    let arg = |key: &str| {
+        call.arguments
+            .get(key)
+            .and_then(Value::as_str)
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(str::to_string)
+    };
+    match call.name.as_str() {
+        RUN_COMMAND_TOOL => arg("command")
+            .map(ToolAction::Command)
+            .unwrap_or_else(|| ToolAction::Invalid("Missing required argument: command".into())),
+        SEARCH_TOOL => arg("query")
+            .map(ToolAction::Search)
+            .unwrap_or_else(|| ToolAction::Invalid("Missing required argument: query".into())),
+        id => ToolAction::Skill {
+            id: id.to_string(),
+            params: call.arguments.clone(),
+        },
+    }
+}
+
+/// Build a [`SkillInput`] from tool-call (or `<skill>` tag) arguments. A
+/// string `query` also fills [`SkillInput::query`]; every argument is kept in
+/// `params` for skills that read it from there.
+pub fn skill_input(params: &Value) -> SkillInput {
+    let query = params
+        .get("query")
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    let mut input = SkillInput::from_query(query);
+    if let Value::Object(map) = params {
+        for (k, v) in map {
+            input = input.with_param(k.clone(), v.clone());
+        }
+    }
+    input
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(name: &str, arguments: Value) -> ToolCall {
+        ToolCall {
+            id: "call_0".to_string(),
+            name: name.to_string(),
+            arguments,
+        }
+    }
+
+    #[test]
+    fn test_builtin_tool_specs_respect_permissions() {
+        assert!(builtin_tool_specs(false, false).is_empty());
+        let names: Vec<String> = builtin_tool_specs(true, true)
+            .into_iter()
+            .map(|s| s.name)
+            .collect();
+        assert_eq!(names, vec![RUN_COMMAND_TOOL, SEARCH_TOOL]);
+    }
+
+    #[test]
+    fn test_tool_action_mapping() {
+        assert_eq!(
+            tool_action(&call(RUN_COMMAND_TOOL, json!({"command": " ls -la "}))),
+            ToolAction::Command("ls -la".to_string())
+        );
+        assert_eq!(
+            tool_action(&call(SEARCH_TOOL, json!({"query": "rust async"}))),
+            ToolAction::Search("rust async".to_string())
+        );
+        assert!(matches!(
+            tool_action(&call(RUN_COMMAND_TOOL, json!({}))),
+            ToolAction::Invalid(_)
+        ));
+        assert_eq!(
+            tool_action(&call("file_preview", json!({"path": "~/notes.md"}))),
+            ToolAction::Skill {
+                id: "file_preview".to_string(),
+                params: json!({"path": "~/notes.md"}),
+            }
+        );
+    }
+
+    #[test]
+    fn test_skill_input_splits_query_and_params() {
+        let input = skill_input(&json!({"query": "big files", "path": "/tmp"}));
+        assert_eq!(input.query, "big files");
+        assert_eq!(input.params.get("path"), Some(&json!("/tmp")));
+        assert_eq!(skill_input(&Value::Null).query, "");
+    }
+}
@@ -0,0 +1,770 @@
+//! Vector indexes for semantic search over graph nodes and document
+//! passages.
+//!
+//! [`VectorIndex`] is the interface `GraphStore` and `ContextManager` search
+//! through: keyed vectors with insert, delete and cosine top-k search.
+//! Vectors are normalised on insert, so similarity is a dot product.
+//!
+//! - [`BruteForceIndex`] -- exact, in memory. Fine for a few hundred
+//!   vectors and for tests.
+//! - [`HnswIndex`] -- approximate nearest-neighbour search over a
+//!   hierarchical navigable small-world graph, with incremental insert and
+//!   delete. Given a directory it persists there as two files:
+//!   `vectors.bin`, a flat array of native-endian `f32` that is
+//!   memory-mapped on open (so loading costs nothing until a vector is
+//!   touched), and `graph.json`, the keys and neighbour lists. Changes stay
+//!   in memory until [`VectorIndex::flush`].
+//!
+//! Deleting marks a node as a tombstone: it still routes searches but is
+//! never returned. Flushing rebuilds the graph once tombstones make up a
+//! quarter of it.
+
+use anyhow::{bail, Context, Result};
+use memmap2::Mmap;
+use parking_lot::RwLock;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Keyed vectors searchable by cosine similarity.
+pub trait VectorIndex: std::fmt::Debug + Send + Sync {
+    /// Add the vector for `key`, replacing any previous one.
+    fn insert(&mut self, key: &str, vector: &[f32]) -> Result<()>;
+
+    /// Remove `key`. Returns whether it was present.
+    fn remove(&mut self, key: &str) -> bool;
+
+    fn contains(&self, key: &str) -> bool;
+
+    /// The (normalised) vector stored for `key`.
+    fn get(&self, key: &str) -> Option<Vec<f32>>;
+
+    /// Up to `limit` keys whose vectors are most similar to `query`, with
+    /// similarity at least `min_score`, best first.
+    fn search(&self, query: &[f32], limit: usize, min_score: f32) -> Vec<(String, f32)>;
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Persist changes made since the last flush. In-memory indexes have
+    /// nothing to do.
+    fn flush(&mut self) -> Result<()>;
+}
+
+/// An index shared between its owner and anything searching it.
+pub type SharedVectorIndex = Arc<RwLock<dyn VectorIndex>>;
+
+pub fn shared(index: impl VectorIndex + 'static) -> SharedVectorIndex {
+    Arc::new(RwLock::new(index))
+}
+
+fn normalize(vector: &[f32]) -> Vec<f32> {
+    let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm == 0.0 {
+        vector.to_vec()
+    } else {
+        vector.iter().map(|x| x / norm).collect()
+    }
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+/// Exact search over every vector.
+#[derive(Debug, Default)]
+pub struct BruteForceIndex {
+    vectors: HashMap<String, Vec<f32>>,
+}
+
+impl BruteForceIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl VectorIndex for BruteForceIndex {
+    fn insert(&mut self, key: &str, vector: &[f32]) -> Result<()> {
+        self.vectors.insert(key.to_string(), normalize(vector));
+        Ok(())
+    }
+
+    fn remove(&mut self, key: &str) -> bool {
+        self.vectors.remove(key).is_some()
+    }
+
+    fn contains(&self, key: &str) -> bool {
+        self.vectors.contains_key(key)
+    }
+
+    fn get(&self, key: &str) -> Option<Vec<f32>> {
+        self.vectors.get(key).cloned()
+    }
+
+    fn search(&self, query: &[f32], limit: usize, min_score: f32) -> Vec<(String, f32)> {
+        let query = normalize(query);
+        let mut results: Vec<(String, f32)> = self
+            .vectors
+            .iter()
+            .map(|(key, vector)| (key.clone(), dot(&query, vector)))
+            .filter(|(_, score)| *score >= min_score)
+            .collect();
+        results.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        results.truncate(limit);
+        results
+    }
+
+    fn len(&self) -> usize {
+        self.vectors.len()
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// HNSW build and search parameters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HnswConfig {
+    /// Neighbours kept per node on the upper layers (twice this on layer 0)
+    pub m: usize,
+    /// Candidates considered when linking a new node
+    pub ef_construction: usize,
+    /// Candidates considered per search (at least the requested limit)
+    pub ef_search: usize,
+}
+
+impl Default for HnswConfig {
+    fn default() -> Self {
+        Self {
+            m: 16,
+            ef_construction: 100,
+            ef_search: 64,
+        }
+    }
+}
+
+const VECTORS_FILE: &str = "vectors.bin";
+const GRAPH_FILE: &str = "graph.json";
+const MAGIC: &[u8; 8] = b"LHVECS01";
+/// Magic, then dimension and count as little-endian `u32`s. A multiple of
+/// 4, so the `f32`s after it stay aligned in the (page-aligned) mapping.
+const HEADER_LEN: usize = 16;
+const MAX_LEVEL: usize = 16;
+
+/// A node of the HNSW graph.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct HnswNode {
+    key: String,
+    /// Neighbour ids on each layer the node is on, layer 0 first
+    layers: Vec<Vec<u32>>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    deleted: bool,
+}
+
+/// `graph.json`.
+#[derive(Serialize, Deserialize)]
+struct GraphFile {
+    dim: usize,
+    m: usize,
+    ef_construction: usize,
+    entry: Option<u32>,
+    nodes: Vec<HnswNode>,
+}
+
+impl GraphFile {
+    /// Check the ids and layers the search code indexes by, so a damaged
+    /// file is refused instead of panicking later.
+    fn validate(&self) -> Result<()> {
+        if self.dim == 0 && !self.nodes.is_empty() {
+            bail!("nodes without a vector dimension");
+        }
+        if let Some(entry) = self.entry {
+            if entry as usize >= self.nodes.len() {
+                bail!("entry point {} is not a node", entry);
+            }
+        }
+        for (id, node) in self.nodes.iter().enumerate() {
+            if node.layers.is_empty() {
+                bail!("node {} is on no layer", id);
+            }
+            for (layer, neighbours) in node.layers.iter().enumerate() {
+                for &neighbour in neighbours {
+                    // Linking a new node indexes the neighbour's own list
+                    // for this layer.
+                    let on_layer = self
+                        .nodes
+                        .get(neighbour as usize)
+                        .is_some_and(|n| n.layers.len() > layer);
+                    if !on_layer {
+                        bail!(
+                            "node {} has neighbour {} on layer {}, which isn't on that layer",
+                            id,
+                            neighbour,
+                            layer
+                        );
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Vector storage: the vectors mapped from `vectors.bin`, then the ones
+/// inserted since it was written.
+#[derive(Debug, Default)]
+struct VectorStore {
+    mapped: Option<Mmap>,
+    mapped_count: usize,
+    appended: Vec<f32>,
+}
+
+impl VectorStore {
+    fn open(path: &Path, dim: usize) -> Result<Self> {
+        let file = fs::File::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+        // SAFETY: the index only ever replaces `vectors.bin` by renaming a
+        // new file over it, never by writing in place, so the mapped bytes
+        // don't change under us.
+        let mapped =
+            unsafe { Mmap::map(&file) }.with_context(|| format!("Failed to map {:?}", path))?;
+        if mapped.len() < HEADER_LEN || &mapped[..8] != MAGIC {
+            bail!("{:?} is not a vector file", path);
+        }
+        let file_dim = u32::from_le_bytes(mapped[8..12].try_into().unwrap()) as usize;
+        let count = u32::from_le_bytes(mapped[12..16].try_into().unwrap()) as usize;
+        if file_dim != dim || mapped.len() != HEADER_LEN + count * dim * 4 {
+            bail!("{:?} doesn't match the index graph", path);
+        }
+        Ok(Self {
+            mapped: Some(mapped),
+            mapped_count: count,
+            appended: Vec::new(),
+        })
+    }
+
+    fn len(&self, dim: usize) -> usize {
+        self.mapped_count + self.appended.len() / dim.max(1)
+    }
+
+    fn get(&self, id: u32, dim: usize) -> &[f32] {
+        let id = id as usize;
+        match &self.mapped {
+            Some(mapped) if id < self.mapped_count => {
+                let floats: &[f32] = bytemuck::cast_slice(&mapped[HEADER_LEN..]);
+                &floats[id * dim..(id + 1) * dim]
+            }
+            _ => {
+                let i = id - self.mapped_count;
+                &self.appended[i * dim..(i + 1) * dim]
+            }
+        }
+    }
+
+    fn push(&mut self, vector: &[f32]) {
+        self.appended.extend_from_slice(vector);
+    }
+}
+
+/// A similarity and the node it belongs to, ordered by similarity.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Scored {
+    score: f32,
+    id: u32,
+}
+
+impl Eq for Scored {}
+
+impl Ord for Scored {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.score
+            .total_cmp(&other.score)
+            .then_with(|| other.id.cmp(&self.id))
+    }
+}
+
+impl PartialOrd for Scored {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Approximate nearest-neighbour index (HNSW), optionally persisted to a
+/// directory.
+#[derive(Debug)]
+pub struct HnswIndex {
+    dir: Option<PathBuf>,
+    config: HnswConfig,
+    /// Set by the first insert; every vector must match it.
+    dim: usize,
+    nodes: Vec<HnswNode>,
+    /// Live (not deleted) keys.
+    keys: HashMap<String, u32>,
+    entry: Option<u32>,
+    vectors: VectorStore,
+    rng: StdRng,
+    dirty: bool,
+}
+
+impl HnswIndex {
+    /// An empty index that lives in memory only.
+    pub fn new(config: HnswConfig) -> Self {
+        Self {
+            dir: None,
+            config,
+            dim: 0,
+            nodes: Vec::new(),
+            keys: HashMap::new(),
+            entry: None,
+            vectors: VectorStore::default(),
+            rng: StdRng::seed_from_u64(0),
+            dirty: false,
+        }
+    }
+
+    /// An empty index persisted to `dir`, replacing whatever is there on
+    /// the first flush. For starting over when `open` fails.
+    pub fn create(dir: impl Into<PathBuf>, config: HnswConfig) -> Self {
+        let mut index = Self::new(config);
+        index.dir = Some(dir.into());
+        index
+    }
+
+    /// Open the index stored in `dir`, or start an empty one there.
+    pub fn open(dir: impl Into<PathBuf>, config: HnswConfig) -> Result<Self> {
+        let dir = dir.into();
+        let mut index = Self::create(dir.clone(), config);
+
+        let graph_path = dir.join(GRAPH_FILE);
+        if !graph_path.exists() {
+            return Ok(index);
+        }
+        let graph: GraphFile = serde_json::from_slice(
+            &fs::read(&graph_path).with_context(|| format!("Failed to read {:?}", graph_path))?,
+        )
+        .with_context(|| format!("Invalid index graph {:?}", graph_path))?;
+        graph
+            .validate()
+            .with_context(|| format!("Invalid index graph {:?}", graph_path))?;
+        let vectors = VectorStore::open(&dir.join(VECTORS_FILE), graph.dim)?;
+        if vectors.len(graph.dim) != graph.nodes.len() {
+            bail!("Vector index in {:?} is incomplete", dir);
+        }
+
+        index.config.m = graph.m;
+        index.config.ef_construction = graph.ef_construction;
+        index.dim = graph.dim;
+        index.entry = graph.entry;
+        index.keys = graph
+            .nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, n)| !n.deleted)
+            .map(|(id, n)| (n.key.clone(), id as u32))
+            .collect();
+        index.rng = StdRng::seed_from_u64(graph.nodes.len() as u64);
+        index.nodes = graph.nodes;
+        index.vectors = vectors;
+        Ok(index)
+    }
+
+    fn vector(&self, id: u32) -> &[f32] {
+        self.vectors.get(id, self.dim)
+    }
+
+    fn random_level(&mut self) -> usize {
+        let ml = 1.0 / (self.config.m.max(2) as f64).ln();
+        let uniform: f64 = 1.0 - self.rng.gen::<f64>();
+        ((-uniform.ln() * ml).floor() as usize).min(MAX_LEVEL)
+    }
+
+    fn top_layer(&self) -> usize {
+        self.entry
+            .map(|e| self.nodes[e as usize].layers.len() - 1)
+            .unwrap_or(0)
+    }
+
+    /// Greedy best-first search of one layer from `entries`, keeping the
+    /// `ef` best nodes seen. Returns them best first.
+    fn search_layer(&self, query: &[f32], entries: &[u32], ef: usize, layer: usize) -> Vec<Scored> {
+        let mut visited: HashSet<u32> = entries.iter().copied().collect();
+        let mut candidates = BinaryHeap::new();
+        let mut best = BinaryHeap::new();
+        for &id in entries {
+            let scored = Scored {
+                score: dot(query, self.vector(id)),
+                id,
+            };
+            candidates.push(scored);
+            best.push(Reverse(scored));
+        }
+
+        while let Some(candidate) = candidates.pop() {
+            let worst = best.peek().map(|Reverse(s): &Reverse<Scored>| s.score);
+            if best.len() >= ef && worst.is_some_and(|w| candidate.score < w) {
+                break;
+            }
+            let Some(neighbours) = self.nodes[candidate.id as usize].layers.get(layer) else {
+                continue;
+            };
+            for &id in neighbours {
+                if !visited.insert(id) {
+                    continue;
+                }
+                let scored = Scored {
+                    score: dot(query, self.vector(id)),
+                    id,
+                };
+                let worst = best.peek().map(|Reverse(s)| s.score);
+                if best.len() < ef || worst.is_some_and(|w| scored.score > w) {
+                    candidates.push(scored);
+                    best.push(Reverse(scored));
+                    if best.len() > ef {
+                        best.pop();
+                    }
+                }
+            }
+        }
+
+        let mut found: Vec<Scored> = best.into_iter().map(|Reverse(s)| s).collect();
+        found.sort_by(|a, b| b.cmp(a));
+        found
+    }
+
+    /// Descend from the entry point to `layer` following the single best
+    /// neighbour on each layer above it.
+    fn descend(&self, query: &[f32], layer: usize) -> Vec<u32> {
+        let Some(entry) = self.entry else {
+            return Vec::new();
+        };
+        let mut entries = vec![entry];
+        for upper in (layer + 1..=self.top_layer()).rev() {
+            entries = vec![self.search_layer(query, &entries, 1, upper)[0].id];
+        }
+        entries
+    }
+
+    /// Add `id` to `node`'s neighbours on `layer`, dropping its least
+    /// similar neighbour if that takes it over `max`.
+    fn link(&mut self, node: u32, id: u32, layer: usize, max: usize) {
+        let mut neighbours = std::mem::take(&mut self.nodes[node as usize].layers[layer]);
+        neighbours.push(id);
+        if neighbours.len() > max {
+            let base = self.vector(node);
+            let mut scored: Vec<Scored> = neighbours
+                .iter()
+                .map(|&n| Scored {
+                    score: dot(base, self.vector(n)),
+                    id: n,
+                })
+                .collect();
+            scored.sort_by(|a, b| b.cmp(a));
+            neighbours = scored.into_iter().take(max).map(|s| s.id).collect();
+        }
+        self.nodes[node as usize].layers[layer] = neighbours;
+    }
+
+    fn tombstones(&self) -> usize {
+        self.nodes.len() - self.keys.len()
+    }
+
+    /// Rebuild the graph from the live vectors, dropping tombstones.
+    fn rebuild(&mut self) {
+        let live: Vec<(String, Vec<f32>)> = self
+            .nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, n)| !n.deleted)
+            .map(|(id, n)| (n.key.clone(), self.vector(id as u32).to_vec()))
+            .collect();
+        let mut fresh = Self::new(self.config);
+        fresh.dir = self.dir.take();
+        for (key, vector) in live {
+            // Same dimension throughout, so this can't fail.
+            let _ = fresh.insert(&key, &vector);
+        }
+        *self = fresh;
+    }
+}
+
+impl VectorIndex for HnswIndex {
+    fn insert(&mut self, key: &str, vector: &[f32]) -> Result<()> {
+        if vector.is_empty() {
+            bail!("Cannot index an empty vector");
+        }
+        if self.dim == 0 {
+            self.dim = vector.len();
+        } else if vector.len() != self.dim {
+            bail!(
+                "Expected a {}-dimensional vector, got {}",
+                self.dim,
+                vector.len()
+            );
+        }
+        self.remove(key);
+
+        let vector = normalize(vector);
+        let id = self.nodes.len() as u32;
+        let level = self.random_level();
+        self.vectors.push(&vector);
+        self.nodes.push(HnswNode {
+            key: key.to_string(),
+            layers: vec![Vec::new(); level + 1],
+            deleted: false,
+        });
+        self.keys.insert(key.to_string(), id);
+        self.dirty = true;
+
+        let Some(entry) = self.entry else {
+            self.entry = Some(id);
+            return Ok(());
+        };
+        let top = self.top_layer();
+        let mut entries = if level < top {
+            self.descend(&vector, level)
+        } else {
+            vec![entry]
+        };
+        for layer in (0..=level.min(top)).rev() {
+            let found = self.search_layer(&vector, &entries, self.config.ef_construction, layer);
+            let max = if layer == 0 {
+                self.config.m * 2
+            } else {
+                self.config.m
+            };
+            let selected: Vec<u32> = found.iter().take(self.config.m).map(|s| s.id).collect();
+            for &neighbour in &selected {
+                self.link(neighbour, id, layer, max);
+            }
+            self.nodes[id as usize].layers[layer] = selected;
+            entries = found.iter().map(|s| s.id).collect();
+        }
+        if level > top {
+            self.entry = Some(id);
+        }
+        Ok(())
+    }
+
+    fn remove(&mut self, key: &str) -> bool {
+        let Some(id) = self.keys.remove(key) else {
+            return false;
+        };
+        self.nodes[id as usize].deleted = true;
+        self.dirty = true;
+        if self.keys.is_empty() {
+            // Nothing left to route to; start over.
+            self.nodes.clear();
+            self.entry = None;
+            self.vectors = VectorStore::default();
+        }
+        true
+    }
+
+    fn contains(&self, key: &str) -> bool {
+        self.keys.contains_key(key)
+    }
+
+    fn get(&self, key: &str) -> Option<Vec<f32>> {
+        self.keys.get(key).map(|&id| self.vector(id).to_vec())
+    }
+
+    fn search(&self, query: &[f32], limit: usize, min_score: f32) -> Vec<(String, f32)> {
+        if self.entry.is_none() || query.len() != self.dim || limit == 0 {
+            return Vec::new();
+        }
+        let query = normalize(query);
+        let entries = self.descend(&query, 0);
+        let ef = self.config.ef_search.max(limit * 2);
+        self.search_layer(&query, &entries, ef, 0)
+            .into_iter()
+            .filter(|s| s.score >= min_score)
+            .filter_map(|s| {
+                let node = &self.nodes[s.id as usize];
+                (!node.deleted).then(|| (node.key.clone(), s.score))
+            })
+            .take(limit)
+            .collect()
+    }
+
+    fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        let Some(dir) = self.dir.clone() else {
+            return Ok(());
+        };
+        if !self.dirty {
+            return Ok(());
+        }
+        if self.tombstones() * 4 > self.nodes.len() {
+            self.rebuild();
+        }
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create index directory {:?}", dir))?;
+
+        let count = self.nodes.len();
+        let floats: Vec<f32> = (0..count)
+            .flat_map(|id| self.vector(id as u32).iter().copied())
+            .collect();
+        let mut bytes = Vec::with_capacity(HEADER_LEN + floats.len() * 4);
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&(self.dim as u32).to_le_bytes());
+        bytes.extend_from_slice(&(count as u32).to_le_bytes());
+        bytes.extend_from_slice(bytemuck::cast_slice(&floats));
+        let graph = serde_json::to_vec(&GraphFile {
+            dim: self.dim,
+            m: self.config.m,
+            ef_construction: self.config.ef_construction,
+            entry: self.entry,
+            nodes: self.nodes.clone(),
+        })?;
+
+        // Unmap before replacing the file (Windows refuses otherwise).
+        self.vectors = VectorStore::default();
+        let vectors_path = dir.join(VECTORS_FILE);
+        let written = write_replacing(&vectors_path, &bytes)
+            .and_then(|()| write_replacing(&dir.join(GRAPH_FILE), &graph))
+            .and_then(|()| VectorStore::open(&vectors_path, self.dim));
+        match written {
+            Ok(vectors) => {
+                self.vectors = vectors;
+                self.dirty = false;
+                Ok(())
+            }
+            Err(e) => {
+                // Keep serving from memory; the next flush tries again.
+                self.vectors.appended = floats;
+                Err(e)
+            }
+        }
+    }
+}
+
+/// Write `path` via a temporary file and a rename, so readers never see
+/// a partial file.
+fn write_replacing(path: &Path, bytes: &[u8]) -> Result<()> {
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, bytes).with_context(|| format!("Failed to write {:?}", tmp))?;
+    fs::rename(&tmp, path).with_context(|| format!("Failed to replace {:?}", path))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn random_vectors(count: usize, dim: usize, seed: u64) -> Vec<Vec<f32>> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        (0..count)
+            .map(|_| (0..dim).map(|_| rng.gen_range(-1.0..1.0)).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_hnsw_recall_matches_brute_force() {
+        let mut exact = BruteForceIndex::new();
+        let mut approx = HnswIndex::new(HnswConfig::default());
+        for (i, vector) in random_vectors(1000, 32, 1).iter().enumerate() {
+            exact.insert(&format!("v{}", i), vector).unwrap();
+            approx.insert(&format!("v{}", i), vector).unwrap();
+        }
+
+        let mut hits = 0;
+        let queries = random_vectors(50, 32, 2);
+        for query in &queries {
+            let truth: HashSet<String> = exact
+                .search(query, 10, -1.0)
+                .into_iter()
+                .map(|(k, _)| k)
+                .collect();
+            hits += approx
+                .search(query, 10, -1.0)
+                .iter()
+                .filter(|(k, _)| truth.contains(k))
+                .count();
+        }
+        let recall = hits as f32 / (queries.len() * 10) as f32;
+        assert!(recall >= 0.9, "recall@10 was {}", recall);
+    }
+
+    #[test]
+    fn test_hnsw_persists_inserts_and_deletes() {
+        let dir = tempfile::tempdir().unwrap();
+        let vectors = random_vectors(200, 8, 3);
+        {
+            let mut index = HnswIndex::open(dir.path(), HnswConfig::default()).unwrap();
+            for (i, vector) in vectors.iter().enumerate() {
+                index.insert(&format!("v{}", i), vector).unwrap();
+            }
+            assert!(index.remove("v0"));
+            assert!(!index.remove("v0"));
+            assert!(index.insert("bad", &[1.0, 2.0]).is_err());
+            index.flush().unwrap();
+        }
+
+        let mut index = HnswIndex::open(dir.path(), HnswConfig::default()).unwrap();
+        assert_eq!(index.len(), 199);
+        assert!(!index.contains("v0"));
+        let results = index.search(&vectors[5], 3, 0.0);
+        assert_eq!(results[0].0, "v5");
+        assert!((results[0].1 - 1.0).abs() < 1e-5);
+        assert!(index.search(&vectors[0], 10, 0.99).is_empty());
+
+        // Changes after loading land next to the mapped vectors.
+        index.insert("v5", &vectors[0]).unwrap();
+        assert_eq!(index.search(&vectors[0], 1, 0.0)[0].0, "v5");
+        index.flush().unwrap();
+        let index = HnswIndex::open(dir.path(), HnswConfig::default()).unwrap();
+        assert_eq!(index.len(), 199);
+        assert_eq!(index.get("v5"), Some(normalize(&vectors[0])));
+    }
+
+    #[test]
+    fn test_hnsw_open_refuses_damaged_graph() {
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let mut index = HnswIndex::open(dir.path(), HnswConfig::default()).unwrap();
+            for (i, vector) in random_vectors(20, 4, 5).iter().enumerate() {
+                index.insert(&format!("v{}", i), vector).unwrap();
+            }
+            index.flush().unwrap();
+        }
+        let graph_path = dir.path().join(GRAPH_FILE);
+        let good: serde_json::Value =
+            serde_json::from_slice(&fs::read(&graph_path).unwrap()).unwrap();
+
+        let damage: [fn(&mut serde_json::Value); 4] = [
+            |g| g["entry"] = 20.into(),
+            |g| g["nodes"][3]["layers"] = serde_json::json!([]),
+            |g| g["nodes"][3]["layers"][0] = serde_json::json!([1, 99]),
+            |g| g["nodes"][3]["layers"] = serde_json::json!([[1], [2], [4], [5], [6]]),
+        ];
+        for (i, damage) in damage.iter().enumerate() {
+            let mut graph = good.clone();
+            damage(&mut graph);
+            fs::write(&graph_path, serde_json::to_vec(&graph).unwrap()).unwrap();
+            let err = HnswIndex::open(dir.path(), HnswConfig::default()).unwrap_err();
+            assert!(
+                format!("{:#}", err).contains("Invalid index graph"),
+                "{i}: {err:#}"
+            );
+        }
+
+        fs::write(&graph_path, serde_json::to_vec(&good).unwrap()).unwrap();
+        assert_eq!(
+            HnswIndex::open(dir.path(), HnswConfig::default())
+                .unwrap()
+                .len(),
+            20
+        );
+    }
+}
@@ -41,6 +41,30 @@ fn try_read_clipboard_text() -> Option<String> {
     }
 }
 
+/// Show how the provider has been responding lately (nothing until it has
+/// been used this session).
+fn render_provider_health(ui: &mut egui::Ui, provider: &str) {
+    use providers::health::{CircuitState, HealthTracker};
+
+    let Some(health) = HealthTracker::global().get(provider) else {
+        return;
+    };
+    let color = match health.state {
+        CircuitState::Open => egui::Color32::from_rgb(220, 80, 80),
+        CircuitState::HalfOpen => egui::Color32::from_rgb(220, 160, 80),
+        CircuitState::Closed if health.error_rate > 0.0 => egui::Color32::from_rgb(220, 160, 80),
+        CircuitState::Closed => egui::Color32::from_rgb(0, 180, 0),
+    };
+    let label = ui.label(
+        egui::RichText::new(format!("  ● {}", health.summary()))
+            .color(color)
+            .size(12.0),
+    );
+    if let Some(err) = &health.last_error {
+        label.on_hover_text(format!("Last error: {}", err));
+    }
+}
+
 /// Rebuild the provider preference list with the given provider first.
 /// Always keeps "local" (Ollama) as a fallback so the app can degrade
 /// gracefully when cloud keys are missing or quota is exhausted.
@@ -217,6 +241,7 @@ fn format_error_message(error: &str) -> String {
     if error_lower.contains("rate limit")
         || error_lower.contains("429")
         || error_lower.contains("too many requests")
+        || error_lower.contains("temporarily unavailable")
     {
         return format!(
             "The AI service is temporarily busy. Please wait a moment and try again.\n\n\
@@ -354,6 +379,18 @@ impl eframe::App for LittleHelperApp {
         s.poll_command_result();
         s.poll_web_preview();
 
+        // Files dropped onto the window are attached like "Attach file".
+        let dropped: Vec<PathBuf> = ctx.input(|i| {
+            i.raw
+                .dropped_files
+                .iter()
+                .filter_map(|f| f.path.clone())
+                .collect()
+        });
+        for path in dropped {
+            s.attach_file_for_analysis(path);
+        }
+
         // Request repaint if we're waiting for AI or web preview
         if s.web_preview_rx.is_some() {
             ctx.request_repaint();
@@ -591,8 +628,12 @@ impl eframe::App for LittleHelperApp {
         // Poll for background Ollama setup completion
         s.poll_ollama_setup();
 
+        // Poll for the model catalog refresh (starts after Ollama setup)
+        s.poll_model_catalog();
+
         // Poll for background OAuth flow completion
         s.poll_oauth_result();
+        s.poll_auth_events();
 
         // Show onboarding for first-run users
         if s.current_screen == AppScreen::Onboarding {
@@ -1585,6 +1626,9 @@ impl eframe::App for LittleHelperApp {
                         // Save current thread before clearing
                         let mode = s.current_mode;
                         s.sync_thread_history(mode);
+                        if let Some(id) = s.current_thread_id.clone() {
+                            s.extract_thread_entities(&id);
+                        }
                         s.current_thread_id = None;
 
                         let user_name = if s.settings.user_profile.name.is_empty() {
@@ -1787,6 +1831,20 @@ impl eframe::App for LittleHelperApp {
                     .get(&current_mode)
                     .cloned()
                     .unwrap_or_default();
+                let thinking_partial = s
+                    .thinking_partial
+                    .get(&current_mode)
+                    .cloned()
+                    .unwrap_or_default();
+                let command_output = s
+                    .command_output
+                    .get(&current_mode)
+                    .cloned()
+                    .unwrap_or_default();
+                let command_progress = s.command_progress.get(&current_mode).copied();
+                let busy_shell = s.busy_shell(current_mode);
+                let mut stdin_input = std::mem::take(&mut s.command_stdin_input);
+                let mut kill_shell = false;
 
                 // Lightweight agent diagnostics: helps debug slowness/quota/auth issues.
                 if s.last_llm_provider.is_some()
@@ -1842,6 +1900,63 @@ impl eframe::App for LittleHelperApp {
                                 .rounding(egui::Rounding::same(12.0))
                                 .inner_margin(egui::Margin::same(12.0))
                                 .show(ui, |ui| {
+                                    // Streamed answer so far, rendered like a normal reply
+                                    if !thinking_partial.trim().is_empty() {
+                                        ui.set_max_width(600.0);
+                                        simple_md::render_markdown(
+                                            ui,
+                                            &thinking_partial,
+                                            if dark {
+                                                egui::Color32::from_rgb(220, 220, 230)
+                                            } else {
+                                                egui::Color32::from_rgb(40, 40, 50)
+                                            },
+                                        );
+                                        ui.add_space(6.0);
+                                    }
+                                    // Live output of the running command
+                                    if !command_output.is_empty() {
+                                        ui.set_max_width(600.0);
+                                        egui::ScrollArea::vertical()
+                                            .id_source("command_output")
+                                            .max_height(160.0)
+                                            .stick_to_bottom(true)
+                                            .show(ui, |ui| {
+                                                ui.label(
+                                                    egui::RichText::new(&command_output)
+                                                        .monospace()
+                                                        .size(11.0),
+                                                );
+                                            });
+                                        ui.add_space(4.0);
+                                    }
+                                    if let Some(shell) = &busy_shell {
+                                        // Answer prompts, or give up on a hung shell
+                                        ui.horizontal(|ui| {
+                                            let input = ui.add(
+                                                egui::TextEdit::singleline(&mut stdin_input)
+                                                    .hint_text("Type a reply to the command…")
+                                                    .desired_width(320.0),
+                                            );
+                                            let enter = input.lost_focus()
+                                                && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                                            if enter || ui.button("Send").clicked() {
+                                                let _ = shell
+                                                    .send_input(&format!("{}\n", stdin_input));
+                                                stdin_input.clear();
+                                            }
+                                            if ui
+                                                .button("Kill")
+                                                .on_hover_text(
+                                                    "End the shell; the next command starts a new one",
+                                                )
+                                                .clicked()
+                                            {
+                                                kill_shell = true;
+                                            }
+                                        });
+                                        ui.add_space(4.0);
+                                    }
                                     ui.horizontal(|ui| {
                                         // Animated spinner dots
                                         let time = ui.input(|i| i.time);
@@ -1852,11 +1967,14 @@ impl eframe::App for LittleHelperApp {
                                             _ => "...",
                                         };
 
-                                        let status = if thinking_status.is_empty() {
+                                        let mut status = if thinking_status.is_empty() {
                                             "Thinking".to_string()
                                         } else {
                                             thinking_status.clone()
                                         };
+                                        if let Some(percent) = command_progress {
+                                            status.push_str(&format!(" ({}%)", percent));
+                                        }
 
                                         ui.label(
                                             egui::RichText::new(format!("{}{}", status, dots))
@@ -1874,6 +1992,11 @@ impl eframe::App for LittleHelperApp {
                         }
                     });
 
+                s.command_stdin_input = stdin_input;
+                if kill_shell {
+                    s.kill_shell(current_mode);
+                }
+
                 // Handle clicked path after iteration
                 if let Some(path) = clicked_path {
                     s.open_file(&path, ctx);
@@ -1898,13 +2021,39 @@ impl eframe::App for LittleHelperApp {
                         );
                         ui.add_space(6.0);
                         let pending = s.pending_commands.clone();
+                        s.command_plans.retain(|c, _| pending.contains(c));
                         for cmd in pending {
                             let friendly = friendly_command_description(&cmd);
                             ui.label(&friendly);
+                            let plan = s.command_plan(&cmd);
+                            let changes_files = !plan.changes.is_empty();
+                            ui.label(
+                                egui::RichText::new(format!("This {}.", plan.summary()))
+                                    .small()
+                                    .weak(),
+                            );
                             ui.horizontal(|ui| {
                                 if ui.button("Allow").clicked() {
                                     s.approve_command(cmd.clone());
                                 }
+                                if changes_files
+                                    && ui
+                                        .button("Preview changes")
+                                        .on_hover_text("See every file this would change")
+                                        .clicked()
+                                {
+                                    s.preview_command_plan(&cmd);
+                                }
+                                if s.command_policy.can_always_allow(&cmd)
+                                    && ui
+                                        .button("Always allow")
+                                        .on_hover_text(
+                                            "Run this exact command without asking from now on",
+                                        )
+                                        .clicked()
+                                {
+                                    s.always_allow_command(cmd.clone());
+                                }
                                 if ui.button("Skip").clicked() {
                                     s.pending_commands.retain(|c| c != &cmd);
                                 }
@@ -1916,6 +2065,33 @@ impl eframe::App for LittleHelperApp {
                     ui.add_space(8.0);
                 }
 
+                if let Some((cmd, snapshots)) = &s.last_command_snapshots {
+                    let count = snapshots.len();
+                    let hover = format!("Put back the files as they were before `{}`", cmd);
+                    ui.horizontal(|ui| {
+                        ui.label(
+                            egui::RichText::new("I saved the files that command changed.")
+                                .small()
+                                .weak(),
+                        );
+                        if ui
+                            .small_button(format!(
+                                "Restore {} {}",
+                                count,
+                                if count == 1 { "file" } else { "files" }
+                            ))
+                            .on_hover_text(hover)
+                            .clicked()
+                        {
+                            s.restore_last_command();
+                        }
+                        if ui.small_button("Dismiss").clicked() {
+                            s.last_command_snapshots = None;
+                        }
+                    });
+                    ui.add_space(8.0);
+                }
+
                 // Research controls (Quick vs Deep + file picker)
                 if s.current_mode == ChatMode::Research {
                     ui.horizontal(|ui| {
@@ -2164,6 +2340,19 @@ impl eframe::App for LittleHelperApp {
                         {
                             needs_save = true;
                         }
+                        if cfg!(target_os = "linux")
+                            && ui
+                                .checkbox(
+                                    &mut s.settings.hardened_execution,
+                                    "Run commands in a locked-down sandbox",
+                                )
+                                .on_hover_text(
+                                    "Commands can only change your allowed folders, only go online when they need to, and can't use up your computer",
+                                )
+                                .changed()
+                        {
+                            needs_save = true;
+                        }
 
                         // Brave Search API key
                         if s.settings.enable_internet_research {
@@ -2213,12 +2402,22 @@ impl eframe::App for LittleHelperApp {
                         );
                         ui.add_space(4.0);
 
-                        let providers = [
+                        let mut providers: Vec<(String, String)> = [
                             ("local", "Local (Ollama) — free, private"),
                             ("openai", "OpenAI (GPT-4)"),
                             ("anthropic", "Anthropic (Claude)"),
                             ("gemini", "Google (Gemini)"),
-                        ];
+                        ]
+                        .iter()
+                        .map(|(id, name)| (id.to_string(), name.to_string()))
+                        .collect();
+                        // Named OpenAI-compatible endpoints from settings.json
+                        for profile in &s.settings.model.profiles {
+                            providers.push((
+                                profile.name.clone(),
+                                format!("{} (custom endpoint)", profile.name),
+                            ));
+                        }
 
                         let current_provider = s
                             .settings
@@ -2230,16 +2429,16 @@ impl eframe::App for LittleHelperApp {
 
                         let provider_label = providers
                             .iter()
-                            .find(|(id, _)| *id == current_provider.as_str())
-                            .map(|(_, name)| *name)
+                            .find(|(id, _)| *id == current_provider)
+                            .map(|(_, name)| name.as_str())
                             .unwrap_or("Local (Ollama) — free, private");
 
                         egui::ComboBox::from_label("")
                             .selected_text(provider_label)
                             .width(280.0)
                             .show_ui(ui, |ui| {
-                                for (id, name) in providers {
-                                    if ui.selectable_label(current_provider == id, name).clicked() {
+                                for (id, name) in &providers {
+                                    if ui.selectable_label(current_provider == *id, name).clicked() {
                                         set_primary_provider_preference(
                                             &mut s.settings.model.provider_preference,
                                             id,
@@ -2252,7 +2451,9 @@ impl eframe::App for LittleHelperApp {
                             });
 
                         // Contextual explanation based on selected provider
-                        let is_cloud = current_provider != "local";
+                        let profile = s.settings.model.profile(&current_provider).cloned();
+                        let is_cloud = current_provider != "local"
+                            && !profile.as_ref().is_some_and(|p| p.local);
                         let subtle = if dark {
                             egui::Color32::from_rgb(160, 160, 170)
                         } else {
@@ -2277,6 +2478,7 @@ impl eframe::App for LittleHelperApp {
                                     .color(egui::Color32::from_rgb(0, 180, 0))
                                     .size(12.0),
                             );
+                            render_provider_health(ui, &current_provider);
                         } else {
                             // Cloud provider — explain what that means
                             ui.label(
@@ -2293,7 +2495,10 @@ impl eframe::App for LittleHelperApp {
                                 "openai" => s.settings.model.openai_auth.has_auth(),
                                 "anthropic" => s.settings.model.anthropic_auth.has_auth(),
                                 "gemini" => s.settings.model.gemini_auth.has_auth(),
-                                _ => false,
+                                _ => profile.as_ref().is_some_and(|p| {
+                                    p.auth.has_auth()
+                                        || p.auth_header == shared::settings::AuthHeaderStyle::None
+                                }),
                             };
                             let has_oauth = match current_provider.as_str() {
                                 "gemini" => s.settings.model.gemini_auth.oauth.is_some(),
@@ -2319,6 +2524,7 @@ impl eframe::App for LittleHelperApp {
                                         .size(12.0),
                                 );
                             }
+                            render_provider_health(ui, &current_provider);
 
                             // ── Keys (visible, right after provider picker) ──
                             ui.add_space(8.0);
@@ -2326,8 +2532,9 @@ impl eframe::App for LittleHelperApp {
                                 "openai" => "OpenAI",
                                 "anthropic" => "Anthropic",
                                 "gemini" => "Gemini",
-                                _ => "",
-                            };
+                                other => other,
+                            }
+                            .to_string();
 
                             ui.horizontal(|ui| {
                                 let input_field = match current_provider.as_str() {
@@ -2364,7 +2571,17 @@ impl eframe::App for LittleHelperApp {
                                         "gemini" => {
                                             s.settings.model.gemini_auth.api_key = Some(key_value);
                                         }
-                                        _ => {}
+                                        other => {
+                                            if let Some(p) = s
+                                                .settings
+                                                .model
+                                                .profiles
+                                                .iter_mut()
+                                                .find(|p| p.name == other)
+                                            {
+                                                p.auth.api_key = Some(key_value);
+                                            }
+                                        }
                                     }
                                     save_settings(&s.settings);
                                     s.settings_status = Some(format!("{} key saved", key_name));
@@ -2411,8 +2628,8 @@ impl eframe::App for LittleHelperApp {
                                                     let flow = providers::oauth_helper::OAuthFlow::new(
                                                         client_id,
                                                         client_secret,
-                                                        "https://accounts.google.com/o/oauth2/v2/auth".to_string(),
-                                                        "https://oauth2.googleapis.com/token".to_string(),
+                                                        providers::oauth_helper::GOOGLE_AUTH_URL.to_string(),
+                                                        providers::oauth_helper::GOOGLE_TOKEN_URL.to_string(),
                                                         vec![
                                                             "https://www.googleapis.com/auth/generative-language.retriever".to_string(),
                                                         ],
@@ -2422,19 +2639,17 @@ impl eframe::App for LittleHelperApp {
                                                 Err(e) => Err(anyhow::anyhow!("Failed to start runtime: {}", e)),
                                             };
                                             match result {
-                                                Ok((access_token, refresh_token)) => {
+                                                Ok(credentials) => {
                                                     let _ = tx.send(crate::types::OAuthResult {
                                                         provider: "gemini".to_string(),
-                                                        access_token,
-                                                        refresh_token,
+                                                        credentials: Some(credentials),
                                                         error: None,
                                                     });
                                                 }
                                                 Err(e) => {
                                                     let _ = tx.send(crate::types::OAuthResult {
                                                         provider: "gemini".to_string(),
-                                                        access_token: String::new(),
-                                                        refresh_token: None,
+                                                        credentials: None,
                                                         error: Some(e.to_string()),
                                                     });
                                                 }
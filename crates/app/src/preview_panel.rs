@@ -13,7 +13,9 @@
 
 use agent_host::get_mode_introduction;
 use anyhow::Result;
-use shared::preview_types::{AsciiState, FileType, ImageSource, PreviewContent, SearchResultItem};
+use shared::preview_types::{
+    AsciiState, FileType, ImageSource, PlannedChangeKind, PreviewContent, SearchResultItem,
+};
 use std::path::{Path, PathBuf};
 use viewers::{
     csv_viewer::CsvViewer, html_viewer::HtmlViewer, image_viewer::ImageViewer,
@@ -468,6 +470,7 @@ impl PreviewPanel {
                     PreviewContent::SkillsList { mode, .. } => format!("{} Skills", mode),
                     PreviewContent::Tip { title, .. } => title.clone(),
                     PreviewContent::CleanupPlan { title, .. } => title.clone(),
+                    PreviewContent::CommandPlan { command, .. } => format!("Preview: {}", command),
                 };
                 ui.label(label);
             }
@@ -864,6 +867,103 @@ impl PreviewPanel {
                     }
                 });
             }
+            Some(PreviewContent::CommandPlan {
+                command,
+                folder,
+                changes,
+                unplanned,
+            }) => {
+                let subtle = if is_dark_mode {
+                    egui::Color32::from_rgb(170, 170, 190)
+                } else {
+                    egui::Color32::from_rgb(90, 90, 110)
+                };
+                let file_name = |path: &Path| {
+                    path.strip_prefix(folder.as_path())
+                        .unwrap_or(path)
+                        .display()
+                        .to_string()
+                };
+
+                ui.vertical(|ui| {
+                    ui.label(
+                        egui::RichText::new("What this command would change")
+                            .strong()
+                            .size(14.0)
+                            .color(accent_color),
+                    );
+                    ui.label(egui::RichText::new(command).monospace().small());
+                    ui.label(
+                        egui::RichText::new(format!("Folder: {}", folder.display()))
+                            .small()
+                            .color(subtle),
+                    );
+                    ui.add_space(8.0);
+
+                    if changes.is_empty() {
+                        ui.label(
+                            egui::RichText::new("I don't see any files this would change.")
+                                .color(text_color),
+                        );
+                    }
+                    for change in changes.iter().take(80) {
+                        let target = match &change.kind {
+                            PlannedChangeKind::Move { to } | PlannedChangeKind::Copy { to } => {
+                                format!(" → {}", file_name(to))
+                            }
+                            _ => String::new(),
+                        };
+                        ui.horizontal(|ui| {
+                            ui.label(egui::RichText::new(change.kind.verb()).small().color(
+                                match change.kind {
+                                    PlannedChangeKind::Delete => {
+                                        egui::Color32::from_rgb(200, 90, 90)
+                                    }
+                                    _ => subtle,
+                                },
+                            ));
+                            ui.label(
+                                egui::RichText::new(format!(
+                                    "{}{}",
+                                    file_name(&change.path),
+                                    target
+                                ))
+                                .small(),
+                            );
+                        });
+                    }
+                    if changes.len() > 80 {
+                        ui.label(
+                            egui::RichText::new(format!("…and {} more", changes.len() - 80))
+                                .small()
+                                .weak(),
+                        );
+                    }
+
+                    if !unplanned.is_empty() {
+                        ui.add_space(10.0);
+                        ui.label(
+                            egui::RichText::new(
+                                "I can't tell ahead of time what these parts will change:",
+                            )
+                            .small()
+                            .color(subtle),
+                        );
+                        for part in unplanned {
+                            ui.label(egui::RichText::new(part).monospace().small());
+                        }
+                    }
+
+                    ui.add_space(10.0);
+                    ui.label(
+                        egui::RichText::new(
+                            "Files that already exist are saved first, so you can undo the run.",
+                        )
+                        .small()
+                        .weak(),
+                    );
+                });
+            }
             Some(PreviewContent::Ascii { state }) => {
                 let time = ui.input(|i| i.time);
                 let ascii_art = crate::ascii_art::get_ascii_art_animated(state, time);
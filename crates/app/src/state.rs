@@ -1,30 +1,48 @@
 //! AI generation pipeline for the Little Helper app.
 //!
 //! This module runs on a **background thread** (spawned from `types.rs`) and
-//! communicates with the UI via two `mpsc` channels:
+//! communicates with the UI via three `mpsc` channels:
 //!
 //! - `tx: Sender<AiResult>` -- final result (response text, preview data, errors)
 //! - `status_tx: Sender<String>` -- live progress updates ("Searching...", "Running: ls")
+//! - `partial_tx: Sender<String>` -- the answer streamed so far, as a cleaned snapshot
+//! - `output_tx: Sender<CommandEvent>` -- output and progress of the running command
 //!
 //! The pipeline is a multi-turn agentic loop:
-//! 1. Send the conversation to the LLM via `ProviderRouter`
-//! 2. Parse the response for action tags (`<search>`, `<command>`, `<skill>`)
+//! 1. Stream the conversation to the LLM via `ProviderRouter::generate_stream`
+//! 2. Collect the requested actions: native tool calls (`run_command`, `search`,
+//!    one tool per skill), or action tags (`<search>`, `<command>`, `<skill>`)
+//!    from models without tool support
 //! 3. Execute safe actions automatically; queue dangerous ones for user approval
-//! 4. Feed results back into the conversation and loop (up to `max_iterations`)
+//! 4. Feed results back into the conversation (as tool results, or as a user
+//!    message for tags) and loop (up to `max_iterations`)
 //! 5. If iterations are exhausted, ask the LLM for a summary of what it found
 //!
-//! The entire pipeline is wrapped in `Abortable` so the user can cancel mid-flight.
+//! The whole turn shares one `CancellationToken`: the Stop button or the turn
+//! deadline aborts the pending LLM request or kills the running command, and
+//! the answer streamed so far is kept with a note that the turn was stopped.
 
 use crate::types::*;
 use crate::utils::*;
+use agent_host::command_policy::CommandPolicy;
 use agent_host::executor::SessionState;
-use shared::agent_api::ChatMessage as ApiChatMessage;
+use agent_host::process_sandbox::ProcessSandbox;
+use agent_host::security::SecurityContext;
+use agent_host::shell_session::{CommandEvent, ShellSession};
+use agent_host::skills::common::AuditLogger;
+use shared::agent_api::{ChatMessage as ApiChatMessage, ToolCall};
+use shared::secrets::SecretScanner;
 use std::path::PathBuf;
 use std::sync::mpsc::Sender;
 
 use agent_host::skills::SkillRegistry;
-use futures::future::{AbortRegistration, Abortable};
-use shared::skill::{Mode, SkillContext, SkillInput};
+use agent_host::token_tracker::TokenTracker;
+use providers::cancel::{is_cancelled, run_cancellable, with_deadline, CancellationToken};
+use providers::catalog::ModelCatalogs;
+use providers::oauth_refresh::TokenRefresher;
+use providers::stream::StreamEvent;
+use providers::usage::Usage;
+use shared::skill::{Mode, SkillContext};
 use std::sync::Arc;
 
 /// Run the multi-turn AI generation loop in a background thread (non-blocking).
@@ -36,7 +54,38 @@ use std::sync::Arc;
 /// `status_tx` sends live status strings back to the UI (e.g. "Searching the web...").
 /// The UI polls this channel each frame to update the thinking indicator.
 ///
-/// `abort_reg` allows the user to cancel the operation mid-flight via the Stop button.
+/// `partial_tx` sends the streamed answer text (with action tags stripped) so the
+/// thinking bubble fills in while the model is still writing. An empty string
+/// means "clear" -- sent at the start of each LLM call and when a provider fails
+/// mid-stream and the router falls back.
+///
+/// Commands run in the mode's persistent `shell`, started here if the slot
+/// is empty or its shell has exited, so working directory, environment and
+/// activated virtualenvs carry over between turns. Their output streams to
+/// `output_tx` while they run.
+///
+/// Commands are classified under `command_policy`, with its directory and
+/// mode rules applied for the session's directory and `current_mode`.
+///
+/// `secrets` redacts credentials from tool results before they join the
+/// conversation, and from every request the router sends.
+///
+/// Commands refused for safety and 2FA attempts are recorded in `audit` as
+/// security events under `conversation_id`. Commands that need 2FA run only
+/// when a grant in `security_context` covers them.
+///
+/// `model_catalogs` gives routing the real context window and capabilities
+/// of each listed model.
+///
+/// `token_refresher` renews OAuth sign-ins that are about to expire before
+/// each request.
+///
+/// Every call's reported token usage goes to `token_tracker` under
+/// `conversation_id`, and the total comes back in `AiResult::usage`.
+///
+/// Cancelling `cancel` (the Stop button) stops the turn mid-flight; so does
+/// running past `agent_host::TURN_DEADLINE`. Each LLM request also gets
+/// `agent_host::LLM_REQUEST_TIMEOUT` before the router falls back.
 #[allow(clippy::too_many_arguments)]
 pub fn run_ai_generation(
     messages: Vec<ApiChatMessage>,
@@ -46,12 +95,24 @@ pub fn run_ai_generation(
     research_depth: ResearchDepth,
     current_mode: Mode,
     allowed_dirs: Vec<String>,
+    process_sandbox: Option<ProcessSandbox>,
+    shell: Option<ShellSlot>,
+    command_policy: Arc<CommandPolicy>,
+    secrets: Arc<SecretScanner>,
+    audit: Arc<AuditLogger>,
+    security_context: Arc<SecurityContext>,
     skill_registry: Arc<SkillRegistry>,
+    model_catalogs: Arc<ModelCatalogs>,
+    token_refresher: Arc<TokenRefresher>,
+    token_tracker: Arc<TokenTracker>,
+    conversation_id: String,
     tx: Sender<AiResult>,
     status_tx: Sender<String>,
-    abort_reg: AbortRegistration,
+    partial_tx: Sender<String>,
+    output_tx: Sender<CommandEvent>,
+    cancel: CancellationToken,
 ) {
-    use agent_host::{classify_command, web_search, DangerLevel};
+    use agent_host::{web_search, DangerLevel};
     use providers::router::ProviderRouter;
     use tracing::info;
 
@@ -74,6 +135,7 @@ pub fn run_ai_generation(
                 fallback: None,
                 llm_calls: 0,
                 llm_duration_ms: 0,
+                usage: None,
                 executed_commands: Vec::new(),
                 pending_commands: Vec::new(),
             });
@@ -81,21 +143,60 @@ pub fn run_ai_generation(
         }
     };
 
-    // Fast Mode Logic: Use cheaper/faster models for Find/Fix/Content since these
-    // modes need quick responses and don't require deep reasoning. Research/Build/Data
-    // keep the full-power model for multi-step synthesis.
-    let mut model_settings = settings.clone();
-    if matches!(current_mode, Mode::Find | Mode::Fix | Mode::Content) {
-        model_settings.openai_model = model_settings.openai_fast_model.clone();
-        model_settings.anthropic_model = model_settings.anthropic_fast_model.clone();
-        model_settings.gemini_model = model_settings.gemini_fast_model.clone();
+    let mut tools = agent_host::tools::builtin_tool_specs(allow_terminal, allow_web);
+    tools.extend(skill_registry.tool_specs_for_mode(current_mode));
+
+    // Routing policy: picks provider order and fast vs. full models for this
+    // mode (by default Find/Fix/Content use the fast models), honouring
+    // local-only modes, prompt size, cost, and model capabilities.
+    let plan = agent_host::routing::plan_route(
+        &settings,
+        &agent_host::routing::RouteRequest {
+            messages: &messages,
+            mode: Some(current_mode),
+            tools: !tools.is_empty(),
+            remaining_budget_usd: token_tracker.remaining_budget_usd(),
+            catalog: Some(&model_catalogs),
+        },
+    );
+    for note in &plan.notes {
+        info!("routing: {}", note);
+    }
+    let turn = {
+        let _rt = rt.enter();
+        with_deadline(&cancel, agent_host::TURN_DEADLINE)
+    };
+    let router = ProviderRouter::new(plan.config)
+        .with_tools(tools)
+        .with_usage_recorder(token_tracker, conversation_id.clone())
+        .with_token_refresher(token_refresher)
+        .with_cancel(turn.clone())
+        .with_request_timeout(agent_host::LLM_REQUEST_TIMEOUT)
+        .with_secret_scanner(secrets.clone());
+    let mut session_state = SessionState::new()
+        .with_sandbox(command_sandbox(&allowed_dirs))
+        .with_cancel(turn.clone())
+        .with_output_events(output_tx)
+        .with_policy(command_policy)
+        .with_mode(current_mode)
+        .with_secret_scanner(secrets.clone())
+        .with_audit(audit)
+        .with_security_context(security_context)
+        .with_session_id(conversation_id.clone());
+    if let Some(sandbox) = process_sandbox {
+        session_state = session_state.with_process_sandbox(sandbox);
+    }
+    if let Some(slot) = shell {
+        let cwd = shell_start_dir(&allowed_dirs);
+        if let Some(shell) = rt.block_on(persistent_shell(&slot, &cwd)) {
+            // The shell keeps its own cwd across turns; start from it.
+            session_state.cwd = shell.cwd();
+            session_state = session_state.with_shell(shell);
+        }
     }
 
-    let router = ProviderRouter::new(model_settings);
-    let mut session_state = SessionState::new();
-
-    // Pre-compile regexes for parsing structured action tags from LLM output.
-    // The LLM is instructed to use these XML-like tags to request tool use.
+    // Pre-compile regexes for parsing action tags from LLM output. Models
+    // without native tool calling are instructed to use these XML-like tags.
     let search_re = regex::Regex::new(r"(?s)<search>(.*?)</search>").unwrap();
     // Accept multiple tag names because different models vary in what they emit.
     let cmd_re =
@@ -105,6 +206,33 @@ pub fn run_ai_generation(
     // instead of proper XML tags despite system prompt instructions).
     let md_cmd_re = regex::Regex::new(r"(?s)```(?:bash|sh|shell|zsh)?\n(.*?)```").unwrap();
     let skill_re = regex::Regex::new(r"(?s)<skill id=[\x22'](.*?)[\x22']>(.*?)</skill>").unwrap();
+    let captures = |re: &regex::Regex, response: &str| -> Vec<(Option<String>, String)> {
+        re.captures_iter(response)
+            .filter_map(|cap| cap.get(1).map(|m| (None, m.as_str().trim().to_string())))
+            .collect()
+    };
+    let parse_tags = |response: &str| {
+        let mut commands = captures(&cmd_re, response);
+        // Also extract commands from markdown code blocks (fallback for AI not using tags)
+        commands.extend(captures(&md_cmd_re, response));
+        let skills = skill_re
+            .captures_iter(response)
+            .map(|cap| {
+                let params: Option<serde_json::Value> = serde_json::from_str(cap[2].trim()).ok();
+                (
+                    None,
+                    cap[1].trim().to_string(),
+                    params.unwrap_or(serde_json::Value::Null),
+                )
+            })
+            .collect();
+        TurnActions {
+            searches: captures(&search_re, response),
+            commands,
+            skills,
+            invalid: Vec::new(),
+        }
+    };
 
     // Heuristic: detect when the model wrote command-looking text (e.g. "cd ~/foo")
     // but forgot to wrap it in tags. Triggers a one-shot "format repair" retry
@@ -114,7 +242,14 @@ pub fn run_ai_generation(
     )
     .unwrap();
 
-    let result = rt.block_on(Abortable::new(async {
+    // Reported usage summed over every call; the tracker also gets each call
+    // through the router's recorder.
+    let mut usage: Option<Usage> = None;
+    // Kept outside the turn so a stopped turn can still report what it got
+    // done: the latest streamed answer and the commands already run.
+    let mut partial = String::new();
+    let mut all_executed_commands: Vec<(String, String, bool)> = Vec::new();
+    let result = rt.block_on(run_cancellable(&turn, async {
         let mut msgs = messages;
         let mut file_to_preview: Option<PathBuf> = None;
         let mut preview_web_url: Option<String> = None;
@@ -125,7 +260,6 @@ pub fn run_ai_generation(
         let mut preview_web_search_time_ms: Option<u64> = None;
         let mut preview_web_results: Option<Vec<shared::preview_types::WebSearchResultItem>> =
             None;
-        let mut all_executed_commands: Vec<(String, String, bool)> = Vec::new();
         let mut pending_commands: Vec<String> = Vec::new();
 
         let mut last_provider: Option<String> = None;
@@ -146,10 +280,17 @@ pub fn run_ai_generation(
             // Get AI response
             let stage = if iteration == 0 { "Thinking" } else { "Thinking again with new info" };
             let _ = status_tx.send(stage.to_string());
-            let gen = router.generate_with_meta(msgs.clone()).await?;
-            let mut response = gen.text;
+            let _ = partial_tx.send(String::new());
+            partial.clear();
+            let gen = router
+                .generate_stream(msgs.clone(), |event| {
+                    forward_partial(&mut partial, &partial_tx, event)
+                })
+                .await?;
+            let mut response = gen.text.clone();
             llm_calls = llm_calls.saturating_add(1);
             llm_duration_ms = llm_duration_ms.saturating_add(gen.meta.duration_ms);
+            add_usage(&mut usage, gen.meta.usage);
             last_provider = Some(gen.meta.provider.clone());
             last_model = Some(gen.meta.model.clone());
             if let (Some(from), Some(err)) = (gen.meta.fallback_from.clone(), gen.meta.fallback_error.clone()) {
@@ -175,37 +316,18 @@ pub fn run_ai_generation(
                 }
             }
 
-            // Check for search and command tags
-            let mut searches: Vec<String> = search_re
-                .captures_iter(&response)
-                .filter_map(|cap| cap.get(1).map(|m| m.as_str().trim().to_string()))
-                .collect();
-
-            let mut commands: Vec<String> = cmd_re
-                .captures_iter(&response)
-                .filter_map(|cap| cap.get(1).map(|m| m.as_str().trim().to_string()))
-                .collect();
-
-            // Also extract commands from markdown code blocks (fallback for AI not using tags)
-            let md_commands: Vec<String> = md_cmd_re
-                .captures_iter(&response)
-                .filter_map(|cap| cap.get(1).map(|m| m.as_str().trim().to_string()))
-                .collect();
-            commands.extend(md_commands);
-
-            // Parse skills
-            let mut skill_calls = Vec::new();
-            for cap in skill_re.captures_iter(&response) {
-                let id = cap[1].trim().to_string();
-                let params_str = cap[2].trim();
-                let params: Option<serde_json::Value> = serde_json::from_str(params_str).ok();
-                skill_calls.push((id, params.unwrap_or(serde_json::Value::Null)));
-            }
+            // Structured tool calls take precedence; the tags are the fallback for
+            // models and providers without native tool support.
+            let mut tool_calls = gen.tool_calls.clone();
+            let mut assistant_msg = gen.to_message();
+            let mut actions = if tool_calls.is_empty() {
+                parse_tags(&response)
+            } else {
+                TurnActions::from_tool_calls(&tool_calls)
+            };
 
             // If the model is describing commands instead of emitting tags, do a one-shot repair.
-            if searches.is_empty()
-                && commands.is_empty()
-                && skill_calls.is_empty()
+            if actions.is_empty()
                 && (allow_terminal || allow_web)
                 && !response.contains("<command>")
                 && !response.contains("<search>")
@@ -213,18 +335,13 @@ pub fn run_ai_generation(
             {
                 let _ = status_tx.send("Fixing tool format…".to_string());
                 let mut repair_msgs = msgs.clone();
-                repair_msgs.push(ApiChatMessage {
-                    role: "assistant".to_string(),
-                    content: response.clone(),
-                });
-                repair_msgs.push(ApiChatMessage {
-                    role: "user".to_string(),
-                    content: "Re-send ONLY tool tags. Use <command>...</command> and/or <search>...</search>. No prose, no code blocks. If you cannot run anything, reply with: BLOCKED: <short reason>.".to_string(),
-                });
+                repair_msgs.push(ApiChatMessage::assistant(response.clone()));
+                repair_msgs.push(ApiChatMessage::user("Re-send ONLY tool calls or tool tags. Use <command>...</command> and/or <search>...</search>. No prose, no code blocks. If you cannot run anything, reply with: BLOCKED: <short reason>."));
 
                 let repair = router.generate_with_meta(repair_msgs).await?;
                 llm_calls = llm_calls.saturating_add(1);
                 llm_duration_ms = llm_duration_ms.saturating_add(repair.meta.duration_ms);
+                add_usage(&mut usage, repair.meta.usage);
                 last_provider = Some(repair.meta.provider.clone());
                 last_model = Some(repair.meta.model.clone());
                 info!(
@@ -235,32 +352,16 @@ pub fn run_ai_generation(
                     "llm.generate.repair"
                 );
 
-                response = repair.text;
+                response = repair.text.clone();
+                tool_calls = repair.tool_calls.clone();
+                assistant_msg = repair.to_message();
 
                 // Re-parse after repair.
-                searches = search_re
-                    .captures_iter(&response)
-                    .filter_map(|cap| cap.get(1).map(|m| m.as_str().trim().to_string()))
-                    .collect();
-
-                commands = cmd_re
-                    .captures_iter(&response)
-                    .filter_map(|cap| cap.get(1).map(|m| m.as_str().trim().to_string()))
-                    .collect();
-
-                let md_commands: Vec<String> = md_cmd_re
-                    .captures_iter(&response)
-                    .filter_map(|cap| cap.get(1).map(|m| m.as_str().trim().to_string()))
-                    .collect();
-                commands.extend(md_commands);
-
-                skill_calls.clear();
-                for cap in skill_re.captures_iter(&response) {
-                    let id = cap[1].trim().to_string();
-                    let params_str = cap[2].trim();
-                    let params: Option<serde_json::Value> = serde_json::from_str(params_str).ok();
-                    skill_calls.push((id, params.unwrap_or(serde_json::Value::Null)));
-                }
+                actions = if tool_calls.is_empty() {
+                    parse_tags(&response)
+                } else {
+                    TurnActions::from_tool_calls(&tool_calls)
+                };
 
                 // Re-check preview tags after repair.
                 for tag in shared::preview_types::parse_preview_tags(&response) {
@@ -276,7 +377,7 @@ pub fn run_ai_generation(
             }
 
             // If no actions needed, return the response
-            if searches.is_empty() && commands.is_empty() && skill_calls.is_empty() {
+            if actions.is_empty() {
                 return Ok::<
                     (
                         String,
@@ -300,7 +401,7 @@ pub fn run_ai_generation(
                 >((
                     response, // Was display_response
                     file_to_preview,
-                    all_executed_commands,
+                    std::mem::take(&mut all_executed_commands),
                     pending_commands,
                     last_provider,
                     last_model,
@@ -319,25 +420,26 @@ pub fn run_ai_generation(
 
 
 
-            // Add assistant response to conversation
-            msgs.push(ApiChatMessage {
-                role: "assistant".to_string(),
-                content: response.clone(),
-            });
+            // Add assistant response (and any tool calls) to conversation
+            msgs.push(assistant_msg);
 
-            let mut results = Vec::new();
+            // Each result is tagged with the tool call it answers (None for tags).
+            let mut results: Vec<(Option<String>, String)> = Vec::new();
+            for (call_id, reason) in &actions.invalid {
+                results.push((Some(call_id.clone()), reason.clone()));
+            }
 
             // Execute searches
             let search_limit = match (current_mode, research_depth) {
                 (Mode::Research, ResearchDepth::Quick) => 1,
                 _ => usize::MAX,
             };
-            for query in searches.iter().take(search_limit) {
+            for (call_id, query) in actions.searches.iter().take(search_limit) {
                 let _ = status_tx.send(format!("Searching: {}", query));
                 if !allow_web {
-                    results.push(format!(
-                        "[Search blocked: Internet access disabled]\nQuery: {}",
-                        query
+                    results.push((
+                        call_id.clone(),
+                        format!("[Search blocked: Internet access disabled]\nQuery: {}", query),
                     ));
                     continue;
                 }
@@ -360,13 +462,16 @@ pub fn run_ai_generation(
                                 preview_web_search_time_ms = Some(result.duration_ms);
                             }
                         }
-                        results.push(format!(
-                            "[Search Results for '{}']\n{}",
-                            query, result.output
+                        results.push((
+                            call_id.clone(),
+                            format!("[Search Results for '{}']\n{}", query, result.output),
                         ));
                     }
                     Err(e) => {
-                        results.push(format!("[Search failed for '{}']: {}", query, e));
+                        results.push((
+                            call_id.clone(),
+                            format!("[Search failed for '{}']: {}", query, e),
+                        ));
                     }
                 }
             }
@@ -375,29 +480,29 @@ pub fn run_ai_generation(
             // commands get queued for user approval, and blocked commands are rejected.
             // Only one safe command runs per iteration to keep the loop predictable.
             let mut safe_command_executed = false;
-            for cmd in &commands {
+            for (call_id, cmd) in &actions.commands {
                 if !allow_terminal {
                     all_executed_commands.push((
                         cmd.clone(),
                         "Terminal access disabled in settings".to_string(),
                         false,
                     ));
-                    results.push(format!(
-                        "[Command blocked: terminal access disabled]\n$ {}",
-                        cmd
+                    results.push((
+                        call_id.clone(),
+                        format!("[Command blocked: terminal access disabled]\n$ {}", cmd),
                     ));
                     continue;
                 }
 
                 // Apply folder and safety policy before showing to user.
-                if let Err(reason) = validate_command_against_allowed(cmd, &allowed_dirs) {
-                    results.push(format!(
-                        "[Command blocked: {}]\n$ {}",
-                        reason, cmd
+                if let Err(reason) = check_command_paths(cmd, &session_state, &allowed_dirs) {
+                    results.push((
+                        call_id.clone(),
+                        format!("[Command blocked: {}]\n$ {}", reason, cmd),
                     ));
                     continue;
                 }
-                let danger = classify_command(cmd);
+                let danger = session_state.classify(cmd);
                 eprintln!("COMMAND CLASSIFY: {} -> {:?}", cmd, danger);
                 if danger == DangerLevel::Blocked {
                     all_executed_commands.push((
@@ -405,7 +510,7 @@ pub fn run_ai_generation(
                         "Blocked for safety".to_string(),
                         false,
                     ));
-                    results.push(format!("[Command blocked for safety: {}]", cmd));
+                    results.push((call_id.clone(), format!("[Command blocked for safety: {}]", cmd)));
                     continue;
                 }
 
@@ -418,26 +523,35 @@ pub fn run_ai_generation(
                                 r.output.clone(),
                                 r.success,
                             ));
-                            results.push(format!(
-                                "[Command completed]\n{}",
-                                if r.output.trim().is_empty() {
-                                    "(no output)".to_string()
-                                } else {
-                                    r.output
-                                }
+                            results.push((
+                                call_id.clone(),
+                                format!(
+                                    "[Command completed]\n{}",
+                                    if r.output.trim().is_empty() {
+                                        "(no output)".to_string()
+                                    } else {
+                                        r.output
+                                    }
+                                ),
                             ));
                             safe_command_executed = true;
                         }
                         Err(e) => {
                             all_executed_commands
                                 .push((cmd.clone(), e.to_string(), false));
-                            results.push(format!("[Command failed]\n$ {}\n{}", cmd, e));
+                            results.push((
+                                call_id.clone(),
+                                format!("[Command failed]\n$ {}\n{}", cmd, e),
+                            ));
                             safe_command_executed = true;
                         }
                     }
                 } else {
                     let _ = status_tx.send("Waiting for your approval".to_string());
-                    results.push(format!("[Command '{}' queued for user approval]", cmd));
+                    results.push((
+                        call_id.clone(),
+                        format!("[Command '{}' queued for user approval]", cmd),
+                    ));
                     if !pending_commands.iter().any(|c| c == cmd) {
                         pending_commands.push(cmd.clone());
                     }
@@ -449,59 +563,64 @@ pub fn run_ai_generation(
             }
 
             // Execute skills
-            for (id, params) in skill_calls {
+            for (call_id, id, params) in &actions.skills {
                 let _ = status_tx.send(format!("Running skill: {}", id));
-                let mut input = SkillInput::from_query("");
-                if let serde_json::Value::Object(map) = params {
-                    for (k, v) in map {
-                        input = input.with_param(k, v);
-                    }
-                }
-
-                let ctx = SkillContext::new(current_mode, PathBuf::from("."));
-
-                match skill_registry.invoke(&id, input, &ctx).await {
-                    Ok(execution) => {
-                        match execution.output {
-                            Some(output) => {
-                                 results.push(format!("[Skill {} completed]\n{:?}", id, output));
-                            }
-                            None => {
-                                results.push(format!("[Skill {} completed (no output)]", id));
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        results.push(format!("[Skill {} failed]: {}", id, e));
-                    }
-                }
-            }
-
-            if !searches.is_empty() || !commands.is_empty() || !results.is_empty() {
-                // response cleared
+                let input = agent_host::tools::skill_input(params);
+                let ctx = SkillContext::new(current_mode, PathBuf::from("."))
+                    .with_session_id(conversation_id.clone());
+
+                let output = match skill_registry.invoke(id, input, &ctx).await {
+                    Ok(execution) => match execution.output {
+                        Some(output) => format!("[Skill {} completed]\n{:?}", id, output),
+                        None => format!("[Skill {} completed (no output)]", id),
+                    },
+                    Err(e) => format!("[Skill {} failed]: {}", id, e),
+                };
+                results.push((call_id.clone(), output));
             }
 
-            // Add results back to conversation
-            if !results.is_empty() {
-                msgs.push(ApiChatMessage {
-                    role: "user".to_string(),
-                    content: results.join("\n\n"),
-                });
+            // Add results back to conversation, without any secrets they turned up
+            let results: Vec<(Option<String>, String)> = results
+                .into_iter()
+                .map(|(id, r)| (id, secrets.redact(&r).into_owned()))
+                .collect();
+            if tool_calls.is_empty() {
+                if !results.is_empty() {
+                    let text: Vec<String> = results.into_iter().map(|(_, r)| r).collect();
+                    msgs.push(ApiChatMessage::user(text.join("\n\n")));
+                }
+            } else {
+                // Every tool call must be answered, including ones skipped this turn.
+                for call in &tool_calls {
+                    let output: Vec<&str> = results
+                        .iter()
+                        .filter(|(id, _)| id.as_deref() == Some(call.id.as_str()))
+                        .map(|(_, r)| r.as_str())
+                        .collect();
+                    let output = if output.is_empty() {
+                        "[Skipped this turn. Call the tool again if you still need it.]".to_string()
+                    } else {
+                        output.join("\n\n")
+                    };
+                    msgs.push(ApiChatMessage::tool_result(call, output));
+                }
             }
         }
 
         // Ran out of iterations -- ask the model to summarize what it found so far.
         // This prevents the user from seeing a raw "tool output" as the final response.
         let _ = status_tx.send("Summarizing results...".to_string());
-        msgs.push(ApiChatMessage {
-            role: "user".to_string(),
-            content: "Summarize what you found so far in plain language. Don't include any command tags.".to_string(),
-        });
-        let gen = router.generate_with_meta(msgs).await;
+        let _ = partial_tx.send(String::new());
+        msgs.push(ApiChatMessage::user("Summarize what you found so far in plain language. Don't include any command tags."));
+        partial.clear();
+        let gen = router
+            .generate_stream(msgs, |event| forward_partial(&mut partial, &partial_tx, event))
+            .await;
         let summary = match gen {
             Ok(gen) => {
                 llm_calls = llm_calls.saturating_add(1);
                 llm_duration_ms = llm_duration_ms.saturating_add(gen.meta.duration_ms);
+            add_usage(&mut usage, gen.meta.usage);
                 last_provider = Some(gen.meta.provider.clone());
                 last_model = Some(gen.meta.model.clone());
                 if let (Some(from), Some(err)) = (gen.meta.fallback_from.clone(), gen.meta.fallback_error.clone()) {
@@ -516,6 +635,7 @@ pub fn run_ai_generation(
                 );
                 gen.text
             }
+            Err(e) if is_cancelled(&e) => return Err(e),
             Err(_) => {
                 "I ran several searches but couldn't generate a summary. Check the preview panel for raw results.".to_string()
             }
@@ -523,7 +643,7 @@ pub fn run_ai_generation(
         Ok((
             summary,
             file_to_preview,
-            all_executed_commands,
+            std::mem::take(&mut all_executed_commands),
             pending_commands,
             last_provider,
             last_model,
@@ -538,11 +658,11 @@ pub fn run_ai_generation(
             llm_calls,
             llm_duration_ms,
         ))
-    }, abort_reg));
+    }));
 
     // Send result back to UI
     let ai_result = match result {
-        Ok(Ok((
+        Ok((
             response,
             preview_file,
             executed_commands,
@@ -559,7 +679,7 @@ pub fn run_ai_generation(
             preview_web_results,
             llm_calls,
             llm_duration_ms,
-        ))) => AiResult {
+        )) => AiResult {
             response,
             preview_file,
             preview_web_url,
@@ -575,10 +695,40 @@ pub fn run_ai_generation(
             fallback,
             llm_calls,
             llm_duration_ms,
+            usage,
             executed_commands,
             pending_commands,
         },
-        Ok(Err(e)) => AiResult {
+        // Stopped: keep what was streamed and run so far, plus a note saying why.
+        Err(e) if is_cancelled(&e) => {
+            let streamed = clean_ai_response(&partial);
+            let note = agent_host::stopped_note(cancel.is_cancelled());
+            AiResult {
+                response: if streamed.trim().is_empty() {
+                    note
+                } else {
+                    format!("{}\n\n{}", streamed.trim_end(), note)
+                },
+                preview_file: None,
+                preview_web_url: None,
+                preview_web_title: None,
+                preview_web_snippet: None,
+                preview_web_query: None,
+                preview_web_source: None,
+                preview_web_search_time_ms: None,
+                preview_web_results: None,
+                error: None,
+                provider: None,
+                model: None,
+                fallback: None,
+                llm_calls: 0,
+                llm_duration_ms: 0,
+                usage,
+                executed_commands: all_executed_commands,
+                pending_commands: Vec::new(),
+            }
+        }
+        Err(e) => AiResult {
             response: String::new(),
             preview_file: None,
             preview_web_url: None,
@@ -594,25 +744,7 @@ pub fn run_ai_generation(
             fallback: None,
             llm_calls: 0,
             llm_duration_ms: 0,
-            executed_commands: Vec::new(),
-            pending_commands: Vec::new(),
-        },
-        Err(_aborted) => AiResult {
-            response: String::new(),
-            preview_file: None,
-            preview_web_url: None,
-            preview_web_title: None,
-            preview_web_snippet: None,
-            preview_web_query: None,
-            preview_web_source: None,
-            preview_web_search_time_ms: None,
-            preview_web_results: None,
-            error: Some("Cancelled".to_string()),
-            provider: None,
-            model: None,
-            fallback: None,
-            llm_calls: 0,
-            llm_duration_ms: 0,
+            usage,
             executed_commands: Vec::new(),
             pending_commands: Vec::new(),
         },
@@ -621,6 +753,60 @@ pub fn run_ai_generation(
     let _ = tx.send(ai_result);
 }
 
+/// Actions requested by one model turn. Each carries the ID of the tool call
+/// that asked for it, or `None` when it came from a tag in the response text.
+#[derive(Default)]
+struct TurnActions {
+    searches: Vec<(Option<String>, String)>,
+    commands: Vec<(Option<String>, String)>,
+    skills: Vec<(Option<String>, String, serde_json::Value)>,
+    /// Tool calls that could not be mapped, with the error to send back.
+    invalid: Vec<(String, String)>,
+}
+
+impl TurnActions {
+    fn from_tool_calls(calls: &[ToolCall]) -> Self {
+        use agent_host::tools::{tool_action, ToolAction};
+
+        let mut actions = Self::default();
+        for call in calls {
+            let id = Some(call.id.clone());
+            match tool_action(call) {
+                ToolAction::Search(query) => actions.searches.push((id, query)),
+                ToolAction::Command(cmd) => actions.commands.push((id, cmd)),
+                ToolAction::Skill { id: skill, params } => actions.skills.push((id, skill, params)),
+                ToolAction::Invalid(reason) => actions.invalid.push((call.id.clone(), reason)),
+            }
+        }
+        actions
+    }
+
+    fn is_empty(&self) -> bool {
+        self.searches.is_empty()
+            && self.commands.is_empty()
+            && self.skills.is_empty()
+            && self.invalid.is_empty()
+    }
+}
+
+fn add_usage(total: &mut Option<Usage>, usage: Option<Usage>) {
+    if let Some(u) = usage {
+        let t = total.get_or_insert_with(Usage::default);
+        t.input_tokens = t.input_tokens.saturating_add(u.input_tokens);
+        t.output_tokens = t.output_tokens.saturating_add(u.output_tokens);
+    }
+}
+
+/// Apply one streaming event to the accumulated answer and push a cleaned
+/// snapshot (action tags stripped) to the UI.
+fn forward_partial(partial: &mut String, partial_tx: &Sender<String>, event: StreamEvent) {
+    match event {
+        StreamEvent::Delta(delta) => partial.push_str(&delta),
+        StreamEvent::Reset { .. } => partial.clear(),
+    }
+    let _ = partial_tx.send(clean_ai_response(partial));
+}
+
 /// Truncate a command string for display in the status indicator.
 fn truncate_for_status(s: &str) -> String {
     let first_line = s.lines().next().unwrap_or(s);
@@ -758,3 +944,34 @@ fn infer_web_source(output: &str) -> String {
         "Web".to_string()
     }
 }
+
+/// Where a new persistent shell starts: the first allowed folder, else home.
+pub(crate) fn shell_start_dir(allowed_dirs: &[String]) -> PathBuf {
+    allowed_dirs
+        .first()
+        .map(PathBuf::from)
+        .or_else(dirs::home_dir)
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+/// The shell in `slot`, starting a new one in `cwd` if there is none or the
+/// old one has exited. `None` (commands run one-off) if bash won't start.
+pub(crate) async fn persistent_shell(
+    slot: &ShellSlot,
+    cwd: &std::path::Path,
+) -> Option<Arc<ShellSession>> {
+    if let Some(shell) = slot.lock().as_ref().filter(|shell| shell.is_alive()) {
+        return Some(shell.clone());
+    }
+    match ShellSession::start(cwd, &std::collections::HashMap::new()).await {
+        Ok(shell) => {
+            let shell = Arc::new(shell);
+            *slot.lock() = Some(shell.clone());
+            Some(shell)
+        }
+        Err(e) => {
+            tracing::warn!("persistent shell unavailable: {:#}", e);
+            None
+        }
+    }
+}
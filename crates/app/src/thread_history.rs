@@ -132,6 +132,16 @@ impl Thread {
         }
     }
 
+    /// The conversation as plain text, one "role: content" paragraph per
+    /// message
+    pub fn transcript(&self) -> String {
+        self.messages
+            .iter()
+            .map(|m| format!("{}: {}", m.role, m.content))
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+
     /// Truncate message for preview
     fn truncate_preview(message: &str) -> String {
         let max_len = 80;
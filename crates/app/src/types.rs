@@ -11,18 +11,27 @@
 //! - **Channel-based async**: Background work (AI generation, command execution,
 //!   web preview fetching, OAuth flows) communicates results via `mpsc::Receiver`
 //!   fields that are polled each frame.
-//! - **Abort handles**: In-flight AI requests can be cancelled via `AbortHandle`,
-//!   stored per-mode in `ai_abort_handles`.
-
-use agent_host::{classify_command, AgentHost, CommandResult, DangerLevel};
+//! - **Cancellation**: In-flight AI requests can be stopped via a
+//!   `CancellationToken`, stored per-mode in `ai_abort_handles`.
+
+use agent_host::command_policy::CommandPolicy;
+use agent_host::dry_run::{self, CommandPlan, FileSnapshot};
+use agent_host::executor::SessionState;
+use agent_host::process_sandbox::ProcessSandbox;
+use agent_host::security::SecurityContext;
+use agent_host::shell_session::{CommandEvent, ShellSession};
+use agent_host::skills::common::AuditLogger;
+use agent_host::token_tracker::TokenTracker;
+use agent_host::{AgentHost, CommandResult, DangerLevel};
+use shared::secrets::SecretScanner;
 
 #[cfg(not(windows))]
 use agent_host::execute_with_sudo;
 use eframe::egui;
 use services::web_preview::WebPreviewService;
-use shared::agent_api::ChatMessage as ApiChatMessage;
+use shared::agent_api::{Attachment, ChatMessage as ApiChatMessage};
 use shared::preview_types::{parse_preview_tags, strip_preview_tags, PreviewContent};
-use shared::settings::AppSettings;
+use shared::settings::{AppSettings, OAuthCredentials};
 use shared::skill::Mode;
 use std::collections::HashMap;
 use std::collections::HashSet;
@@ -31,19 +40,30 @@ use std::sync::mpsc::{channel, Receiver};
 use std::sync::Arc;
 use std::time::Instant;
 
-use futures::future::AbortHandle;
+use providers::cancel::CancellationToken;
 use sysinfo::System;
 
 use crate::context::{
     get_campaign_summary, load_campaign_context, load_ddd_workflow, load_personas,
 };
 use crate::set_primary_provider_preference;
-use crate::state::run_ai_generation;
+use crate::state::{run_ai_generation, shell_start_dir};
 use crate::utils::{
-    clean_ai_response, is_path_in_allowed_dirs, run_user_command, validate_command_against_allowed,
+    check_command_paths, clean_ai_response, command_policy_path, command_sandbox,
+    is_path_in_allowed_dirs, load_command_policy, load_model_catalogs, load_token_tracker,
+    model_catalog_path, run_user_command, token_usage_path,
 };
+use providers::catalog::{ModelCatalogs, ModelIssue};
+use providers::oauth_refresh::{AuthEvent, OAuthClientConfig, TokenRefresher};
 use shared::preview_types::WebSearchResultItem;
 
+/// A mode's persistent shell. Empty until a turn with terminal access
+/// starts one; emptied again when the user kills it.
+pub type ShellSlot = Arc<parking_lot::Mutex<Option<Arc<ShellSession>>>>;
+
+/// How much of a running command's output the thinking bubble keeps.
+const COMMAND_OUTPUT_TAIL: usize = 4000;
+
 /// Result from background AI generation
 #[derive(Debug)]
 pub struct AiResult {
@@ -66,6 +86,8 @@ pub struct AiResult {
     /// Aggregate stats for all LLM calls made during this request (multi-turn loop)
     pub llm_calls: u32,
     pub llm_duration_ms: u64,
+    /// Token usage reported by the providers, summed over all calls
+    pub usage: Option<providers::usage::Usage>,
     /// Commands that were executed (for transparency)
     pub executed_commands: Vec<(String, String, bool)>, // (command, output, success)
     pub pending_commands: Vec<String>,
@@ -76,6 +98,8 @@ pub struct AiResult {
 pub struct CommandExecResult {
     pub command: String,
     pub output: Result<CommandResult, String>,
+    /// Files saved before the command ran, for undo
+    pub snapshots: Vec<FileSnapshot>,
 }
 
 /// Result from background web preview fetch
@@ -91,8 +115,7 @@ pub struct WebPreviewResult {
 /// Result from a background OAuth flow.
 pub struct OAuthResult {
     pub provider: String,
-    pub access_token: String,
-    pub refresh_token: Option<String>,
+    pub credentials: Option<OAuthCredentials>,
     pub error: Option<String>,
 }
 
@@ -104,6 +127,13 @@ pub struct OllamaSetupResult {
     pub recommended_desc: String,
 }
 
+/// Result from the background model catalog refresh
+pub struct ModelCatalogResult {
+    pub catalogs: ModelCatalogs,
+    /// Configured model names their provider doesn't offer
+    pub issues: Vec<ModelIssue>,
+}
+
 /// Current app screen
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum AppScreen {
@@ -235,10 +265,18 @@ pub struct AppState {
     pub is_thinking: std::collections::HashMap<ChatMode, bool>,
     /// What the agent is currently doing (per mode)
     pub thinking_status: std::collections::HashMap<ChatMode, String>,
+    /// Answer text streamed so far for the in-flight request (per mode)
+    pub thinking_partial: HashMap<ChatMode, String>,
     /// Which mode currently has an active AI request
     pub thinking_mode: Option<ChatMode>,
     /// When an AI request started (per mode)
     pub thinking_started_at: HashMap<ChatMode, std::time::Instant>,
+    /// Tail of the running command's output (per mode)
+    pub command_output: HashMap<ChatMode, String>,
+    /// Percent complete reported by the running command (per mode)
+    pub command_progress: HashMap<ChatMode, u8>,
+    /// Text typed into the running command's stdin box
+    pub command_stdin_input: String,
     /// Whether we've shown a slow-response hint (per mode)
     pub slow_response_hint_shown: HashMap<ChatMode, bool>,
     /// Whether to show attention near the model indicator
@@ -275,6 +313,10 @@ pub struct AppState {
 
     // Pending command approvals
     pub pending_commands: Vec<String>,
+    /// Dry-run plans of pending commands, made once per command
+    pub command_plans: HashMap<String, CommandPlan>,
+    /// Files saved before the last approved command, until restored or dismissed
+    pub last_command_snapshots: Option<(String, Vec<FileSnapshot>)>,
 
     // Sudo password handling
     pub password_dialog: crate::modals::PasswordDialog,
@@ -292,7 +334,10 @@ pub struct AppState {
     pub ai_result_rx: Option<Receiver<AiResult>>,
 
     // Abort handles for in-flight AI work (per mode)
-    pub ai_abort_handles: HashMap<ChatMode, AbortHandle>,
+    pub ai_abort_handles: HashMap<ChatMode, CancellationToken>,
+
+    // Persistent shell per mode, started by the first turn with terminal access
+    pub shell_sessions: HashMap<ChatMode, ShellSlot>,
 
     // Web preview service and async fetch channel
     pub web_preview_service: Arc<WebPreviewService>,
@@ -315,11 +360,25 @@ pub struct AppState {
     pub build_status: Option<String>,
     pub build_status_is_error: bool,
 
-    // Session usage (approx)
+    // Session usage (provider-reported when available, otherwise estimated)
     pub session_input_tokens_est: u64,
     pub session_output_tokens_est: u64,
     pub last_prompt_tokens_est: u32,
     pub last_response_tokens_est: u32,
+    /// Real usage and cost per conversation, persisted between runs
+    pub token_tracker: Arc<TokenTracker>,
+    /// Models each provider offers (context windows, capabilities), cached between runs
+    pub model_catalogs: Arc<ModelCatalogs>,
+    /// The user's allow/deny rules for commands, on top of the built-in lists
+    pub command_policy: CommandPolicy,
+    /// Redacts secrets from tool output, context documents and LLM requests
+    pub secret_scanner: Arc<SecretScanner>,
+    /// Records refused commands and 2FA attempts as security events
+    pub audit_logger: Arc<AuditLogger>,
+    /// 2FA grants from the security skill, checked before destructive commands
+    pub security_context: Arc<SecurityContext>,
+    /// Renews OAuth sign-ins before they expire; shared by every AI request
+    pub token_refresher: Arc<TokenRefresher>,
 
     // Last AI request diagnostics (for UX + debugging)
     pub last_llm_provider: Option<String>,
@@ -341,13 +400,27 @@ pub struct AppState {
     // Background Ollama setup channel (fires once at startup)
     pub ollama_setup_rx: Option<Receiver<OllamaSetupResult>>,
 
+    // Background model catalog refresh (starts once Ollama setup is done)
+    pub model_catalog_rx: Option<Receiver<ModelCatalogResult>>,
+
     // Live status updates from the AI pipeline (e.g. "Searching…", "Running command…")
     pub ai_status_rx: Option<Receiver<String>>,
 
+    // Partial answer text streamed from the AI pipeline (latest snapshot wins)
+    pub ai_partial_rx: Option<Receiver<String>>,
+
+    // Output and progress of commands as they run
+    pub ai_output_rx: Option<Receiver<CommandEvent>>,
+
     // Background OAuth flow channel
     pub oauth_result_rx: Option<Receiver<OAuthResult>>,
     /// True while an OAuth browser flow is in progress
     pub oauth_in_progress: bool,
+
+    // Token refreshes and expired sign-ins reported by `token_refresher`
+    pub auth_event_rx: Receiver<AuthEvent>,
+    /// Providers already told to sign in again (cleared on the next sign-in)
+    pub reauth_notified: HashSet<String>,
 }
 
 impl Default for AppState {
@@ -514,6 +587,8 @@ impl Default for AppState {
             AppScreen::Onboarding
         };
 
+        let secret_scanner = Arc::new(SecretScanner::from_settings(&settings.secret_scanning));
+
         // Initialize Context Manager (Shared)
         let context_manager = std::sync::Arc::new(parking_lot::Mutex::new({
             let mut cm = agent_host::context_manager::ContextManager::new(
@@ -525,6 +600,7 @@ impl Default for AppState {
                 ))
                 .expect("Failed to create context manager")
             });
+            cm.set_secret_scanner(secret_scanner.clone());
 
             // Scan configured external directories
             if !settings.external_context_dirs.is_empty() {
@@ -538,12 +614,27 @@ impl Default for AppState {
         // thread to avoid blocking the UI. Two triggers:
         //   - Reactive: fires when 50+ new documents have been added
         //   - Periodic: daily pruning of old, low-feedback nodes
+        // It also extracts entities and relations from documents that are new
+        // or changed since their last extraction, a few per minute.
         let bg_cm = context_manager.clone();
+        let extractor = agent_host::extraction::Extractor::for_settings(&settings.model);
         std::thread::spawn(move || {
             let mut last_prune = std::time::SystemTime::now();
+            let rt = tokio::runtime::Runtime::new().ok();
             loop {
                 std::thread::sleep(std::time::Duration::from_secs(60));
 
+                // 0. Entity extraction. The extractor may wait on the local
+                // model, so only fetching jobs and applying results lock.
+                if let Some(rt) = &rt {
+                    let jobs = bg_cm.lock().pending_extractions(5);
+                    for job in jobs {
+                        let extraction = rt.block_on(extractor.extract(&job));
+                        let merged = bg_cm.lock().apply_extraction(&job, &extraction);
+                        tracing::debug!("extracted {} entities from {}", merged, job.source_id);
+                    }
+                }
+
                 // 1. Reactive Check (New Docs)
                 let needs_opt = {
                     let mut cm = bg_cm.lock();
@@ -586,23 +677,25 @@ impl Default for AppState {
             }
         });
 
+        // Initialize infrastructure (SafeFileOps, Audit, etc.)
+        let data_dir = agent_host::context_manager::ContextManager::default_dir();
+        let infra = Arc::new(
+            agent_host::skills::common::init_common_infrastructure(&data_dir).unwrap_or_else(|e| {
+                eprintln!("Failed to init common infra: {}", e);
+                // Fallback to local dir if system dir fails
+                agent_host::skills::common::init_common_infrastructure(&std::path::PathBuf::from(
+                    ".",
+                ))
+                .expect("Failed to init common infra fallback")
+            }),
+        );
+        let audit_logger = infra.audit_logger.clone();
+        let security_context = infra.security_context.clone();
+        let token_tracker = Arc::new(load_token_tracker());
+        let model_catalogs = Arc::new(load_model_catalogs());
+
         // Initialize Skill Registry
         let skill_registry = {
-            let data_dir = agent_host::context_manager::ContextManager::default_dir();
-            // Initialize infrastructure (SafeFileOps, Audit, etc.)
-            let infra = Arc::new(
-                agent_host::skills::common::init_common_infrastructure(&data_dir).unwrap_or_else(
-                    |e| {
-                        eprintln!("Failed to init common infra: {}", e);
-                        // Fallback to local dir if system dir fails
-                        agent_host::skills::common::init_common_infrastructure(
-                            &std::path::PathBuf::from("."),
-                        )
-                        .expect("Failed to init common infra fallback")
-                    },
-                ),
-            );
-
             // Initialize File Index
             let file_index = Arc::new(
                 services::file_index::FileIndexService::new(&data_dir).unwrap_or_else(|e| {
@@ -616,6 +709,21 @@ impl Default for AppState {
             agent_host::skills::init_registry(file_index, infra, context_manager.clone())
         };
 
+        // OAuth refresh: Google sign-ins renew through the same client that
+        // signed in.
+        let (auth_event_tx, auth_event_rx) = channel::<AuthEvent>();
+        let mut token_refresher = TokenRefresher::new().with_sink(Arc::new(auth_event_tx));
+        if let Some((client_id, client_secret)) = crate::secrets::google_oauth_credentials() {
+            token_refresher = token_refresher.with_client(
+                "gemini",
+                OAuthClientConfig {
+                    client_id,
+                    client_secret,
+                    token_url: providers::oauth_helper::GOOGLE_TOKEN_URL.to_string(),
+                },
+            );
+        }
+
         Self {
             settings: settings.clone(),
             current_screen: initial_screen,
@@ -678,12 +786,18 @@ impl Default for AppState {
                 m.insert(ChatMode::Build, String::new());
                 m
             },
+            thinking_partial: HashMap::new(),
+            command_output: HashMap::new(),
+            command_progress: HashMap::new(),
+            command_stdin_input: String::new(),
             thinking_mode: None,
             thinking_started_at: HashMap::new(),
             slow_response_hint_shown: HashMap::new(),
             show_model_hint: false,
             model_hint_started_at: None,
-            agent_host: AgentHost::new(settings.clone()),
+            agent_host: AgentHost::new(settings.clone())
+                .with_token_tracker(token_tracker.clone())
+                .with_model_catalogs(model_catalogs.clone()),
             context_manager,
             skill_registry,
             preview_panel,
@@ -694,6 +808,8 @@ impl Default for AppState {
             refocus_input: false,
             onboarding_name: String::new(),
             pending_commands: Vec::new(),
+            command_plans: HashMap::new(),
+            last_command_snapshots: None,
             password_dialog: crate::modals::PasswordDialog::new("sudo_password"),
             pending_sudo_command: None,
             command_result_rx: None,
@@ -701,6 +817,7 @@ impl Default for AppState {
             mascot_loaded: false,
             ai_result_rx: None,
             ai_abort_handles: HashMap::new(),
+            shell_sessions: HashMap::new(),
             web_preview_service: Arc::new(WebPreviewService::new()),
             web_preview_rx: None,
             show_settings_dialog: false,
@@ -725,6 +842,13 @@ impl Default for AppState {
             session_output_tokens_est: 0,
             last_prompt_tokens_est: 0,
             last_response_tokens_est: 0,
+            token_tracker,
+            model_catalogs,
+            command_policy: load_command_policy(),
+            secret_scanner,
+            audit_logger,
+            security_context,
+            token_refresher: Arc::new(token_refresher),
 
             last_llm_provider: None,
             last_llm_model: None,
@@ -740,9 +864,14 @@ impl Default for AppState {
             cpu_high_since: None,
             cpu_nudge_dismissed: false,
             ollama_setup_rx: Some(ollama_rx),
+            model_catalog_rx: None,
             ai_status_rx: None,
+            ai_partial_rx: None,
+            ai_output_rx: None,
             oauth_result_rx: None,
             oauth_in_progress: false,
+            auth_event_rx,
+            reauth_notified: HashSet::new(),
         }
     }
 }
@@ -849,6 +978,71 @@ impl AppState {
                 h.push(chat_msg);
             }
         }
+
+        // Ollama has settled, so the local model list is accurate now.
+        self.start_model_catalog_refresh();
+    }
+
+    /// List the models each configured provider offers (reusing cached lists
+    /// younger than a day) and check the model names in settings against
+    /// them. Results arrive via `model_catalog_rx`.
+    pub fn start_model_catalog_refresh(&mut self) {
+        let (tx, rx) = channel::<ModelCatalogResult>();
+        self.model_catalog_rx = Some(rx);
+        let mut catalogs = (*self.model_catalogs).clone();
+        let config = self.settings.model.clone();
+        std::thread::spawn(move || {
+            if let Ok(rt) = tokio::runtime::Runtime::new() {
+                let errors =
+                    rt.block_on(catalogs.refresh(&config, providers::catalog::DEFAULT_MAX_AGE));
+                for (provider, e) in errors {
+                    tracing::debug!("model catalog: couldn't list {}: {}", provider, e);
+                }
+            }
+            if let Some(path) = model_catalog_path() {
+                let _ = catalogs.save(&path);
+            }
+            let issues = catalogs.validate(&config);
+            let _ = tx.send(ModelCatalogResult { catalogs, issues });
+        });
+    }
+
+    /// Poll for the model catalog refresh. Call once per frame. Model names
+    /// the provider doesn't offer are reported in the chat, since they would
+    /// otherwise only show up as failed requests.
+    pub fn poll_model_catalog(&mut self) {
+        let result = match &self.model_catalog_rx {
+            Some(rx) => rx.try_recv().ok(),
+            None => return,
+        };
+        let Some(result) = result else { return };
+        self.model_catalog_rx = None;
+        self.model_catalogs = Arc::new(result.catalogs);
+
+        if result.issues.is_empty() {
+            return;
+        }
+        let lines: Vec<String> = result
+            .issues
+            .iter()
+            .map(|issue| format!("- {}", issue))
+            .collect();
+        let chat_msg = ChatMessage {
+            role: "assistant".to_string(),
+            content: format!(
+                "Some model names in Settings don't match what your providers offer, \
+                so requests using them will fail:\n\n{}\n\n\
+                Open **Settings** to pick a listed model.",
+                lines.join("\n")
+            ),
+            details: None,
+            timestamp: chrono::Utc::now().format("%H:%M").to_string(),
+        };
+        for mode in [ChatMode::Find, ChatMode::Fix] {
+            if let Some(h) = self.mode_chat_histories.get_mut(&mode) {
+                h.push(chat_msg.clone());
+            }
+        }
     }
 
     /// Poll for background OAuth flow completion. Call once per frame.
@@ -867,21 +1061,74 @@ impl AppState {
             return;
         }
 
-        let oauth_creds = shared::settings::OAuthCredentials {
-            access_token: result.access_token,
-            refresh_token: result.refresh_token,
-            expires_at: Some(chrono::Utc::now().timestamp() + 3600), // ~1 hour
+        let Some(oauth_creds) = result.credentials else {
+            return;
         };
-
         if result.provider.as_str() == "gemini" {
             self.settings.model.gemini_auth.oauth = Some(oauth_creds);
         }
+        self.reauth_notified.remove(&result.provider);
 
         crate::utils::save_settings(&self.settings);
         self.settings_status = Some("Signed in with Google!".to_string());
         self.settings_status_is_error = false;
     }
 
+    /// Apply token refreshes from AI requests and report sign-ins that can no
+    /// longer be renewed. Call once per frame.
+    pub fn poll_auth_events(&mut self) {
+        while let Ok(event) = self.auth_event_rx.try_recv() {
+            match event {
+                AuthEvent::Refreshed {
+                    provider,
+                    credentials,
+                } => {
+                    let auth = match provider.as_str() {
+                        "openai" => &mut self.settings.model.openai_auth,
+                        "anthropic" => &mut self.settings.model.anthropic_auth,
+                        "gemini" => &mut self.settings.model.gemini_auth,
+                        _ => continue,
+                    };
+                    auth.oauth = Some(credentials);
+                    crate::utils::save_settings(&self.settings);
+                }
+                AuthEvent::ReauthRequired { provider, reason } => {
+                    tracing::warn!("{} sign-in expired: {}", provider, reason);
+                    if !self.reauth_notified.insert(provider.clone()) {
+                        continue;
+                    }
+                    let name = match provider.as_str() {
+                        "openai" => "OpenAI",
+                        "anthropic" => "Anthropic",
+                        "gemini" => "Gemini",
+                        other => other,
+                    };
+                    self.settings_status = Some(format!(
+                        "Your {} sign-in has expired. Please sign in again.",
+                        name
+                    ));
+                    self.settings_status_is_error = true;
+                    let chat_msg = ChatMessage {
+                        role: "assistant".to_string(),
+                        content: format!(
+                            "Your {} sign-in has expired and couldn't be renewed, so I'm \
+                            using other providers for now. Open **Settings** and sign in \
+                            again to use {}.",
+                            name, name
+                        ),
+                        details: None,
+                        timestamp: chrono::Utc::now().format("%H:%M").to_string(),
+                    };
+                    for mode in [ChatMode::Find, ChatMode::Fix] {
+                        if let Some(h) = self.mode_chat_histories.get_mut(&mode) {
+                            h.push(chat_msg.clone());
+                        }
+                    }
+                }
+            }
+        }
+    }
+
     fn ollama_reachable() -> bool {
         crate::ollama_manager::ollama_reachable()
     }
@@ -944,10 +1191,7 @@ impl AppState {
         const RESERVED_FOR_REPLY: u32 = 2_000;
         let budget = COMFORT_TOTAL_TOKENS.saturating_sub(RESERVED_FOR_REPLY);
 
-        let mut msgs: Vec<ApiChatMessage> = vec![ApiChatMessage {
-            role: "system".to_string(),
-            content: system_prompt,
-        }];
+        let mut msgs: Vec<ApiChatMessage> = vec![ApiChatMessage::system(system_prompt)];
 
         let mut used = Self::estimate_tokens(&msgs[0].content);
 
@@ -964,10 +1208,7 @@ impl AppState {
                 break;
             }
             used = used.saturating_add(t);
-            kept_rev.push(ApiChatMessage {
-                role: msg.role.clone(),
-                content: msg.content.clone(),
-            });
+            kept_rev.push(ApiChatMessage::new(msg.role.clone(), msg.content.clone()));
         }
 
         kept_rev.reverse();
@@ -1094,9 +1335,14 @@ impl AppState {
             self.preview_panel.show_mode_intro("build");
         }
 
+        let session_state = self.user_command_session();
         std::thread::spawn(move || {
-            let output = run_user_command(&command);
-            let _ = tx.send(CommandExecResult { command, output });
+            let output = run_user_command(&command, session_state, None);
+            let _ = tx.send(CommandExecResult {
+                command,
+                output,
+                snapshots: Vec::new(),
+            });
         });
     }
 
@@ -1129,6 +1375,19 @@ impl AppState {
         }
     }
 
+    /// The thread id in `slot`, starting a new thread if there is none.
+    /// Takes the field rather than `self` so callers can hold other borrows.
+    fn ensure_thread_id(slot: &mut Option<String>) -> String {
+        slot.get_or_insert_with(|| {
+            format!(
+                "{}-{}",
+                chrono::Utc::now().format("%Y%m%d-%H%M%S"),
+                std::process::id() % 10000
+            )
+        })
+        .clone()
+    }
+
     /// Sync the current chat into thread_history and save to disk.
     /// Call after adding a user or assistant message.
     pub fn sync_thread_history(&mut self, mode: ChatMode) {
@@ -1143,16 +1402,7 @@ impl AppState {
             return;
         }
 
-        // Get or create thread ID
-        let thread_id = self.current_thread_id.clone().unwrap_or_else(|| {
-            let id = format!(
-                "{}-{}",
-                chrono::Utc::now().format("%Y%m%d-%H%M%S"),
-                std::process::id() % 10000
-            );
-            self.current_thread_id = Some(id.clone());
-            id
-        });
+        let thread_id = Self::ensure_thread_id(&mut self.current_thread_id);
 
         // Find first user message for title generation
         let first_user_msg = history
@@ -1200,6 +1450,31 @@ impl AppState {
         self.thread_history.save_to_disk();
     }
 
+    /// Extract entities and relations from a finished thread into the
+    /// knowledge graph, in the background. Threads already extracted in
+    /// their current form are skipped.
+    pub fn extract_thread_entities(&self, thread_id: &str) {
+        let Some(thread) = self.thread_history.get_thread(thread_id) else {
+            return;
+        };
+        let job = agent_host::extraction::ExtractionJob::new(
+            format!("thread/{}", thread.id),
+            thread.transcript(),
+            Mode::from(thread.mode).into(),
+        );
+        if !self.context_manager.lock().needs_extraction(&job) {
+            return;
+        }
+        let extractor = agent_host::extraction::Extractor::for_settings(&self.settings.model);
+        let context_manager = self.context_manager.clone();
+        std::thread::spawn(move || {
+            if let Ok(rt) = tokio::runtime::Runtime::new() {
+                let extraction = rt.block_on(extractor.extract(&job));
+                context_manager.lock().apply_extraction(&job, &extraction);
+            }
+        });
+    }
+
     /// Load a thread from history back into the active chat.
     /// Switches mode if needed and closes the history panel.
     pub fn load_thread(&mut self, thread_id: &str) {
@@ -1245,6 +1520,53 @@ impl AppState {
                 }
             }
         }
+        if let Some(rx) = &self.ai_partial_rx {
+            // Each message is a full snapshot of the answer so far.
+            let mut latest: Option<String> = None;
+            while let Ok(partial) = rx.try_recv() {
+                latest = Some(partial);
+            }
+            if let Some(partial) = latest {
+                if let Some(mode) = self.thinking_mode {
+                    self.thinking_partial.insert(mode, partial);
+                }
+            }
+        }
+        if let (Some(rx), Some(mode)) = (&self.ai_output_rx, self.thinking_mode) {
+            while let Ok(event) = rx.try_recv() {
+                match event {
+                    CommandEvent::Output(text) => {
+                        let tail = self.command_output.entry(mode).or_default();
+                        tail.push_str(&text);
+                        if tail.len() > COMMAND_OUTPUT_TAIL {
+                            let mut cut = tail.len() - COMMAND_OUTPUT_TAIL;
+                            while !tail.is_char_boundary(cut) {
+                                cut += 1;
+                            }
+                            tail.drain(..cut);
+                        }
+                    }
+                    CommandEvent::Progress(percent) => {
+                        self.command_progress.insert(mode, percent);
+                    }
+                }
+            }
+        }
+    }
+
+    /// The mode's persistent shell while a command is running in it.
+    pub fn busy_shell(&self, mode: ChatMode) -> Option<Arc<ShellSession>> {
+        let slot = self.shell_sessions.get(&mode)?.lock();
+        slot.as_ref().filter(|shell| shell.is_busy()).cloned()
+    }
+
+    /// Kill the mode's persistent shell; the next command starts a fresh one.
+    pub fn kill_shell(&mut self, mode: ChatMode) {
+        if let Some(slot) = self.shell_sessions.get(&mode) {
+            if let Some(shell) = slot.lock().take() {
+                shell.kill();
+            }
+        }
     }
 
     pub fn poll_ai_response(&mut self) {
@@ -1257,12 +1579,17 @@ impl AppState {
                 if let Some(mode) = self.thinking_mode {
                     self.is_thinking.insert(mode, false);
                     self.thinking_status.insert(mode, String::new());
+                    self.thinking_partial.remove(&mode);
                     self.ai_abort_handles.remove(&mode);
                     self.thinking_started_at.remove(&mode);
+                    self.command_output.remove(&mode);
+                    self.command_progress.remove(&mode);
                     self.slow_response_hint_shown.remove(&mode);
                 }
                 self.thinking_mode = None;
                 self.ai_status_rx = None;
+                self.ai_partial_rx = None;
+                self.ai_output_rx = None;
                 self.show_model_hint = false;
                 self.model_hint_started_at = None;
                 self.ai_result_rx = None;
@@ -1273,6 +1600,15 @@ impl AppState {
                     self.active_viewer = ActiveViewer::Panel;
                 }
 
+                // Swap the prompt estimate made when sending for the real count.
+                if let Some(usage) = result.usage {
+                    self.session_input_tokens_est = self
+                        .session_input_tokens_est
+                        .saturating_sub(self.last_prompt_tokens_est as u64)
+                        .saturating_add(usage.input_tokens as u64);
+                }
+                self.surface_budget_alerts(response_mode.unwrap_or(self.current_mode));
+
                 if let Some(error) = result.error {
                     self.pending_commands.clear();
 
@@ -1482,7 +1818,10 @@ What to do next:\n\
                         }
                     }
 
-                    self.last_response_tokens_est = Self::estimate_tokens(&result.response);
+                    self.last_response_tokens_est = match result.usage {
+                        Some(usage) => usage.output_tokens,
+                        None => Self::estimate_tokens(&result.response),
+                    };
                     self.session_output_tokens_est = self
                         .session_output_tokens_est
                         .saturating_add(self.last_response_tokens_est as u64);
@@ -1529,6 +1868,12 @@ What to do next:\n\
                                     (result.llm_duration_ms as f64) / 1000.0
                                 ));
                             }
+                            if let Some(usage) = result.usage {
+                                lines.push(format!(
+                                    "Tokens: {} in / {} out",
+                                    usage.input_tokens, usage.output_tokens
+                                ));
+                            }
                             if lines.is_empty() {
                                 None
                             } else {
@@ -1555,15 +1900,43 @@ What to do next:\n\
         }
     }
 
+    /// Post any budget alerts raised by the last request and save usage.
+    fn surface_budget_alerts(&mut self, mode: ChatMode) {
+        for alert in self.token_tracker.take_alerts() {
+            self.push_chat_to(
+                mode,
+                ChatMessage {
+                    role: "assistant".to_string(),
+                    content: alert.message(),
+                    details: None,
+                    timestamp: chrono::Utc::now().format("%H:%M").to_string(),
+                },
+            );
+        }
+        if let Some(path) = token_usage_path() {
+            if let Some(parent) = path.parent() {
+                let _ = std::fs::create_dir_all(parent);
+            }
+            let _ = self.token_tracker.save_history(&path);
+        }
+    }
+
     pub fn poll_command_result(&mut self) {
         if let Some(rx) = &self.command_result_rx {
             if let Ok(result) = rx.try_recv() {
                 self.command_result_rx = None;
+                self.ai_output_rx = None;
+                if !result.snapshots.is_empty() {
+                    self.last_command_snapshots =
+                        Some((result.command.clone(), result.snapshots.clone()));
+                }
                 // Clear thinking state for the mode that was processing
                 let active_mode = self.thinking_mode;
                 if let Some(mode) = active_mode {
                     self.is_thinking.insert(mode, false);
                     self.thinking_status.insert(mode, String::new());
+                    self.command_output.remove(&mode);
+                    self.command_progress.remove(&mode);
                 }
                 self.thinking_mode = None;
 
@@ -1711,7 +2084,9 @@ What to do next:\n\
 
     pub fn approve_command(&mut self, command: String) {
         self.pending_commands.retain(|c| c != &command);
-        if let Err(reason) = validate_command_against_allowed(&command, &self.settings.allowed_dirs)
+        let session_state = self.user_command_session();
+        if let Err(reason) =
+            check_command_paths(&command, &session_state, &self.settings.allowed_dirs)
         {
             self.push_chat(ChatMessage {
                 role: "assistant".to_string(),
@@ -1728,7 +2103,7 @@ What to do next:\n\
         }
 
         // Check if command needs sudo
-        let danger_level = classify_command(&command);
+        let danger_level = session_state.classify(&command);
         eprintln!(
             "DEBUG: Command '{}' classified as {:?}",
             command, danger_level
@@ -1770,12 +2145,125 @@ What to do next:\n\
         self.thinking_status
             .insert(self.current_mode, format!("Running {}", command));
 
+        let (output_tx, output_rx) = channel::<CommandEvent>();
+        self.ai_output_rx = Some(output_rx);
+        self.command_output.remove(&self.current_mode);
+        self.command_progress.remove(&self.current_mode);
+
+        let session_state = self.user_command_session().with_output_events(output_tx);
+        let shell = self.user_command_shell();
         std::thread::spawn(move || {
-            let output = run_user_command(&command);
-            let _ = tx.send(CommandExecResult { command, output });
+            // Save what the command would change, so the run can be undone.
+            let snapshots = dry_run::plan_command(&command, &session_state.cwd).snapshot();
+            let output = run_user_command(&command, session_state, shell);
+            let _ = tx.send(CommandExecResult {
+                command,
+                output,
+                snapshots,
+            });
+        });
+    }
+
+    /// Dry-run plan of a pending command, made on first use.
+    pub fn command_plan(&mut self, command: &str) -> &CommandPlan {
+        if !self.command_plans.contains_key(command) {
+            let plan = dry_run::plan_command(command, &self.user_command_session().cwd);
+            self.command_plans.insert(command.to_string(), plan);
+        }
+        &self.command_plans[command]
+    }
+
+    /// Show what a pending command would change in the preview panel.
+    pub fn preview_command_plan(&mut self, command: &str) {
+        let content = self.command_plan(command).preview();
+        self.preview_panel.show_content(content);
+        self.active_viewer = ActiveViewer::Panel;
+        self.show_preview = true;
+    }
+
+    /// Put back the files the last approved command changed.
+    pub fn restore_last_command(&mut self) {
+        let Some((command, snapshots)) = self.last_command_snapshots.take() else {
+            return;
+        };
+        let content = match dry_run::restore_snapshots(&snapshots) {
+            Ok(restored) => format!(
+                "Restored {} {} from before `{}`.",
+                restored,
+                if restored == 1 { "file" } else { "files" },
+                command
+            ),
+            Err(e) => format!(
+                "Some files from before `{}` couldn't be restored: {}",
+                command, e
+            ),
+        };
+        self.push_chat(ChatMessage {
+            role: "assistant".to_string(),
+            content,
+            details: None,
+            timestamp: chrono::Utc::now().format("%H:%M").to_string(),
         });
     }
 
+    /// Approve `command` and remember it in the command policy, so the
+    /// agent runs it without asking next time.
+    pub fn always_allow_command(&mut self, command: String) {
+        self.command_policy.allow_always(&command);
+        if let Some(path) = command_policy_path() {
+            if let Err(e) = self.command_policy.save(&path) {
+                tracing::warn!("failed to save command policy: {:#}", e);
+            }
+        }
+        self.approve_command(command);
+    }
+
+    /// Session for running a command the user approved: confined like the
+    /// agent's commands, subject to the same command policy, and audited
+    /// under the current conversation.
+    fn user_command_session(&self) -> SessionState {
+        let mut session_state = SessionState::new()
+            .with_sandbox(command_sandbox(&self.settings.allowed_dirs))
+            .with_policy(Arc::new(self.command_policy.clone()))
+            .with_mode(self.current_mode.into())
+            .with_secret_scanner(self.secret_scanner.clone())
+            .with_audit(self.audit_logger.clone())
+            .with_security_context(self.security_context.clone());
+        if let Some(thread_id) = &self.current_thread_id {
+            session_state = session_state.with_session_id(thread_id.clone());
+        }
+        if let Some(sandbox) = ProcessSandbox::for_settings(&self.settings) {
+            session_state = session_state.with_process_sandbox(sandbox);
+        } else {
+            // Approved commands run in the mode's persistent shell, so they
+            // start where the agent's commands left off.
+            let live = self
+                .shell_sessions
+                .get(&self.current_mode)
+                .and_then(|slot| slot.lock().clone())
+                .filter(|shell| shell.is_alive());
+            session_state.cwd = match live {
+                Some(shell) => shell.cwd(),
+                None => shell_start_dir(&self.settings.allowed_dirs),
+            };
+        }
+        session_state
+    }
+
+    /// The current mode's persistent shell slot, for running an approved
+    /// command. Hardened execution runs each command in its own sandbox,
+    /// so there is none then.
+    fn user_command_shell(&mut self) -> Option<ShellSlot> {
+        ProcessSandbox::for_settings(&self.settings)
+            .is_none()
+            .then(|| {
+                self.shell_sessions
+                    .entry(self.current_mode)
+                    .or_default()
+                    .clone()
+            })
+    }
+
     /// Execute a sudo command with the provided password
     pub fn execute_sudo_command(&mut self, command: String, password: String) {
         #[cfg(windows)]
@@ -1820,7 +2308,11 @@ What to do next:\n\
                     }),
                     Err(e) => Err(format!("Failed to create runtime: {}", e)),
                 };
-                let _ = tx.send(CommandExecResult { command, output });
+                let _ = tx.send(CommandExecResult {
+                    command,
+                    output,
+                    snapshots: Vec::new(),
+                });
             });
         }
     }
@@ -1972,6 +2464,9 @@ What to do next:\n\
 
         // Add user message to chat
         let mut content = self.input_text.clone();
+        // Images ride along on this turn's API message only; the chat history
+        // keeps the path reference.
+        let mut outgoing_attachment = None;
 
         if let Some(path) = self.attached_file.take() {
             // Attach a small excerpt inline so the model can summarize without requiring terminal access.
@@ -1984,6 +2479,8 @@ What to do next:\n\
                 content.push_str("\n\nExcerpt:\n```");
                 content.push_str(&excerpt);
                 content.push_str("\n```\n");
+            } else if let Ok(image @ Attachment::Image { .. }) = Attachment::from_path(&path) {
+                outgoing_attachment = Some(image);
             } else {
                 content.push_str("\n\n(For now I can only auto-extract text from simple text files. I can still open/preview this file.)\n");
             }
@@ -2129,21 +2626,18 @@ IMPORTANT: Always use -iname (case-insensitive) with *wildcards* (e.g. *tax* not
             // Use query + mode as search terms
             let search_query = format!("{} {}", self.current_mode.as_str(), self.input_text);
 
-            // Limit to 3 relevant snippets to keep prompt fast
-            let results = cm.search(&search_query, None);
+            // Best passages only, capped at ~400 tokens to keep prompt fast
+            let passages = cm.search_passages(&search_query, None, 8);
+            let passages =
+                agent_host::context_manager::ContextManager::format_passages(&passages, 400);
 
-            if results.is_empty() {
+            if passages.is_empty() {
                 String::new()
             } else {
-                let mut ctx = String::from("\n\nRELEVANT CONTEXT (from your knowledge graph):\n");
-                for res in results.into_iter().take(3) {
-                    ctx.push_str(&format!("- [{}]", res.document.name));
-                    // Use excerpts as the content summary
-                    for excerpt in res.excerpts {
-                        ctx.push_str(&format!("\n  > {}\n", excerpt.trim()));
-                    }
-                }
-                ctx
+                format!(
+                    "\n\nRELEVANT CONTEXT (from your knowledge graph):\n{}",
+                    passages
+                )
             }
         };
 
@@ -2307,8 +2801,13 @@ WORKFLOW:
             },
         };
 
-        let (api_messages, prompt_tokens_est, dropped) =
+        let (mut api_messages, prompt_tokens_est, dropped) =
             self.build_api_messages_with_budget(system_prompt);
+        if let Some(attachment) = outgoing_attachment {
+            if let Some(last) = api_messages.last_mut().filter(|m| m.role == "user") {
+                last.attachments.push(attachment);
+            }
+        }
 
         self.last_prompt_tokens_est = prompt_tokens_est;
         self.session_input_tokens_est = self
@@ -2334,7 +2833,7 @@ WORKFLOW:
     }
 
     /// Spawn the background AI generation thread and wire up all the channels.
-    /// Sets up abort handles so the user can cancel, and wraps the generation in
+    /// Registers a cancellation token so the user can stop it, and wraps the generation in
     /// `catch_unwind` so a panic in the AI pipeline doesn't crash the app.
     pub fn start_ai_generation(
         &mut self,
@@ -2349,10 +2848,19 @@ WORKFLOW:
         let (status_tx, status_rx) = channel::<String>();
         self.ai_status_rx = Some(status_rx);
 
+        let (partial_tx, partial_rx) = channel::<String>();
+        self.ai_partial_rx = Some(partial_rx);
+
+        let (output_tx, output_rx) = channel::<CommandEvent>();
+        self.ai_output_rx = Some(output_rx);
+
         let mode = self.thinking_mode.unwrap_or(self.current_mode);
+        self.thinking_partial.remove(&mode);
+        self.command_output.remove(&mode);
+        self.command_progress.remove(&mode);
 
-        let (abort_handle, abort_reg) = futures::future::AbortHandle::new_pair();
-        self.ai_abort_handles.insert(mode, abort_handle);
+        let cancel = CancellationToken::new();
+        self.ai_abort_handles.insert(mode, cancel.clone());
         // Set thinking status for the mode that initiated the request (unless already set)
         if let Some(mode) = self.thinking_mode {
             let current = self.thinking_status.get(&mode).cloned().unwrap_or_default();
@@ -2373,7 +2881,20 @@ WORKFLOW:
 
         let settings = self.settings.model.clone();
         let allowed_dirs = self.settings.allowed_dirs.clone();
+        let process_sandbox = ProcessSandbox::for_settings(&self.settings);
+        let command_policy = Arc::new(self.command_policy.clone());
+        let secret_scanner = self.secret_scanner.clone();
+        let audit_logger = self.audit_logger.clone();
+        let security_context = self.security_context.clone();
+        // Hardened execution runs every command in its own sandbox, so
+        // there is no long-lived shell to keep.
+        let shell = (process_sandbox.is_none() && allow_terminal)
+            .then(|| self.shell_sessions.entry(mode).or_default().clone());
         let skill_registry = self.skill_registry.clone();
+        let model_catalogs = self.model_catalogs.clone();
+        let token_refresher = self.token_refresher.clone();
+        let token_tracker = self.token_tracker.clone();
+        let conversation_id = Self::ensure_thread_id(&mut self.current_thread_id);
 
         // Spawn background thread for AI work
         std::thread::spawn(move || {
@@ -2387,10 +2908,22 @@ WORKFLOW:
                     research_depth,
                     mode.into(),
                     allowed_dirs,
+                    process_sandbox,
+                    shell,
+                    command_policy,
+                    secret_scanner,
+                    audit_logger,
+                    security_context,
                     Arc::new(skill_registry),
+                    model_catalogs,
+                    token_refresher,
+                    token_tracker,
+                    conversation_id,
                     tx,
                     status_tx,
-                    abort_reg,
+                    partial_tx,
+                    output_tx,
+                    cancel,
                 );
             }));
             if res.is_err() {
@@ -2412,6 +2945,7 @@ WORKFLOW:
                     fallback: None,
                     llm_calls: 0,
                     llm_duration_ms: 0,
+                    usage: None,
                     executed_commands: Vec::new(),
                     pending_commands: Vec::new(),
                 });
@@ -2419,12 +2953,12 @@ WORKFLOW:
         });
     }
 
-    /// Cancel an in-flight AI request by aborting the future.
-    /// The background thread will receive an `Aborted` error and send back
-    /// a "Cancelled" result, which poll_ai_response handles gracefully.
+    /// Stop an in-flight AI request. The pending LLM call is aborted and any
+    /// running command is killed; the background thread then sends back what
+    /// was produced so far with a "stopped" note, handled like any reply.
     pub fn cancel_ai(&mut self, mode: ChatMode) {
-        if let Some(handle) = self.ai_abort_handles.remove(&mode) {
-            handle.abort();
+        if let Some(token) = self.ai_abort_handles.remove(&mode) {
+            token.cancel();
         }
         self.thinking_status.insert(mode, "Stopping...".to_string());
     }
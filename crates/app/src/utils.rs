@@ -4,21 +4,19 @@
 //! UI component:
 //!
 //! - **Path safety**: Expanding `~`, validating paths against allowed directories,
-//!   blocking access to sensitive locations (`.ssh`, `.aws`, `.gnupg`, etc.)
-//! - **Shell injection prevention**: Blocking command chaining operators (`;`, `&&`,
-//!   `||`, backticks, `$()`) outside of quoted strings
+//!   and building the agent's `PathSandbox` for commands the user runs
 //! - **Settings I/O**: Loading, saving, and migrating settings (including automatic
 //!   migration of retired model names like Gemini 1.x to current equivalents)
 //! - **Bundled tool management**: Auto-installing the Spec Kit Assistant from a
 //!   compiled-in tarball on first run
 
+use crate::state::persistent_shell;
+use crate::types::ShellSlot;
+use agent_host::executor::SessionState;
+use agent_host::security::PathSandbox;
 use agent_host::CommandResult;
 use shared::settings::AppSettings;
 use std::path::{Path, PathBuf};
-use std::sync::OnceLock;
-
-/// Cached regex for extracting file paths from shell commands (compiled once).
-static COMMAND_PATH_REGEX: OnceLock<regex::Regex> = OnceLock::new();
 
 /// The Spec Kit Assistant tarball, embedded at compile time. Unpacked on first run
 /// into the user's config directory so Build mode works out of the box.
@@ -91,127 +89,6 @@ fn try_install_bundled_speckit(settings: &mut AppSettings) -> bool {
     true
 }
 
-/// Scan a command string for dangerous shell operators that could enable injection.
-/// Returns the first forbidden operator found, or None if the command is clean.
-///
-/// Allowed: pipes (`|`), simple redirects (`>`, `2>&1`)
-/// Blocked: `;`, `&&`, `||`, backticks, `$()`, heredocs (`<<`)
-///
-/// Quoted strings are skipped -- operators inside quotes are harmless.
-fn contains_forbidden_shell_ops(command: &str) -> Option<&'static str> {
-    let mut in_single = false;
-    let mut in_double = false;
-    let mut prev = '\0';
-    let mut chars = command.chars().peekable();
-
-    while let Some(c) = chars.next() {
-        if c == '\\' {
-            // Skip escaped char
-            let _ = chars.next();
-            prev = c;
-            continue;
-        }
-        if !in_double && c == '\'' {
-            in_single = !in_single;
-            prev = c;
-            continue;
-        }
-        if !in_single && c == '"' {
-            in_double = !in_double;
-            prev = c;
-            continue;
-        }
-
-        if in_single || in_double {
-            prev = c;
-            continue;
-        }
-
-        // Multi-command chaining
-        if c == ';' {
-            return Some(";");
-        }
-        if c == '&' {
-            if chars.peek().copied() == Some('&') {
-                return Some("&&");
-            }
-            // Allow 2>&1 only
-            if prev.is_ascii_digit() && chars.peek().copied() == Some('>') {
-                // ok
-            } else {
-                return Some("&");
-            }
-        }
-        if c == '|' && chars.peek().copied() == Some('|') {
-            return Some("||");
-        }
-        // Substitution / heredocs
-        if c == '`' {
-            return Some("`");
-        }
-        if c == '$' && chars.peek().copied() == Some('(') {
-            return Some("$()");
-        }
-        if c == '<' && chars.peek().copied() == Some('<') {
-            return Some("<<");
-        }
-
-        prev = c;
-    }
-
-    None
-}
-
-/// Strip glob wildcards from a path, returning only the concrete directory prefix.
-/// Used when validating command paths -- we need the real directory to check
-/// against allowed dirs, even if the command uses wildcards like `~/Documents/*.pdf`.
-fn strip_glob_prefix(path: &str) -> &str {
-    let wildcard_pos = path.find(['*', '?', '[', ']']).unwrap_or(path.len());
-    if wildcard_pos == path.len() {
-        return path;
-    }
-
-    // Trim to the last separator before the wildcard
-    let prefix = &path[..wildcard_pos];
-    let sep_pos = prefix.rfind(['/', '\\']).unwrap_or(0);
-    if sep_pos == 0 {
-        prefix
-    } else {
-        &prefix[..sep_pos]
-    }
-}
-
-fn normalize_windows_env_vars(s: &str) -> String {
-    // Best-effort expansion for common Windows env vars used in example commands.
-    let mut out = s.to_string();
-    if out.contains("%USERNAME%") {
-        if let Ok(user) = std::env::var("USERNAME") {
-            out = out.replace("%USERNAME%", &user);
-        }
-    }
-    out
-}
-
-/// Check if a path touches a well-known sensitive location (SSH keys, AWS
-/// credentials, GPG keyrings, keychains, .npmrc, .env files). These are
-/// blocked regardless of the allowed-dirs setting to prevent accidental
-/// credential exposure.
-fn is_sensitive_path(path: &Path) -> bool {
-    let s = path.to_string_lossy().to_lowercase();
-    s.contains("/.ssh/")
-        || s.contains("\\\\.ssh\\\\")
-        || s.contains("/.aws/")
-        || s.contains("\\\\.aws\\\\")
-        || s.contains("/.gnupg/")
-        || s.contains("\\\\.gnupg\\\\")
-        || s.contains("/library/keychains")
-        || s.contains("\\\\library\\\\keychains")
-        || s.ends_with("/.npmrc")
-        || s.ends_with("\\\\.npmrc")
-        || s.ends_with("/.env")
-        || s.ends_with("\\\\.env")
-}
-
 /// Expand a path string that may start with ~ to the full home directory path
 pub fn expand_user_path(path_str: &str) -> PathBuf {
     if let Some(stripped) = path_str.strip_prefix("~/") {
@@ -236,16 +113,27 @@ pub fn is_path_in_allowed_dirs(path: &Path, allowed_dirs: &[String]) -> bool {
     })
 }
 
-/// Run a user command using the agent_host
-pub fn run_user_command(command: &str) -> Result<CommandResult, String> {
+/// Run a user command using the agent_host in `session_state`, which
+/// carries the process sandbox and command policy (see
+/// `AppState::user_command_session`). With a `shell` slot the command runs
+/// in the mode's persistent shell, starting one in `session_state.cwd` if
+/// needed.
+pub fn run_user_command(
+    command: &str,
+    mut session_state: agent_host::executor::SessionState,
+    shell: Option<ShellSlot>,
+) -> Result<CommandResult, String> {
     let runtime = tokio::runtime::Runtime::new().map_err(|e| e.to_string())?;
-    let mut session_state = agent_host::executor::SessionState::new();
     runtime
-        .block_on(agent_host::executor::execute_command(
-            command,
-            60,
-            &mut session_state,
-        ))
+        .block_on(async {
+            if let Some(slot) = shell {
+                if let Some(shell) = persistent_shell(&slot, &session_state.cwd).await {
+                    session_state.cwd = shell.cwd();
+                    session_state = session_state.with_shell(shell);
+                }
+            }
+            agent_host::executor::execute_command(command, 60, &mut session_state).await
+        })
         .map_err(|e| e.to_string())
 }
 
@@ -263,6 +151,49 @@ pub fn config_path() -> Option<std::path::PathBuf> {
     })
 }
 
+/// Path of the saved token usage history (`little_helper/token_usage.json`)
+pub fn token_usage_path() -> Option<std::path::PathBuf> {
+    config_path().map(|p| p.with_file_name("token_usage.json"))
+}
+
+/// Token tracker seeded with the saved usage history, if any.
+pub fn load_token_tracker() -> agent_host::token_tracker::TokenTracker {
+    let tracker = agent_host::token_tracker::TokenTracker::new();
+    if let Some(path) = token_usage_path().filter(|p| p.exists()) {
+        let _ = tracker.load_history(&path);
+    }
+    tracker
+}
+
+/// Path of the cached model catalogs (`little_helper/model_catalog.json`)
+pub fn model_catalog_path() -> Option<std::path::PathBuf> {
+    config_path().map(|p| p.with_file_name("model_catalog.json"))
+}
+
+/// Path of the user's command policy (`little_helper/command_policy.json`)
+pub fn command_policy_path() -> Option<std::path::PathBuf> {
+    config_path().map(|p| p.with_file_name("command_policy.json"))
+}
+
+/// The saved command policy, or the default (built-in lists only) if there
+/// is none. A policy file that fails to parse is reported and ignored.
+pub fn load_command_policy() -> agent_host::command_policy::CommandPolicy {
+    let Some(path) = command_policy_path().filter(|p| p.exists()) else {
+        return Default::default();
+    };
+    agent_host::command_policy::CommandPolicy::load(&path).unwrap_or_else(|e| {
+        tracing::warn!("ignoring command policy: {:#}", e);
+        Default::default()
+    })
+}
+
+/// Model catalogs from the last run, or empty ones if none were saved.
+pub fn load_model_catalogs() -> providers::catalog::ModelCatalogs {
+    model_catalog_path()
+        .and_then(|p| providers::catalog::ModelCatalogs::load(&p).ok())
+        .unwrap_or_default()
+}
+
 /// Load settings from disk, applying migrations and bundled-tool setup.
 /// Returns `(settings, true)` if a saved config was found, or `(defaults, false)`
 /// for first-run. Also supports "seed settings" for bespoke builds -- a
@@ -488,68 +419,34 @@ pub fn normalize_allowed_dir_input(input: &str) -> Option<PathBuf> {
     }
 }
 
-/// Validate a command against the allowed-directory sandbox and security rules.
-/// Checks three things in order:
-/// 1. At least one allowed directory is configured
-/// 2. No forbidden shell operators (injection prevention)
-/// 3. All file paths in the command fall within allowed directories
-///
-/// Returns `Ok(())` if safe, or `Err(reason)` with a user-friendly explanation.
-pub fn validate_command_against_allowed(
+/// The filesystem sandbox for the user's allowed folders, as `AgentHost`
+/// attaches to its own session.
+pub fn command_sandbox(allowed_dirs: &[String]) -> PathSandbox {
+    PathSandbox::new(allowed_dirs.iter().map(|d| expand_user_path(d)).collect())
+}
+
+/// Check `command` against the session's sandbox before it is shown or
+/// run, so the user isn't asked to approve (or type a password for) a
+/// command that would be refused. Returns a user-friendly reason.
+pub fn check_command_paths(
     command: &str,
+    session_state: &SessionState,
     allowed_dirs: &[String],
 ) -> Result<(), String> {
     if allowed_dirs.is_empty() {
         return Err("No folders are allowed. Add one in Settings first.".to_string());
     }
 
-    if let Some(op) = contains_forbidden_shell_ops(command) {
-        return Err(format!(
-            "This command includes a blocked shell feature ({}). Please run one step at a time.",
-            op
-        ));
-    }
-
     // Block environment dumps (high risk for accidental secret exposure)
     let cmd_trim = command.trim().to_lowercase();
     if cmd_trim == "env" || cmd_trim.starts_with("env ") || cmd_trim == "printenv" {
         return Err("For privacy, printing all environment variables is blocked.".to_string());
     }
 
-    let regex = COMMAND_PATH_REGEX.get_or_init(|| {
-        regex::Regex::new(r#"(?P<path>(?:~|/|\./|\.\./|[A-Za-z]:\\)[^\s"'`]+)"#).unwrap()
-    });
-
-    for capture in regex.captures_iter(command) {
-        if let Some(path_match) = capture.name("path") {
-            let raw = path_match.as_str();
-            let raw = normalize_windows_env_vars(raw);
-            let raw = strip_glob_prefix(raw.as_str());
-            let candidate = expand_user_path(raw);
-
-            // Sensitive locations are blocked by default.
-            if is_sensitive_path(&candidate) {
-                return Err(format!(
-                    "This command touches a sensitive path (`{}`). For safety, Little Helper blocks this by default.",
-                    raw
-                ));
-            }
-
-            // If the path doesn't exist (e.g. redirect target), validate its parent.
-            let to_check = if candidate.exists() {
-                candidate
-            } else {
-                candidate
-                    .parent()
-                    .map(|p| p.to_path_buf())
-                    .unwrap_or(candidate)
-            };
-
-            if !is_path_in_allowed_dirs(&to_check, allowed_dirs) {
-                return Err(format!("Path `{}` is outside the allowed folders.", raw));
-            }
-        }
+    match &session_state.sandbox {
+        Some(sandbox) => sandbox
+            .validate_command(command, &session_state.cwd)
+            .map_err(|violation| violation.to_string()),
+        None => Ok(()),
     }
-
-    Ok(())
 }
@@ -1,7 +1,8 @@
 //! Audit log viewer widget for settings screen.
 //!
-//! Displays file operations and skill executions to the primary user.
-//! Helps users understand what changes Little Helper has made.
+//! Displays file operations, skill executions and security events to the
+//! primary user. Helps users understand what changes Little Helper has
+//! made, and what it tried to do but was stopped from doing.
 
 use chrono::{DateTime, Local, Utc};
 use egui;
@@ -14,6 +15,8 @@ pub struct AuditViewerFilter {
     pub file_ops_only: bool,
     /// Show only errors
     pub errors_only: bool,
+    /// Show only security events (refused commands, 2FA attempts)
+    pub security_only: bool,
     /// Filter by skill ID
     pub skill_filter: Option<String>,
 }
@@ -64,6 +67,9 @@ impl AuditViewer {
                 if self.filter.errors_only && !matches!(e.event_type, EventType::Error) {
                     return false;
                 }
+                if self.filter.security_only && !matches!(e.event_type, EventType::Security) {
+                    return false;
+                }
                 // Apply skill filter
                 if let Some(ref skill) = self.filter.skill_filter {
                     if e.skill_id.as_deref() != Some(skill.as_str()) {
@@ -74,6 +80,10 @@ impl AuditViewer {
                 if !self.search_text.is_empty() {
                     let search = self.search_text.to_lowercase();
                     let matches = e.action.to_lowercase().contains(&search)
+                        || e.security.as_ref().map_or(false, |s| {
+                            s.rule.as_ref().map_or(false, |r| r.to_lowercase().contains(&search))
+                                || s.session_id.as_ref().map_or(false, |id| id.to_lowercase().contains(&search))
+                        })
                         || e.skill_id.as_ref().map_or(false, |s| s.to_lowercase().contains(&search))
                         || e.file_path.as_ref().map_or(false, |p| p.to_string_lossy().to_lowercase().contains(&search));
                     if !matches {
@@ -118,6 +128,7 @@ impl AuditViewer {
                 // Type filters
                 ui.checkbox(&mut self.filter.file_ops_only, "File ops only");
                 ui.checkbox(&mut self.filter.errors_only, "Errors only");
+                ui.checkbox(&mut self.filter.security_only, "Security events");
             });
 
             ui.add_space(12.0);
@@ -133,7 +144,11 @@ impl AuditViewer {
                             .weak()
                             .size(16.0)
                     );
-                    if !self.search_text.is_empty() || self.filter.file_ops_only || self.filter.errors_only {
+                    if !self.search_text.is_empty()
+                        || self.filter.file_ops_only
+                        || self.filter.errors_only
+                        || self.filter.security_only
+                    {
                         ui.add_space(8.0);
                         if ui.button("Clear filters").clicked() {
                             self.search_text.clear();
@@ -187,6 +202,7 @@ impl AuditViewer {
                         EventType::FileOp => ("", egui::Color32::from_rgb(100, 150, 200)),
                         EventType::PermChange => ("", egui::Color32::from_rgb(200, 180, 100)),
                         EventType::Error => ("", egui::Color32::from_rgb(200, 100, 100)),
+                        EventType::Security => ("", egui::Color32::from_rgb(220, 130, 60)),
                     };
                     ui.colored_label(icon_color, icon);
 
@@ -201,6 +217,11 @@ impl AuditViewer {
                                 })
                         );
 
+                        // Matched rule, for security events
+                        if let Some(rule) = entry.security.as_ref().and_then(|s| s.rule.as_ref()) {
+                            ui.label(egui::RichText::new(rule).small().weak());
+                        }
+
                         // Details line
                         ui.horizontal(|ui| {
                             // Skill name
@@ -213,6 +234,12 @@ impl AuditViewer {
                                 ui.label(egui::RichText::new("•").small().weak());
                             }
 
+                            // Session the security event happened in
+                            if let Some(ref session) = entry.security.as_ref().and_then(|s| s.session_id.clone()) {
+                                ui.label(egui::RichText::new(format!("session {}", session)).small().weak());
+                                ui.label(egui::RichText::new("•").small().weak());
+                            }
+
                             // File path (truncated)
                             if let Some(ref path) = entry.file_path {
                                 let path_str = path.to_string_lossy();
@@ -277,4 +304,27 @@ mod tests {
         let filtered = viewer.filtered_entries();
         assert_eq!(filtered.len(), 1);
     }
+
+    #[test]
+    fn test_security_filter() {
+        use shared::events::{SecurityEvent, SecurityEventKind};
+
+        let entries = vec![
+            AuditEntry::skill_execution("skill_a", "Action A", None),
+            AuditEntry::security(
+                SecurityEvent::new(SecurityEventKind::SandboxViolation)
+                    .with_command("cat /etc/shadow")
+                    .with_rule("/etc/shadow")
+                    .with_session_id("thread-1"),
+            ),
+        ];
+        let mut viewer = AuditViewer::new(entries);
+
+        viewer.filter.security_only = true;
+        assert_eq!(viewer.filtered_entries().len(), 1);
+
+        viewer.filter.security_only = false;
+        viewer.search_text = "thread-1".to_string();
+        assert_eq!(viewer.filtered_entries().len(), 1);
+    }
 }
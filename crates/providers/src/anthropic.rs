@@ -1,16 +1,24 @@
 //! Anthropic Messages API client.
 //!
-//! Anthropic differs from OpenAI in two key ways:
+//! Anthropic differs from OpenAI in three key ways:
 //! 1. System messages are a separate top-level `system` field, not a message role.
 //! 2. Auth uses `x-api-key` header plus a required `anthropic-version` header.
+//! 3. Tool calls and results are `tool_use` / `tool_result` content blocks inside
+//!    assistant and user messages rather than separate fields and roles.
 //!
-//! The [`generate`](AnthropicClient::generate) method handles both transformations
+//! The [`generate`](AnthropicClient::generate) method handles these transformations
 //! transparently so callers can pass a uniform `Vec<ChatMessage>`.
+//! [`generate_stream`](AnthropicClient::generate_stream) sends the same request with
+//! `stream: true` and forwards each `content_block_delta` event.
 
+use crate::capabilities::supports_vision;
+use crate::catalog::ModelInfo;
+use crate::stream::{for_each_line, sse_data, status_error};
+use crate::usage::Usage;
 use anyhow::{anyhow, Result};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use shared::agent_api::ChatMessage;
+use shared::agent_api::{Attachment, ChatMessage, ToolCall, ToolSpec};
 use shared::settings::ProviderAuth;
 use std::env;
 use std::sync::LazyLock;
@@ -32,32 +40,169 @@ struct AnthropicRequest {
     #[serde(skip_serializing_if = "Option::is_none")]
     system: Option<String>,
     messages: Vec<AnthropicMessage>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<AnthropicTool>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    stream: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 struct AnthropicMessage {
     role: String,
-    content: String,
+    content: AnthropicContent,
 }
 
+/// Plain text for ordinary turns, content blocks once tools are involved.
 #[derive(Debug, Serialize, Deserialize)]
-struct AnthropicContent {
+#[serde(untagged)]
+enum AnthropicContent {
+    Text(String),
+    Blocks(Vec<AnthropicBlock>),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum AnthropicBlock {
+    Text {
+        text: String,
+    },
+    ToolUse {
+        id: String,
+        name: String,
+        input: serde_json::Value,
+    },
+    ToolResult {
+        tool_use_id: String,
+        content: String,
+    },
+    Image {
+        source: AnthropicImageSource,
+    },
+    /// Block types we don't act on (e.g. `thinking`).
+    #[serde(other)]
+    Other,
+}
+
+/// Inline image data; the API also accepts `url` sources, which we don't use.
+#[derive(Debug, Serialize, Deserialize)]
+struct AnthropicImageSource {
     #[serde(rename = "type")]
-    content_type: String,
-    text: String,
+    kind: String,
+    media_type: String,
+    data: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct AnthropicTool {
+    name: String,
+    description: String,
+    input_schema: serde_json::Value,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 struct AnthropicResponse {
-    content: Vec<AnthropicContent>,
+    content: Vec<AnthropicBlock>,
+    #[serde(default)]
+    usage: Option<AnthropicUsage>,
+}
+
+/// Token counts. Cached prompt tokens are reported separately from
+/// `input_tokens`; we count them as input.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct AnthropicUsage {
+    #[serde(default)]
+    input_tokens: u32,
+    #[serde(default)]
+    output_tokens: u32,
+    #[serde(default)]
+    cache_creation_input_tokens: u32,
+    #[serde(default)]
+    cache_read_input_tokens: u32,
+}
+
+impl AnthropicUsage {
+    fn input(&self) -> u32 {
+        self.input_tokens
+            .saturating_add(self.cache_creation_input_tokens)
+            .saturating_add(self.cache_read_input_tokens)
+    }
+}
+
+/// The `message` object of a `message_start` event.
+#[derive(Debug, Deserialize)]
+struct AnthropicStreamMessage {
+    #[serde(default)]
+    usage: Option<AnthropicUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AnthropicStreamDelta {
+    #[serde(default)]
+    text: Option<String>,
+    /// Fragment of a `tool_use` block's JSON input (`input_json_delta`).
+    #[serde(default)]
+    partial_json: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AnthropicStreamError {
+    message: String,
+}
+
+/// One `data:` payload of a streaming Messages response. Only the event
+/// types we act on carry fields we read; the rest (`message_start`, `ping`,
+/// ...) are ignored.
+#[derive(Debug, Deserialize)]
+struct AnthropicStreamEvent {
+    #[serde(rename = "type")]
+    event_type: String,
+    #[serde(default)]
+    index: usize,
+    #[serde(default)]
+    content_block: Option<AnthropicBlock>,
+    #[serde(default)]
+    delta: Option<AnthropicStreamDelta>,
+    #[serde(default)]
+    error: Option<AnthropicStreamError>,
+    /// Prompt usage, on `message_start`.
+    #[serde(default)]
+    message: Option<AnthropicStreamMessage>,
+    /// Cumulative output usage, on `message_delta`.
+    #[serde(default)]
+    usage: Option<AnthropicUsage>,
+}
+
+/// Turn response content blocks into a provider-neutral assistant message.
+fn message_from_blocks(blocks: Vec<AnthropicBlock>) -> ChatMessage {
+    let mut msg = ChatMessage::assistant("");
+    for block in blocks {
+        match block {
+            AnthropicBlock::Text { text } => msg.content.push_str(&text),
+            AnthropicBlock::ToolUse { id, name, input } => {
+                msg.tool_calls.push(ToolCall {
+                    id,
+                    name,
+                    arguments: input,
+                });
+            }
+            AnthropicBlock::ToolResult { .. }
+            | AnthropicBlock::Image { .. }
+            | AnthropicBlock::Other => {}
+        }
+    }
+    msg
 }
 
 pub struct AnthropicClient {
     http: Client,
     auth_token: String,
     model: String,
+    /// Base URL for the API. Defaults to `https://api.anthropic.com`.
+    base_url: String,
 }
 
+const DEFAULT_BASE_URL: &str = "https://api.anthropic.com";
+
 impl AnthropicClient {
     pub fn new(model: &str) -> Result<Self> {
         let key =
@@ -66,6 +211,7 @@ impl AnthropicClient {
             http: SHARED_HTTP.clone(),
             auth_token: key,
             model: model.to_string(),
+            base_url: DEFAULT_BASE_URL.to_string(),
         })
     }
 
@@ -84,16 +230,30 @@ impl AnthropicClient {
             http: SHARED_HTTP.clone(),
             auth_token,
             model: model.to_string(),
+            base_url: DEFAULT_BASE_URL.to_string(),
         })
     }
 
-    /// Send a chat completion request.
+    /// Send requests to a different Messages API host (proxies, local stand-ins).
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Build the request body.
     ///
     /// System messages are extracted and concatenated into the Anthropic-specific
-    /// top-level `system` field. All other messages pass through as-is.
-    pub async fn generate(&self, messages: Vec<ChatMessage>) -> Result<String> {
-        let url = "https://api.anthropic.com/v1/messages";
-
+    /// top-level `system` field. Assistant tool calls become `tool_use` blocks and
+    /// consecutive "tool" messages are merged into a single user message of
+    /// `tool_result` blocks. Attachments become `image` blocks (or text notes
+    /// for models without vision). All other messages pass through as-is.
+    fn build_request(
+        &self,
+        messages: Vec<ChatMessage>,
+        tools: &[ToolSpec],
+        stream: bool,
+    ) -> AnthropicRequest {
+        let vision = supports_vision("anthropic", &self.model);
         // Anthropic requires system messages in a separate field, not inline.
         let mut system_prompt = String::new();
         let mut anthropic_messages: Vec<AnthropicMessage> = Vec::new();
@@ -103,10 +263,72 @@ impl AnthropicClient {
                     system_prompt.push_str("\n\n");
                 }
                 system_prompt.push_str(&m.content);
+            } else if m.role == "tool" {
+                let block = AnthropicBlock::ToolResult {
+                    tool_use_id: m.tool_call_id.unwrap_or_default(),
+                    content: m.content,
+                };
+                match anthropic_messages.last_mut() {
+                    Some(AnthropicMessage {
+                        role,
+                        content: AnthropicContent::Blocks(blocks),
+                    }) if role == "user"
+                        && blocks
+                            .iter()
+                            .all(|b| matches!(b, AnthropicBlock::ToolResult { .. })) =>
+                    {
+                        blocks.push(block)
+                    }
+                    _ => anthropic_messages.push(AnthropicMessage {
+                        role: "user".to_string(),
+                        content: AnthropicContent::Blocks(vec![block]),
+                    }),
+                }
+            } else if !m.tool_calls.is_empty() {
+                let mut blocks = Vec::new();
+                if !m.content.trim().is_empty() {
+                    blocks.push(AnthropicBlock::Text { text: m.content });
+                }
+                blocks.extend(m.tool_calls.into_iter().map(|c| AnthropicBlock::ToolUse {
+                    id: c.id,
+                    name: c.name,
+                    input: c.arguments,
+                }));
+                anthropic_messages.push(AnthropicMessage {
+                    role: m.role,
+                    content: AnthropicContent::Blocks(blocks),
+                });
+            } else if m.attachments.is_empty() {
+                anthropic_messages.push(AnthropicMessage {
+                    role: m.role,
+                    content: AnthropicContent::Text(m.content),
+                });
             } else {
+                // Images go before the text, as Anthropic recommends.
+                let text = m.content_with_attachment_notes(vision);
+                let mut blocks: Vec<AnthropicBlock> = if vision {
+                    m.attachments
+                        .into_iter()
+                        .filter_map(|a| match a {
+                            Attachment::Image { mime_type, data } => Some(AnthropicBlock::Image {
+                                source: AnthropicImageSource {
+                                    kind: "base64".to_string(),
+                                    media_type: mime_type,
+                                    data,
+                                },
+                            }),
+                            Attachment::File { .. } => None,
+                        })
+                        .collect()
+                } else {
+                    Vec::new()
+                };
+                if !text.trim().is_empty() {
+                    blocks.push(AnthropicBlock::Text { text });
+                }
                 anthropic_messages.push(AnthropicMessage {
                     role: m.role,
-                    content: m.content,
+                    content: AnthropicContent::Blocks(blocks),
                 });
             }
         }
@@ -117,39 +339,330 @@ impl AnthropicClient {
             Some(system_prompt)
         };
 
-        let req = AnthropicRequest {
+        AnthropicRequest {
             model: self.model.clone(),
             max_tokens: 4096,
             system,
             messages: anthropic_messages,
-        };
+            tools: tools
+                .iter()
+                .map(|t| AnthropicTool {
+                    name: t.name.clone(),
+                    description: t.description.clone(),
+                    input_schema: t.parameters.clone(),
+                })
+                .collect(),
+            stream,
+        }
+    }
 
+    async fn send(&self, req: &AnthropicRequest) -> Result<reqwest::Response> {
+        let url = format!("{}/v1/messages", self.base_url);
         let resp = self
             .http
             .post(url)
             .header("x-api-key", &self.auth_token)
             .header("anthropic-version", "2023-06-01")
             .header("Content-Type", "application/json")
-            .json(&req)
+            .json(req)
             .send()
             .await?;
 
         if !resp.status().is_success() {
-            let status = resp.status();
-            let body = resp.text().await.unwrap_or_default();
-            let detail: String = body.chars().take(800).collect();
-            if detail.trim().is_empty() {
-                return Err(anyhow!("anthropic error: {}", status));
-            }
-            return Err(anyhow!("anthropic error: {}\n{}", status, detail));
+            return Err(status_error("anthropic", resp).await);
+        }
+        Ok(resp)
+    }
+
+    /// Models available to this key (`GET /v1/models`).
+    pub async fn list_models(&self) -> Result<Vec<ModelInfo>> {
+        #[derive(Deserialize)]
+        struct ModelList {
+            data: Vec<ModelEntry>,
+        }
+        #[derive(Deserialize)]
+        struct ModelEntry {
+            id: String,
+            #[serde(default)]
+            max_input_tokens: Option<usize>,
+        }
+
+        let url = format!("{}/v1/models?limit=1000", self.base_url);
+        let resp = self
+            .http
+            .get(url)
+            .header("x-api-key", &self.auth_token)
+            .header("anthropic-version", "2023-06-01")
+            .send()
+            .await?;
+        if !resp.status().is_success() {
+            return Err(status_error("anthropic", resp).await);
         }
+        let list: ModelList = resp.json().await?;
+        Ok(list
+            .data
+            .into_iter()
+            .map(|m| ModelInfo::new("anthropic", m.id, m.max_input_tokens))
+            .collect())
+    }
+
+    /// Send a chat completion request. Returns the reply and reported usage.
+    pub async fn generate(
+        &self,
+        messages: Vec<ChatMessage>,
+        tools: &[ToolSpec],
+    ) -> Result<(ChatMessage, Option<Usage>)> {
+        let req = self.build_request(messages, tools, false);
+        let resp = self.send(&req).await?;
 
         let body: AnthropicResponse = resp.json().await?;
-        let text = body
-            .content
-            .first()
-            .map(|c| c.text.clone())
-            .unwrap_or_default();
-        Ok(text)
+        let usage = body.usage.map(|u| Usage::new(u.input(), u.output_tokens));
+        Ok((message_from_blocks(body.content), usage))
+    }
+
+    /// Stream the reply over SSE, calling `on_delta` for every text delta.
+    /// Returns the full reply (text plus any `tool_use` blocks) and usage
+    /// after `message_stop`.
+    pub async fn generate_stream<F>(
+        &self,
+        messages: Vec<ChatMessage>,
+        tools: &[ToolSpec],
+        mut on_delta: F,
+    ) -> Result<(ChatMessage, Option<Usage>)>
+    where
+        F: FnMut(&str) + Send,
+    {
+        let req = self.build_request(messages, tools, true);
+        let resp = self.send(&req).await?;
+
+        let mut text = String::new();
+        let mut usage: Option<Usage> = None;
+        // (block index, id, name, accumulated input JSON) per tool_use block.
+        let mut tool_blocks: Vec<(usize, String, String, String)> = Vec::new();
+        for_each_line(resp, |line| {
+            let Some(data) = sse_data(line) else {
+                return Ok(true);
+            };
+            let event: AnthropicStreamEvent = serde_json::from_str(data)?;
+            match event.event_type.as_str() {
+                "message_start" => {
+                    if let Some(u) = event.message.and_then(|m| m.usage) {
+                        usage = Some(Usage::new(u.input(), u.output_tokens));
+                    }
+                    Ok(true)
+                }
+                "message_delta" => {
+                    if let Some(u) = event.usage {
+                        usage.get_or_insert_with(Usage::default).output_tokens = u.output_tokens;
+                    }
+                    Ok(true)
+                }
+                "content_block_start" => {
+                    if let Some(AnthropicBlock::ToolUse { id, name, .. }) = event.content_block {
+                        tool_blocks.push((event.index, id, name, String::new()));
+                    }
+                    Ok(true)
+                }
+                "content_block_delta" => {
+                    let Some(delta) = event.delta else {
+                        return Ok(true);
+                    };
+                    if let Some(text_delta) = delta.text {
+                        on_delta(&text_delta);
+                        text.push_str(&text_delta);
+                    }
+                    if let Some(json) = delta.partial_json {
+                        if let Some(block) = tool_blocks.iter_mut().find(|b| b.0 == event.index) {
+                            block.3.push_str(&json);
+                        }
+                    }
+                    Ok(true)
+                }
+                "error" => Err(anyhow!(
+                    "anthropic error: {}",
+                    event
+                        .error
+                        .map(|e| e.message)
+                        .unwrap_or_else(|| "stream error".to_string())
+                )),
+                "message_stop" => Ok(false),
+                _ => Ok(true),
+            }
+        })
+        .await?;
+
+        let mut msg = ChatMessage::assistant(text);
+        msg.tool_calls = tool_blocks
+            .into_iter()
+            .map(|(_, id, name, json)| ToolCall {
+                id,
+                name,
+                arguments: crate::openai::parse_arguments(&json),
+            })
+            .collect();
+        Ok((msg, usage))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream::test_server;
+
+    fn user(content: &str) -> Vec<ChatMessage> {
+        vec![ChatMessage::user(content.to_string())]
+    }
+
+    #[tokio::test]
+    async fn test_generate_stream_reads_sse() {
+        let base = test_server::serve(
+            200,
+            "text/event-stream",
+            "event: message_start\n\
+             data: {\"type\":\"message_start\",\"message\":{\"usage\":{\"input_tokens\":12,\"output_tokens\":1}}}\n\n\
+             event: content_block_delta\n\
+             data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"Hello\"}}\n\n\
+             event: ping\n\
+             data: {\"type\":\"ping\"}\n\n\
+             event: content_block_delta\n\
+             data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\" world\"}}\n\n\
+             event: message_delta\n\
+             data: {\"type\":\"message_delta\",\"delta\":{\"stop_reason\":\"end_turn\"},\"usage\":{\"output_tokens\":5}}\n\n\
+             event: message_stop\n\
+             data: {\"type\":\"message_stop\"}\n\n",
+        );
+        let client = AnthropicClient::from_auth(
+            "claude-3-haiku-20240307",
+            &ProviderAuth {
+                api_key: Some("test-key".to_string()),
+                oauth: None,
+            },
+        )
+        .unwrap()
+        .with_base_url(&base);
+        let mut deltas = Vec::new();
+        let (reply, usage) = client
+            .generate_stream(user("hi"), &[], |d| deltas.push(d.to_string()))
+            .await
+            .unwrap();
+        assert_eq!(reply.content, "Hello world");
+        assert_eq!(deltas, vec!["Hello", " world"]);
+        assert_eq!(usage, Some(Usage::new(12, 5)));
+    }
+
+    #[tokio::test]
+    async fn test_generate_stream_surfaces_error_event() {
+        let base = test_server::serve(
+            200,
+            "text/event-stream",
+            "event: error\n\
+             data: {\"type\":\"error\",\"error\":{\"type\":\"overloaded_error\",\"message\":\"Overloaded\"}}\n\n",
+        );
+        let client = AnthropicClient::from_auth(
+            "claude-3-haiku-20240307",
+            &ProviderAuth {
+                api_key: Some("test-key".to_string()),
+                oauth: None,
+            },
+        )
+        .unwrap()
+        .with_base_url(&base);
+        let err = client
+            .generate_stream(user("hi"), &[], |_| {})
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "anthropic error: Overloaded");
+    }
+
+    #[tokio::test]
+    async fn test_generate_stream_collects_tool_use() {
+        let base = test_server::serve(
+            200,
+            "text/event-stream",
+            "data: {\"type\":\"content_block_start\",\"index\":0,\"content_block\":{\"type\":\"text\",\"text\":\"\"}}\n\n\
+             data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"Checking.\"}}\n\n\
+             data: {\"type\":\"content_block_start\",\"index\":1,\"content_block\":{\"type\":\"tool_use\",\"id\":\"toolu_1\",\"name\":\"run_command\",\"input\":{}}}\n\n\
+             data: {\"type\":\"content_block_delta\",\"index\":1,\"delta\":{\"type\":\"input_json_delta\",\"partial_json\":\"{\\\"command\\\": \"}}\n\n\
+             data: {\"type\":\"content_block_delta\",\"index\":1,\"delta\":{\"type\":\"input_json_delta\",\"partial_json\":\"\\\"df -h\\\"}\"}}\n\n\
+             data: {\"type\":\"message_stop\"}\n\n",
+        );
+        let client = AnthropicClient::from_auth(
+            "claude-3-haiku-20240307",
+            &ProviderAuth {
+                api_key: Some("test-key".to_string()),
+                oauth: None,
+            },
+        )
+        .unwrap()
+        .with_base_url(&base);
+        let (reply, _) = client
+            .generate_stream(user("disk space?"), &[], |_| {})
+            .await
+            .unwrap();
+        assert_eq!(reply.content, "Checking.");
+        assert_eq!(reply.tool_calls.len(), 1);
+        assert_eq!(reply.tool_calls[0].id, "toolu_1");
+        assert_eq!(reply.tool_calls[0].arguments["command"], "df -h");
+    }
+
+    #[test]
+    fn test_build_request_groups_tool_results() {
+        let client = AnthropicClient::from_auth(
+            "claude-3-haiku-20240307",
+            &ProviderAuth {
+                api_key: Some("test-key".to_string()),
+                oauth: None,
+            },
+        )
+        .unwrap();
+        let calls: Vec<ToolCall> = ["a", "b"]
+            .iter()
+            .map(|id| ToolCall {
+                id: id.to_string(),
+                name: "search".to_string(),
+                arguments: serde_json::json!({"query": id}),
+            })
+            .collect();
+        let mut assistant = ChatMessage::assistant("");
+        assistant.tool_calls = calls.clone();
+        let req = client.build_request(
+            vec![
+                ChatMessage::system("be brief"),
+                ChatMessage::user("hi"),
+                assistant,
+                ChatMessage::tool_result(&calls[0], "one"),
+                ChatMessage::tool_result(&calls[1], "two"),
+            ],
+            &[],
+            false,
+        );
+        let json = serde_json::to_value(&req).unwrap();
+        assert_eq!(json["system"], "be brief");
+        assert_eq!(json["messages"][0]["content"], "hi");
+        assert_eq!(json["messages"][1]["content"][0]["type"], "tool_use");
+        assert_eq!(json["messages"][2]["role"], "user");
+        assert_eq!(json["messages"][2]["content"][1]["tool_use_id"], "b");
+        assert_eq!(json["messages"].as_array().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_build_request_puts_images_before_text() {
+        let client = AnthropicClient::from_auth(
+            "claude-3-5-sonnet-latest",
+            &ProviderAuth {
+                api_key: Some("test-key".to_string()),
+                oauth: None,
+            },
+        )
+        .unwrap();
+        let msg = ChatMessage::user("what's this?")
+            .with_attachment(Attachment::image("image/jpeg", b"jpg"));
+        let json = serde_json::to_value(client.build_request(vec![msg], &[], false)).unwrap();
+        let blocks = &json["messages"][0]["content"];
+        assert_eq!(blocks[0]["type"], "image");
+        assert_eq!(blocks[0]["source"]["type"], "base64");
+        assert_eq!(blocks[0]["source"]["media_type"], "image/jpeg");
+        assert_eq!(blocks[0]["source"]["data"], "anBn");
+        assert_eq!(blocks[1]["text"], "what's this?");
     }
 }
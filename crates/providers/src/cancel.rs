@@ -0,0 +1,89 @@
+//! Cancellation for in-flight generations.
+//!
+//! A [`CancellationToken`] is shared by everything working on one user turn:
+//! the [`ProviderRouter`](crate::router::ProviderRouter), the command executor
+//! and skill runs. Cancelling it (the "Stop" button, or a turn deadline)
+//! drops the pending provider future, which aborts its HTTP request, and
+//! the work returns a [`Cancelled`] error. Provider clients need no special
+//! support: a reqwest request is aborted when its future is dropped.
+
+use std::fmt;
+use std::future::Future;
+use std::time::Duration;
+
+pub use tokio_util::sync::CancellationToken;
+
+/// Returned when work stops because its token was cancelled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// Whether `err` (or anything it wraps) is a [`Cancelled`].
+pub fn is_cancelled(err: &anyhow::Error) -> bool {
+    err.chain().any(|e| e.is::<Cancelled>())
+}
+
+/// Run `fut` unless `token` is cancelled first, in which case `fut` is
+/// dropped and [`Cancelled`] is returned.
+pub async fn run_cancellable<T, F>(token: &CancellationToken, fut: F) -> anyhow::Result<T>
+where
+    F: Future<Output = anyhow::Result<T>>,
+{
+    tokio::select! {
+        biased;
+        _ = token.cancelled() => Err(Cancelled.into()),
+        out = fut => out,
+    }
+}
+
+/// A child of `token` that is also cancelled after `deadline`, for
+/// whole-turn time limits. Must be called inside a Tokio runtime.
+pub fn with_deadline(token: &CancellationToken, deadline: Duration) -> CancellationToken {
+    let child = token.child_token();
+    let timer = child.clone();
+    tokio::spawn(async move {
+        tokio::select! {
+            _ = timer.cancelled() => {}
+            _ = tokio::time::sleep(deadline) => timer.cancel(),
+        }
+    });
+    child
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_cancel_drops_future() {
+        let token = CancellationToken::new();
+        let ok = run_cancellable(&token, async { Ok(1) }).await.unwrap();
+        assert_eq!(ok, 1);
+
+        token.cancel();
+        let err = run_cancellable(&token, async {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            Ok(())
+        })
+        .await
+        .unwrap_err();
+        assert!(is_cancelled(&err));
+    }
+
+    #[tokio::test]
+    async fn test_deadline_cancels_child_only() {
+        let token = CancellationToken::new();
+        let turn = with_deadline(&token, Duration::from_millis(10));
+        tokio::time::timeout(Duration::from_secs(1), turn.cancelled())
+            .await
+            .unwrap();
+        assert!(!token.is_cancelled());
+    }
+}
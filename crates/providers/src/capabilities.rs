@@ -0,0 +1,126 @@
+//! What each provider/model can accept, used to decide how to serialize a
+//! request (e.g. whether images go inline or as text descriptions) and which
+//! provider a request should be routed to.
+//!
+//! These are name-based heuristics: providers don't expose capabilities in a
+//! uniform way, and local Ollama models are arbitrary tags.
+
+/// Whether `model` on `provider` accepts image input.
+pub fn supports_vision(provider: &str, model: &str) -> bool {
+    let m = model.to_lowercase();
+    match provider {
+        // All Claude 3+ models accept images; Claude 2 / Instant do not.
+        "anthropic" => !m.starts_with("claude-2") && !m.starts_with("claude-instant"),
+        // Every Gemini generation is multimodal.
+        "gemini" => m.starts_with("gemini"),
+        "openai" => {
+            [
+                "gpt-4o",
+                "gpt-4.1",
+                "gpt-4-turbo",
+                "gpt-5",
+                "o1",
+                "o3",
+                "o4",
+                "vision",
+            ]
+            .iter()
+            .any(|p| m.contains(p))
+                && !m.contains("o1-mini")
+                && !m.contains("o3-mini")
+        }
+        "local" => [
+            "llava",
+            "vision",
+            "moondream",
+            "minicpm-v",
+            "qwen2.5vl",
+            "qwen2-vl",
+            "gemma3",
+            "granite3.2-vision",
+            "llama4",
+        ]
+        .iter()
+        .any(|p| m.contains(p)),
+        _ => false,
+    }
+}
+
+/// Whether `model` on `provider` supports native tool calling. Models without
+/// it still work through the `<command>`/`<search>` tag fallback.
+pub fn supports_tools(provider: &str, model: &str) -> bool {
+    let m = model.to_lowercase();
+    match provider {
+        "anthropic" => !m.starts_with("claude-2") && !m.starts_with("claude-instant"),
+        "gemini" => true,
+        "openai" => !m.contains("o1-mini") && !m.contains("o1-preview"),
+        "local" => [
+            "llama3.1",
+            "llama3.2",
+            "llama3.3",
+            "llama4",
+            "qwen2.5",
+            "qwen3",
+            "mistral",
+            "mixtral",
+            "command-r",
+            "hermes3",
+            "granite3",
+            "firefunction",
+        ]
+        .iter()
+        .any(|p| m.contains(p)),
+        _ => false,
+    }
+}
+
+/// Approximate context window of `model` on `provider`, in tokens.
+pub fn context_window(provider: &str, model: &str) -> usize {
+    let m = model.to_lowercase();
+    match provider {
+        "anthropic" if m.starts_with("claude-2") || m.starts_with("claude-instant") => 100_000,
+        "anthropic" => 200_000,
+        "gemini" if m.contains("1.0") || m == "gemini-pro" => 32_768,
+        "gemini" => 1_000_000,
+        "openai" if m.contains("gpt-4.1") => 1_000_000,
+        "openai" if m.contains("gpt-4-32k") => 32_768,
+        "openai" if m.contains("gpt-3.5") => 16_385,
+        "openai" if m.starts_with("gpt-4") && !m.contains("turbo") && !m.contains("gpt-4o") => {
+            8_192
+        }
+        // gpt-4o, o-series, and OpenAI-compatible hosts (Kimi, OpenRouter, ...).
+        "openai" => 128_000,
+        // Ollama's default `num_ctx`; larger windows need a custom Modelfile.
+        "local" => 8_192,
+        _ => 8_192,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_supports_vision() {
+        assert!(supports_vision("openai", "gpt-4o-mini"));
+        assert!(!supports_vision("openai", "gpt-3.5-turbo"));
+        assert!(!supports_vision("openai", "o3-mini"));
+        assert!(supports_vision("anthropic", "claude-3-5-sonnet-latest"));
+        assert!(!supports_vision("anthropic", "claude-2.1"));
+        assert!(supports_vision("gemini", "gemini-2.0-flash"));
+        assert!(supports_vision("local", "llava:7b"));
+        assert!(supports_vision("local", "llama3.2-vision:11b"));
+        assert!(!supports_vision("local", "llama3.2:3b"));
+    }
+
+    #[test]
+    fn test_tools_and_context_window() {
+        assert!(supports_tools("local", "llama3.2:3b"));
+        assert!(!supports_tools("local", "llava:7b"));
+        assert!(supports_tools("anthropic", "claude-3-haiku-20240307"));
+        assert_eq!(context_window("openai", "gpt-4o-mini"), 128_000);
+        assert_eq!(context_window("openai", "gpt-4"), 8_192);
+        assert_eq!(context_window("gemini", "gemini-2.0-flash"), 1_000_000);
+        assert_eq!(context_window("local", "llama3.2:3b"), 8_192);
+    }
+}
@@ -0,0 +1,407 @@
+//! Model catalogs: which models each provider actually serves.
+//!
+//! Model names in [`ModelProvider`] are free text, so a typo used to show up
+//! only as a failed request. Every client can list its models (Ollama
+//! `/api/tags`, OpenAI-compatible `/v1/models`, Anthropic `/v1/models`,
+//! Gemini `/v1beta/models`). [`ModelCatalogs`] caches those lists on disk
+//! with each model's context window and capabilities, and
+//! [`ModelCatalogs::validate`] checks the configured names against them.
+//!
+//! Most endpoints don't report context windows or capabilities; those are
+//! filled in from the name-based guesses in [`crate::capabilities`].
+
+use crate::capabilities;
+use crate::router::ProviderRouter;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use shared::settings::ModelProvider;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How long a fetched catalog is trusted before it is listed again.
+pub const DEFAULT_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+const BUILTIN_PROVIDERS: [&str; 4] = ["local", "openai", "anthropic", "gemini"];
+
+/// One model offered by a provider.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModelInfo {
+    pub id: String,
+    /// Context window in tokens.
+    pub context_window: usize,
+    pub supports_vision: bool,
+    pub supports_tools: bool,
+}
+
+impl ModelInfo {
+    /// Entry for `id` as served by a `kind` client (`local`, `openai`,
+    /// `anthropic` or `gemini`). A context window reported by the endpoint
+    /// wins over the guess from the name.
+    pub fn new(kind: &str, id: impl Into<String>, context_window: Option<usize>) -> Self {
+        let id = id.into();
+        Self {
+            context_window: context_window
+                .unwrap_or_else(|| capabilities::context_window(kind, &id)),
+            supports_vision: capabilities::supports_vision(kind, &id),
+            supports_tools: capabilities::supports_tools(kind, &id),
+            id,
+        }
+    }
+}
+
+/// The models one provider served when it was last listed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ModelCatalog {
+    /// Unix time (seconds) of the listing.
+    pub fetched_at: u64,
+    pub models: Vec<ModelInfo>,
+}
+
+impl ModelCatalog {
+    /// Look up `model`, accepting the names providers resolve themselves:
+    /// Ollama's implicit `:latest` tag and Anthropic-style aliases for dated
+    /// snapshots (`claude-3-5-sonnet-latest`, `claude-sonnet-4-5`).
+    pub fn get(&self, model: &str) -> Option<&ModelInfo> {
+        if let Some(info) = self.models.iter().find(|m| m.id == model) {
+            return Some(info);
+        }
+        if !model.contains(':') {
+            let tagged = format!("{}:latest", model);
+            if let Some(info) = self.models.iter().find(|m| m.id == tagged) {
+                return Some(info);
+            }
+        }
+        let stem = format!("{}-", model.strip_suffix("-latest").unwrap_or(model));
+        self.models
+            .iter()
+            .filter(|m| {
+                m.id.strip_prefix(&stem)
+                    .is_some_and(|rest| rest.chars().all(|c| c.is_ascii_digit()))
+            })
+            .max_by(|a, b| a.id.cmp(&b.id))
+    }
+}
+
+/// A configured model name the provider doesn't serve.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModelIssue {
+    pub provider: String,
+    /// The setting holding the name, e.g. `openai_fast_model`.
+    pub setting: String,
+    pub model: String,
+    /// The closest name the provider does serve, if any is close.
+    pub suggestion: Option<String>,
+}
+
+impl fmt::Display for ModelIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} doesn't offer a model called \"{}\" ({})",
+            self.provider, self.model, self.setting
+        )?;
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, "; did you mean \"{}\"?", suggestion)?;
+        }
+        Ok(())
+    }
+}
+
+/// Catalogs for every provider that has been listed, keyed by provider id
+/// (built-in ids and profile names).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ModelCatalogs {
+    pub providers: HashMap<String, ModelCatalog>,
+}
+
+impl ModelCatalogs {
+    /// Load a cache written by [`save`](Self::save).
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn get(&self, provider: &str) -> Option<&ModelCatalog> {
+        self.providers.get(provider)
+    }
+
+    /// Catalog entry for `model` on `provider`, if it has been listed.
+    pub fn model(&self, provider: &str, model: &str) -> Option<&ModelInfo> {
+        self.get(provider).and_then(|c| c.get(model))
+    }
+
+    /// Context window of `model` on `provider`: the catalog's value when
+    /// listed, otherwise the name-based guess.
+    pub fn context_window(&self, provider: &str, model: &str) -> usize {
+        self.model(provider, model)
+            .map(|m| m.context_window)
+            .unwrap_or_else(|| capabilities::context_window(kind(provider), model))
+    }
+
+    /// Whether `provider` was never listed or was listed more than
+    /// `max_age` ago.
+    pub fn is_stale(&self, provider: &str, max_age: Duration) -> bool {
+        self.get(provider)
+            .is_none_or(|c| now_secs().saturating_sub(c.fetched_at) > max_age.as_secs())
+    }
+
+    /// List models for every provider in `provider_preference` whose catalog
+    /// is missing or older than `max_age`. Providers that can't be listed
+    /// (no credentials, unreachable) keep their old catalog and are returned
+    /// with the error.
+    pub async fn refresh(
+        &mut self,
+        config: &ModelProvider,
+        max_age: Duration,
+    ) -> Vec<(String, anyhow::Error)> {
+        let router = ProviderRouter::new(config.clone());
+        let mut errors = Vec::new();
+        for provider in dedup(&config.provider_preference) {
+            if !self.is_stale(provider, max_age) {
+                continue;
+            }
+            match router.list_models(provider).await {
+                Ok(models) => {
+                    self.providers.insert(
+                        provider.to_string(),
+                        ModelCatalog {
+                            fetched_at: now_secs(),
+                            models,
+                        },
+                    );
+                }
+                Err(e) => errors.push((provider.to_string(), e)),
+            }
+        }
+        errors
+    }
+
+    /// Configured model names that their provider's catalog doesn't list.
+    /// Providers without a (non-empty) catalog are not checked.
+    pub fn validate(&self, config: &ModelProvider) -> Vec<ModelIssue> {
+        let mut issues = Vec::new();
+        for provider in dedup(&config.provider_preference) {
+            let Some(catalog) = self.get(provider).filter(|c| !c.models.is_empty()) else {
+                continue;
+            };
+            for (setting, model) in configured_models(config, provider) {
+                if model.is_empty() || catalog.get(&model).is_some() {
+                    continue;
+                }
+                issues.push(ModelIssue {
+                    provider: provider.to_string(),
+                    setting,
+                    suggestion: closest(catalog, &model),
+                    model,
+                });
+            }
+        }
+        issues
+    }
+}
+
+/// The capability family of `provider`; profiles speak the OpenAI protocol.
+fn kind(provider: &str) -> &str {
+    if BUILTIN_PROVIDERS.contains(&provider) {
+        provider
+    } else {
+        "openai"
+    }
+}
+
+fn dedup(providers: &[String]) -> Vec<&str> {
+    let mut seen: Vec<&str> = Vec::new();
+    for p in providers {
+        if !seen.contains(&p.as_str()) {
+            seen.push(p);
+        }
+    }
+    seen
+}
+
+/// `(setting, model)` pairs configured for `provider`.
+fn configured_models(config: &ModelProvider, provider: &str) -> Vec<(String, String)> {
+    let pair = |setting: &str, model: &str| (setting.to_string(), model.to_string());
+    match provider {
+        "local" => vec![pair("local_model", &config.local_model)],
+        "openai" => vec![
+            pair("openai_model", &config.openai_model),
+            pair("openai_fast_model", &config.openai_fast_model),
+        ],
+        "anthropic" => vec![
+            pair("anthropic_model", &config.anthropic_model),
+            pair("anthropic_fast_model", &config.anthropic_fast_model),
+        ],
+        "gemini" => vec![
+            pair("gemini_model", &config.gemini_model),
+            pair("gemini_fast_model", &config.gemini_fast_model),
+        ],
+        _ => config
+            .profile(provider)
+            .map(|profile| {
+                let mut models: Vec<_> = profile
+                    .models
+                    .iter()
+                    .map(|m| (format!("{}.models", profile.name), m.clone()))
+                    .collect();
+                if let Some(fast) = &profile.fast_model {
+                    models.push((format!("{}.fast_model", profile.name), fast.clone()));
+                }
+                models
+            })
+            .unwrap_or_default(),
+    }
+}
+
+/// The catalog name nearest to `model` by edit distance, if it is close
+/// enough to be a likely typo.
+fn closest(catalog: &ModelCatalog, model: &str) -> Option<String> {
+    let model = model.to_lowercase();
+    let limit = (model.chars().count() / 3).max(2);
+    catalog
+        .models
+        .iter()
+        .map(|m| (edit_distance(&model, &m.id.to_lowercase()), &m.id))
+        .filter(|(d, _)| *d <= limit)
+        .min_by_key(|(d, _)| *d)
+        .map(|(_, id)| id.clone())
+}
+
+/// Levenshtein distance between `a` and `b`, by characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut cur = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitute = prev[j] + usize::from(ca != *cb);
+            cur[j + 1] = substitute.min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        prev = cur;
+    }
+    prev[b.len()]
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream::test_server;
+    use shared::settings::{AppSettings, ProviderAuth};
+
+    fn catalog(kind: &str, ids: &[&str]) -> ModelCatalog {
+        ModelCatalog {
+            fetched_at: now_secs(),
+            models: ids
+                .iter()
+                .map(|id| ModelInfo::new(kind, *id, None))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_catalog_lookup_aliases() {
+        let ollama = catalog("local", &["llama3.2:latest", "qwen2.5:7b"]);
+        assert!(ollama.get("llama3.2").is_some());
+        assert!(ollama.get("qwen2.5").is_none());
+
+        let anthropic = catalog(
+            "anthropic",
+            &[
+                "claude-3-5-sonnet-20240620",
+                "claude-3-5-sonnet-20241022",
+                "claude-3-5-haiku-20241022",
+            ],
+        );
+        let latest = anthropic.get("claude-3-5-sonnet-latest").unwrap();
+        assert_eq!(latest.id, "claude-3-5-sonnet-20241022");
+        assert!(anthropic.get("claude-3-5-haiku").is_some());
+        assert!(anthropic.get("claude-3-5").is_none());
+    }
+
+    #[test]
+    fn test_validate_suggests_close_names() {
+        let mut config = AppSettings::default().model;
+        config.provider_preference = vec!["openai".to_string(), "gemini".to_string()];
+        config.openai_model = "gpt-4o-mnii".to_string();
+        config.openai_fast_model = "gpt-4o-mini".to_string();
+        config.gemini_model = "something-else-entirely".to_string();
+
+        let mut catalogs = ModelCatalogs::default();
+        catalogs.providers.insert(
+            "openai".to_string(),
+            catalog("openai", &["gpt-4o", "gpt-4o-mini"]),
+        );
+        // Gemini has no catalog, so it is not checked.
+        let issues = catalogs.validate(&config);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].setting, "openai_model");
+        assert_eq!(issues[0].suggestion.as_deref(), Some("gpt-4o-mini"));
+        assert!(issues[0]
+            .to_string()
+            .contains("did you mean \"gpt-4o-mini\""));
+
+        catalogs.providers.insert(
+            "gemini".to_string(),
+            catalog("gemini", &["gemini-2.0-flash"]),
+        );
+        let issues = catalogs.validate(&config);
+        let gemini: Vec<_> = issues.iter().filter(|i| i.provider == "gemini").collect();
+        assert_eq!(gemini.len(), 2);
+        assert_eq!(gemini[0].suggestion, None);
+    }
+
+    #[tokio::test]
+    async fn test_refresh_lists_stale_providers() {
+        let base = test_server::serve(
+            200,
+            "application/json",
+            "{\"data\":[{\"id\":\"kimi-k2.5\",\"context_length\":262144},{\"id\":\"gpt-4o\"}]}",
+        );
+        let mut config = AppSettings::default().model;
+        config.provider_preference = vec!["openai".to_string(), "missing".to_string()];
+        config.openai_base_url = Some(base);
+        config.openai_auth = ProviderAuth {
+            api_key: Some("test-key".to_string()),
+            oauth: None,
+        };
+        config.openai_model = "kimi-k2-5".to_string();
+
+        let mut catalogs = ModelCatalogs::default();
+        let errors = catalogs.refresh(&config, DEFAULT_MAX_AGE).await;
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, "missing");
+        assert!(!catalogs.is_stale("openai", DEFAULT_MAX_AGE));
+        assert_eq!(catalogs.context_window("openai", "kimi-k2.5"), 262_144);
+        assert_eq!(catalogs.context_window("openai", "gpt-4o"), 128_000);
+        // Unlisted models fall back to the name-based guess.
+        assert_eq!(catalogs.context_window("local", "llama3.2:3b"), 8_192);
+
+        let issues = catalogs.validate(&config);
+        assert_eq!(issues[0].model, "kimi-k2-5");
+        assert_eq!(issues[0].suggestion.as_deref(), Some("kimi-k2.5"));
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("same", "same"), 0);
+    }
+}
@@ -1,8 +1,13 @@
 //! External provider registry for skill integrations.
 //!
-//! Manages external tools like Playwright, Canva, Gemini CLI, and spec-kit.
+//! Manages external tools like Playwright, Canva, Gemini CLI, and spec-kit,
+//! plus the user's OpenAI-compatible [`ProviderProfile`]s, which are
+//! health-checked against their `/v1/models` endpoint.
 
+use crate::health::ProviderHttpError;
+use crate::openai::OpenAIClient;
 use serde::{Deserialize, Serialize};
+use shared::settings::ProviderProfile;
 use std::collections::HashMap;
 use std::sync::Arc;
 
@@ -29,16 +34,16 @@ impl ProviderStatus {
 #[async_trait::async_trait]
 pub trait ExternalProvider: Send + Sync {
     /// Provider identifier
-    fn id(&self) -> &'static str;
+    fn id(&self) -> &str;
 
     /// Human-readable name
-    fn name(&self) -> &'static str;
+    fn name(&self) -> &str;
 
     /// Check if provider is available
     async fn health_check(&self) -> ProviderStatus;
 
     /// Get setup instructions
-    fn setup_instructions(&self) -> &'static str;
+    fn setup_instructions(&self) -> &str;
 }
 
 /// Registry for external providers
@@ -63,6 +68,13 @@ impl ProviderRegistry {
         self.providers.insert(id, provider);
     }
 
+    /// Register (or replace) one [`ProfileProvider`] per profile.
+    pub fn register_profiles(&mut self, profiles: &[ProviderProfile]) {
+        for profile in profiles {
+            self.register(Arc::new(ProfileProvider::new(profile.clone())));
+        }
+    }
+
     /// Get a provider by ID
     pub fn get(&self, id: &str) -> Option<&Arc<dyn ExternalProvider>> {
         self.providers.get(id)
@@ -107,9 +119,9 @@ impl ProviderRegistry {
     /// Get provider info for display
     pub fn provider_info(&self, id: &str) -> Option<ProviderInfo> {
         self.providers.get(id).map(|p| ProviderInfo {
-            id: p.id(),
-            name: p.name(),
-            setup_instructions: p.setup_instructions(),
+            id: p.id().to_string(),
+            name: p.name().to_string(),
+            setup_instructions: p.setup_instructions().to_string(),
             status: self.status_cache.get(id).cloned(),
         })
     }
@@ -119,9 +131,9 @@ impl ProviderRegistry {
         self.providers
             .iter()
             .map(|(id, p)| ProviderInfo {
-                id: p.id(),
-                name: p.name(),
-                setup_instructions: p.setup_instructions(),
+                id: p.id().to_string(),
+                name: p.name().to_string(),
+                setup_instructions: p.setup_instructions().to_string(),
                 status: self.status_cache.get(id).cloned(),
             })
             .collect()
@@ -137,9 +149,9 @@ impl Default for ProviderRegistry {
 /// Provider information for display
 #[derive(Debug, Clone)]
 pub struct ProviderInfo {
-    pub id: &'static str,
-    pub name: &'static str,
-    pub setup_instructions: &'static str,
+    pub id: String,
+    pub name: String,
+    pub setup_instructions: String,
     pub status: Option<ProviderStatus>,
 }
 
@@ -162,11 +174,11 @@ impl PlaceholderProvider {
 
 #[async_trait::async_trait]
 impl ExternalProvider for PlaceholderProvider {
-    fn id(&self) -> &'static str {
+    fn id(&self) -> &str {
         self.id
     }
 
-    fn name(&self) -> &'static str {
+    fn name(&self) -> &str {
         self.name
     }
 
@@ -176,11 +188,70 @@ impl ExternalProvider for PlaceholderProvider {
         }
     }
 
-    fn setup_instructions(&self) -> &'static str {
+    fn setup_instructions(&self) -> &str {
         self.instructions
     }
 }
 
+/// An OpenAI-compatible endpoint from settings, checked by listing its models.
+pub struct ProfileProvider {
+    profile: ProviderProfile,
+    name: String,
+    instructions: String,
+}
+
+impl ProfileProvider {
+    pub fn new(profile: ProviderProfile) -> Self {
+        let name = format!("{} (OpenAI-compatible)", profile.name);
+        let instructions = format!(
+            "Check that {} is running and reachable, and that its API key is set in Settings",
+            profile.base_url
+        );
+        Self {
+            profile,
+            name,
+            instructions,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl ExternalProvider for ProfileProvider {
+    fn id(&self) -> &str {
+        &self.profile.name
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn health_check(&self) -> ProviderStatus {
+        let client = match OpenAIClient::from_profile(&self.profile, self.profile.model()) {
+            Ok(client) => client,
+            Err(e) => {
+                return ProviderStatus::NeedsSetup {
+                    instructions: e.to_string(),
+                }
+            }
+        };
+        match client.list_models().await {
+            Ok(_) => ProviderStatus::Available,
+            Err(e) => match e.downcast_ref::<ProviderHttpError>() {
+                Some(http) if matches!(http.status, 401 | 403) => ProviderStatus::NeedsSetup {
+                    instructions: format!("{} rejected the API key", self.profile.name),
+                },
+                _ => ProviderStatus::Unavailable {
+                    reason: e.to_string(),
+                },
+            },
+        }
+    }
+
+    fn setup_instructions(&self) -> &str {
+        &self.instructions
+    }
+}
+
 /// Initialize provider registry with all known providers.
 ///
 /// All providers start as placeholders (NeedsSetup status). As integration
@@ -254,4 +325,46 @@ mod tests {
         let status = registry.check("playwright").await;
         assert!(matches!(status, ProviderStatus::NeedsSetup { .. }));
     }
+
+    #[tokio::test]
+    async fn test_profiles_are_health_checked() {
+        use crate::stream::test_server;
+        use shared::settings::{AuthHeaderStyle, ProviderAuth};
+
+        let up = test_server::serve(200, "application/json", "{\"data\":[{\"id\":\"m\"}]}");
+        let denied = test_server::serve(401, "application/json", "{\"error\":\"bad key\"}");
+        let profile = |name: &str, base_url: String| ProviderProfile {
+            name: name.to_string(),
+            base_url,
+            auth_header: AuthHeaderStyle::Bearer,
+            auth: ProviderAuth {
+                api_key: Some("k".to_string()),
+                oauth: None,
+            },
+            models: vec!["m".to_string()],
+            fast_model: None,
+            supports_tools: true,
+            supports_vision: false,
+            context_window: None,
+            local: false,
+        };
+
+        let mut registry = ProviderRegistry::new();
+        registry.register_profiles(&[
+            profile("up", up),
+            profile("denied", denied),
+            profile("down", "http://127.0.0.1:9".to_string()),
+        ]);
+        let status = registry.check_all().await;
+        assert!(status["up"].is_available());
+        assert!(matches!(
+            status["denied"],
+            ProviderStatus::NeedsSetup { .. }
+        ));
+        assert!(matches!(status["down"], ProviderStatus::Unavailable { .. }));
+        assert_eq!(
+            registry.provider_info("up").unwrap().name,
+            "up (OpenAI-compatible)"
+        );
+    }
 }
@@ -5,12 +5,22 @@
 //! - The `contents` array must start and end with role "user".
 //! - Consecutive same-role messages must be merged (no two "user" in a row).
 //! - API keys go in the URL query string; OAuth tokens go in the `Authorization` header.
-//! - Transient 429/503 errors are retried with exponential backoff (up to 3 retries).
+//! - Transient 429/503 errors are retried by the router (see [`crate::health`]).
+//! - Streaming uses `streamGenerateContent?alt=sse`; each SSE payload is a regular
+//!   `GenerateContentResponse` carrying the next slice of text.
+//! - Tools are `functionDeclarations` whose schemas may not contain
+//!   `additionalProperties`. Function calls carry no ID, so we synthesise
+//!   `call_<n>` and match `functionResponse` parts back to them by name.
+//! - Image attachments are sent as `inlineData` parts.
 
+use crate::capabilities::supports_vision;
+use crate::catalog::ModelInfo;
+use crate::stream::{for_each_line, sse_data, status_error};
+use crate::usage::Usage;
 use anyhow::{anyhow, Result};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use shared::agent_api::ChatMessage;
+use shared::agent_api::{Attachment, ChatMessage, ToolCall, ToolSpec};
 use shared::settings::ProviderAuth;
 use std::env;
 use std::sync::LazyLock;
@@ -31,9 +41,48 @@ struct GeminiContent {
     parts: Vec<GeminiPart>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// A content part: text, inline image data, a model function call, or our
+/// function response.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 struct GeminiPart {
-    text: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    inline_data: Option<GeminiInlineData>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    function_call: Option<GeminiFunctionCall>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    function_response: Option<GeminiFunctionResponse>,
+}
+
+impl GeminiPart {
+    fn text(text: impl Into<String>) -> Self {
+        Self {
+            text: Some(text.into()),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiInlineData {
+    mime_type: String,
+    data: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct GeminiFunctionCall {
+    name: String,
+    #[serde(default)]
+    args: serde_json::Value,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct GeminiFunctionResponse {
+    name: String,
+    response: serde_json::Value,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -41,6 +90,8 @@ struct GeminiRequest {
     contents: Vec<GeminiContent>,
     #[serde(skip_serializing_if = "Option::is_none")]
     system_instruction: Option<GeminiSystemInstruction>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<GeminiTools>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -49,13 +100,22 @@ struct GeminiSystemInstruction {
 }
 
 #[derive(Debug, Serialize, Deserialize)]
-struct GeminiCandidatePart {
-    text: String,
+struct GeminiTools {
+    #[serde(rename = "functionDeclarations")]
+    function_declarations: Vec<GeminiFunctionDeclaration>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct GeminiFunctionDeclaration {
+    name: String,
+    description: String,
+    parameters: serde_json::Value,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 struct GeminiCandidateContent {
-    parts: Vec<GeminiCandidatePart>,
+    #[serde(default)]
+    parts: Vec<GeminiPart>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -67,6 +127,60 @@ struct GeminiCandidate {
 struct GeminiResponse {
     #[serde(default)]
     candidates: Vec<GeminiCandidate>,
+    /// Cumulative when streaming, so the last chunk's value is the total.
+    #[serde(default, rename = "usageMetadata")]
+    usage_metadata: Option<GeminiUsage>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiUsage {
+    #[serde(default)]
+    prompt_token_count: u32,
+    #[serde(default)]
+    candidates_token_count: u32,
+}
+
+impl From<&GeminiUsage> for Usage {
+    fn from(u: &GeminiUsage) -> Self {
+        Usage::new(u.prompt_token_count, u.candidates_token_count)
+    }
+}
+
+impl GeminiResponse {
+    /// Parts of the first candidate (the only one we request).
+    fn into_parts(self) -> Vec<GeminiPart> {
+        self.candidates
+            .into_iter()
+            .next()
+            .and_then(|c| c.content)
+            .map(|c| c.parts)
+            .unwrap_or_default()
+    }
+}
+
+/// Gemini's OpenAPI-subset schema rejects some JSON Schema keywords.
+fn gemini_schema(schema: &serde_json::Value) -> serde_json::Value {
+    match schema {
+        serde_json::Value::Object(map) => map
+            .iter()
+            .filter(|(k, _)| k.as_str() != "additionalProperties" && k.as_str() != "$schema")
+            .map(|(k, v)| (k.clone(), gemini_schema(v)))
+            .collect::<serde_json::Map<_, _>>()
+            .into(),
+        serde_json::Value::Array(items) => items.iter().map(gemini_schema).collect(),
+        other => other.clone(),
+    }
+}
+
+/// Find the function name for a tool result by looking back for the call it answers.
+fn call_name(history: &[ChatMessage], call_id: &str) -> Option<String> {
+    history
+        .iter()
+        .rev()
+        .flat_map(|m| m.tool_calls.iter())
+        .find(|c| c.id == call_id)
+        .map(|c| c.name.clone())
 }
 
 pub struct GeminiClient {
@@ -76,8 +190,12 @@ pub struct GeminiClient {
     /// Determines auth strategy: `true` puts token in Authorization header,
     /// `false` puts API key in the URL query parameter.
     use_oauth: bool,
+    /// Base URL for the API. Defaults to `https://generativelanguage.googleapis.com`.
+    base_url: String,
 }
 
+const DEFAULT_BASE_URL: &str = "https://generativelanguage.googleapis.com";
+
 impl GeminiClient {
     pub fn new(model: &str) -> Result<Self> {
         let key = env::var("GEMINI_API_KEY").map_err(|_| anyhow!("GEMINI_API_KEY not set"))?;
@@ -86,6 +204,7 @@ impl GeminiClient {
             auth_token: key,
             model: model.to_string(),
             use_oauth: false,
+            base_url: DEFAULT_BASE_URL.to_string(),
         })
     }
 
@@ -106,23 +225,87 @@ impl GeminiClient {
             auth_token,
             model: model.to_string(),
             use_oauth,
+            base_url: DEFAULT_BASE_URL.to_string(),
         })
     }
 
-    pub async fn generate(&self, messages: Vec<ChatMessage>) -> Result<String> {
-        // OAuth tokens go in Authorization header; API keys go in URL query
-        let url = if self.use_oauth {
-            format!(
-                "https://generativelanguage.googleapis.com/v1beta/models/{}:generateContent",
-                self.model
-            )
-        } else {
-            format!(
-                "https://generativelanguage.googleapis.com/v1beta/models/{}:generateContent?key={}",
-                self.model, self.auth_token
-            )
-        };
+    /// Send requests to a different API host (proxies, local stand-ins).
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
 
+    /// Build the endpoint URL for `method` (`generateContent` or
+    /// `streamGenerateContent`). OAuth tokens go in the Authorization header;
+    /// API keys go in the URL query.
+    fn endpoint(&self, method: &str, stream: bool) -> String {
+        let mut url = format!("{}/v1beta/models/{}:{}", self.base_url, self.model, method);
+        let mut query = Vec::new();
+        if stream {
+            query.push("alt=sse".to_string());
+        }
+        if !self.use_oauth {
+            query.push(format!("key={}", self.auth_token));
+        }
+        if !query.is_empty() {
+            url.push('?');
+            url.push_str(&query.join("&"));
+        }
+        url
+    }
+
+    /// Models that can generate content (`GET /v1beta/models`), with the
+    /// input token limit Gemini reports for each.
+    pub async fn list_models(&self) -> Result<Vec<ModelInfo>> {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct ModelList {
+            #[serde(default)]
+            models: Vec<ModelEntry>,
+        }
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct ModelEntry {
+            name: String,
+            #[serde(default)]
+            input_token_limit: Option<usize>,
+            #[serde(default)]
+            supported_generation_methods: Vec<String>,
+        }
+
+        let mut url = format!("{}/v1beta/models?pageSize=1000", self.base_url);
+        if !self.use_oauth {
+            url.push_str(&format!("&key={}", self.auth_token));
+        }
+        let mut request = self.http.get(&url);
+        if self.use_oauth {
+            request = request.header("Authorization", format!("Bearer {}", self.auth_token));
+        }
+        let resp = request.send().await?;
+        if !resp.status().is_success() {
+            return Err(status_error("gemini", resp).await);
+        }
+        let list: ModelList = resp.json().await?;
+        Ok(list
+            .models
+            .into_iter()
+            .filter(|m| {
+                m.supported_generation_methods
+                    .iter()
+                    .any(|g| g == "generateContent")
+            })
+            .map(|m| {
+                let id = m
+                    .name
+                    .strip_prefix("models/")
+                    .unwrap_or(&m.name)
+                    .to_string();
+                ModelInfo::new("gemini", id, m.input_token_limit)
+            })
+            .collect())
+    }
+
+    fn build_request(&self, messages: &[ChatMessage], tools: &[ToolSpec]) -> Result<GeminiRequest> {
         // Gemini has strict requirements:
         //   1. contents must start with role "user"
         //   2. contents must end with role "user"
@@ -130,37 +313,69 @@ impl GeminiClient {
         //   4. system messages go in the separate system_instruction field
         //
         // We merge consecutive same-role messages and ensure alternation.
+        // Tool results are sent as "user" turns of functionResponse parts.
 
+        let vision = supports_vision("gemini", &self.model);
         let mut system_parts: Vec<GeminiPart> = Vec::new();
         let mut raw_contents: Vec<GeminiContent> = Vec::new();
 
-        for m in &messages {
+        for (i, m) in messages.iter().enumerate() {
             if m.role == "system" {
-                system_parts.push(GeminiPart {
-                    text: m.content.clone(),
+                system_parts.push(GeminiPart::text(m.content.clone()));
+                continue;
+            }
+            let role = match m.role.as_str() {
+                "assistant" => "model",
+                "user" => "user",
+                _ => "user",
+            };
+            let mut parts = Vec::new();
+            if m.role == "tool" {
+                let call_id = m.tool_call_id.as_deref().unwrap_or_default();
+                parts.push(GeminiPart {
+                    function_response: Some(GeminiFunctionResponse {
+                        name: call_name(&messages[..i], call_id)
+                            .unwrap_or_else(|| call_id.to_string()),
+                        response: serde_json::json!({ "content": m.content }),
+                    }),
+                    ..Default::default()
                 });
             } else {
-                let role = match m.role.as_str() {
-                    "assistant" => "model",
-                    "user" => "user",
-                    _ => "user",
-                };
-                // Merge consecutive messages with the same role
-                if let Some(last) = raw_contents.last_mut() {
-                    if last.role == role {
-                        last.parts.push(GeminiPart {
-                            text: m.content.clone(),
-                        });
-                        continue;
-                    }
+                let text = m.content_with_attachment_notes(vision);
+                if !text.is_empty() || (m.tool_calls.is_empty() && m.attachments.is_empty()) {
+                    parts.push(GeminiPart::text(text));
+                }
+                if vision {
+                    parts.extend(m.attachments.iter().filter_map(|a| match a {
+                        Attachment::Image { mime_type, data } => Some(GeminiPart {
+                            inline_data: Some(GeminiInlineData {
+                                mime_type: mime_type.clone(),
+                                data: data.clone(),
+                            }),
+                            ..Default::default()
+                        }),
+                        Attachment::File { .. } => None,
+                    }));
+                }
+                parts.extend(m.tool_calls.iter().map(|c| GeminiPart {
+                    function_call: Some(GeminiFunctionCall {
+                        name: c.name.clone(),
+                        args: c.arguments.clone(),
+                    }),
+                    ..Default::default()
+                }));
+            }
+            // Merge consecutive messages with the same role
+            if let Some(last) = raw_contents.last_mut() {
+                if last.role == role {
+                    last.parts.extend(parts);
+                    continue;
                 }
-                raw_contents.push(GeminiContent {
-                    role: role.to_string(),
-                    parts: vec![GeminiPart {
-                        text: m.content.clone(),
-                    }],
-                });
             }
+            raw_contents.push(GeminiContent {
+                role: role.to_string(),
+                parts,
+            });
         }
 
         // Ensure contents starts with "user"
@@ -171,9 +386,7 @@ impl GeminiClient {
         if raw_contents.last().map(|c| c.role.as_str()) == Some("model") {
             raw_contents.push(GeminiContent {
                 role: "user".to_string(),
-                parts: vec![GeminiPart {
-                    text: "Continue.".to_string(),
-                }],
+                parts: vec![GeminiPart::text("Continue.")],
             });
         }
         // If empty after trimming, nothing to send
@@ -189,53 +402,244 @@ impl GeminiClient {
             })
         };
 
-        let req = GeminiRequest {
+        let tools = if tools.is_empty() {
+            Vec::new()
+        } else {
+            vec![GeminiTools {
+                function_declarations: tools
+                    .iter()
+                    .map(|t| GeminiFunctionDeclaration {
+                        name: t.name.clone(),
+                        description: t.description.clone(),
+                        parameters: gemini_schema(&t.parameters),
+                    })
+                    .collect(),
+            }]
+        };
+
+        Ok(GeminiRequest {
             contents: raw_contents,
             system_instruction,
-        };
-        // Retry loop for transient errors (429 rate-limit, 503 overloaded)
-        let mut last_status = reqwest::StatusCode::OK;
-        let mut last_body = String::new();
-        for attempt in 0..4u32 {
-            if attempt > 0 {
-                let delay = Duration::from_millis(1000 * 2u64.pow(attempt - 1)); // 1s, 2s, 4s
-                tokio::time::sleep(delay).await;
+            tools,
+        })
+    }
+
+    /// POST the request and return the response so callers can read it whole
+    /// or as a stream. Transient failures are retried by the router.
+    async fn send(&self, url: &str, req: &GeminiRequest) -> Result<reqwest::Response> {
+        let mut request = self.http.post(url).json(req);
+        if self.use_oauth {
+            request = request.header("Authorization", format!("Bearer {}", self.auth_token));
+        }
+        let resp = request.send().await?;
+        if !resp.status().is_success() {
+            return Err(status_error("gemini", resp).await);
+        }
+        Ok(resp)
+    }
+
+    /// Append response parts to `reply`, numbering function calls after any
+    /// already collected. Returns the new text, if any.
+    fn absorb_parts(reply: &mut ChatMessage, parts: Vec<GeminiPart>) -> String {
+        let mut text = String::new();
+        for part in parts {
+            if let Some(t) = part.text {
+                text.push_str(&t);
             }
-            let mut request = self.http.post(&url).json(&req);
-            if self.use_oauth {
-                request = request.header("Authorization", format!("Bearer {}", self.auth_token));
+            if let Some(call) = part.function_call {
+                reply.tool_calls.push(ToolCall {
+                    id: format!("call_{}", reply.tool_calls.len()),
+                    name: call.name,
+                    arguments: if call.args.is_null() {
+                        serde_json::json!({})
+                    } else {
+                        call.args
+                    },
+                });
             }
-            let resp = request.send().await?;
-            if resp.status().is_success() {
-                let body: GeminiResponse = resp.json().await?;
-                let text = body
-                    .candidates
-                    .first()
-                    .and_then(|c| c.content.as_ref())
-                    .and_then(|c| c.parts.first())
-                    .map(|p| p.text.clone())
-                    .unwrap_or_default();
-                return Ok(text);
+        }
+        reply.content.push_str(&text);
+        text
+    }
+
+    pub async fn generate(
+        &self,
+        messages: Vec<ChatMessage>,
+        tools: &[ToolSpec],
+    ) -> Result<(ChatMessage, Option<Usage>)> {
+        let req = self.build_request(&messages, tools)?;
+        let url = self.endpoint("generateContent", false);
+        let resp = self.send(&url, &req).await?;
+        let body: GeminiResponse = resp.json().await?;
+        let usage = body.usage_metadata.as_ref().map(Usage::from);
+        let mut reply = ChatMessage::assistant("");
+        Self::absorb_parts(&mut reply, body.into_parts());
+        Ok((reply, usage))
+    }
+
+    /// Stream the reply via `streamGenerateContent`, calling `on_delta` for
+    /// every text slice. Returns the full reply and usage when the stream ends.
+    pub async fn generate_stream<F>(
+        &self,
+        messages: Vec<ChatMessage>,
+        tools: &[ToolSpec],
+        mut on_delta: F,
+    ) -> Result<(ChatMessage, Option<Usage>)>
+    where
+        F: FnMut(&str) + Send,
+    {
+        let req = self.build_request(&messages, tools)?;
+        let url = self.endpoint("streamGenerateContent", true);
+        let resp = self.send(&url, &req).await?;
+
+        let mut reply = ChatMessage::assistant("");
+        let mut usage = None;
+        for_each_line(resp, |line| {
+            let Some(data) = sse_data(line) else {
+                return Ok(true);
+            };
+            let chunk: GeminiResponse = serde_json::from_str(data)?;
+            if let Some(u) = &chunk.usage_metadata {
+                usage = Some(Usage::from(u));
             }
-            last_status = resp.status();
-            last_body = resp.text().await.unwrap_or_default();
-            // Only retry on 429 (rate limit) or 503 (overloaded)
-            if last_status != reqwest::StatusCode::TOO_MANY_REQUESTS
-                && last_status != reqwest::StatusCode::SERVICE_UNAVAILABLE
-            {
-                break;
+            let text = Self::absorb_parts(&mut reply, chunk.into_parts());
+            if !text.is_empty() {
+                on_delta(&text);
             }
-        }
+            Ok(true)
+        })
+        .await?;
+        Ok((reply, usage))
+    }
+}
 
-        let body = last_body.trim().to_string();
-        if body.is_empty() {
-            return Err(anyhow!("gemini error: {}", last_status));
-        }
-        let body = if body.len() > 800 {
-            format!("{}...", &body.chars().take(800).collect::<String>())
-        } else {
-            body
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream::test_server;
+
+    fn client(base: &str) -> GeminiClient {
+        let auth = ProviderAuth {
+            api_key: Some("test-key".to_string()),
+            oauth: None,
         };
-        Err(anyhow!("gemini error: {}\n{}", last_status, body))
+        GeminiClient::from_auth("gemini-2.0-flash", &auth)
+            .unwrap()
+            .with_base_url(base)
+    }
+
+    #[test]
+    fn test_endpoint_query() {
+        let c = client("http://localhost:1/");
+        assert_eq!(
+            c.endpoint("streamGenerateContent", true),
+            "http://localhost:1/v1beta/models/gemini-2.0-flash:streamGenerateContent?alt=sse&key=test-key"
+        );
+        assert_eq!(
+            c.endpoint("generateContent", false),
+            "http://localhost:1/v1beta/models/gemini-2.0-flash:generateContent?key=test-key"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_generate_stream_reads_sse() {
+        let base = test_server::serve(
+            200,
+            "text/event-stream",
+            "data: {\"candidates\":[{\"content\":{\"role\":\"model\",\"parts\":[{\"text\":\"Bon\"}]}}]}\r\n\r\n\
+             data: {\"candidates\":[{\"content\":{\"role\":\"model\",\"parts\":[{\"text\":\"jour\"}]},\"finishReason\":\"STOP\"}],\"usageMetadata\":{\"promptTokenCount\":4,\"candidatesTokenCount\":2,\"totalTokenCount\":6}}\r\n\r\n",
+        );
+        let messages = vec![ChatMessage::user("hi")];
+        let mut deltas = Vec::new();
+        let (reply, usage) = client(&base)
+            .generate_stream(messages, &[], |d| deltas.push(d.to_string()))
+            .await
+            .unwrap();
+        assert_eq!(reply.content, "Bonjour");
+        assert_eq!(deltas, vec!["Bon", "jour"]);
+        assert_eq!(usage, Some(Usage::new(4, 2)));
+    }
+
+    #[test]
+    fn test_build_request_maps_tools_and_results() {
+        let call = ToolCall {
+            id: "call_0".to_string(),
+            name: "search".to_string(),
+            arguments: serde_json::json!({"query": "weather"}),
+        };
+        let mut assistant = ChatMessage::assistant("");
+        assistant.tool_calls = vec![call.clone()];
+        let tools = [ToolSpec {
+            name: "search".to_string(),
+            description: "Search the web".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {"query": {"type": "string"}},
+                "additionalProperties": false,
+            }),
+        }];
+        let req = client("http://unused")
+            .build_request(
+                &[
+                    ChatMessage::user("weather?"),
+                    assistant,
+                    ChatMessage::tool_result(&call, "sunny"),
+                ],
+                &tools,
+            )
+            .unwrap();
+        let json = serde_json::to_value(&req).unwrap();
+        let decl = &json["tools"][0]["functionDeclarations"][0];
+        assert_eq!(decl["name"], "search");
+        assert!(decl["parameters"].get("additionalProperties").is_none());
+        assert_eq!(
+            json["contents"][1]["parts"][0]["functionCall"]["name"],
+            "search"
+        );
+        let response = &json["contents"][2]["parts"][0]["functionResponse"];
+        assert_eq!(response["name"], "search");
+        assert_eq!(response["response"]["content"], "sunny");
+    }
+
+    #[tokio::test]
+    async fn test_generate_reads_function_call() {
+        let base = test_server::serve(
+            200,
+            "application/json",
+            "{\"candidates\":[{\"content\":{\"role\":\"model\",\"parts\":[{\"functionCall\":{\"name\":\"run_command\",\"args\":{\"command\":\"uptime\"}}}]}}]}",
+        );
+        let (reply, _) = client(&base)
+            .generate(vec![ChatMessage::user("uptime?")], &[])
+            .await
+            .unwrap();
+        assert_eq!(reply.tool_calls.len(), 1);
+        assert_eq!(reply.tool_calls[0].id, "call_0");
+        assert_eq!(reply.tool_calls[0].arguments["command"], "uptime");
+    }
+
+    #[tokio::test]
+    async fn test_list_models_keeps_generative_models() {
+        let base = test_server::serve(
+            200,
+            "application/json",
+            "{\"models\":[{\"name\":\"models/gemini-2.0-flash\",\"inputTokenLimit\":1048576,\"supportedGenerationMethods\":[\"generateContent\",\"countTokens\"]},{\"name\":\"models/text-embedding-004\",\"inputTokenLimit\":2048,\"supportedGenerationMethods\":[\"embedContent\"]}]}",
+        );
+        let models = client(&base).list_models().await.unwrap();
+        assert_eq!(models.len(), 1);
+        assert_eq!(models[0].id, "gemini-2.0-flash");
+        assert_eq!(models[0].context_window, 1_048_576);
+        assert!(models[0].supports_vision);
+    }
+
+    #[test]
+    fn test_build_request_inlines_images() {
+        let msg =
+            ChatMessage::user("describe").with_attachment(Attachment::image("image/webp", b"webp"));
+        let req = client("http://unused").build_request(&[msg], &[]).unwrap();
+        let json = serde_json::to_value(req).unwrap();
+        let parts = &json["contents"][0]["parts"];
+        assert_eq!(parts[0]["text"], "describe");
+        assert_eq!(parts[1]["inlineData"]["mimeType"], "image/webp");
+        assert_eq!(parts[1]["inlineData"]["data"], "d2VicA==");
     }
 }
@@ -0,0 +1,402 @@
+//! Rolling per-provider health and a simple circuit breaker.
+//!
+//! [`ProviderRouter`](crate::router::ProviderRouter) records the outcome of
+//! every request here. After [`HealthPolicy::failure_threshold`] consecutive
+//! outage-type failures (connection errors, timeouts, 429/5xx) a provider's
+//! circuit opens and the router skips it until the cool-down passes; then one
+//! probe request is let through (half-open) and its result closes or re-opens
+//! the circuit. A rate limit that carries `Retry-After` opens the circuit for
+//! at least that long.
+//!
+//! State lives in a process-wide tracker ([`HealthTracker::global`]) because
+//! routers are cheap and created per conversation turn; the settings screen
+//! reads the same tracker through [`HealthTracker::snapshot`].
+
+use parking_lot::Mutex;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::sync::{Arc, LazyLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// A non-2xx HTTP response from a provider.
+///
+/// Clients return this (wrapped in `anyhow::Error`) so the router can tell
+/// transient failures from permanent ones and honour `Retry-After`.
+#[derive(Debug, Clone)]
+pub struct ProviderHttpError {
+    pub provider: String,
+    pub status: u16,
+    /// Parsed `Retry-After` header (delta-seconds form only).
+    pub retry_after: Option<Duration>,
+    /// First 800 characters of the response body.
+    pub body: String,
+}
+
+impl ProviderHttpError {
+    /// Rate limits and server-side failures are worth retrying; other 4xx
+    /// responses (bad key, bad request) are not.
+    pub fn is_transient(&self) -> bool {
+        // 529 is Anthropic's "overloaded".
+        matches!(self.status, 408 | 429 | 500 | 502 | 503 | 504 | 529)
+    }
+}
+
+impl fmt::Display for ProviderHttpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = reqwest::StatusCode::from_u16(self.status)
+            .map(|s| s.to_string())
+            .unwrap_or_else(|_| self.status.to_string());
+        if self.body.trim().is_empty() {
+            write!(f, "{} error: {}", self.provider, status)
+        } else {
+            write!(f, "{} error: {}\n{}", self.provider, status, self.body)
+        }
+    }
+}
+
+impl std::error::Error for ProviderHttpError {}
+
+/// Whether `err` looks like a provider outage rather than a configuration or
+/// protocol problem: a transient HTTP status, or a connection/timeout error.
+pub fn is_transient(err: &anyhow::Error) -> bool {
+    if let Some(http) = err.downcast_ref::<ProviderHttpError>() {
+        return http.is_transient();
+    }
+    if is_deadline(err) {
+        return true;
+    }
+    err.chain()
+        .filter_map(|e| e.downcast_ref::<reqwest::Error>())
+        .any(|e| e.is_timeout() || e.is_connect() || e.is_request())
+}
+
+/// Whether `err` is the router's own per-request timeout.
+pub fn is_deadline(err: &anyhow::Error) -> bool {
+    err.chain().any(|e| e.is::<tokio::time::error::Elapsed>())
+}
+
+/// The `Retry-After` hint attached to `err`, if any.
+pub fn retry_after(err: &anyhow::Error) -> Option<Duration> {
+    err.downcast_ref::<ProviderHttpError>()
+        .and_then(|e| e.retry_after)
+}
+
+/// Tuning knobs for retries and the circuit breaker.
+#[derive(Debug, Clone)]
+pub struct HealthPolicy {
+    /// Consecutive outage-type failures before the circuit opens.
+    pub failure_threshold: u32,
+    /// How long an open circuit stays open before a probe is allowed.
+    pub cooldown: Duration,
+    /// Extra attempts on the same provider for transient errors.
+    pub max_retries: u32,
+    /// First retry delay; doubles per attempt, with up to 50% jitter added.
+    pub base_backoff: Duration,
+    /// Longest we will sleep before retrying. A `Retry-After` beyond this
+    /// makes the router fall back instead of waiting.
+    pub max_backoff: Duration,
+    /// Number of recent outcomes used for the error rate and latency.
+    pub window: usize,
+}
+
+impl Default for HealthPolicy {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 3,
+            cooldown: Duration::from_secs(60),
+            max_retries: 2,
+            base_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(8),
+            window: 20,
+        }
+    }
+}
+
+impl HealthPolicy {
+    /// Delay before retry number `attempt` (0-based). A server `Retry-After`
+    /// wins over the exponential schedule.
+    pub fn backoff(&self, attempt: u32, retry_after: Option<Duration>) -> Duration {
+        if let Some(wait) = retry_after {
+            return wait.min(self.max_backoff);
+        }
+        let base = self
+            .base_backoff
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_backoff);
+        // Cheap jitter without pulling in an RNG: the clock's sub-second noise.
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.subsec_nanos())
+            .unwrap_or(0);
+        let jitter = base.mul_f64((nanos % 1000) as f64 / 2000.0);
+        (base + jitter).min(self.max_backoff)
+    }
+}
+
+/// Circuit breaker position for one provider.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Requests flow normally.
+    Closed,
+    /// Requests are skipped until the cool-down ends.
+    Open,
+    /// Cool-down over; the next request is a probe.
+    HalfOpen,
+}
+
+/// Point-in-time health of one provider, for display.
+#[derive(Debug, Clone)]
+pub struct ProviderHealth {
+    pub provider: String,
+    pub state: CircuitState,
+    /// Share of failed requests among the last [`HealthPolicy::window`].
+    pub error_rate: f32,
+    /// Mean latency of recent successful requests.
+    pub avg_latency_ms: Option<u64>,
+    pub consecutive_failures: u32,
+    pub last_error: Option<String>,
+    /// Time left before an open circuit lets a probe through.
+    pub retry_in: Option<Duration>,
+}
+
+impl ProviderHealth {
+    /// Short status line for the settings screen.
+    pub fn summary(&self) -> String {
+        match self.state {
+            CircuitState::Open => format!(
+                "Unavailable, retrying in {}s",
+                self.retry_in.map(|d| d.as_secs().max(1)).unwrap_or(1)
+            ),
+            CircuitState::HalfOpen => "Recovering".to_string(),
+            CircuitState::Closed if self.error_rate > 0.0 => format!(
+                "Working ({:.0}% of recent requests failed)",
+                self.error_rate * 100.0
+            ),
+            CircuitState::Closed => match self.avg_latency_ms {
+                Some(ms) => format!("Healthy ({:.1}s average reply)", ms as f64 / 1000.0),
+                None => "Healthy".to_string(),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Record {
+    /// `Some(latency)` for successes, `None` for failures, newest last.
+    outcomes: VecDeque<Option<Duration>>,
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+    /// A probe is in flight after the cool-down; other callers keep skipping.
+    probing: bool,
+    last_error: Option<String>,
+}
+
+/// Thread-safe health records keyed by provider id (`"openai"`, `"local"`, ...).
+#[derive(Debug, Default)]
+pub struct HealthTracker {
+    policy: HealthPolicy,
+    records: Mutex<HashMap<String, Record>>,
+}
+
+static GLOBAL: LazyLock<Arc<HealthTracker>> = LazyLock::new(|| Arc::new(HealthTracker::default()));
+
+impl HealthTracker {
+    pub fn new(policy: HealthPolicy) -> Self {
+        Self {
+            policy,
+            records: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The tracker shared by every router in this process.
+    pub fn global() -> Arc<HealthTracker> {
+        GLOBAL.clone()
+    }
+
+    pub fn policy(&self) -> &HealthPolicy {
+        &self.policy
+    }
+
+    /// Check whether `provider` may be called now. Returns the remaining
+    /// cool-down if its circuit is open. Once the cool-down has passed, the
+    /// first caller gets through as the half-open probe.
+    pub fn allow(&self, provider: &str) -> Result<(), Duration> {
+        let mut records = self.records.lock();
+        let Some(record) = records.get_mut(provider) else {
+            return Ok(());
+        };
+        let Some(until) = record.open_until else {
+            return Ok(());
+        };
+        let now = Instant::now();
+        if now < until {
+            return Err(until - now);
+        }
+        if record.probing {
+            return Err(Duration::ZERO);
+        }
+        record.probing = true;
+        Ok(())
+    }
+
+    pub fn record_success(&self, provider: &str, latency: Duration) {
+        let mut records = self.records.lock();
+        let record = records.entry(provider.to_string()).or_default();
+        self.push_outcome(record, Some(latency));
+        record.consecutive_failures = 0;
+        record.open_until = None;
+        record.probing = false;
+    }
+
+    /// Record a failed request. Only outage-type errors (see [`is_transient`])
+    /// count towards opening the circuit; a failed probe re-opens it at once.
+    pub fn record_failure(&self, provider: &str, err: &anyhow::Error) {
+        let mut records = self.records.lock();
+        let record = records.entry(provider.to_string()).or_default();
+        self.push_outcome(record, None);
+        record.last_error = Some(err.to_string().lines().next().unwrap_or("").to_string());
+        let was_probe = std::mem::take(&mut record.probing);
+        if !is_transient(err) {
+            return;
+        }
+        record.consecutive_failures += 1;
+        let now = Instant::now();
+        let mut open_until = None;
+        if was_probe || record.consecutive_failures >= self.policy.failure_threshold {
+            open_until = Some(now + self.policy.cooldown);
+        }
+        if let Some(wait) = retry_after(err) {
+            open_until = open_until.max(Some(now + wait));
+        }
+        if open_until.is_some() {
+            record.open_until = open_until;
+        }
+    }
+
+    /// Health of one provider, or `None` if it has never been called.
+    pub fn get(&self, provider: &str) -> Option<ProviderHealth> {
+        let records = self.records.lock();
+        records.get(provider).map(|r| Self::health(provider, r))
+    }
+
+    /// Health of every provider called so far, sorted by id.
+    pub fn snapshot(&self) -> Vec<ProviderHealth> {
+        let records = self.records.lock();
+        let mut all: Vec<ProviderHealth> = records
+            .iter()
+            .map(|(provider, r)| Self::health(provider, r))
+            .collect();
+        all.sort_by(|a, b| a.provider.cmp(&b.provider));
+        all
+    }
+
+    fn push_outcome(&self, record: &mut Record, outcome: Option<Duration>) {
+        record.outcomes.push_back(outcome);
+        while record.outcomes.len() > self.policy.window.max(1) {
+            record.outcomes.pop_front();
+        }
+    }
+
+    fn health(provider: &str, record: &Record) -> ProviderHealth {
+        let now = Instant::now();
+        let (state, retry_in) = match record.open_until {
+            Some(until) if now < until => (CircuitState::Open, Some(until - now)),
+            Some(_) => (CircuitState::HalfOpen, None),
+            None => (CircuitState::Closed, None),
+        };
+        let failures = record.outcomes.iter().filter(|o| o.is_none()).count();
+        let latencies: Vec<Duration> = record.outcomes.iter().flatten().copied().collect();
+        ProviderHealth {
+            provider: provider.to_string(),
+            state,
+            error_rate: if record.outcomes.is_empty() {
+                0.0
+            } else {
+                failures as f32 / record.outcomes.len() as f32
+            },
+            avg_latency_ms: if latencies.is_empty() {
+                None
+            } else {
+                Some(
+                    (latencies.iter().sum::<Duration>() / latencies.len() as u32).as_millis()
+                        as u64,
+                )
+            },
+            consecutive_failures: record.consecutive_failures,
+            last_error: record.last_error.clone(),
+            retry_in,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn http_error(status: u16, retry_after: Option<Duration>) -> anyhow::Error {
+        anyhow::Error::new(ProviderHttpError {
+            provider: "openai".to_string(),
+            status,
+            retry_after,
+            body: String::new(),
+        })
+    }
+
+    fn tracker(cooldown: Duration) -> HealthTracker {
+        HealthTracker::new(HealthPolicy {
+            failure_threshold: 2,
+            cooldown,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_circuit_opens_after_threshold_and_probes_after_cooldown() {
+        let health = tracker(Duration::from_millis(30));
+        health.record_failure("openai", &http_error(503, None));
+        assert!(health.allow("openai").is_ok());
+        health.record_failure("openai", &http_error(503, None));
+        assert!(health.allow("openai").is_err());
+        assert_eq!(health.get("openai").unwrap().state, CircuitState::Open);
+
+        std::thread::sleep(Duration::from_millis(40));
+        assert!(health.allow("openai").is_ok(), "first caller probes");
+        assert!(health.allow("openai").is_err(), "others wait for the probe");
+        health.record_success("openai", Duration::from_millis(200));
+        assert!(health.allow("openai").is_ok());
+        let snapshot = health.get("openai").unwrap();
+        assert_eq!(snapshot.state, CircuitState::Closed);
+        assert_eq!(snapshot.avg_latency_ms, Some(200));
+        assert!((snapshot.error_rate - 2.0 / 3.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_permanent_errors_do_not_open_circuit() {
+        let health = tracker(Duration::from_secs(60));
+        for _ in 0..5 {
+            health.record_failure("openai", &http_error(401, None));
+        }
+        assert!(health.allow("openai").is_ok());
+        assert_eq!(health.get("openai").unwrap().consecutive_failures, 0);
+    }
+
+    #[test]
+    fn test_retry_after_opens_circuit_immediately() {
+        let health = tracker(Duration::from_secs(1));
+        health.record_failure("openai", &http_error(429, Some(Duration::from_secs(120))));
+        let wait = health.allow("openai").unwrap_err();
+        assert!(wait > Duration::from_secs(100));
+    }
+
+    #[test]
+    fn test_backoff_is_bounded() {
+        let policy = HealthPolicy::default();
+        let first = policy.backoff(0, None);
+        assert!(first >= policy.base_backoff && first <= policy.base_backoff * 3 / 2);
+        assert_eq!(policy.backoff(10, None), policy.max_backoff);
+        assert_eq!(
+            policy.backoff(0, Some(Duration::from_secs(2))),
+            Duration::from_secs(2)
+        );
+    }
+}
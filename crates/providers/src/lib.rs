@@ -3,16 +3,33 @@
 //! This crate isolates all LLM API integration behind a single [`router::ProviderRouter`]
 //! that tries providers in user-configured preference order, falling back automatically
 //! on failure. Each provider module ([`openai`], [`anthropic`], [`gemini`], [`ollama`])
-//! implements the same contract: accept `Vec<ChatMessage>`, return `Result<String>`.
+//! implements the same contract: accept `Vec<ChatMessage>` plus tool definitions,
+//! return the assistant `ChatMessage` (text and any tool calls), plus a
+//! `generate_stream` variant that reports text deltas as they arrive. Image
+//! attachments are sent natively to vision models and as text notes otherwise.
 //!
 //! Additional modules:
 //! - [`oauth_helper`] -- Browser-based OAuth 2.0 + PKCE flow for cloud providers.
+//! - [`oauth_refresh`] -- Refreshes OAuth access tokens before they expire.
 //! - [`external`] -- Registry for optional tool providers (Playwright, Canva, etc.).
+//! - [`stream`] -- SSE / NDJSON line framing shared by the streaming clients.
+//! - [`capabilities`] -- Per-model feature detection (vision, tools, context window).
+//! - [`catalog`] -- Cached model lists per provider and settings validation.
+//! - [`health`] -- Per-provider health tracking, retries and circuit breaker.
+//! - [`usage`] -- Token counts reported by providers and the recorder hook.
+//! - [`cancel`] -- Cancellation tokens and deadlines for in-flight requests.
 
 pub mod anthropic;
+pub mod cancel;
+pub mod capabilities;
+pub mod catalog;
 pub mod external;
 pub mod gemini;
+pub mod health;
 pub mod oauth_helper;
+pub mod oauth_refresh;
 pub mod ollama;
 pub mod openai;
 pub mod router;
+pub mod stream;
+pub mod usage;
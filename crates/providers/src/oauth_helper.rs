@@ -7,6 +7,8 @@
 //! 3. Wait (up to 5 minutes) for the browser redirect with an auth code.
 //! 4. Exchange the auth code for an access token (and optional refresh token).
 //!
+//! Keeping the resulting tokens fresh is [`crate::oauth_refresh`]'s job.
+//!
 //! PKCE (Proof Key for Code Exchange) is always used, even when a client
 //! secret is provided, for defense-in-depth against interception attacks.
 
@@ -17,11 +19,16 @@ use oauth2::{
     AuthUrl, AuthorizationCode, ClientId, ClientSecret, CsrfToken, PkceCodeChallenge, RedirectUrl,
     Scope, TokenResponse, TokenUrl,
 };
+use shared::settings::OAuthCredentials;
 use std::io::{BufRead, BufReader, Write};
 use std::net::TcpListener;
 use std::time::Duration;
 use url::Url;
 
+/// Google's OAuth endpoints (Gemini sign-in).
+pub const GOOGLE_AUTH_URL: &str = "https://accounts.google.com/o/oauth2/v2/auth";
+pub const GOOGLE_TOKEN_URL: &str = "https://oauth2.googleapis.com/token";
+
 /// Manages a single OAuth 2.0 authorization flow against one provider.
 pub struct OAuthFlow {
     client: BasicClient,
@@ -66,11 +73,12 @@ impl OAuthFlow {
         })
     }
 
-    /// Run the full browser-based auth flow, returning (access_token, optional refresh_token).
+    /// Run the full browser-based auth flow, returning the access token, the
+    /// refresh token (if the provider issued one) and the expiry.
     ///
     /// Opens the user's browser, waits for the callback, verifies the CSRF
     /// state parameter, and exchanges the authorization code for tokens.
-    pub async fn authenticate(&self) -> Result<(String, Option<String>)> {
+    pub async fn authenticate(&self) -> Result<OAuthCredentials> {
         let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
 
         // Build authorization URL
@@ -118,10 +126,15 @@ impl OAuthFlow {
             .request_async(async_http_client)
             .await?;
 
-        let access_token = token_result.access_token().secret().clone();
-        let refresh_token = token_result.refresh_token().map(|t| t.secret().clone());
-
-        Ok((access_token, refresh_token))
+        // Assume the usual one-hour lifetime if the provider doesn't say.
+        let lifetime = token_result
+            .expires_in()
+            .unwrap_or(Duration::from_secs(3600));
+        Ok(OAuthCredentials {
+            access_token: token_result.access_token().secret().clone(),
+            refresh_token: token_result.refresh_token().map(|t| t.secret().clone()),
+            expires_at: Some(crate::oauth_refresh::now_secs() + lifetime.as_secs() as i64),
+        })
     }
 }
 
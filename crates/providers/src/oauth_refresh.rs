@@ -0,0 +1,443 @@
+//! Keeping OAuth access tokens fresh.
+//!
+//! Sign-in ([`OAuthFlow`](crate::oauth_helper::OAuthFlow)) stores an access
+//! token, a refresh token and the expiry in [`OAuthCredentials`]. A
+//! [`TokenRefresher`] attached to the router swaps the access token for a new
+//! one shortly before it expires, so requests don't go out with a stale
+//! token and fail with 401.
+//!
+//! Refreshes are single-flight per provider: concurrent requests wait for the
+//! refresh already in progress and reuse its result. Rotated credentials and
+//! failed refreshes are reported to an [`AuthEventSink`]; the app saves the
+//! former to settings and asks the user to sign in again on the latter.
+//! Only a refresh the endpoint turns down (e.g. `invalid_grant`) needs a new
+//! sign-in; network trouble, timeouts and server errors fail the request but
+//! leave the refresh token to be tried again on the next one.
+
+use anyhow::{anyhow, Result};
+use oauth2::basic::{BasicClient, BasicErrorResponse};
+use oauth2::reqwest::async_http_client;
+use oauth2::{AuthUrl, ClientId, ClientSecret, RefreshToken, TokenResponse, TokenUrl};
+use parking_lot::Mutex;
+use shared::settings::OAuthCredentials;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Refresh tokens this long before they expire.
+pub const DEFAULT_REFRESH_AHEAD: Duration = Duration::from_secs(5 * 60);
+
+/// Lifetime assumed when the token endpoint doesn't say.
+const DEFAULT_LIFETIME: Duration = Duration::from_secs(60 * 60);
+
+/// The OAuth client registration needed to refresh a provider's tokens.
+#[derive(Debug, Clone)]
+pub struct OAuthClientConfig {
+    pub client_id: String,
+    pub client_secret: Option<String>,
+    pub token_url: String,
+}
+
+/// Something the app has to act on after a refresh attempt.
+#[derive(Debug, Clone)]
+pub enum AuthEvent {
+    /// `provider` has new credentials; save them in place of the old ones.
+    Refreshed {
+        provider: String,
+        credentials: OAuthCredentials,
+    },
+    /// The refresh token was rejected or is missing; the user has to sign in
+    /// again before `provider` can be used.
+    ReauthRequired { provider: String, reason: String },
+}
+
+/// Receives [`AuthEvent`]s from a [`TokenRefresher`].
+pub trait AuthEventSink: Send + Sync {
+    fn auth_event(&self, event: AuthEvent);
+}
+
+impl AuthEventSink for std::sync::mpsc::Sender<AuthEvent> {
+    fn auth_event(&self, event: AuthEvent) {
+        let _ = self.send(event);
+    }
+}
+
+/// Refreshes OAuth access tokens ahead of expiry. Share one per app (behind
+/// an `Arc`) so every router sees the same refreshed tokens.
+pub struct TokenRefresher {
+    clients: HashMap<String, OAuthClientConfig>,
+    sink: Option<Arc<dyn AuthEventSink>>,
+    refresh_ahead: Duration,
+    /// Newest credentials per provider, which may be newer than the settings
+    /// a router was built from.
+    current: Mutex<HashMap<String, OAuthCredentials>>,
+    /// One lock per provider so only one refresh runs at a time.
+    inflight: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+    /// Refresh tokens the endpoint rejected; not retried until the user signs in again.
+    rejected: Mutex<HashSet<String>>,
+}
+
+impl Default for TokenRefresher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TokenRefresher {
+    pub fn new() -> Self {
+        Self {
+            clients: HashMap::new(),
+            sink: None,
+            refresh_ahead: DEFAULT_REFRESH_AHEAD,
+            current: Mutex::new(HashMap::new()),
+            inflight: Mutex::new(HashMap::new()),
+            rejected: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Refresh `provider`'s tokens through `client`. Providers without a
+    /// registered client keep using their access token as-is.
+    pub fn with_client(mut self, provider: impl Into<String>, client: OAuthClientConfig) -> Self {
+        self.clients.insert(provider.into(), client);
+        self
+    }
+
+    /// Report rotated credentials and failed refreshes to `sink`.
+    pub fn with_sink(mut self, sink: Arc<dyn AuthEventSink>) -> Self {
+        self.sink = Some(sink);
+        self
+    }
+
+    /// Refresh tokens this long before they expire instead of
+    /// [`DEFAULT_REFRESH_AHEAD`].
+    pub fn with_refresh_ahead(mut self, ahead: Duration) -> Self {
+        self.refresh_ahead = ahead;
+        self
+    }
+
+    /// Credentials for `provider` that are good for at least the refresh
+    /// window, refreshing `creds` first if they are about to expire. Fails if
+    /// a refresh was needed and didn't work. If the endpoint rejected the
+    /// refresh token the user has to sign in again (an
+    /// [`AuthEvent::ReauthRequired`] has been sent); after a
+    /// [`RefreshError::Transient`] failure the next call tries again.
+    pub async fn fresh(&self, provider: &str, creds: &OAuthCredentials) -> Result<OAuthCredentials> {
+        let creds = self.latest(provider, creds);
+        if !self.needs_refresh(&creds) {
+            return Ok(creds);
+        }
+        let Some(client) = self.clients.get(provider) else {
+            return Ok(creds);
+        };
+
+        let gate = self.inflight.lock().entry(provider.to_string()).or_default().clone();
+        let _guard = gate.lock().await;
+        // Another request may have refreshed while this one waited.
+        let creds = self.latest(provider, &creds);
+        if !self.needs_refresh(&creds) {
+            return Ok(creds);
+        }
+
+        let Some(refresh_token) = creds.refresh_token.clone() else {
+            return Err(self.reauth(provider, "no refresh token was saved at sign-in"));
+        };
+        if self.rejected.lock().contains(&refresh_token) {
+            return Err(anyhow!("{} sign-in expired; sign in again in Settings", provider));
+        }
+        match refresh(client, &refresh_token).await {
+            Ok(mut fresh) => {
+                // Endpoints that don't rotate refresh tokens omit them.
+                fresh.refresh_token.get_or_insert(refresh_token);
+                self.current.lock().insert(provider.to_string(), fresh.clone());
+                if let Some(sink) = &self.sink {
+                    sink.auth_event(AuthEvent::Refreshed {
+                        provider: provider.to_string(),
+                        credentials: fresh.clone(),
+                    });
+                }
+                Ok(fresh)
+            }
+            Err(e) => match e.downcast_ref::<RefreshError>() {
+                Some(RefreshError::Rejected(reason)) => {
+                    let reason = reason.clone();
+                    self.rejected.lock().insert(refresh_token);
+                    Err(self.reauth(provider, &reason))
+                }
+                _ => Err(e.context(format!(
+                    "{} sign-in could not be renewed; will retry",
+                    provider
+                ))),
+            },
+        }
+    }
+
+    /// `creds`, or the credentials this refresher obtained for `provider` if
+    /// those last longer.
+    fn latest(&self, provider: &str, creds: &OAuthCredentials) -> OAuthCredentials {
+        match self.current.lock().get(provider) {
+            Some(cached) if cached.expires_at > creds.expires_at => cached.clone(),
+            _ => creds.clone(),
+        }
+    }
+
+    fn needs_refresh(&self, creds: &OAuthCredentials) -> bool {
+        creds
+            .expires_at
+            .is_some_and(|at| at - now_secs() <= self.refresh_ahead.as_secs() as i64)
+    }
+
+    fn reauth(&self, provider: &str, reason: &str) -> anyhow::Error {
+        if let Some(sink) = &self.sink {
+            sink.auth_event(AuthEvent::ReauthRequired {
+                provider: provider.to_string(),
+                reason: reason.to_string(),
+            });
+        }
+        anyhow!("{} sign-in expired ({}); sign in again in Settings", provider, reason)
+    }
+}
+
+/// Why a token refresh failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RefreshError {
+    /// The endpoint turned the refresh token down (e.g. `invalid_grant`);
+    /// it won't work again until the user signs in.
+    Rejected(String),
+    /// The endpoint couldn't be reached, timed out or failed (5xx); the same
+    /// refresh token may work next time.
+    Transient(String),
+}
+
+impl std::fmt::Display for RefreshError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Rejected(reason) | Self::Transient(reason) => {
+                write!(f, "token refresh failed: {}", reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RefreshError {}
+
+/// Exchange `refresh_token` for new credentials at `client.token_url`.
+/// Failures are a [`RefreshError`].
+pub async fn refresh(client: &OAuthClientConfig, refresh_token: &str) -> Result<OAuthCredentials> {
+    let oauth = BasicClient::new(
+        ClientId::new(client.client_id.clone()),
+        client.client_secret.clone().map(ClientSecret::new),
+        // Unused for refreshes, but the client requires one.
+        AuthUrl::new(client.token_url.clone())?,
+        Some(TokenUrl::new(client.token_url.clone())?),
+    );
+    // The status tells an endpoint turning the grant down from one failing.
+    let status = Mutex::new(None);
+    let token = oauth
+        .exchange_refresh_token(&RefreshToken::new(refresh_token.to_string()))
+        .request_async(|request| async {
+            async_http_client(request).await.inspect(|response| {
+                *status.lock() = Some(response.status_code.as_u16());
+            })
+        })
+        .await
+        .map_err(|e| classify(&e, *status.lock()))?;
+    let lifetime = token.expires_in().unwrap_or(DEFAULT_LIFETIME);
+    Ok(OAuthCredentials {
+        access_token: token.access_token().secret().clone(),
+        refresh_token: token.refresh_token().map(|t| t.secret().clone()),
+        expires_at: Some(now_secs() + lifetime.as_secs() as i64),
+    })
+}
+
+/// A refresh is rejected only when the endpoint answered with an OAuth
+/// error and a 4xx status other than timeout or rate limiting; everything
+/// else is worth retrying.
+fn classify<RE>(
+    err: &oauth2::RequestTokenError<RE, BasicErrorResponse>,
+    status: Option<u16>,
+) -> RefreshError
+where
+    RE: std::error::Error + 'static,
+{
+    let reason = describe(err);
+    let refused = matches!(err, oauth2::RequestTokenError::ServerResponse(_))
+        && status.is_some_and(|s| (400..500).contains(&s) && s != 408 && s != 429);
+    if refused {
+        RefreshError::Rejected(reason)
+    } else {
+        RefreshError::Transient(reason)
+    }
+}
+
+/// The endpoint's error code (e.g. `invalid_grant`) when it sent one.
+fn describe<RE>(err: &oauth2::RequestTokenError<RE, BasicErrorResponse>) -> String
+where
+    RE: std::error::Error + 'static,
+{
+    match err {
+        oauth2::RequestTokenError::ServerResponse(resp) => resp.error().as_ref().to_string(),
+        other => other.to_string(),
+    }
+}
+
+pub(crate) fn now_secs() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::mpsc;
+
+    /// A stand-in token endpoint answering every request with `status` and
+    /// `body`. Returns its token URL and a request counter.
+    fn token_server(status: u16, body: &'static str) -> (String, Arc<AtomicUsize>) {
+        let server = tiny_http::Server::http("127.0.0.1:0").expect("bind test server");
+        let port = server.server_addr().to_ip().expect("ip listener").port();
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        std::thread::spawn(move || {
+            for request in server.incoming_requests() {
+                counter.fetch_add(1, Ordering::SeqCst);
+                // Slow enough that concurrent callers overlap.
+                std::thread::sleep(Duration::from_millis(50));
+                let header =
+                    tiny_http::Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..])
+                        .unwrap();
+                let response = tiny_http::Response::from_string(body)
+                    .with_status_code(status)
+                    .with_header(header);
+                let _ = request.respond(response);
+            }
+        });
+        (format!("http://127.0.0.1:{}/token", port), hits)
+    }
+
+    fn expiring(refresh_token: Option<&str>) -> OAuthCredentials {
+        OAuthCredentials {
+            access_token: "old".to_string(),
+            refresh_token: refresh_token.map(str::to_string),
+            expires_at: Some(now_secs() + 30),
+        }
+    }
+
+    fn refresher(token_url: String) -> (TokenRefresher, mpsc::Receiver<AuthEvent>) {
+        let (tx, rx) = mpsc::channel();
+        let refresher = TokenRefresher::new()
+            .with_client(
+                "gemini",
+                OAuthClientConfig {
+                    client_id: "client".to_string(),
+                    client_secret: Some("secret".to_string()),
+                    token_url,
+                },
+            )
+            .with_sink(Arc::new(tx));
+        (refresher, rx)
+    }
+
+    #[tokio::test]
+    async fn test_refresh_is_single_flight_and_reported() {
+        let (url, hits) = token_server(
+            200,
+            "{\"access_token\":\"new\",\"token_type\":\"Bearer\",\"expires_in\":3600,\"refresh_token\":\"rotated\"}",
+        );
+        let (refresher, events) = refresher(url);
+        let refresher = Arc::new(refresher);
+        let creds = expiring(Some("r1"));
+
+        let tasks: Vec<_> = (0..5)
+            .map(|_| {
+                let refresher = refresher.clone();
+                let creds = creds.clone();
+                tokio::spawn(async move { refresher.fresh("gemini", &creds).await })
+            })
+            .collect();
+        for task in tasks {
+            let fresh = task.await.unwrap().unwrap();
+            assert_eq!(fresh.access_token, "new");
+            assert_eq!(fresh.refresh_token.as_deref(), Some("rotated"));
+        }
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+        // Stale settings still get the refreshed token without another request.
+        let again = refresher.fresh("gemini", &creds).await.unwrap();
+        assert_eq!(again.access_token, "new");
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+        match events.try_recv().unwrap() {
+            AuthEvent::Refreshed {
+                provider,
+                credentials,
+            } => {
+                assert_eq!(provider, "gemini");
+                assert_eq!(credentials.access_token, "new");
+                assert!(credentials.expires_at.unwrap() > now_secs() + 3000);
+            }
+            other => panic!("unexpected event: {:?}", other),
+        }
+        assert!(events.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rejected_refresh_asks_for_reauth_once() {
+        let (url, hits) = token_server(400, "{\"error\":\"invalid_grant\"}");
+        let (refresher, events) = refresher(url);
+
+        let err = refresher
+            .fresh("gemini", &expiring(Some("r1")))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("invalid_grant"));
+        assert!(matches!(
+            events.try_recv().unwrap(),
+            AuthEvent::ReauthRequired { ref provider, .. } if provider == "gemini"
+        ));
+
+        // The rejected token isn't retried.
+        assert!(refresher.fresh("gemini", &expiring(Some("r1"))).await.is_err());
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+        assert!(events.try_recv().is_err());
+
+        // Valid tokens, and providers without a client, are used as-is.
+        let valid = OAuthCredentials {
+            expires_at: Some(now_secs() + 3600),
+            ..expiring(None)
+        };
+        assert_eq!(refresher.fresh("gemini", &valid).await.unwrap().access_token, "old");
+        let other = refresher.fresh("anthropic", &expiring(None)).await.unwrap();
+        assert_eq!(other.access_token, "old");
+
+        // Missing refresh token: nothing to refresh with.
+        assert!(refresher.fresh("gemini", &expiring(None)).await.is_err());
+        assert!(matches!(
+            events.try_recv().unwrap(),
+            AuthEvent::ReauthRequired { .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_transient_refresh_failure_is_retried() {
+        let (url, hits) = token_server(503, "{\"error\":\"temporarily_unavailable\"}");
+        let (refresher, events) = refresher(url);
+
+        let err = refresher
+            .fresh("gemini", &expiring(Some("r1")))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<RefreshError>(),
+            Some(RefreshError::Transient(_))
+        ));
+        assert!(events.try_recv().is_err());
+
+        // The token isn't poisoned: the next request tries it again.
+        assert!(refresher.fresh("gemini", &expiring(Some("r1"))).await.is_err());
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+        assert!(events.try_recv().is_err());
+    }
+}
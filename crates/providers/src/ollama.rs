@@ -2,13 +2,28 @@
 //!
 //! Talks to a locally-running Ollama instance via its HTTP chat API.
 //! Base URL defaults to `http://127.0.0.1:11434` but can be overridden
-//! with the `OLLAMA_BASE_URL` environment variable. Streaming is disabled
-//! (`stream: false`) so we get a single response object.
+//! with the `OLLAMA_BASE_URL` environment variable. [`generate`](OllamaClient::generate)
+//! sets `stream: false` to get a single response object;
+//! [`generate_stream`](OllamaClient::generate_stream) reads the NDJSON stream instead.
+//!
+//! Tools are sent in the OpenAI `tools` format. Models whose template has no
+//! tool support make Ollama reject the request; the client then retries
+//! without tools so the caller's tag-based fallback still works.
+//!
+//! Image attachments go in the message's `images` array for vision models.
+//!
+//! [`generate_structured`](OllamaClient::generate_structured) passes a JSON
+//! schema as `format`, which constrains the reply to JSON matching it.
 
+use crate::capabilities::supports_vision;
+use crate::catalog::ModelInfo;
+use crate::openai::tool_defs;
+use crate::stream::{for_each_line, status_error};
+use crate::usage::Usage;
 use anyhow::{anyhow, Result};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use shared::agent_api::ChatMessage;
+use shared::agent_api::{Attachment, ChatMessage, ToolCall, ToolSpec};
 use std::env;
 use std::sync::LazyLock;
 use std::time::Duration;
@@ -26,18 +41,83 @@ static SHARED_HTTP: LazyLock<Client> = LazyLock::new(|| {
 struct OllamaChatRequest<'a> {
     model: &'a str,
     messages: Vec<OllamaMessage>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<serde_json::Value>,
     stream: bool,
+    /// JSON schema the reply must follow
+    #[serde(skip_serializing_if = "Option::is_none")]
+    format: Option<serde_json::Value>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 struct OllamaChatResponse {
     message: OllamaMessage,
+    #[serde(default)]
+    prompt_eval_count: Option<u32>,
+    #[serde(default)]
+    eval_count: Option<u32>,
+}
+
+/// Ollama omits `prompt_eval_count` when the prompt was fully cached.
+fn usage_from(prompt_eval_count: Option<u32>, eval_count: Option<u32>) -> Option<Usage> {
+    eval_count.map(|out| Usage::new(prompt_eval_count.unwrap_or(0), out))
+}
+
+/// One line of the NDJSON stream. The final line has `done: true` and the
+/// token counts.
+#[derive(Debug, Deserialize)]
+struct OllamaStreamChunk {
+    #[serde(default)]
+    message: Option<OllamaMessage>,
+    #[serde(default)]
+    done: bool,
+    #[serde(default)]
+    prompt_eval_count: Option<u32>,
+    #[serde(default)]
+    eval_count: Option<u32>,
+    #[serde(default)]
+    error: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 struct OllamaMessage {
     role: String,
     content: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tool_calls: Vec<OllamaToolCall>,
+    /// Base64 images for multimodal models (llava, llama3.2-vision, ...).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    images: Vec<String>,
+}
+
+/// Ollama returns arguments as a JSON object and assigns no call IDs.
+#[derive(Debug, Serialize, Deserialize)]
+struct OllamaToolCall {
+    function: OllamaFunctionCall,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct OllamaFunctionCall {
+    name: String,
+    #[serde(default)]
+    arguments: serde_json::Value,
+}
+
+/// Convert Ollama's ID-less calls, numbering them `call_<n>` from `offset`.
+fn tool_calls_from(calls: Vec<OllamaToolCall>, offset: usize) -> Vec<ToolCall> {
+    calls
+        .into_iter()
+        .enumerate()
+        .map(|(i, c)| ToolCall {
+            id: format!("call_{}", offset + i),
+            name: c.function.name,
+            arguments: if c.function.arguments.is_null() {
+                serde_json::json!({})
+            } else {
+                c.function.arguments
+            },
+        })
+        .collect()
 }
 
 pub struct OllamaClient {
@@ -57,25 +137,271 @@ impl OllamaClient {
         }
     }
 
-    pub async fn generate(&self, messages: Vec<ChatMessage>) -> Result<String> {
+    /// Point the client at a specific Ollama server instead of `OLLAMA_BASE_URL`.
+    pub fn with_base_url(mut self, base: &str) -> Self {
+        self.base = base.trim_end_matches('/').to_string();
+        self
+    }
+
+    fn build_request(
+        &self,
+        messages: Vec<ChatMessage>,
+        tools: &[ToolSpec],
+        stream: bool,
+    ) -> OllamaChatRequest<'_> {
+        let vision = supports_vision("local", &self.model);
         let conversation: Vec<OllamaMessage> = messages
             .into_iter()
             .map(|m| OllamaMessage {
+                content: m.content_with_attachment_notes(vision),
+                images: if vision {
+                    m.attachments
+                        .iter()
+                        .filter_map(|a| match a {
+                            Attachment::Image { data, .. } => Some(data.clone()),
+                            Attachment::File { .. } => None,
+                        })
+                        .collect()
+                } else {
+                    Vec::new()
+                },
                 role: m.role,
-                content: m.content,
+                tool_calls: m
+                    .tool_calls
+                    .into_iter()
+                    .map(|c| OllamaToolCall {
+                        function: OllamaFunctionCall {
+                            name: c.name,
+                            arguments: c.arguments,
+                        },
+                    })
+                    .collect(),
             })
             .collect();
-        let url = format!("{}/api/chat", self.base);
-        let req = OllamaChatRequest {
+        OllamaChatRequest {
             model: &self.model,
             messages: conversation,
-            stream: false,
-        };
-        let resp = self.http.post(url).json(&req).send().await?;
+            tools: tool_defs(tools),
+            stream,
+            format: None,
+        }
+    }
+
+    /// Models pulled into this Ollama server (`GET /api/tags`).
+    pub async fn list_models(&self) -> Result<Vec<ModelInfo>> {
+        #[derive(Deserialize)]
+        struct TagList {
+            models: Vec<TagEntry>,
+        }
+        #[derive(Deserialize)]
+        struct TagEntry {
+            name: String,
+        }
+
+        let url = format!("{}/api/tags", self.base);
+        let resp = self.http.get(&url).send().await?;
+        if !resp.status().is_success() {
+            return Err(status_error("ollama", resp).await);
+        }
+        let list: TagList = resp.json().await?;
+        Ok(list
+            .models
+            .into_iter()
+            .map(|m| ModelInfo::new("local", m.name, None))
+            .collect())
+    }
+
+    /// POST to `/api/chat`, dropping `tools` and retrying once if the model
+    /// does not support them.
+    async fn send(&self, mut req: OllamaChatRequest<'_>) -> Result<reqwest::Response> {
+        let url = format!("{}/api/chat", self.base);
+        let resp = self.http.post(&url).json(&req).send().await?;
+        if resp.status().is_success() {
+            return Ok(resp);
+        }
+        let err = status_error("ollama", resp).await;
+        if req.tools.is_empty() || !err.to_string().contains("does not support tools") {
+            return Err(err);
+        }
+        req.tools.clear();
+        let resp = self.http.post(&url).json(&req).send().await?;
         if !resp.status().is_success() {
-            return Err(anyhow!("ollama error: {}", resp.status()));
+            return Err(status_error("ollama", resp).await);
         }
+        Ok(resp)
+    }
+
+    pub async fn generate(
+        &self,
+        messages: Vec<ChatMessage>,
+        tools: &[ToolSpec],
+    ) -> Result<(ChatMessage, Option<Usage>)> {
+        let req = self.build_request(messages, tools, false);
+        let resp = self.send(req).await?;
+        let body: OllamaChatResponse = resp.json().await?;
+        let mut msg = ChatMessage::assistant(body.message.content);
+        msg.tool_calls = tool_calls_from(body.message.tool_calls, 0);
+        Ok((msg, usage_from(body.prompt_eval_count, body.eval_count)))
+    }
+
+    /// Generate a reply constrained to JSON matching `schema`. Returns the
+    /// raw JSON text; the caller parses it.
+    pub async fn generate_structured(
+        &self,
+        messages: Vec<ChatMessage>,
+        schema: &serde_json::Value,
+    ) -> Result<(String, Option<Usage>)> {
+        let mut req = self.build_request(messages, &[], false);
+        req.format = Some(schema.clone());
+        let resp = self.send(req).await?;
         let body: OllamaChatResponse = resp.json().await?;
-        Ok(body.message.content)
+        Ok((
+            body.message.content,
+            usage_from(body.prompt_eval_count, body.eval_count),
+        ))
+    }
+
+    /// Stream the reply, calling `on_delta` for every piece of text as it
+    /// arrives. Returns the full reply and usage once Ollama reports `done`.
+    pub async fn generate_stream<F>(
+        &self,
+        messages: Vec<ChatMessage>,
+        tools: &[ToolSpec],
+        mut on_delta: F,
+    ) -> Result<(ChatMessage, Option<Usage>)>
+    where
+        F: FnMut(&str) + Send,
+    {
+        let req = self.build_request(messages, tools, true);
+        let resp = self.send(req).await?;
+
+        let mut reply = ChatMessage::assistant("");
+        let mut usage = None;
+        for_each_line(resp, |line| {
+            let chunk: OllamaStreamChunk = serde_json::from_str(line)?;
+            if let Some(err) = chunk.error {
+                return Err(anyhow!("ollama error: {}", err));
+            }
+            if let Some(message) = chunk.message {
+                if !message.content.is_empty() {
+                    on_delta(&message.content);
+                    reply.content.push_str(&message.content);
+                }
+                let offset = reply.tool_calls.len();
+                reply
+                    .tool_calls
+                    .extend(tool_calls_from(message.tool_calls, offset));
+            }
+            if chunk.done {
+                usage = usage_from(chunk.prompt_eval_count, chunk.eval_count);
+            }
+            Ok(!chunk.done)
+        })
+        .await?;
+        Ok((reply, usage))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream::test_server;
+
+    fn user(content: &str) -> Vec<ChatMessage> {
+        vec![ChatMessage::user(content.to_string())]
+    }
+
+    #[tokio::test]
+    async fn test_generate_stream_reads_ndjson() {
+        let base = test_server::serve(
+            200,
+            "application/x-ndjson",
+            "{\"message\":{\"role\":\"assistant\",\"content\":\"Hel\"},\"done\":false}\n\
+             {\"message\":{\"role\":\"assistant\",\"content\":\"lo\"},\"done\":false}\n\
+             {\"message\":{\"role\":\"assistant\",\"content\":\"\"},\"done\":true,\"prompt_eval_count\":26,\"eval_count\":3}\n",
+        );
+        let client = OllamaClient::new("llama3.2:3b".to_string()).with_base_url(&base);
+        let mut deltas = Vec::new();
+        let (reply, usage) = client
+            .generate_stream(user("hi"), &[], |d| deltas.push(d.to_string()))
+            .await
+            .unwrap();
+        assert_eq!(reply.content, "Hello");
+        assert_eq!(deltas, vec!["Hel", "lo"]);
+        assert_eq!(usage, Some(Usage::new(26, 3)));
+    }
+
+    #[tokio::test]
+    async fn test_generate_stream_surfaces_inline_error() {
+        let base = test_server::serve(
+            200,
+            "application/x-ndjson",
+            "{\"error\":\"model 'nope' not found\"}\n",
+        );
+        let client = OllamaClient::new("nope".to_string()).with_base_url(&base);
+        let err = client
+            .generate_stream(user("hi"), &[], |_| {})
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("not found"));
+    }
+
+    #[tokio::test]
+    async fn test_generate_stream_collects_tool_calls() {
+        let base = test_server::serve(
+            200,
+            "application/x-ndjson",
+            "{\"message\":{\"role\":\"assistant\",\"content\":\"\",\"tool_calls\":[{\"function\":{\"name\":\"search\",\"arguments\":{\"query\":\"rust\"}}}]},\"done\":false}\n\
+             {\"message\":{\"role\":\"assistant\",\"content\":\"\"},\"done\":true}\n",
+        );
+        let client = OllamaClient::new("llama3.2:3b".to_string()).with_base_url(&base);
+        let (reply, _) = client
+            .generate_stream(user("find rust docs"), &[], |_| {})
+            .await
+            .unwrap();
+        assert_eq!(reply.tool_calls.len(), 1);
+        assert_eq!(reply.tool_calls[0].id, "call_0");
+        assert_eq!(reply.tool_calls[0].name, "search");
+        assert_eq!(reply.tool_calls[0].arguments["query"], "rust");
+    }
+
+    #[tokio::test]
+    async fn test_generate_structured_returns_json_text() {
+        let base = test_server::serve(
+            200,
+            "application/json",
+            "{\"message\":{\"role\":\"assistant\",\"content\":\"{\\\"entities\\\":[]}\"},\"done\":true}",
+        );
+        let client = OllamaClient::new("llama3.2:3b".to_string()).with_base_url(&base);
+        let schema = serde_json::json!({"type": "object"});
+        let (json, _) = client
+            .generate_structured(user("extract"), &schema)
+            .await
+            .unwrap();
+        assert_eq!(json, "{\"entities\":[]}");
+
+        let mut req = client.build_request(user("extract"), &[], false);
+        req.format = Some(schema);
+        let body = serde_json::to_value(&req).unwrap();
+        assert_eq!(body["format"]["type"], "object");
+    }
+
+    #[test]
+    fn test_build_request_sends_images_to_vision_models() {
+        let msg =
+            ChatMessage::user("describe").with_attachment(Attachment::image("image/png", b"png"));
+        let vision = OllamaClient::new("llava:7b".to_string());
+        let json =
+            serde_json::to_value(vision.build_request(vec![msg.clone()], &[], false)).unwrap();
+        assert_eq!(json["messages"][0]["images"][0], "cG5n");
+        assert_eq!(json["messages"][0]["content"], "describe");
+
+        let text_only = OllamaClient::new("llama3.2:3b".to_string());
+        let json = serde_json::to_value(text_only.build_request(vec![msg], &[], false)).unwrap();
+        assert!(json["messages"][0].get("images").is_none());
+        assert!(json["messages"][0]["content"]
+            .as_str()
+            .unwrap()
+            .contains("[Attached image: image/png"));
     }
 }
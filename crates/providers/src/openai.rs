@@ -1,13 +1,19 @@
 //! OpenAI-compatible chat completions client.
 //!
 //! Also used for any provider exposing an OpenAI-compatible API (Kimi/Moonshot,
-//! OpenRouter, Together, etc.) by setting `openai_base_url` in settings.
+//! OpenRouter, LM Studio, vLLM, llama.cpp server, etc.), either through a named
+//! [`ProviderProfile`] ([`OpenAIClient::from_profile`]) or by setting
+//! `openai_base_url` in settings.
 
+use crate::capabilities::supports_vision;
+use crate::catalog::ModelInfo;
+use crate::stream::{for_each_line, sse_data, status_error};
+use crate::usage::Usage;
 use anyhow::{anyhow, Result};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use shared::agent_api::ChatMessage;
-use shared::settings::ProviderAuth;
+use shared::agent_api::{Attachment, ChatMessage, ToolCall, ToolSpec};
+use shared::settings::{AuthHeaderStyle, ProviderAuth, ProviderProfile};
 use std::env;
 use std::sync::LazyLock;
 use std::time::Duration;
@@ -25,12 +31,61 @@ static SHARED_HTTP: LazyLock<Client> = LazyLock::new(|| {
 struct OpenAIRequest {
     model: String,
     messages: Vec<OpenAIMessage>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<serde_json::Value>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    stream: bool,
+    /// Asks for a final chunk carrying `usage` when streaming.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream_options: Option<serde_json::Value>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 struct OpenAIMessage {
     role: String,
-    content: String,
+    /// `null` on assistant messages that only carry tool calls.
+    #[serde(default)]
+    content: Option<OpenAIContent>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tool_calls: Vec<OpenAIToolCall>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tool_call_id: Option<String>,
+}
+
+/// A plain string, or text + `image_url` parts for vision requests.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+enum OpenAIContent {
+    Text(String),
+    Parts(Vec<OpenAIContentPart>),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum OpenAIContentPart {
+    Text { text: String },
+    ImageUrl { image_url: OpenAIImageUrl },
+}
+
+/// Images are sent inline as `data:` URLs.
+#[derive(Debug, Serialize, Deserialize)]
+struct OpenAIImageUrl {
+    url: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct OpenAIToolCall {
+    id: String,
+    #[serde(rename = "type")]
+    kind: String,
+    function: OpenAIFunctionCall,
+}
+
+/// OpenAI sends function arguments as a JSON-encoded string.
+#[derive(Debug, Serialize, Deserialize)]
+struct OpenAIFunctionCall {
+    name: String,
+    arguments: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -41,6 +96,164 @@ struct OpenAIChoice {
 #[derive(Debug, Serialize, Deserialize)]
 struct OpenAIResponse {
     choices: Vec<OpenAIChoice>,
+    #[serde(default)]
+    usage: Option<OpenAIUsage>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct OpenAIUsage {
+    #[serde(default)]
+    prompt_tokens: u32,
+    #[serde(default)]
+    completion_tokens: u32,
+}
+
+impl From<OpenAIUsage> for Usage {
+    fn from(u: OpenAIUsage) -> Self {
+        Usage::new(u.prompt_tokens, u.completion_tokens)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAIStreamDelta {
+    #[serde(default)]
+    content: Option<String>,
+    #[serde(default)]
+    tool_calls: Vec<OpenAIStreamToolCall>,
+}
+
+/// A fragment of a tool call; fragments sharing an `index` are concatenated.
+#[derive(Debug, Deserialize)]
+struct OpenAIStreamToolCall {
+    index: usize,
+    #[serde(default)]
+    id: Option<String>,
+    #[serde(default)]
+    function: Option<OpenAIStreamFunction>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAIStreamFunction {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    arguments: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenAIStreamChoice {
+    delta: OpenAIStreamDelta,
+}
+
+/// One `data:` payload of a `stream: true` chat completion.
+#[derive(Debug, Deserialize)]
+struct OpenAIStreamChunk {
+    #[serde(default)]
+    choices: Vec<OpenAIStreamChoice>,
+    /// Only on the last chunk, and only with `stream_options.include_usage`.
+    #[serde(default)]
+    usage: Option<OpenAIUsage>,
+}
+
+/// `tools` entries in the chat completions format. Also used by the Ollama
+/// client, whose `/api/chat` accepts the same shape.
+pub(crate) fn tool_defs(tools: &[ToolSpec]) -> Vec<serde_json::Value> {
+    tools
+        .iter()
+        .map(|t| {
+            serde_json::json!({
+                "type": "function",
+                "function": {
+                    "name": t.name,
+                    "description": t.description,
+                    "parameters": t.parameters,
+                },
+            })
+        })
+        .collect()
+}
+
+/// Parse a JSON-encoded arguments string, treating empty input as `{}`.
+pub(crate) fn parse_arguments(raw: &str) -> serde_json::Value {
+    if raw.trim().is_empty() {
+        return serde_json::json!({});
+    }
+    serde_json::from_str(raw).unwrap_or_else(|_| serde_json::json!({ "_raw": raw }))
+}
+
+/// Translate a provider-neutral message. Images become `image_url` parts when
+/// the model has `vision`; otherwise every attachment is described in text.
+fn openai_message(m: ChatMessage, vision: bool) -> OpenAIMessage {
+    let text = m.content_with_attachment_notes(vision);
+    let images: Vec<OpenAIContentPart> = if vision {
+        m.attachments
+            .iter()
+            .filter_map(|a| match a {
+                Attachment::Image { mime_type, data } => Some(OpenAIContentPart::ImageUrl {
+                    image_url: OpenAIImageUrl {
+                        url: format!("data:{};base64,{}", mime_type, data),
+                    },
+                }),
+                Attachment::File { .. } => None,
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+    let tool_calls: Vec<OpenAIToolCall> = m
+        .tool_calls
+        .into_iter()
+        .map(|c| OpenAIToolCall {
+            id: c.id,
+            kind: "function".to_string(),
+            function: OpenAIFunctionCall {
+                name: c.name,
+                arguments: c.arguments.to_string(),
+            },
+        })
+        .collect();
+    let content = if !images.is_empty() {
+        let mut parts = vec![OpenAIContentPart::Text { text }];
+        parts.extend(images);
+        Some(OpenAIContent::Parts(parts))
+    } else if text.is_empty() && !tool_calls.is_empty() {
+        None
+    } else {
+        Some(OpenAIContent::Text(text))
+    };
+    OpenAIMessage {
+        role: m.role,
+        content,
+        tool_calls,
+        tool_call_id: m.tool_call_id,
+    }
+}
+
+impl From<OpenAIMessage> for ChatMessage {
+    fn from(m: OpenAIMessage) -> Self {
+        let text = match m.content {
+            Some(OpenAIContent::Text(text)) => text,
+            Some(OpenAIContent::Parts(parts)) => parts
+                .into_iter()
+                .filter_map(|p| match p {
+                    OpenAIContentPart::Text { text } => Some(text),
+                    OpenAIContentPart::ImageUrl { .. } => None,
+                })
+                .collect(),
+            None => String::new(),
+        };
+        let mut msg = ChatMessage::assistant(text);
+        msg.tool_calls = m
+            .tool_calls
+            .into_iter()
+            .map(|c| ToolCall {
+                id: c.id,
+                name: c.function.name,
+                arguments: parse_arguments(&c.function.arguments),
+            })
+            .collect();
+        msg
+    }
 }
 
 pub struct OpenAIClient {
@@ -50,10 +263,21 @@ pub struct OpenAIClient {
     /// Base URL for the API. Defaults to `https://api.openai.com` but can be
     /// overridden for OpenAI-compatible third-party endpoints.
     base_url: String,
+    /// Provider id used in errors: "openai" or the profile name.
+    provider: String,
+    auth_header: AuthHeaderStyle,
+    /// Whether images are sent inline.
+    vision: bool,
 }
 
 const DEFAULT_BASE_URL: &str = "https://api.openai.com";
 
+/// Server root without a trailing slash or `/v1`, which requests add.
+pub(crate) fn normalize_base_url(base_url: &str) -> String {
+    let base = base_url.trim_end_matches('/');
+    base.strip_suffix("/v1").unwrap_or(base).to_string()
+}
+
 impl OpenAIClient {
     /// Create a client using the `OPENAI_API_KEY` environment variable.
     pub fn new(model: &str) -> Result<Self> {
@@ -63,6 +287,9 @@ impl OpenAIClient {
             auth_token: key,
             model: model.to_string(),
             base_url: DEFAULT_BASE_URL.to_string(),
+            provider: "openai".to_string(),
+            auth_header: AuthHeaderStyle::Bearer,
+            vision: supports_vision("openai", model),
         })
     }
 
@@ -84,49 +311,364 @@ impl OpenAIClient {
             http: SHARED_HTTP.clone(),
             auth_token,
             model: model.to_string(),
-            base_url: base_url
-                .unwrap_or(DEFAULT_BASE_URL)
-                .trim_end_matches('/')
-                .to_string(),
+            base_url: normalize_base_url(base_url.unwrap_or(DEFAULT_BASE_URL)),
+            provider: "openai".to_string(),
+            auth_header: AuthHeaderStyle::Bearer,
+            vision: supports_vision("openai", model),
         })
     }
 
-    pub async fn generate(&self, messages: Vec<ChatMessage>) -> Result<String> {
-        let url = format!("{}/v1/chat/completions", self.base_url);
-        let openai_messages: Vec<OpenAIMessage> = messages
+    /// Create a client for a named OpenAI-compatible profile, using `model`
+    /// (normally `profile.model()` or `profile.fast_model()`).
+    ///
+    /// Auth priority: API key > OAuth token. Profiles with
+    /// [`AuthHeaderStyle::None`] need neither.
+    pub fn from_profile(profile: &ProviderProfile, model: &str) -> Result<Self> {
+        let auth_token = match (&profile.auth.api_key, &profile.auth.oauth) {
+            (Some(key), _) => key.clone(),
+            (None, Some(oauth)) => oauth.access_token.clone(),
+            (None, None) if profile.auth_header == AuthHeaderStyle::None => String::new(),
+            (None, None) => return Err(anyhow!("No API key configured for {}", profile.name)),
+        };
+        Ok(Self {
+            http: SHARED_HTTP.clone(),
+            auth_token,
+            model: model.to_string(),
+            base_url: normalize_base_url(&profile.base_url),
+            provider: profile.name.clone(),
+            auth_header: profile.auth_header.clone(),
+            vision: profile.supports_vision,
+        })
+    }
+
+    /// Attach credentials in the style the endpoint expects.
+    fn authorize(&self, req: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match &self.auth_header {
+            AuthHeaderStyle::Bearer => {
+                req.header("Authorization", format!("Bearer {}", self.auth_token))
+            }
+            AuthHeaderStyle::Header { name } => req.header(name.as_str(), &self.auth_token),
+            AuthHeaderStyle::None => req,
+        }
+    }
+
+    /// Models served by the endpoint (`GET /v1/models`). Also a cheap
+    /// reachability and credentials check. Hosts that report a context
+    /// window (OpenRouter's `context_length`, Groq's `context_window`) have
+    /// it recorded.
+    pub async fn list_models(&self) -> Result<Vec<ModelInfo>> {
+        #[derive(Deserialize)]
+        struct ModelList {
+            data: Vec<ModelEntry>,
+        }
+        #[derive(Deserialize)]
+        struct ModelEntry {
+            id: String,
+            #[serde(default, alias = "context_window")]
+            context_length: Option<usize>,
+        }
+
+        let url = format!("{}/v1/models", self.base_url);
+        let resp = self.authorize(self.http.get(&url)).send().await?;
+        if !resp.status().is_success() {
+            return Err(status_error(&self.provider, resp).await);
+        }
+        let list: ModelList = resp.json().await?;
+        Ok(list
+            .data
             .into_iter()
-            .map(|m| OpenAIMessage {
-                role: m.role,
-                content: m.content,
-            })
-            .collect();
-        let req = OpenAIRequest {
+            .map(|m| ModelInfo::new("openai", m.id, m.context_length))
+            .collect())
+    }
+
+    fn build_request(
+        &self,
+        messages: Vec<ChatMessage>,
+        tools: &[ToolSpec],
+        stream: bool,
+    ) -> OpenAIRequest {
+        let vision = self.vision;
+        OpenAIRequest {
             model: self.model.clone(),
-            messages: openai_messages,
-        };
+            messages: messages
+                .into_iter()
+                .map(|m| openai_message(m, vision))
+                .collect(),
+            tools: tool_defs(tools),
+            stream,
+            stream_options: stream.then(|| serde_json::json!({ "include_usage": true })),
+        }
+    }
+
+    async fn send(&self, req: &OpenAIRequest) -> Result<reqwest::Response> {
+        let url = format!("{}/v1/chat/completions", self.base_url);
         let resp = self
-            .http
-            .post(&url)
-            .header("Authorization", format!("Bearer {}", self.auth_token))
+            .authorize(self.http.post(&url))
             .header("Content-Type", "application/json")
-            .json(&req)
+            .json(req)
             .send()
             .await?;
         if !resp.status().is_success() {
-            let status = resp.status();
-            let body = resp.text().await.unwrap_or_default();
-            let detail: String = body.chars().take(800).collect();
-            if detail.trim().is_empty() {
-                return Err(anyhow!("openai error: {}", status));
-            }
-            return Err(anyhow!("openai error: {}\n{}", status, detail));
+            return Err(status_error(&self.provider, resp).await);
         }
+        Ok(resp)
+    }
+
+    /// Send the conversation and return the assistant reply, including any
+    /// tool calls the model made against `tools`, and the reported usage.
+    pub async fn generate(
+        &self,
+        messages: Vec<ChatMessage>,
+        tools: &[ToolSpec],
+    ) -> Result<(ChatMessage, Option<Usage>)> {
+        let req = self.build_request(messages, tools, false);
+        let resp = self.send(&req).await?;
         let body: OpenAIResponse = resp.json().await?;
-        let text = body
+        let reply = body
             .choices
-            .first()
-            .map(|c| c.message.content.clone())
-            .unwrap_or_default();
-        Ok(text)
+            .into_iter()
+            .next()
+            .map(|c| ChatMessage::from(c.message))
+            .unwrap_or_else(|| ChatMessage::assistant(""));
+        Ok((reply, body.usage.map(Usage::from)))
+    }
+
+    /// Stream the reply over SSE, calling `on_delta` for every content delta.
+    /// Returns the full reply (text plus reassembled tool calls) and usage
+    /// after `data: [DONE]`.
+    pub async fn generate_stream<F>(
+        &self,
+        messages: Vec<ChatMessage>,
+        tools: &[ToolSpec],
+        mut on_delta: F,
+    ) -> Result<(ChatMessage, Option<Usage>)>
+    where
+        F: FnMut(&str) + Send,
+    {
+        let req = self.build_request(messages, tools, true);
+        let resp = self.send(&req).await?;
+
+        let mut text = String::new();
+        let mut usage = None;
+        // (id, name, arguments) per tool-call index.
+        let mut calls: Vec<(String, String, String)> = Vec::new();
+        for_each_line(resp, |line| {
+            let Some(data) = sse_data(line) else {
+                return Ok(true);
+            };
+            if data == "[DONE]" {
+                return Ok(false);
+            }
+            let chunk: OpenAIStreamChunk = serde_json::from_str(data)?;
+            if let Some(u) = chunk.usage {
+                usage = Some(Usage::from(u));
+            }
+            for choice in chunk.choices {
+                if let Some(content) = choice.delta.content.filter(|c| !c.is_empty()) {
+                    on_delta(&content);
+                    text.push_str(&content);
+                }
+                for part in choice.delta.tool_calls {
+                    if calls.len() <= part.index {
+                        calls.resize_with(part.index + 1, Default::default);
+                    }
+                    let call = &mut calls[part.index];
+                    if let Some(id) = part.id {
+                        call.0 = id;
+                    }
+                    if let Some(function) = part.function {
+                        if let Some(name) = function.name {
+                            call.1.push_str(&name);
+                        }
+                        if let Some(args) = function.arguments {
+                            call.2.push_str(&args);
+                        }
+                    }
+                }
+            }
+            Ok(true)
+        })
+        .await?;
+
+        let mut msg = ChatMessage::assistant(text);
+        msg.tool_calls = calls
+            .into_iter()
+            .filter(|(_, name, _)| !name.is_empty())
+            .map(|(id, name, args)| ToolCall {
+                id,
+                name,
+                arguments: parse_arguments(&args),
+            })
+            .collect();
+        Ok((msg, usage))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream::test_server;
+
+    fn client(base: &str) -> OpenAIClient {
+        let auth = ProviderAuth {
+            api_key: Some("test-key".to_string()),
+            oauth: None,
+        };
+        OpenAIClient::from_auth("gpt-4o-mini", &auth, Some(base)).unwrap()
+    }
+
+    fn user(content: &str) -> Vec<ChatMessage> {
+        vec![ChatMessage::user(content.to_string())]
+    }
+
+    #[tokio::test]
+    async fn test_generate_stream_reads_sse() {
+        let base = test_server::serve(
+            200,
+            "text/event-stream",
+            "data: {\"choices\":[{\"delta\":{\"role\":\"assistant\"}}]}\n\n\
+             data: {\"choices\":[{\"delta\":{\"content\":\"Hi \"}}]}\n\n\
+             : keep-alive\n\n\
+             data: {\"choices\":[{\"delta\":{\"content\":\"there\"}}]}\n\n\
+             data: {\"choices\":[],\"usage\":{\"prompt_tokens\":9,\"completion_tokens\":2}}\n\n\
+             data: [DONE]\n\n",
+        );
+        let mut deltas = Vec::new();
+        let (reply, usage) = client(&base)
+            .generate_stream(user("hi"), &[], |d| deltas.push(d.to_string()))
+            .await
+            .unwrap();
+        assert_eq!(reply.content, "Hi there");
+        assert!(reply.tool_calls.is_empty());
+        assert_eq!(deltas, vec!["Hi ", "there"]);
+        assert_eq!(usage, Some(Usage::new(9, 2)));
+    }
+
+    #[tokio::test]
+    async fn test_generate_stream_reports_http_error() {
+        let base = test_server::serve(401, "application/json", "{\"error\":\"bad key\"}");
+        let err = client(&base)
+            .generate_stream(user("hi"), &[], |_| {})
+            .await
+            .unwrap_err();
+        let msg = err.to_string();
+        assert!(msg.starts_with("openai error: 401"));
+        assert!(msg.contains("bad key"));
+    }
+
+    #[tokio::test]
+    async fn test_profile_client_lists_models_and_labels_errors() {
+        let base = test_server::serve_sequence(vec![
+            (
+                200,
+                "application/json",
+                "{\"data\":[{\"id\":\"qwen2.5-7b\"},{\"id\":\"phi-4\"}]}",
+            ),
+            (500, "text/plain", "boom"),
+        ]);
+        let profile = ProviderProfile {
+            name: "lmstudio".to_string(),
+            base_url: format!("{}/v1/", base),
+            auth_header: AuthHeaderStyle::None,
+            auth: ProviderAuth::default(),
+            models: vec!["qwen2.5-7b".to_string()],
+            fast_model: None,
+            supports_tools: true,
+            supports_vision: false,
+            context_window: Some(32_768),
+            local: true,
+        };
+        let client = OpenAIClient::from_profile(&profile, profile.model()).unwrap();
+        let ids: Vec<String> = client
+            .list_models()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|m| m.id)
+            .collect();
+        assert_eq!(ids, vec!["qwen2.5-7b", "phi-4"]);
+        let err = client.generate(user("hi"), &[]).await.unwrap_err();
+        assert!(err.to_string().starts_with("lmstudio error: 500"));
+
+        let keyed = ProviderProfile {
+            auth_header: AuthHeaderStyle::Bearer,
+            ..profile
+        };
+        assert!(OpenAIClient::from_profile(&keyed, "m").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_generate_stream_reassembles_tool_calls() {
+        let base = test_server::serve(
+            200,
+            "text/event-stream",
+            "data: {\"choices\":[{\"delta\":{\"tool_calls\":[{\"index\":0,\"id\":\"call_1\",\"type\":\"function\",\"function\":{\"name\":\"run_command\",\"arguments\":\"\"}}]}}]}\n\n\
+             data: {\"choices\":[{\"delta\":{\"tool_calls\":[{\"index\":0,\"function\":{\"arguments\":\"{\\\"command\\\":\"}}]}}]}\n\n\
+             data: {\"choices\":[{\"delta\":{\"tool_calls\":[{\"index\":0,\"function\":{\"arguments\":\"\\\"ls\\\"}\"}}]}}]}\n\n\
+             data: [DONE]\n\n",
+        );
+        let (reply, _) = client(&base)
+            .generate_stream(user("list files"), &[], |_| {})
+            .await
+            .unwrap();
+        assert_eq!(reply.content, "");
+        assert_eq!(reply.tool_calls.len(), 1);
+        assert_eq!(reply.tool_calls[0].id, "call_1");
+        assert_eq!(reply.tool_calls[0].name, "run_command");
+        assert_eq!(reply.tool_calls[0].arguments["command"], "ls");
+    }
+
+    #[test]
+    fn test_request_round_trips_tool_messages() {
+        let call = ToolCall {
+            id: "call_1".to_string(),
+            name: "search".to_string(),
+            arguments: serde_json::json!({"query": "rust"}),
+        };
+        let mut assistant = ChatMessage::assistant("");
+        assistant.tool_calls = vec![call.clone()];
+        let tools = [ToolSpec {
+            name: "search".to_string(),
+            description: "Search the web".to_string(),
+            parameters: serde_json::json!({"type": "object"}),
+        }];
+        let req = client("http://unused").build_request(
+            vec![assistant, ChatMessage::tool_result(&call, "results")],
+            &tools,
+            false,
+        );
+        let json = serde_json::to_value(&req).unwrap();
+        assert_eq!(json["tools"][0]["function"]["name"], "search");
+        assert!(json["messages"][0]["content"].is_null());
+        assert_eq!(
+            json["messages"][0]["tool_calls"][0]["function"]["arguments"],
+            "{\"query\":\"rust\"}"
+        );
+        assert_eq!(json["messages"][1]["role"], "tool");
+        assert_eq!(json["messages"][1]["tool_call_id"], "call_1");
+    }
+
+    #[test]
+    fn test_request_inlines_images_for_vision_models() {
+        let msg = ChatMessage::user("what's this?")
+            .with_attachment(Attachment::image("image/png", b"png"));
+        let json = serde_json::to_value(client("http://unused").build_request(
+            vec![msg.clone()],
+            &[],
+            false,
+        ))
+        .unwrap();
+        let parts = &json["messages"][0]["content"];
+        assert_eq!(parts[0]["text"], "what's this?");
+        assert_eq!(parts[1]["image_url"]["url"], "data:image/png;base64,cG5n");
+
+        let auth = ProviderAuth {
+            api_key: Some("test-key".to_string()),
+            oauth: None,
+        };
+        let text_only = OpenAIClient::from_auth("gpt-3.5-turbo", &auth, None).unwrap();
+        let json = serde_json::to_value(text_only.build_request(vec![msg], &[], false)).unwrap();
+        let content = json["messages"][0]["content"].as_str().unwrap();
+        assert!(content.starts_with("what's this?\n\n[Attached image: image/png"));
     }
 }
@@ -0,0 +1,229 @@
+//! Incremental (token-by-token) response streaming shared by all provider clients.
+//!
+//! Providers deliver partial output in one of two line-oriented wire formats:
+//! - **Server-Sent Events** (OpenAI, Anthropic, Gemini `streamGenerateContent?alt=sse`):
+//!   each payload arrives on a `data: {...}` line.
+//! - **Newline-delimited JSON** (Ollama `/api/chat` with `stream: true`): one JSON
+//!   object per line.
+//!
+//! Each client reads its response body chunk by chunk through [`for_each_line`]
+//! and decodes the lines with its own serde types; this module only handles the
+//! framing, so a chunk boundary in the middle of a UTF-8 sequence or a JSON
+//! object never reaches the decoders.
+
+use crate::health::ProviderHttpError;
+use anyhow::Result;
+use std::time::Duration;
+
+/// An incremental update emitted by [`ProviderRouter::generate_stream`](crate::router::ProviderRouter::generate_stream).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StreamEvent {
+    /// A new piece of text from the provider currently answering.
+    Delta(String),
+    /// The provider that was streaming failed part-way through its answer.
+    /// Any text received so far should be discarded; the router moves on to
+    /// the next provider in `provider_preference`.
+    Reset { provider: String, error: String },
+}
+
+/// Accumulates raw body chunks and hands back complete lines.
+#[derive(Debug, Default)]
+pub(crate) struct LineBuffer {
+    buf: Vec<u8>,
+}
+
+impl LineBuffer {
+    /// Append a chunk and return every line it completed (without the
+    /// trailing `\n` / `\r\n`).
+    pub(crate) fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.buf.extend_from_slice(chunk);
+        let mut lines = Vec::new();
+        while let Some(pos) = self.buf.iter().position(|&b| b == b'\n') {
+            let mut line: Vec<u8> = self.buf.drain(..=pos).collect();
+            line.pop();
+            if line.last() == Some(&b'\r') {
+                line.pop();
+            }
+            lines.push(String::from_utf8_lossy(&line).into_owned());
+        }
+        lines
+    }
+
+    /// Flush whatever is left once the body ends without a final newline.
+    pub(crate) fn finish(self) -> Option<String> {
+        let rest = String::from_utf8_lossy(&self.buf).trim().to_string();
+        if rest.is_empty() {
+            None
+        } else {
+            Some(rest)
+        }
+    }
+}
+
+/// Return the payload of an SSE `data:` line, or `None` for comments,
+/// `event:`/`id:` fields and blank separators.
+pub(crate) fn sse_data(line: &str) -> Option<&str> {
+    line.strip_prefix("data:").map(|d| d.trim_start())
+}
+
+/// Read a streaming response body line by line until it ends.
+///
+/// `on_line` returns `Ok(true)` to keep reading or `Ok(false)` once it has
+/// seen the provider's end-of-stream marker (e.g. `data: [DONE]`).
+pub(crate) async fn for_each_line<F>(mut resp: reqwest::Response, mut on_line: F) -> Result<()>
+where
+    F: FnMut(&str) -> Result<bool>,
+{
+    let mut lines = LineBuffer::default();
+    while let Some(chunk) = resp.chunk().await? {
+        for line in lines.push(&chunk) {
+            if line.is_empty() {
+                continue;
+            }
+            if !on_line(&line)? {
+                return Ok(());
+            }
+        }
+    }
+    if let Some(line) = lines.finish() {
+        on_line(&line)?;
+    }
+    Ok(())
+}
+
+/// Turn a non-2xx response into a [`ProviderHttpError`], which displays as the
+/// same `"<provider> error: <status>\n<body>"` message the blocking `generate`
+/// methods produce.
+pub(crate) async fn status_error(provider: &str, resp: reqwest::Response) -> anyhow::Error {
+    let status = resp.status().as_u16();
+    let retry_after = resp
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .map(Duration::from_secs);
+    let body = resp.text().await.unwrap_or_default();
+    anyhow::Error::new(ProviderHttpError {
+        provider: provider.to_string(),
+        status,
+        retry_after,
+        body: body.chars().take(800).collect(),
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod test_server {
+    //! A throwaway tiny_http server that replays a canned streaming body.
+
+    use std::thread;
+
+    /// Serve `body` with the given status and content type to every request,
+    /// returning the base URL (`http://127.0.0.1:<port>`).
+    pub(crate) fn serve(status: u16, content_type: &'static str, body: &'static str) -> String {
+        serve_sequence(vec![(status, content_type, body)])
+    }
+
+    /// Serve `responses` in order, repeating the last one once they run out.
+    pub(crate) fn serve_sequence(responses: Vec<(u16, &'static str, &'static str)>) -> String {
+        let server = tiny_http::Server::http("127.0.0.1:0").expect("bind test server");
+        let port = server.server_addr().to_ip().expect("ip listener").port();
+        thread::spawn(move || {
+            for (i, request) in server.incoming_requests().enumerate() {
+                let (status, content_type, body) = responses[i.min(responses.len() - 1)];
+                let header =
+                    tiny_http::Header::from_bytes(&b"Content-Type"[..], content_type.as_bytes())
+                        .unwrap();
+                let response = tiny_http::Response::from_string(body)
+                    .with_status_code(status)
+                    .with_header(header);
+                let _ = request.respond(response);
+            }
+        });
+        format!("http://127.0.0.1:{}", port)
+    }
+
+    /// Like [`serve`], but also hands back the body of every request.
+    pub(crate) fn serve_recording(
+        status: u16,
+        content_type: &'static str,
+        body: &'static str,
+    ) -> (String, std::sync::mpsc::Receiver<String>) {
+        let server = tiny_http::Server::http("127.0.0.1:0").expect("bind test server");
+        let port = server.server_addr().to_ip().expect("ip listener").port();
+        let (tx, rx) = std::sync::mpsc::channel();
+        thread::spawn(move || {
+            for mut request in server.incoming_requests() {
+                let mut received = String::new();
+                let _ = request.as_reader().read_to_string(&mut received);
+                let _ = tx.send(received);
+                let header =
+                    tiny_http::Header::from_bytes(&b"Content-Type"[..], content_type.as_bytes())
+                        .unwrap();
+                let response = tiny_http::Response::from_string(body)
+                    .with_status_code(status)
+                    .with_header(header);
+                let _ = request.respond(response);
+            }
+        });
+        (format!("http://127.0.0.1:{}", port), rx)
+    }
+
+    /// Like [`serve`], but every response is held back for `delay`.
+    pub(crate) fn serve_after(
+        delay: std::time::Duration,
+        status: u16,
+        content_type: &'static str,
+        body: &'static str,
+    ) -> String {
+        let server = tiny_http::Server::http("127.0.0.1:0").expect("bind test server");
+        let port = server.server_addr().to_ip().expect("ip listener").port();
+        thread::spawn(move || {
+            for request in server.incoming_requests() {
+                thread::spawn(move || {
+                    thread::sleep(delay);
+                    let header = tiny_http::Header::from_bytes(
+                        &b"Content-Type"[..],
+                        content_type.as_bytes(),
+                    )
+                    .unwrap();
+                    let response = tiny_http::Response::from_string(body)
+                        .with_status_code(status)
+                        .with_header(header);
+                    let _ = request.respond(response);
+                });
+            }
+        });
+        format!("http://127.0.0.1:{}", port)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_buffer_splits_across_chunks() {
+        let mut buf = LineBuffer::default();
+        assert!(buf.push(b"data: {\"a\":").is_empty());
+        let lines = buf.push(b"1}\r\n\r\ndata: [DO");
+        assert_eq!(lines, vec!["data: {\"a\":1}".to_string(), String::new()]);
+        assert!(buf.push(b"NE]").is_empty());
+        assert_eq!(buf.finish(), Some("data: [DONE]".to_string()));
+    }
+
+    #[test]
+    fn test_line_buffer_keeps_split_utf8_intact() {
+        let mut buf = LineBuffer::default();
+        let bytes = "héllo\n".as_bytes();
+        assert!(buf.push(&bytes[..2]).is_empty());
+        assert_eq!(buf.push(&bytes[2..]), vec!["héllo".to_string()]);
+    }
+
+    #[test]
+    fn test_sse_data() {
+        assert_eq!(sse_data("data: {\"x\":1}"), Some("{\"x\":1}"));
+        assert_eq!(sse_data("data:[DONE]"), Some("[DONE]"));
+        assert_eq!(sse_data("event: message_start"), None);
+        assert_eq!(sse_data(": keep-alive"), None);
+    }
+}
@@ -0,0 +1,36 @@
+//! Token usage reported by providers.
+//!
+//! Every client returns the counts from its response alongside the reply:
+//! OpenAI's and Anthropic's `usage` block, Gemini's `usageMetadata`, and
+//! Ollama's `prompt_eval_count` / `eval_count`. The router attaches them to
+//! [`GenerationMeta`](crate::router::GenerationMeta) and hands them to a
+//! [`UsageRecorder`] (the app's token tracker) so costs and budgets are based
+//! on real numbers.
+
+/// Input/output token counts for one provider call.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Usage {
+    /// Prompt tokens, including the conversation history and tool definitions.
+    pub input_tokens: u32,
+    /// Completion tokens, including tool-call arguments.
+    pub output_tokens: u32,
+}
+
+impl Usage {
+    pub fn new(input_tokens: u32, output_tokens: u32) -> Self {
+        Self {
+            input_tokens,
+            output_tokens,
+        }
+    }
+
+    pub fn total(&self) -> u32 {
+        self.input_tokens.saturating_add(self.output_tokens)
+    }
+}
+
+/// Receives usage for every successful generation made through a router
+/// configured with [`ProviderRouter::with_usage_recorder`](crate::router::ProviderRouter::with_usage_recorder).
+pub trait UsageRecorder: Send + Sync {
+    fn record_usage(&self, conversation_id: &str, provider: &str, model: &str, usage: Usage);
+}
@@ -18,6 +18,84 @@ pub enum EventType {
     PermChange,
     /// Error occurred
     Error,
+    /// Security decision: blocked command, sandbox violation, secret, 2FA
+    Security,
+}
+
+/// What a security audit entry records.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SecurityEventKind {
+    /// Command refused by the blocklist or the user's command policy
+    CommandBlocked,
+    /// Command touched paths outside the allowed directories
+    SandboxViolation,
+    /// Command contained a credential
+    SecretDetected,
+    /// Command needed 2FA and the session wasn't verified
+    TwoFactorRequired,
+    /// 2FA code accepted; commands in the grant's scope unlocked
+    TwoFactorGranted,
+    /// 2FA code rejected
+    TwoFactorFailed,
+    /// A 2FA grant let a command run
+    GrantUsed,
+    /// 2FA grants revoked before they expired
+    GrantRevoked,
+}
+
+impl SecurityEventKind {
+    /// Short label for the activity log, e.g. "Command blocked".
+    pub fn label(&self) -> &'static str {
+        match self {
+            SecurityEventKind::CommandBlocked => "Command blocked",
+            SecurityEventKind::SandboxViolation => "Sandbox violation",
+            SecurityEventKind::SecretDetected => "Secret detected",
+            SecurityEventKind::TwoFactorRequired => "2FA required",
+            SecurityEventKind::TwoFactorGranted => "2FA granted",
+            SecurityEventKind::TwoFactorFailed => "2FA failed",
+            SecurityEventKind::GrantUsed => "2FA grant used",
+            SecurityEventKind::GrantRevoked => "2FA grant revoked",
+        }
+    }
+}
+
+/// Structured details of a security audit entry: enough to reconstruct
+/// what the agent attempted and why it was stopped or allowed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SecurityEvent {
+    pub kind: SecurityEventKind,
+    /// The triggering command, with secrets redacted
+    pub command: Option<String>,
+    /// The rule, pattern or paths that matched
+    pub rule: Option<String>,
+    /// The agent session (conversation) the event happened in
+    pub session_id: Option<String>,
+}
+
+impl SecurityEvent {
+    pub fn new(kind: SecurityEventKind) -> Self {
+        Self {
+            kind,
+            command: None,
+            rule: None,
+            session_id: None,
+        }
+    }
+
+    pub fn with_command(mut self, command: impl Into<String>) -> Self {
+        self.command = Some(command.into());
+        self
+    }
+
+    pub fn with_rule(mut self, rule: impl Into<String>) -> Self {
+        self.rule = Some(rule.into());
+        self
+    }
+
+    pub fn with_session_id(mut self, session_id: impl Into<String>) -> Self {
+        self.session_id = Some(session_id.into());
+        self
+    }
 }
 
 /// Audit log entry for tracking all operations
@@ -39,6 +117,9 @@ pub struct AuditEntry {
     pub details: Option<serde_json::Value>,
     /// Whether to show in user-facing settings panel
     pub user_visible: bool,
+    /// Set on `EventType::Security` entries
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub security: Option<SecurityEvent>,
 }
 
 impl AuditEntry {
@@ -57,6 +138,7 @@ impl AuditEntry {
             action: action.into(),
             details,
             user_visible: true,
+            security: None,
         }
     }
 
@@ -78,6 +160,7 @@ impl AuditEntry {
             action: action_str,
             details: Some(serde_json::to_value(&action).unwrap_or_default()),
             user_visible: true,
+            security: None,
         }
     }
 
@@ -102,6 +185,7 @@ impl AuditEntry {
             action,
             details: None,
             user_visible: true,
+            security: None,
         }
     }
 
@@ -120,6 +204,27 @@ impl AuditEntry {
             action: message.into(),
             details: None,
             user_visible: true,
+            security: None,
+        }
+    }
+
+    /// Create a security audit entry, e.g. "Command blocked: `rm -rf /`"
+    pub fn security(event: SecurityEvent) -> Self {
+        let action = match &event.command {
+            Some(command) => format!("{}: `{}`", event.kind.label(), command),
+            None => event.kind.label().to_string(),
+        };
+
+        Self {
+            id: Uuid::new_v4(),
+            timestamp: Utc::now(),
+            event_type: EventType::Security,
+            skill_id: None,
+            file_path: None,
+            action,
+            details: None,
+            user_visible: true,
+            security: Some(event),
         }
     }
 
@@ -145,6 +250,8 @@ pub struct AuditFilter {
     pub from: Option<DateTime<Utc>>,
     /// End time (exclusive)
     pub to: Option<DateTime<Utc>>,
+    /// Filter by the session a security event happened in
+    pub session_id: Option<String>,
     /// Maximum entries to return
     pub limit: Option<usize>,
 }
@@ -175,6 +282,16 @@ impl AuditFilter {
             .push(event_type);
         self
     }
+
+    /// Only security events.
+    pub fn security(self) -> Self {
+        self.event_type(EventType::Security)
+    }
+
+    pub fn session(mut self, session_id: impl Into<String>) -> Self {
+        self.session_id = Some(session_id.into());
+        self
+    }
 }
 
 /// Skill execution event for real-time status updates
@@ -208,6 +325,11 @@ pub enum SkillEvent {
         execution_id: Uuid,
         duration_ms: u64,
     },
+    /// Skill execution was cancelled
+    Cancelled {
+        execution_id: Uuid,
+        duration_ms: u64,
+    },
 }
 
 impl SkillEvent {
@@ -218,6 +340,7 @@ impl SkillEvent {
             SkillEvent::Completed { execution_id, .. } => *execution_id,
             SkillEvent::Failed { execution_id, .. } => *execution_id,
             SkillEvent::Timeout { execution_id, .. } => *execution_id,
+            SkillEvent::Cancelled { execution_id, .. } => *execution_id,
         }
     }
 }
@@ -262,4 +385,29 @@ mod tests {
         assert_eq!(filter.skill_id, Some("fuzzy_file_search".to_string()));
         assert_eq!(filter.limit, Some(100));
     }
+
+    #[test]
+    fn test_audit_entry_security() {
+        let entry = AuditEntry::security(
+            SecurityEvent::new(SecurityEventKind::CommandBlocked)
+                .with_command("rm -rf /")
+                .with_rule("matches the blocked pattern `rm -rf /`")
+                .with_session_id("thread-1"),
+        );
+        assert!(matches!(entry.event_type, EventType::Security));
+        assert_eq!(entry.action, "Command blocked: `rm -rf /`");
+
+        let json = serde_json::to_string(&entry).unwrap();
+        let back: AuditEntry = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.security, entry.security);
+
+        // Entries written before security events existed still load
+        let old = AuditEntry::error("Error", None, None);
+        let json = serde_json::to_string(&old).unwrap();
+        assert!(!json.contains("security"));
+        assert!(serde_json::from_str::<AuditEntry>(&json)
+            .unwrap()
+            .security
+            .is_none());
+    }
 }
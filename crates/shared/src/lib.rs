@@ -2,15 +2,17 @@
 //!
 //! This crate is the "common vocabulary" for the project. It defines:
 //! - [`settings`] -- Application configuration persisted to disk (providers, auth, user profile).
-//! - [`agent_api`] -- Chat message types used between the UI and LLM providers.
+//! - [`agent_api`] -- Chat message, attachment, and tool-call types used between the UI and LLM providers.
 //! - [`search_types`] -- Query/result types for the fuzzy file finder.
 //! - [`preview_types`] -- Rich preview content shown in the companion panel.
 //! - [`skill`] -- Skill system: traits, permissions, execution lifecycle.
 //! - [`events`] -- Audit log and real-time skill execution events.
 //! - [`version`] -- User-friendly version tracking types (hides git internals).
+//! - [`secrets`] -- Secret detection and redaction for commands, output and LLM requests.
 
 pub mod events;
 pub mod preview_types;
+pub mod secrets;
 pub mod skill;
 pub mod version;
 
@@ -68,13 +70,143 @@ pub mod settings {
 
         /// Custom base URL for OpenAI-compatible APIs (Kimi, OpenRouter, Together, etc.)
         /// When set, the "openai" provider routes to this URL instead of api.openai.com.
+        /// Prefer a named entry in `profiles` for anything other than OpenAI itself.
         #[serde(default)]
         pub openai_base_url: Option<String>,
 
+        /// Named OpenAI-compatible endpoints. Each profile's `name` can be used
+        /// in `provider_preference` alongside the built-in providers.
+        #[serde(default)]
+        pub profiles: Vec<ProviderProfile>,
+
         // Authentication (either API key or OAuth)
         pub openai_auth: ProviderAuth,
         pub anthropic_auth: ProviderAuth,
         pub gemini_auth: ProviderAuth,
+
+        /// Per-request rules that reorder `provider_preference` and pick models
+        /// by cost, prompt size, capabilities, and mode.
+        #[serde(default)]
+        pub routing: RoutingPolicy,
+    }
+
+    impl ModelProvider {
+        /// The profile called `name`, if one is configured.
+        pub fn profile(&self, name: &str) -> Option<&ProviderProfile> {
+            self.profiles.iter().find(|p| p.name == name)
+        }
+    }
+
+    /// How a profile sends its API key.
+    #[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+    #[serde(rename_all = "snake_case", tag = "style")]
+    pub enum AuthHeaderStyle {
+        /// `Authorization: Bearer <key>` (OpenAI, OpenRouter, Kimi, vLLM).
+        #[default]
+        Bearer,
+        /// The raw key in a named header, e.g. `api-key` for Azure OpenAI.
+        Header { name: String },
+        /// No credentials (LM Studio, llama.cpp server, local vLLM).
+        None,
+    }
+
+    /// An OpenAI-compatible endpoint the router can dispatch to by name,
+    /// e.g. OpenRouter, LM Studio, vLLM, or a llama.cpp server.
+    ///
+    /// Capability flags replace the name-based guesses used for the built-in
+    /// providers, since a profile can serve any model.
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct ProviderProfile {
+        /// Id used in `provider_preference` and health tracking (e.g. "openrouter").
+        pub name: String,
+        /// Server root, e.g. `https://openrouter.ai/api` or `http://localhost:1234`.
+        /// `/v1/chat/completions` is appended.
+        pub base_url: String,
+        #[serde(default)]
+        pub auth_header: AuthHeaderStyle,
+        #[serde(default)]
+        pub auth: ProviderAuth,
+        /// Models offered by the endpoint; the first is used by default.
+        #[serde(default)]
+        pub models: Vec<String>,
+        /// Model used for fast modes (see `RoutingPolicy::fast_modes`).
+        #[serde(default)]
+        pub fast_model: Option<String>,
+        #[serde(default)]
+        pub supports_tools: bool,
+        #[serde(default)]
+        pub supports_vision: bool,
+        /// Context window in tokens, if known.
+        #[serde(default)]
+        pub context_window: Option<usize>,
+        /// Runs on this machine: free, and allowed where only local models are.
+        #[serde(default)]
+        pub local: bool,
+    }
+
+    impl ProviderProfile {
+        /// The default model (first in `models`).
+        pub fn model(&self) -> &str {
+            self.models.first().map(String::as_str).unwrap_or_default()
+        }
+
+        /// The fast-mode model, falling back to the default model.
+        pub fn fast_model(&self) -> &str {
+            self.fast_model.as_deref().unwrap_or_else(|| self.model())
+        }
+    }
+
+    /// How candidates from `provider_preference` are ordered for a request.
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+    #[serde(rename_all = "snake_case")]
+    pub enum RoutingStrategy {
+        /// Keep the configured preference order.
+        #[default]
+        Preference,
+        /// Cheapest estimated cost first, among providers that fit the request.
+        Cheapest,
+    }
+
+    /// Routing rules applied on top of `provider_preference` for each request.
+    ///
+    /// Hard rules (local-only modes, exhausted budget, context window) remove
+    /// providers; soft rules (strategy, local threshold, missing vision/tool
+    /// support) only reorder them so fallback still works.
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct RoutingPolicy {
+        #[serde(default)]
+        pub strategy: RoutingStrategy,
+        /// Prefer the local model unless the prompt is larger than this many
+        /// (estimated) tokens. `None` disables the rule.
+        #[serde(default)]
+        pub local_max_prompt_tokens: Option<usize>,
+        /// Modes whose conversations never leave this machine.
+        #[serde(default)]
+        pub local_only_modes: Vec<crate::skill::Mode>,
+        /// Modes that use the `*_fast_model` slots.
+        #[serde(default = "default_fast_modes")]
+        pub fast_modes: Vec<crate::skill::Mode>,
+        /// Drop cloud providers once a configured budget period is spent.
+        #[serde(default = "default_true")]
+        pub respect_budget: bool,
+    }
+
+    impl Default for RoutingPolicy {
+        fn default() -> Self {
+            Self {
+                strategy: RoutingStrategy::default(),
+                local_max_prompt_tokens: None,
+                local_only_modes: Vec::new(),
+                fast_modes: default_fast_modes(),
+                respect_budget: true,
+            }
+        }
+    }
+
+    /// Find/Fix/Content need quick answers rather than deep reasoning.
+    fn default_fast_modes() -> Vec<crate::skill::Mode> {
+        use crate::skill::Mode;
+        vec![Mode::Find, Mode::Fix, Mode::Content]
     }
 
     fn default_fast_openai() -> String {
@@ -132,6 +264,18 @@ pub mod settings {
         pub default_project_folder: Option<String>,
     }
 
+    /// Secret scanning adjustments. Every built-in rule is on by default.
+    #[derive(Debug, Clone, Serialize, Deserialize, Default)]
+    pub struct SecretScanSettings {
+        /// Values never treated as secrets (regexes matched against the
+        /// whole value), e.g. documentation example keys.
+        #[serde(default)]
+        pub allowlist: Vec<String>,
+        /// Rule names to switch off, e.g. `"high-entropy"`.
+        #[serde(default)]
+        pub disabled_rules: Vec<String>,
+    }
+
     /// Root configuration for the entire application.
     ///
     /// Serialized to/from `settings.json` in the app data directory.
@@ -162,6 +306,12 @@ pub mod settings {
         /// Brave Search API key (free tier: 2000 queries/month)
         #[serde(default)]
         pub brave_search_api_key: Option<String>,
+        /// Run commands in an OS-level sandbox (Linux only): allowed
+        /// folders writable, network only when needed, resource caps.
+        #[serde(default)]
+        pub hardened_execution: bool,
+        #[serde(default)]
+        pub secret_scanning: SecretScanSettings,
     }
 
     impl ProviderAuth {
@@ -192,9 +342,11 @@ pub mod settings {
                     anthropic_fast_model: default_fast_anthropic(),
                     gemini_fast_model: default_fast_gemini(),
                     openai_base_url: None,
+                    profiles: Vec::new(),
                     openai_auth: ProviderAuth::default(),
                     anthropic_auth: ProviderAuth::default(),
                     gemini_auth: ProviderAuth::default(),
+                    routing: RoutingPolicy::default(),
                 },
                 // For early testers: start enabled; user can turn off anytime.
                 enable_internet_research: true,
@@ -208,6 +360,8 @@ pub mod settings {
                 // For early testers: start enabled; user can turn off anytime.
                 share_system_summary: true,
                 brave_search_api_key: None,
+                hardened_execution: false,
+                secret_scanning: SecretScanSettings::default(),
             }
         }
     }
@@ -219,13 +373,217 @@ pub mod agent_api {
 
     /// A single message in an LLM conversation.
     ///
-    /// Role follows the OpenAI/Anthropic convention: "system", "user", or
-    /// "assistant". Provider clients translate to API-specific formats
-    /// (e.g., Anthropic's separate `system` field, Gemini's "model" role).
-    #[derive(Debug, Clone, Serialize, Deserialize)]
+    /// Role follows the OpenAI/Anthropic convention: "system", "user",
+    /// "assistant", or "tool". Provider clients translate to API-specific
+    /// formats (e.g., Anthropic's separate `system` field, Gemini's "model" role).
+    ///
+    /// A message is multi-part: the `content` text, optional [`Attachment`]s
+    /// (images, file references), and for tool use either structured
+    /// [`ToolCall`]s (assistant) or a `tool_call_id` (a "tool" message
+    /// answering that call).
+    #[derive(Debug, Clone, Default, Serialize, Deserialize)]
     pub struct ChatMessage {
-        pub role: String, // "system" | "user" | "assistant"
+        pub role: String, // "system" | "user" | "assistant" | "tool"
         pub content: String,
+        /// Images and files sent alongside the text (user messages only).
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        pub attachments: Vec<Attachment>,
+        /// Tool invocations requested by the model (assistant messages only).
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        pub tool_calls: Vec<ToolCall>,
+        /// The call this message answers (tool messages only).
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub tool_call_id: Option<String>,
+    }
+
+    impl ChatMessage {
+        pub fn new(role: impl Into<String>, content: impl Into<String>) -> Self {
+            Self {
+                role: role.into(),
+                content: content.into(),
+                ..Default::default()
+            }
+        }
+
+        pub fn system(content: impl Into<String>) -> Self {
+            Self::new("system", content)
+        }
+
+        pub fn user(content: impl Into<String>) -> Self {
+            Self::new("user", content)
+        }
+
+        pub fn assistant(content: impl Into<String>) -> Self {
+            Self::new("assistant", content)
+        }
+
+        pub fn with_attachment(mut self, attachment: Attachment) -> Self {
+            self.attachments.push(attachment);
+            self
+        }
+
+        /// The text to send to a provider that receives attachments only as
+        /// descriptions: `content` followed by a note for every file, and for
+        /// every image unless `vision` is true.
+        pub fn content_with_attachment_notes(&self, vision: bool) -> String {
+            let mut text = self.content.clone();
+            for attachment in &self.attachments {
+                if vision && matches!(attachment, Attachment::Image { .. }) {
+                    continue;
+                }
+                if !text.is_empty() {
+                    text.push_str("\n\n");
+                }
+                text.push_str(&attachment.describe());
+            }
+            text
+        }
+
+        /// The result of running `call`, to be sent back to the model.
+        pub fn tool_result(call: &ToolCall, content: impl Into<String>) -> Self {
+            Self {
+                role: "tool".to_string(),
+                content: content.into(),
+                tool_call_id: Some(call.id.clone()),
+                ..Default::default()
+            }
+        }
+    }
+
+    /// Non-text content attached to a [`ChatMessage`].
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    #[serde(tag = "type", rename_all = "snake_case")]
+    pub enum Attachment {
+        /// An image, sent natively to vision-capable models.
+        Image {
+            /// e.g. `image/png`
+            mime_type: String,
+            /// Base64-encoded bytes (standard alphabet, padded).
+            data: String,
+        },
+        /// A file on disk, referenced by path. Models only see its description.
+        File { path: String },
+    }
+
+    impl Attachment {
+        /// Largest image we inline. Provider limits are around 5-20 MB.
+        pub const MAX_IMAGE_BYTES: usize = 5 * 1024 * 1024;
+
+        pub fn image(mime_type: impl Into<String>, bytes: &[u8]) -> Self {
+            use base64::Engine;
+            Self::Image {
+                mime_type: mime_type.into(),
+                data: base64::engine::general_purpose::STANDARD.encode(bytes),
+            }
+        }
+
+        /// Load `path` as an image attachment if it is a supported image type
+        /// within [`MAX_IMAGE_BYTES`](Self::MAX_IMAGE_BYTES); otherwise reference
+        /// it as a file.
+        pub fn from_path(path: &std::path::Path) -> std::io::Result<Self> {
+            if let Some(mime_type) = image_mime_type(path) {
+                let size = std::fs::metadata(path)?.len();
+                if size as usize <= Self::MAX_IMAGE_BYTES {
+                    return Ok(Self::image(mime_type, &std::fs::read(path)?));
+                }
+            }
+            Ok(Self::File {
+                path: path.to_string_lossy().to_string(),
+            })
+        }
+
+        /// Plain-text stand-in for models that cannot receive this attachment.
+        pub fn describe(&self) -> String {
+            match self {
+                Self::Image { mime_type, data } => format!(
+                    "[Attached image: {}, {} KB. The current model cannot view images.]",
+                    mime_type,
+                    data.len() * 3 / 4 / 1024
+                ),
+                Self::File { path } => format!("[Attached file: {}]", path),
+            }
+        }
+    }
+
+    /// MIME type for image extensions every vision provider accepts.
+    pub fn image_mime_type(path: &std::path::Path) -> Option<&'static str> {
+        match path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase())
+            .as_deref()
+        {
+            Some("png") => Some("image/png"),
+            Some("jpg" | "jpeg") => Some("image/jpeg"),
+            Some("gif") => Some("image/gif"),
+            Some("webp") => Some("image/webp"),
+            _ => None,
+        }
+    }
+
+    /// A function the model may call, with a JSON Schema describing its arguments.
+    ///
+    /// Provider clients translate this into OpenAI/Ollama `tools`, Anthropic
+    /// `tools` (`input_schema`) or Gemini `functionDeclarations`.
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    pub struct ToolSpec {
+        /// Function name (`[a-zA-Z0-9_-]`, at most 64 chars).
+        pub name: String,
+        pub description: String,
+        /// JSON Schema (`{"type": "object", ...}`) for the arguments.
+        pub parameters: serde_json::Value,
+    }
+
+    /// A structured tool invocation returned by the model.
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    pub struct ToolCall {
+        /// Provider-assigned call ID (synthesised for providers without one).
+        pub id: String,
+        pub name: String,
+        /// Parsed JSON arguments (an object; `{}` when the model sent none).
+        pub arguments: serde_json::Value,
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_attachment_from_path() {
+            let dir = std::env::temp_dir().join(format!("lh-attach-{}", std::process::id()));
+            std::fs::create_dir_all(&dir).unwrap();
+            let png = dir.join("shot.PNG");
+            std::fs::write(&png, b"\x89PNG").unwrap();
+            let notes = dir.join("notes.txt");
+            std::fs::write(&notes, "hi").unwrap();
+
+            assert_eq!(
+                Attachment::from_path(&png).unwrap(),
+                Attachment::Image {
+                    mime_type: "image/png".to_string(),
+                    data: "iVBORw==".to_string(),
+                }
+            );
+            assert!(matches!(
+                Attachment::from_path(&notes).unwrap(),
+                Attachment::File { .. }
+            ));
+            std::fs::remove_dir_all(&dir).unwrap();
+        }
+
+        #[test]
+        fn test_content_with_attachment_notes() {
+            let msg = ChatMessage::user("What is this?")
+                .with_attachment(Attachment::image("image/png", b"png"))
+                .with_attachment(Attachment::File {
+                    path: "/tmp/report.pdf".to_string(),
+                });
+            let vision = msg.content_with_attachment_notes(true);
+            assert_eq!(vision, "What is this?\n\n[Attached file: /tmp/report.pdf]");
+            let text_only = msg.content_with_attachment_notes(false);
+            assert!(text_only.contains("[Attached image: image/png"));
+            assert!(text_only.ends_with("[Attached file: /tmp/report.pdf]"));
+        }
     }
 }
 
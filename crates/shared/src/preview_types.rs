@@ -130,6 +130,15 @@ pub enum PreviewContent {
         moves: Vec<CleanupMove>,
         renames: Vec<CleanupRename>,
     },
+
+    /// What a shell command would do to files, before it runs
+    CommandPlan {
+        command: String,
+        folder: PathBuf,
+        changes: Vec<PlannedChange>,
+        /// Parts of the command whose effect couldn't be worked out
+        unplanned: Vec<String>,
+    },
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -151,6 +160,43 @@ pub struct CleanupRename {
     pub to: PathBuf,
 }
 
+/// One file a command would change, from the dry-run planner
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlannedChange {
+    pub path: PathBuf,
+    pub kind: PlannedChangeKind,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PlannedChangeKind {
+    Create,
+    /// Written to or overwritten
+    Modify,
+    Delete,
+    Move {
+        to: PathBuf,
+    },
+    Copy {
+        to: PathBuf,
+    },
+    /// Mode or owner changed
+    Permissions,
+}
+
+impl PlannedChangeKind {
+    /// Short verb for previews ("moved", "deleted", ...)
+    pub fn verb(&self) -> &'static str {
+        match self {
+            PlannedChangeKind::Create => "created",
+            PlannedChangeKind::Modify => "changed",
+            PlannedChangeKind::Delete => "deleted",
+            PlannedChangeKind::Move { .. } => "moved",
+            PlannedChangeKind::Copy { .. } => "copied",
+            PlannedChangeKind::Permissions => "permissions changed",
+        }
+    }
+}
+
 /// Source for an image to display
 #[derive(Clone, Debug)]
 pub enum ImageSource {
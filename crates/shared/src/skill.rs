@@ -75,6 +75,8 @@ pub enum ExecutionStatus {
     Completed,
     Failed,
     Timeout,
+    /// Stopped by the user before it finished
+    Cancelled,
 }
 
 /// Result type classification
@@ -241,6 +243,8 @@ pub struct SkillContext {
     pub data_dir: PathBuf,
     /// Current working directory for file operations
     pub working_dir: PathBuf,
+    /// Agent session (conversation) the skill runs in, for the audit log
+    pub session_id: Option<String>,
 }
 
 impl SkillContext {
@@ -251,6 +255,7 @@ impl SkillContext {
             session_approvals: Arc::new(RwLock::new(HashSet::new())),
             data_dir,
             working_dir,
+            session_id: None,
         }
     }
 
@@ -261,9 +266,15 @@ impl SkillContext {
             session_approvals: Arc::new(RwLock::new(HashSet::new())),
             data_dir,
             working_dir,
+            session_id: None,
         }
     }
 
+    pub fn with_session_id(mut self, session_id: impl Into<String>) -> Self {
+        self.session_id = Some(session_id.into());
+        self
+    }
+
     /// Check if a skill is approved for this session
     pub fn is_session_approved(&self, skill_id: &str) -> bool {
         self.session_approvals.read().contains(skill_id)
@@ -324,6 +335,13 @@ impl SkillExecution {
         self.duration_ms = duration_ms;
         self
     }
+
+    pub fn cancel(mut self, duration_ms: u64) -> Self {
+        self.status = ExecutionStatus::Cancelled;
+        self.error = Some("Execution cancelled".to_string());
+        self.duration_ms = duration_ms;
+        self
+    }
 }
 
 /// Core skill trait that all skills must implement
@@ -351,6 +369,24 @@ pub trait Skill: Send + Sync {
     fn validate_input(&self, _input: &SkillInput) -> anyhow::Result<()> {
         Ok(())
     }
+
+    /// JSON Schema for the arguments when this skill is offered as a native
+    /// tool. A `query` argument fills [`SkillInput::query`]; any other
+    /// arguments land in [`SkillInput::params`].
+    ///
+    /// The default accepts a single free-form `query`.
+    fn input_schema(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "query": {
+                    "type": "string",
+                    "description": "What to do, in plain language"
+                }
+            },
+            "required": ["query"]
+        })
+    }
 }
 
 /// Skill error types